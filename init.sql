@@ -37,13 +37,28 @@ CREATE TABLE IF NOT EXISTS transactions (
   max_priority_fee_per_gas TEXT,
   gas_provided TEXT NOT NULL,
   input_data BYTEA,
-  status SMALLINT
+  status SMALLINT,
+  gas_used TEXT, -- from the receipt
+  effective_gas_price TEXT,
+  nonce TEXT,
+  transaction_type SMALLINT,
+  contract_address TEXT -- from the receipt of a creation transaction
 );
 
+-- Fields added after the table was first created; no-ops on a fresh database
+ALTER TABLE transactions
+  ADD COLUMN IF NOT EXISTS gas_used TEXT,
+  ADD COLUMN IF NOT EXISTS effective_gas_price TEXT,
+  ADD COLUMN IF NOT EXISTS nonce TEXT,
+  ADD COLUMN IF NOT EXISTS transaction_type SMALLINT,
+  ADD COLUMN IF NOT EXISTS contract_address TEXT;
+
 -- Create indexes for common transaction queries
 CREATE INDEX IF NOT EXISTS idx_transactions_block_number ON transactions(block_number);
 CREATE INDEX IF NOT EXISTS idx_transactions_from_address ON transactions(from_address);
 CREATE INDEX IF NOT EXISTS idx_transactions_to_address ON transactions(to_address);
+-- Composite for cursor pagination on POST /transactions
+CREATE INDEX IF NOT EXISTS idx_transactions_block_tx_index ON transactions(block_number, transaction_index);
 
 -- logs table
 CREATE TABLE IF NOT EXISTS logs (
@@ -72,3 +87,381 @@ CREATE INDEX IF NOT EXISTS idx_logs_topic1 ON logs(topic1);
 CREATE INDEX IF NOT EXISTS idx_logs_topic0_block ON logs(topic0, block_number);
 -- Composite for address + block range queries
 CREATE INDEX IF NOT EXISTS idx_logs_composite ON logs(block_number, contract_address);
+
+-- webhook notification rules (see src/webhooks.rs)
+CREATE TABLE IF NOT EXISTS webhook_rules (
+  id BIGSERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  address TEXT,
+  topic0 TEXT,
+  method_id TEXT,
+  target_url TEXT NOT NULL,
+  secret TEXT NOT NULL,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row per payload sent to a rule's target; retractions reference the match they undo.
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id BIGSERIAL PRIMARY KEY,
+  rule_id BIGINT NOT NULL REFERENCES webhook_rules(id) ON DELETE CASCADE,
+  kind TEXT NOT NULL, -- 'match' | 'retraction'
+  block_number BIGINT NOT NULL,
+  block_hash TEXT NOT NULL,
+  payload JSONB NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending', -- 'pending' | 'delivered' | 'dead'
+  attempts INT NOT NULL DEFAULT 0,
+  last_error TEXT,
+  retracted BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_pending ON webhook_deliveries(status) WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_unretracted ON webhook_deliveries(block_number)
+  WHERE kind = 'match' AND NOT retracted;
+
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+  id BIGSERIAL PRIMARY KEY,
+  delivery_id BIGINT NOT NULL REFERENCES webhook_deliveries(id) ON DELETE CASCADE,
+  rule_id BIGINT NOT NULL REFERENCES webhook_rules(id) ON DELETE CASCADE,
+  target_url TEXT NOT NULL,
+  payload JSONB NOT NULL,
+  attempts INT NOT NULL,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- User-defined event handlers (see src/handlers.rs)
+CREATE TABLE IF NOT EXISTS event_handlers (
+  id BIGSERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  address TEXT NOT NULL,
+  event_signature TEXT NOT NULL,
+  topic0 TEXT NOT NULL,
+  script TEXT NOT NULL,
+  enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  failed_at_block BIGINT,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Versioned rows of handler entity tables: one version per key and block, NULL data marks a
+-- removal. The current row is the highest block_number; reorg rollback deletes by height.
+CREATE TABLE IF NOT EXISTS handler_entities (
+  handler_id BIGINT NOT NULL REFERENCES event_handlers(id) ON DELETE CASCADE,
+  entity TEXT NOT NULL,
+  entity_key TEXT NOT NULL,
+  block_number BIGINT NOT NULL,
+  data JSONB,
+  PRIMARY KEY (handler_id, entity, entity_key, block_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_handler_entities_block ON handler_entities(block_number);
+
+-- Built-in Uniswap V2/V3 decoding (see src/dex.rs). Forks sharing the event signatures are
+-- stored under the protocol they mirror; factory_address tells them apart.
+CREATE TABLE IF NOT EXISTS dex_pools (
+  pool_address TEXT PRIMARY KEY,
+  protocol TEXT NOT NULL, -- 'uniswap_v2' | 'uniswap_v3'
+  factory_address TEXT,
+  token0 TEXT NOT NULL,
+  token1 TEXT NOT NULL,
+  fee INT,
+  tick_spacing INT,
+  created_block BIGINT, -- set when the PairCreated/PoolCreated event was indexed
+  first_seen_block BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_dex_pools_token0 ON dex_pools(token0);
+CREATE INDEX IF NOT EXISTS idx_dex_pools_token1 ON dex_pools(token1);
+CREATE INDEX IF NOT EXISTS idx_dex_pools_first_seen ON dex_pools(first_seen_block);
+
+-- Amounts are signed raw token units from the pool's side: positive flowed into the pool.
+CREATE TABLE IF NOT EXISTS dex_swaps (
+  log_id BIGINT PRIMARY KEY,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  tx_hash TEXT NOT NULL,
+  log_index BIGINT,
+  pool_address TEXT NOT NULL,
+  protocol TEXT NOT NULL,
+  token0 TEXT NOT NULL,
+  token1 TEXT NOT NULL,
+  trader TEXT NOT NULL, -- transaction sender
+  sender TEXT NOT NULL,
+  recipient TEXT NOT NULL,
+  amount0 NUMERIC NOT NULL,
+  amount1 NUMERIC NOT NULL,
+  sqrt_price_x96 NUMERIC,
+  liquidity NUMERIC,
+  tick INT
+);
+
+CREATE INDEX IF NOT EXISTS idx_dex_swaps_pool ON dex_swaps(pool_address, block_number, log_id);
+CREATE INDEX IF NOT EXISTS idx_dex_swaps_trader ON dex_swaps(trader, block_number, log_id);
+CREATE INDEX IF NOT EXISTS idx_dex_swaps_token0 ON dex_swaps(token0, block_number, log_id);
+CREATE INDEX IF NOT EXISTS idx_dex_swaps_token1 ON dex_swaps(token1, block_number, log_id);
+CREATE INDEX IF NOT EXISTS idx_dex_swaps_block ON dex_swaps(block_number);
+
+-- Mint, Burn and Sync events. For 'sync' rows the amounts are the pool's new reserves.
+CREATE TABLE IF NOT EXISTS dex_liquidity_events (
+  log_id BIGINT PRIMARY KEY,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  tx_hash TEXT NOT NULL,
+  log_index BIGINT,
+  pool_address TEXT NOT NULL,
+  kind TEXT NOT NULL, -- 'mint' | 'burn' | 'sync'
+  owner TEXT,
+  sender TEXT,
+  recipient TEXT,
+  amount0 NUMERIC NOT NULL,
+  amount1 NUMERIC NOT NULL,
+  liquidity NUMERIC,
+  tick_lower INT,
+  tick_upper INT
+);
+
+CREATE INDEX IF NOT EXISTS idx_dex_liquidity_pool ON dex_liquidity_events(pool_address, block_number, log_id);
+CREATE INDEX IF NOT EXISTS idx_dex_liquidity_block ON dex_liquidity_events(block_number);
+
+-- On-chain pricing (see src/pricing.rs). Prices are derived from the dex_* events; `bucket`
+-- is the block number, or the minute's Unix timestamp with PRICE_RESOLUTION=minute, in which
+-- case the row holds the last observation of that minute.
+CREATE TABLE IF NOT EXISTS tokens (
+  token_address TEXT PRIMARY KEY,
+  decimals SMALLINT, -- NULL when decimals() reverts
+  symbol TEXT -- NULL when symbol() reverts or returns no usable string
+);
+
+CREATE INDEX IF NOT EXISTS idx_tokens_symbol ON tokens(UPPER(symbol));
+
+CREATE TABLE IF NOT EXISTS token_prices (
+  token_address TEXT NOT NULL,
+  bucket BIGINT NOT NULL,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  price_eth DOUBLE PRECISION NOT NULL,
+  pool_address TEXT NOT NULL,
+  liquidity_eth DOUBLE PRECISION NOT NULL, -- WETH depth of the pool, in ETH
+  PRIMARY KEY (token_address, bucket)
+);
+
+CREATE INDEX IF NOT EXISTS idx_token_prices_token_block ON token_prices(token_address, block_number);
+CREATE INDEX IF NOT EXISTS idx_token_prices_block ON token_prices(block_number);
+
+CREATE TABLE IF NOT EXISTS eth_usd_prices (
+  bucket BIGINT PRIMARY KEY,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  price_usd DOUBLE PRECISION NOT NULL,
+  pool_address TEXT NOT NULL,
+  liquidity_eth DOUBLE PRECISION NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_eth_usd_prices_block ON eth_usd_prices(block_number);
+
+-- Contract deployments (see src/contracts.rs). `factory_address` is only filled in with
+-- CONTRACT_TRACES=true; proxy columns describe the contract as deployed.
+CREATE TABLE IF NOT EXISTS contracts (
+  address TEXT PRIMARY KEY,
+  deployer TEXT NOT NULL, -- sender of the creating transaction
+  factory_address TEXT,
+  creation_tx_hash TEXT NOT NULL,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  code_hash TEXT, -- keccak256 of the runtime bytecode, NULL when empty
+  code_size INT NOT NULL,
+  proxy_type TEXT, -- 'eip1167', 'eip1967' or 'eip1967_beacon'
+  implementation_address TEXT,
+  beacon_address TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_contracts_block ON contracts(block_number);
+CREATE INDEX IF NOT EXISTS idx_contracts_deployer ON contracts(deployer);
+CREATE INDEX IF NOT EXISTS idx_contracts_factory ON contracts(factory_address);
+CREATE INDEX IF NOT EXISTS idx_contracts_code_hash ON contracts(code_hash);
+CREATE INDEX IF NOT EXISTS idx_contracts_implementation ON contracts(implementation_address);
+
+-- Upgraded / BeaconUpgraded events of indexed contracts.
+CREATE TABLE IF NOT EXISTS contract_upgrades (
+  log_id BIGINT PRIMARY KEY,
+  block_number BIGINT NOT NULL,
+  block_timestamp BIGINT NOT NULL,
+  tx_hash TEXT NOT NULL,
+  contract_address TEXT NOT NULL,
+  kind TEXT NOT NULL, -- 'implementation' or 'beacon'
+  target_address TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_contract_upgrades_contract ON contract_upgrades(contract_address, block_number);
+CREATE INDEX IF NOT EXISTS idx_contract_upgrades_block ON contract_upgrades(block_number);
+
+-- ENS registry, resolver and registrar events (see src/ens.rs), one row per event. A node's
+-- current record of each kind is its latest row; `value` is an address or a name.
+CREATE TABLE IF NOT EXISTS ens_names (
+  log_id BIGINT PRIMARY KEY,
+  block_number BIGINT NOT NULL,
+  node TEXT NOT NULL,
+  kind TEXT NOT NULL, -- 'registered', 'owner', 'resolver', 'addr' or 'name'
+  value TEXT NOT NULL,
+  source TEXT NOT NULL -- the emitting contract
+);
+
+CREATE INDEX IF NOT EXISTS idx_ens_names_node ON ens_names(node, kind, block_number DESC, log_id DESC);
+CREATE INDEX IF NOT EXISTS idx_ens_names_block ON ens_names(block_number);
+
+-- Gas rollups (see src/gas.rs). Wei amounts are NUMERIC; priority fees are gas-weighted
+-- percentiles of effective gas price minus base fee, NULL when a block's receipts are incomplete.
+CREATE TABLE IF NOT EXISTS gas_blocks (
+  block_number BIGINT PRIMARY KEY,
+  block_timestamp BIGINT NOT NULL,
+  base_fee_per_gas NUMERIC,
+  gas_used NUMERIC NOT NULL,
+  gas_limit NUMERIC NOT NULL,
+  utilization DOUBLE PRECISION NOT NULL,
+  tx_count INT NOT NULL,
+  priority_fee_p10 NUMERIC,
+  priority_fee_p25 NUMERIC,
+  priority_fee_p50 NUMERIC,
+  priority_fee_p75 NUMERIC,
+  priority_fee_p90 NUMERIC
+);
+
+CREATE INDEX IF NOT EXISTS idx_gas_blocks_timestamp ON gas_blocks(block_timestamp);
+
+-- One row per hour (`bucket` is its Unix start), recomputed from gas_blocks on every write.
+-- Priority fees are the median over the hour's blocks of each block's percentile.
+CREATE TABLE IF NOT EXISTS gas_hourly (
+  bucket BIGINT PRIMARY KEY,
+  first_block BIGINT NOT NULL,
+  last_block BIGINT NOT NULL,
+  blocks BIGINT NOT NULL,
+  base_fee_avg NUMERIC,
+  base_fee_min NUMERIC,
+  base_fee_max NUMERIC,
+  gas_used NUMERIC NOT NULL,
+  utilization DOUBLE PRECISION NOT NULL,
+  tx_count BIGINT NOT NULL,
+  priority_fee_p10 NUMERIC,
+  priority_fee_p25 NUMERIC,
+  priority_fee_p50 NUMERIC,
+  priority_fee_p75 NUMERIC,
+  priority_fee_p90 NUMERIC
+);
+
+-- Gas spent calling each contract per hour (transactions with calldata only).
+CREATE TABLE IF NOT EXISTS gas_contract_hourly (
+  bucket BIGINT NOT NULL,
+  contract_address TEXT NOT NULL,
+  gas_used NUMERIC NOT NULL,
+  fees_paid NUMERIC NOT NULL, -- wei
+  tx_count BIGINT NOT NULL,
+  PRIMARY KEY (bucket, contract_address)
+);
+
+-- Running totals behind GET /stats (see src/stats.rs), kept by the ingester and rollbacks.
+-- Seeded from the tables on startup when missing.
+CREATE TABLE IF NOT EXISTS stats_counters (
+  id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+  blocks BIGINT NOT NULL,
+  transactions BIGINT NOT NULL,
+  logs BIGINT NOT NULL
+);
+
+-- Blocks folded into the activity rollups, so a rollback only subtracts what was added.
+CREATE TABLE IF NOT EXISTS stats_blocks (
+  block_number BIGINT PRIMARY KEY,
+  block_timestamp BIGINT NOT NULL
+);
+
+-- Activity per hour or UTC day; `bucket` is the Unix start of the hour or day.
+CREATE TABLE IF NOT EXISTS stats_rollups (
+  resolution TEXT NOT NULL CHECK (resolution IN ('hour', 'day')),
+  bucket BIGINT NOT NULL,
+  blocks BIGINT NOT NULL,
+  tx_count BIGINT NOT NULL,
+  unique_senders BIGINT NOT NULL,
+  eth_volume NUMERIC NOT NULL, -- wei moved by successful transactions
+  log_count BIGINT NOT NULL,
+  PRIMARY KEY (resolution, bucket)
+);
+
+-- Each bucket's distinct senders, for exact unique_senders, with the first block they appear in.
+CREATE TABLE IF NOT EXISTS stats_senders (
+  resolution TEXT NOT NULL,
+  bucket BIGINT NOT NULL,
+  address TEXT NOT NULL,
+  first_block BIGINT NOT NULL,
+  PRIMARY KEY (resolution, bucket, address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stats_senders_first_block ON stats_senders(first_block);
+
+-- Logs per bucket, contract and topic0 (NULL for anonymous events).
+CREATE TABLE IF NOT EXISTS stats_log_rollups (
+  resolution TEXT NOT NULL,
+  bucket BIGINT NOT NULL,
+  contract_address TEXT NOT NULL,
+  topic0 TEXT,
+  log_count BIGINT NOT NULL,
+  UNIQUE NULLS NOT DISTINCT (resolution, bucket, contract_address, topic0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stats_log_rollups_contract ON stats_log_rollups(resolution, contract_address, bucket);
+CREATE INDEX IF NOT EXISTS idx_stats_log_rollups_topic0 ON stats_log_rollups(resolution, topic0, bucket);
+
+-- Inbound ERC-20 transfers (GET /accounts/{address}/transfers) filter on the `to` topic.
+CREATE INDEX IF NOT EXISTS idx_logs_topic2 ON logs(topic2);
+
+-- Hex digits (no 0x prefix) to NUMERIC; decodes uint256 amounts kept as text in logs.data.
+CREATE OR REPLACE FUNCTION hex_to_numeric(hex TEXT) RETURNS NUMERIC
+LANGUAGE plpgsql IMMUTABLE STRICT AS $$
+DECLARE
+  result NUMERIC := 0;
+  chunk TEXT;
+  pos INT := 1;
+  len INT;
+BEGIN
+  WHILE pos <= length(hex) LOOP
+    -- 7 digits at a time still fits a signed 32-bit bit string cast.
+    len := LEAST(7, length(hex) - pos + 1);
+    chunk := substr(hex, pos, len);
+    result := result * (1::BIGINT << (4 * len)) + ('x' || lpad(chunk, 8, '0'))::bit(32)::INT;
+    pos := pos + len;
+  END LOOP;
+  RETURN result;
+END
+$$;
+
+-- Per-sink progress for at-least-once delivery (see src/sinks/mod.rs)
+CREATE TABLE IF NOT EXISTS sink_checkpoints (
+  sink_name TEXT PRIMARY KEY,
+  last_block BIGINT NOT NULL,
+  last_block_hash TEXT,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- API keys (see src/auth.rs). Only the SHA-256 of each key is stored; NULL limits mean none.
+CREATE TABLE IF NOT EXISTS api_keys (
+  id BIGSERIAL PRIMARY KEY,
+  name TEXT NOT NULL,
+  key_prefix TEXT NOT NULL,
+  key_hash TEXT NOT NULL UNIQUE,
+  scopes TEXT[] NOT NULL, -- 'read', 'export', 'admin'
+  rate_limit_per_minute INT,
+  daily_quota BIGINT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_used_at TIMESTAMPTZ,
+  revoked_at TIMESTAMPTZ
+);
+
+-- Requests per key, UTC day and endpoint (`METHOD /route`).
+CREATE TABLE IF NOT EXISTS api_key_usage (
+  key_id BIGINT NOT NULL REFERENCES api_keys(id),
+  day DATE NOT NULL,
+  endpoint TEXT NOT NULL,
+  requests BIGINT NOT NULL,
+  PRIMARY KEY (key_id, day, endpoint)
+);
@@ -8,22 +8,33 @@ use utoipa_swagger_ui::SwaggerUi;
 
 // --- Imports for Axum and Business Logic ---
 use crate::{
-    api_models::{GetLogsFilter, IndexerStats, LogsResponse},
+    api_models::{
+        BlockByTimestampQuery, BlockTag, BlocksResponse, Closest, GetBlocksQuery, GetLogsFilter,
+        GetTransactionsFilter, IndexerStats, LogsResponse, TransactionsResponse,
+    },
     models::{MyBlock, MyLog, MyTransaction},
+    storage::{Page, Storage},
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Json},
     routing::{get, post},
     Router,
 };
 use ethers::core::types::{Address, H256, U256, U64};
-use sqlx::{PgPool, QueryBuilder, Row as SqlxRow};
+use ethers::providers::{Http, Provider};
+use sqlx::{postgres::PgRow, PgPool, QueryBuilder, Row as SqlxRow};
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-const MAX_PAGE_SIZE: u64 = 100;
+pub(crate) const MAX_PAGE_SIZE: u64 = 100;
+const MAX_FILTER_VALUES: usize = 1000; // Max addresses / values per topic position in a log filter
+const SAFE_BLOCK_DEPTH: i64 = 32; // `safe` tag: one epoch behind the chain head
+const FINALIZED_BLOCK_DEPTH: i64 = 64; // `finalized` tag: two epochs behind the chain head
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10); // Open requests after a shutdown signal
 
 #[derive(Debug)]
 pub enum ApiError {
@@ -79,6 +90,416 @@ impl From<eyre::Report> for ApiError {
     }
 }
 
+pub(crate) const TRANSACTION_COLUMNS: &str =
+    "tx_hash, block_number, block_hash, transaction_index, \
+     from_address, to_address, value, gas_price, max_fee_per_gas, \
+     max_priority_fee_per_gas, gas_provided, ENCODE(input_data, 'escape') AS input_data, status, \
+     gas_used, effective_gas_price, nonce, transaction_type, contract_address, \
+     (SELECT b.timestamp FROM blocks b WHERE b.block_hash = transactions.block_hash) \
+     AS block_timestamp";
+
+/// Maps a row selected with `TRANSACTION_COLUMNS` into a `MyTransaction`.
+pub(crate) fn row_to_transaction(row: &PgRow) -> Result<MyTransaction, ApiError> {
+    Ok(MyTransaction {
+        tx_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "tx_hash")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid tx_hash: {}", e)))?,
+        block_number: U64::from(SqlxRow::try_get::<i64, _>(row, "block_number")?),
+        block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "block_hash")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid block_hash: {}", e)))?,
+        block_timestamp: SqlxRow::try_get::<Option<i64>, _>(row, "block_timestamp")?
+            .map(|t| t as u64),
+        transaction_index: SqlxRow::try_get::<Option<i64>, _>(row, "transaction_index")?
+            .map(U64::from),
+        from_address: Address::from_str(&SqlxRow::try_get::<String, _>(row, "from_address")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid from_address: {}", e)))?,
+        to_address: SqlxRow::try_get::<Option<String>, _>(row, "to_address")?
+            .and_then(|s| Address::from_str(&s).ok()),
+        value: U256::from_dec_str(&SqlxRow::try_get::<String, _>(row, "value")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid value: {}", e)))?,
+        gas_price: SqlxRow::try_get::<Option<String>, _>(row, "gas_price")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+        max_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(row, "max_fee_per_gas")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+        max_priority_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(
+            row,
+            "max_priority_fee_per_gas",
+        )?
+        .and_then(|s| U256::from_dec_str(&s).ok()),
+        gas: U256::from_dec_str(&SqlxRow::try_get::<String, _>(row, "gas_provided")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas: {}", e)))?,
+        nonce: SqlxRow::try_get::<Option<String>, _>(row, "nonce")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+        input_data: SqlxRow::try_get::<Option<String>, _>(row, "input_data")?.unwrap_or_default(),
+        status: SqlxRow::try_get::<Option<i16>, _>(row, "status")?.map(|s| s as u64),
+        gas_used: SqlxRow::try_get::<Option<String>, _>(row, "gas_used")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+        effective_gas_price: SqlxRow::try_get::<Option<String>, _>(row, "effective_gas_price")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+        transaction_type: SqlxRow::try_get::<Option<i16>, _>(row, "transaction_type")?
+            .map(|t| t as u64),
+        contract_address: SqlxRow::try_get::<Option<String>, _>(row, "contract_address")?
+            .and_then(|s| Address::from_str(&s).ok()),
+        value_usd: None,
+        from_ens_name: None,
+        to_ens_name: None,
+    })
+}
+
+pub(crate) const LOG_COLUMNS: &str = "id, log_index_in_tx AS log_index, transaction_hash, \
+     transaction_index_in_block AS transaction_index, \
+     block_number, block_hash, contract_address AS address, \
+     ENCODE(data, 'escape') AS data, all_topics AS topics, \
+     (SELECT b.timestamp FROM blocks b WHERE b.block_hash = logs.block_hash) AS block_timestamp";
+
+pub(crate) const BLOCK_COLUMNS: &str =
+    "block_number, block_hash, parent_hash, timestamp, gas_used, gas_limit, base_fee_per_gas";
+
+/// Maps a row selected with `LOG_COLUMNS` into a `MyLog`.
+pub(crate) fn row_to_log(row: &PgRow) -> Result<MyLog, ApiError> {
+    Ok(MyLog {
+        log_index: SqlxRow::try_get::<Option<i64>, _>(row, "log_index")?
+            .and_then(|v| U256::from_dec_str(&v.to_string()).ok()),
+        transaction_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "transaction_hash")?)
+            .map_err(|e| {
+                ApiError::InternalServerError(format!("Invalid transaction_hash: {}", e))
+            })?,
+        transaction_index: SqlxRow::try_get::<Option<i64>, _>(row, "transaction_index")?
+            .map(|v| v as u64),
+        block_number: SqlxRow::try_get::<i64, _>(row, "block_number")? as u64,
+        block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "block_hash")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid block_hash: {}", e)))?,
+        block_timestamp: SqlxRow::try_get::<Option<i64>, _>(row, "block_timestamp")?
+            .map(|t| t as u64),
+        address: Address::from_str(&SqlxRow::try_get::<String, _>(row, "address")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid address: {}", e)))?,
+        data: SqlxRow::try_get::<Option<String>, _>(row, "data")?.unwrap_or_default(),
+        topics: SqlxRow::try_get(row, "topics").unwrap_or_default(),
+        address_ens_name: None,
+    })
+}
+
+/// Maps a row selected with `BLOCK_COLUMNS` into a `MyBlock`.
+pub(crate) fn row_to_block(row: &PgRow) -> Result<MyBlock, ApiError> {
+    Ok(MyBlock {
+        block_number: U64::from(SqlxRow::try_get::<i64, _>(row, "block_number")?),
+        block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "block_hash")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid block_hash: {}", e)))?,
+        parent_hash: H256::from_str(&SqlxRow::try_get::<String, _>(row, "parent_hash")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid parent_hash: {}", e)))?,
+        timestamp: U256::from(SqlxRow::try_get::<i64, _>(row, "timestamp")?),
+        gas_used: U256::from_dec_str(&SqlxRow::try_get::<String, _>(row, "gas_used")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas_used: {}", e)))?,
+        gas_limit: U256::from_dec_str(&SqlxRow::try_get::<String, _>(row, "gas_limit")?)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas_limit: {}", e)))?,
+        base_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(row, "base_fee_per_gas")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+    })
+}
+
+/// Looks up a block row by number or 0x-prefixed hash.
+pub(crate) async fn fetch_block_row(pool: &PgPool, identifier: &str) -> Result<PgRow, ApiError> {
+    let row = if identifier.starts_with("0x") {
+        sqlx::query(&format!(
+            "SELECT {} FROM blocks WHERE block_hash = $1",
+            BLOCK_COLUMNS
+        ))
+        .bind(identifier.to_lowercase())
+        .fetch_one(pool)
+        .await?
+    } else {
+        let block_number = identifier
+            .parse::<i64>()
+            .map_err(|_| ApiError::BadRequest("Invalid block number format".to_string()))?;
+        sqlx::query(&format!(
+            "SELECT {} FROM blocks WHERE block_number = $1",
+            BLOCK_COLUMNS
+        ))
+        .bind(block_number)
+        .fetch_one(pool)
+        .await?
+    };
+    Ok(row)
+}
+
+/// Resolves a JSON-RPC block tag to a concrete height using the indexer status.
+/// `latest` is the last indexed block; `safe`/`finalized` trail the polled chain head.
+pub(crate) async fn resolve_block_tag(pool: &PgPool, tag: BlockTag) -> Result<i64, ApiError> {
+    match tag {
+        BlockTag::Number(_) | BlockTag::Earliest => block_tag_height(tag, None),
+        _ => block_tag_height(tag, crate::db::get_indexer_status(pool).await?),
+    }
+}
+
+/// Maps a block tag to a height given `(last_synced, chain_head)` from the indexer status.
+pub(crate) fn block_tag_height(tag: BlockTag, status: Option<(i64, i64)>) -> Result<i64, ApiError> {
+    let (last_synced, chain_head) = match tag {
+        BlockTag::Number(n) => return Ok(n as i64),
+        BlockTag::Earliest => return Ok(0),
+        _ => status
+            .ok_or_else(|| ApiError::NotFound("No blocks have been indexed yet.".to_string()))?,
+    };
+    Ok(match tag {
+        BlockTag::Safe => (chain_head - SAFE_BLOCK_DEPTH).max(0),
+        BlockTag::Finalized => (chain_head - FINALIZED_BLOCK_DEPTH).max(0),
+        _ => last_synced,
+    })
+}
+
+/// Lowercases and validates 0x-prefixed hex filter values of the given byte length.
+pub(crate) fn normalize_hex_values(
+    values: &[String],
+    byte_len: usize,
+    field: &str,
+) -> Result<Vec<String>, ApiError> {
+    if values.len() > MAX_FILTER_VALUES {
+        return Err(ApiError::BadRequest(format!(
+            "Too many values for {} (max {}).",
+            field, MAX_FILTER_VALUES
+        )));
+    }
+    values
+        .iter()
+        .map(|v| {
+            let valid = v.starts_with("0x")
+                && v.len() == 2 + byte_len * 2
+                && v[2..].chars().all(|c| c.is_ascii_hexdigit());
+            if valid {
+                Ok(v.to_lowercase())
+            } else {
+                Err(ApiError::BadRequest(format!(
+                    "Invalid {} value: {}",
+                    field, v
+                )))
+            }
+        })
+        .collect()
+}
+
+/// The receipt status filter: 1 for success, 0 for reverted.
+pub(crate) fn transaction_status(status: u64) -> Result<i16, ApiError> {
+    match status {
+        0 | 1 => Ok(status as i16),
+        _ => Err(ApiError::BadRequest(format!(
+            "Invalid status value: {}, expected 0 or 1.",
+            status
+        ))),
+    }
+}
+
+/// Lowercases and validates a `from`/`to` transaction filter: a 0x-prefixed 20-byte address.
+pub(crate) fn normalize_address(address: &str, field: &str) -> Result<String, ApiError> {
+    normalize_hex_values(&[address.to_string()], 20, field).map(|mut values| values.remove(0))
+}
+
+/// Lowercases and validates a `methodId` filter: a 0x-prefixed 4-byte selector.
+pub(crate) fn normalize_method_id(method_id: &str) -> Result<String, ApiError> {
+    normalize_hex_values(&[method_id.to_string()], 4, "methodId")
+        .map(|mut values| values.remove(0))
+        .map_err(|_| {
+            ApiError::BadRequest(
+                "Invalid methodId format, expected a 0x-prefixed 4-byte selector.".to_string(),
+            )
+        })
+}
+
+/// Rejects filter combinations that `eth_getLogs` does not allow.
+pub(crate) fn validate_log_filter(filters: &GetLogsFilter) -> Result<(), ApiError> {
+    let uses_positional_topics = filters.topic0.is_some()
+        || filters.topic1.is_some()
+        || filters.topic2.is_some()
+        || filters.topic3.is_some();
+    if let Some(topics) = &filters.topics {
+        if uses_positional_topics {
+            return Err(ApiError::BadRequest(
+                "Use either `topics` or `topic0`..`topic3`, not both.".to_string(),
+            ));
+        }
+        if topics.len() > 4 {
+            return Err(ApiError::BadRequest(
+                "At most 4 topic positions are supported.".to_string(),
+            ));
+        }
+    }
+
+    if filters.block_hash.is_some() && (filters.from_block.is_some() || filters.to_block.is_some())
+    {
+        return Err(ApiError::BadRequest(
+            "blockHash cannot be combined with fromBlock/toBlock.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Replaces `fromTimestamp`/`toTimestamp` in a log filter with the block bounds they resolve
+/// to. Each timestamp bound excludes the block bound (or `blockHash`) on the same side.
+pub(crate) async fn resolve_log_time_range(
+    storage: &dyn Storage,
+    filters: &mut GetLogsFilter,
+) -> Result<(), ApiError> {
+    if filters.from_timestamp.is_none() && filters.to_timestamp.is_none() {
+        return Ok(());
+    }
+    if filters.block_hash.is_some()
+        || (filters.from_timestamp.is_some() && filters.from_block.is_some())
+        || (filters.to_timestamp.is_some() && filters.to_block.is_some())
+    {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp/toTimestamp cannot be combined with blockHash or the matching \
+             fromBlock/toBlock."
+                .to_string(),
+        ));
+    }
+
+    let (from_block, to_block) = crate::storage::resolve_time_range(
+        storage,
+        filters.from_timestamp.take(),
+        filters.to_timestamp.take(),
+    )
+    .await?;
+    if let Some(fb) = from_block {
+        filters.from_block = Some(BlockTag::Number(fb as u64));
+    }
+    if let Some(tb) = to_block {
+        filters.to_block = Some(BlockTag::Number(tb as u64));
+    }
+    Ok(())
+}
+
+/// `resolve_log_time_range` for `GetTransactionsFilter`.
+pub(crate) async fn resolve_transaction_time_range(
+    storage: &dyn Storage,
+    filters: &mut GetTransactionsFilter,
+) -> Result<(), ApiError> {
+    if (filters.from_timestamp.is_some() && filters.from_block.is_some())
+        || (filters.to_timestamp.is_some() && filters.to_block.is_some())
+    {
+        return Err(ApiError::BadRequest(
+            "fromTimestamp/toTimestamp cannot be combined with the matching fromBlock/toBlock."
+                .to_string(),
+        ));
+    }
+
+    let (from_block, to_block) = crate::storage::resolve_time_range(
+        storage,
+        filters.from_timestamp.take(),
+        filters.to_timestamp.take(),
+    )
+    .await?;
+    if let Some(fb) = from_block {
+        filters.from_block = Some(fb as u64);
+    }
+    if let Some(tb) = to_block {
+        filters.to_block = Some(tb as u64);
+    }
+    Ok(())
+}
+
+/// Appends `eth_getLogs`-style filter clauses to a query over the `logs` table.
+/// Address and topic OR-lists become `= ANY(...)`, which Postgres serves from the
+/// existing per-column and `(topic0, block_number)` indexes.
+pub(crate) async fn push_log_filters(
+    pool: &PgPool,
+    query_builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    filters: &GetLogsFilter,
+) -> Result<(), ApiError> {
+    validate_log_filter(filters)?;
+
+    if let Some(bh_filter) = &filters.block_hash {
+        query_builder.push(" AND block_hash = LOWER(");
+        query_builder.push_bind(bh_filter.clone());
+        query_builder.push(")");
+    } else {
+        if let Some(fb) = filters.from_block {
+            query_builder.push(" AND block_number >= ");
+            query_builder.push_bind(resolve_block_tag(pool, fb).await?);
+        }
+        if let Some(tb) = filters.to_block {
+            query_builder.push(" AND block_number <= ");
+            query_builder.push_bind(resolve_block_tag(pool, tb).await?);
+        }
+    }
+
+    // An empty OR-list matches anything, as in eth_getLogs.
+    if let Some(addresses) = &filters.address {
+        let addresses = normalize_hex_values(addresses.as_slice(), 20, "address")?;
+        if !addresses.is_empty() {
+            query_builder.push(" AND contract_address = ANY(");
+            query_builder.push_bind(addresses);
+            query_builder.push(")");
+        }
+    }
+    for (position, column) in ["topic0", "topic1", "topic2", "topic3"].iter().enumerate() {
+        if let Some(values) = filters.topic_filter(position) {
+            let values = normalize_hex_values(values.as_slice(), 32, column)?;
+            if !values.is_empty() {
+                query_builder.push(format!(" AND {} = ANY(", column));
+                query_builder.push_bind(values);
+                query_builder.push(")");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends the `GetTransactionsFilter` clauses to a query over the `transactions` table.
+pub(crate) fn push_transaction_filters(
+    query_builder: &mut QueryBuilder<'_, sqlx::Postgres>,
+    filters: &GetTransactionsFilter,
+) -> Result<(), ApiError> {
+    if let Some(fb) = filters.from_block {
+        query_builder.push(" AND block_number >= ");
+        query_builder.push_bind(fb as i64);
+    }
+    if let Some(tb) = filters.to_block {
+        query_builder.push(" AND block_number <= ");
+        query_builder.push_bind(tb as i64);
+    }
+    if let Some(from) = &filters.from {
+        query_builder.push(" AND from_address = ");
+        query_builder.push_bind(normalize_address(from, "from")?);
+    }
+    if let Some(to) = &filters.to {
+        query_builder.push(" AND to_address = ");
+        query_builder.push_bind(normalize_address(to, "to")?);
+    }
+    if let Some(status) = filters.status {
+        query_builder.push(" AND status = ");
+        query_builder.push_bind(transaction_status(status)?);
+    }
+    // Values are stored as decimal TEXT, so compare numerically rather than lexically.
+    if let Some(min) = &filters.min_value {
+        U256::from_dec_str(min)
+            .map_err(|_| ApiError::BadRequest("Invalid minValue format".to_string()))?;
+        query_builder.push(" AND value::NUMERIC >= ");
+        query_builder.push_bind(min.clone());
+        query_builder.push("::NUMERIC");
+    }
+    if let Some(max) = &filters.max_value {
+        U256::from_dec_str(max)
+            .map_err(|_| ApiError::BadRequest("Invalid maxValue format".to_string()))?;
+        query_builder.push(" AND value::NUMERIC <= ");
+        query_builder.push_bind(max.clone());
+        query_builder.push("::NUMERIC");
+    }
+    if let Some(method_id) = &filters.method_id {
+        let method_id = normalize_method_id(method_id)?;
+        query_builder.push(" AND LEFT(ENCODE(input_data, 'escape'), 10) = ");
+        query_builder.push_bind(method_id);
+    }
+    match filters.contract_creation {
+        Some(true) => {
+            query_builder.push(" AND to_address IS NULL");
+        }
+        Some(false) => {
+            query_builder.push(" AND to_address IS NOT NULL");
+        }
+        None => {}
+    }
+
+    Ok(())
+}
+
 /// API Root
 ///
 /// Provides a simple welcome message to verify the API is running.
@@ -95,8 +516,13 @@ pub async fn root_handler() -> Html<&'static str> {
 
 /// Get Filtered Logs
 ///
-/// Retrieves a paginated list of event logs. Supports both offset pagination (page/page_size)
-/// and stable cursor-based pagination (cursor_block + cursor_log_id from a previous response).
+/// Retrieves a paginated list of event logs. Filters follow `eth_getLogs` semantics: `address`
+/// and each topic position accept a single value or an OR-array, and `fromBlock`/`toBlock`
+/// accept numbers or the `latest`, `safe` and `finalized` tags. `fromTimestamp`/`toTimestamp`
+/// are resolved to the indexed blocks inside that window. Addresses may be ENS names, and
+/// `includeEnsNames` adds each emitting contract's primary name.
+/// Supports both offset pagination (page/page_size) and stable cursor-based pagination
+/// (cursor_block + cursor_log_id from a previous response).
 /// Cursor-based pagination is preferred at scale — O(log n) vs OFFSET's O(n) full scan.
 #[utoipa::path(
     post,
@@ -109,118 +535,38 @@ pub async fn root_handler() -> Html<&'static str> {
     )
 )]
 async fn get_logs_handler(
-    State(pool): State<PgPool>,
-    Json(filters): Json<GetLogsFilter>,
+    State(storage): State<Arc<dyn Storage>>,
+    Json(mut filters): Json<GetLogsFilter>,
 ) -> Result<Json<LogsResponse>, ApiError> {
-    let page_size = filters.page_size.clamp(1, MAX_PAGE_SIZE);
-    let use_cursor = filters.cursor_block.is_some() || filters.cursor_log_id.is_some();
-
-    let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new(
-        "SELECT id, log_index_in_tx AS log_index, transaction_hash, \
-         transaction_index_in_block AS transaction_index, \
-         block_number, block_hash, contract_address AS address, \
-         ENCODE(data, 'escape') AS data, all_topics AS topics \
-         FROM logs WHERE 1=1",
-    );
-
-    if let Some(bh_filter) = &filters.block_hash {
-        query_builder.push(" AND LOWER(block_hash) = LOWER(");
-        query_builder.push_bind(bh_filter);
-        query_builder.push(")");
-    } else {
-        if let Some(fb) = filters.from_block {
-            query_builder.push(" AND block_number >= ");
-            query_builder.push_bind(fb as i64);
+    resolve_log_time_range(storage.as_ref(), &mut filters).await?;
+    let pool = storage.postgres_pool();
+    if let Some(addresses) = &mut filters.address {
+        for address in addresses.as_mut_slice() {
+            crate::ens::resolve_filter_value(pool.as_ref(), address).await?;
         }
-        if let Some(tb) = filters.to_block {
-            query_builder.push(" AND block_number <= ");
-            query_builder.push_bind(tb as i64);
-        }
-    }
-    if let Some(addr_filter) = &filters.address {
-        query_builder.push(" AND LOWER(contract_address) = LOWER(");
-        query_builder.push_bind(addr_filter);
-        query_builder.push(")");
-    }
-    if let Some(t) = &filters.topic0 {
-        query_builder.push(" AND topic0 = ");
-        query_builder.push_bind(t);
-    }
-    if let Some(t) = &filters.topic1 {
-        query_builder.push(" AND topic1 = ");
-        query_builder.push_bind(t);
-    }
-    if let Some(t) = &filters.topic2 {
-        query_builder.push(" AND topic2 = ");
-        query_builder.push_bind(t);
-    }
-    if let Some(t) = &filters.topic3 {
-        query_builder.push(" AND topic3 = ");
-        query_builder.push_bind(t);
     }
+    let use_cursor = filters.cursor_block.is_some() || filters.cursor_log_id.is_some();
+    let cursor = use_cursor.then(|| {
+        (
+            filters.cursor_block.unwrap_or(0),
+            filters.cursor_log_id.unwrap_or(0),
+        )
+    });
+    let page = Page::new(filters.page, filters.page_size, cursor);
 
-    // Cursor: WHERE (block_number, id) > ($cursor_block, $cursor_log_id)
-    // Deterministic ordering, no duplicate/skipped rows, O(log n) with composite index.
-    if use_cursor {
-        let cb = filters.cursor_block.unwrap_or(0);
-        let cl = filters.cursor_log_id.unwrap_or(0);
-        query_builder.push(" AND (block_number, id) > (");
-        query_builder.push_bind(cb);
-        query_builder.push(", ");
-        query_builder.push_bind(cl);
-        query_builder.push(")");
+    let rows = storage.get_logs(&filters, page).await?;
+    let (next_cursor_block, next_cursor_log_id) = match rows.last() {
+        Some((id, log)) => (Some(log.block_number as i64), Some(*id)),
+        None => (None, None),
+    };
+    let mut logs: Vec<MyLog> = rows.into_iter().map(|(_, log)| log).collect();
+    if filters.include_ens_names {
+        let pool = pool.ok_or_else(|| {
+            ApiError::BadRequest("includeEnsNames requires PostgreSQL storage.".to_string())
+        })?;
+        crate::ens::fill_log_names(&pool, &mut logs).await?;
     }
 
-    query_builder.push(" ORDER BY block_number ASC, id ASC LIMIT ");
-    query_builder.push_bind(page_size as i64);
-
-    if !use_cursor {
-        let page = filters.page.max(1);
-        let offset = (page - 1) * page_size;
-        query_builder.push(" OFFSET ");
-        query_builder.push_bind(offset as i64);
-    }
-
-    let rows = query_builder.build().fetch_all(&pool).await?;
-
-    let mut next_cursor_block: Option<i64> = None;
-    let mut next_cursor_log_id: Option<i64> = None;
-
-    let logs = rows
-        .into_iter()
-        .map(|row| -> Result<MyLog, ApiError> {
-            let row_id: i64 = SqlxRow::try_get(&row, "id")?;
-            let block_num: i64 = SqlxRow::try_get(&row, "block_number")?;
-            next_cursor_block = Some(block_num);
-            next_cursor_log_id = Some(row_id);
-
-            Ok(MyLog {
-                log_index: SqlxRow::try_get::<Option<i64>, _>(&row, "log_index")?
-                    .and_then(|v| U256::from_dec_str(&v.to_string()).ok()),
-                transaction_hash: H256::from_str(&SqlxRow::try_get::<String, _>(
-                    &row,
-                    "transaction_hash",
-                )?)
-                .map_err(|e| {
-                    ApiError::InternalServerError(format!("Invalid transaction_hash: {}", e))
-                })?,
-                transaction_index: SqlxRow::try_get::<Option<i64>, _>(&row, "transaction_index")?
-                    .map(|v| v as u64),
-                block_number: block_num as u64,
-                block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(&row, "block_hash")?)
-                    .map_err(|e| {
-                        ApiError::InternalServerError(format!("Invalid block_hash: {}", e))
-                    })?,
-                address: Address::from_str(&SqlxRow::try_get::<String, _>(&row, "address")?)
-                    .map_err(|e| {
-                        ApiError::InternalServerError(format!("Invalid address: {}", e))
-                    })?,
-                data: SqlxRow::try_get::<Option<String>, _>(&row, "data")?.unwrap_or_default(),
-                topics: SqlxRow::try_get(&row, "topics").unwrap_or_default(),
-            })
-        })
-        .collect::<Result<Vec<MyLog>, ApiError>>()?;
-
     Ok(Json(LogsResponse {
         logs,
         next_cursor_block,
@@ -228,6 +574,63 @@ async fn get_logs_handler(
     }))
 }
 
+/// Get Filtered Transactions
+///
+/// Retrieves a paginated list of transactions. Supports the same offset and cursor pagination
+/// as `POST /logs`; the cursor is `(cursor_block, cursor_tx_index)` from a previous response.
+/// `fromTimestamp`/`toTimestamp` narrow the range like they do for logs.
+/// With `includeUsd`, each transaction also carries `valueUsd` at ETH/USD in its block.
+/// `from`/`to` may be ENS names, and `includeEnsNames` adds both parties' primary names.
+#[utoipa::path(
+    post,
+    path = "/transactions",
+    request_body = GetTransactionsFilter,
+    responses(
+        (status = 200, description = "Successfully retrieved transactions", body = TransactionsResponse),
+        (status = 400, description = "Bad request due to invalid filters", body = GenericErrorResponse),
+        (status = 500, description = "Internal server error", body = GenericErrorResponse),
+    )
+)]
+async fn get_transactions_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Json(mut filters): Json<GetTransactionsFilter>,
+) -> Result<Json<TransactionsResponse>, ApiError> {
+    resolve_transaction_time_range(storage.as_ref(), &mut filters).await?;
+    let pool = storage.postgres_pool();
+    for address in [&mut filters.from, &mut filters.to].into_iter().flatten() {
+        crate::ens::resolve_filter_value(pool.as_ref(), address).await?;
+    }
+    let use_cursor = filters.cursor_block.is_some() || filters.cursor_tx_index.is_some();
+    let cursor = use_cursor.then(|| {
+        (
+            filters.cursor_block.unwrap_or(0),
+            filters.cursor_tx_index.unwrap_or(-1),
+        )
+    });
+    let page = Page::new(filters.page, filters.page_size, cursor);
+
+    let mut transactions = storage.get_transactions(&filters, page).await?;
+    if filters.include_usd {
+        let pool = pool.as_ref().ok_or_else(|| {
+            ApiError::BadRequest("includeUsd requires PostgreSQL storage.".to_string())
+        })?;
+        crate::pricing::fill_value_usd(pool, &mut transactions).await?;
+    }
+    if filters.include_ens_names {
+        let pool = pool.as_ref().ok_or_else(|| {
+            ApiError::BadRequest("includeEnsNames requires PostgreSQL storage.".to_string())
+        })?;
+        crate::ens::fill_transaction_names(pool, &mut transactions).await?;
+    }
+
+    let last = transactions.last();
+    Ok(Json(TransactionsResponse {
+        next_cursor_block: last.map(|tx| tx.block_number.as_u64() as i64),
+        next_cursor_tx_index: last.and_then(|tx| tx.transaction_index.map(|i| i.as_u64() as i64)),
+        transactions,
+    }))
+}
+
 /// Get Indexer Stats
 ///
 /// Retrieves overall statistics for the indexer including total blocks, transactions, logs, and the last synced block.
@@ -239,31 +642,10 @@ async fn get_logs_handler(
         (status = 500, description = "Internal server error", body = GenericErrorResponse)
     )
 )]
-pub async fn get_stats_handler(State(pool): State<PgPool>) -> Result<Json<IndexerStats>, ApiError> {
-    let total_blocks: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM blocks")
-        .fetch_one(&pool)
-        .await?;
-    let total_transactions: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM transactions")
-        .fetch_one(&pool)
-        .await?;
-    let total_logs: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM logs")
-        .fetch_one(&pool)
-        .await?;
-
-    // Fetch last_processed_block and chain_head for lag calculation
-    let status = crate::db::get_indexer_status(&pool).await?;
-    let (last_synced_block, ingestion_lag) = match status {
-        Some((last, head)) => (Some(last), Some(head - last)),
-        None => (None, None),
-    };
-
-    Ok(Json(IndexerStats {
-        total_blocks: total_blocks.0,
-        total_transactions: total_transactions.0,
-        total_logs: total_logs.0,
-        last_synced_block,
-        ingestion_lag,
-    }))
+pub async fn get_stats_handler(
+    State(storage): State<Arc<dyn Storage>>,
+) -> Result<Json<IndexerStats>, ApiError> {
+    Ok(Json(storage.get_stats().await?))
 }
 
 /// Get Block by Number or Hash
@@ -282,42 +664,142 @@ pub async fn get_stats_handler(State(pool): State<PgPool>) -> Result<Json<Indexe
     )
 )]
 pub async fn get_block_handler(
-    State(pool): State<PgPool>,
+    State(storage): State<Arc<dyn Storage>>,
     Path(identifier): Path<String>,
 ) -> Result<Json<MyBlock>, ApiError> {
-    let query = "SELECT block_number, block_hash, parent_hash, timestamp, gas_used, gas_limit, base_fee_per_gas FROM blocks";
+    Ok(Json(storage.get_block(&identifier).await?))
+}
 
-    let row = if identifier.starts_with("0x") {
-        sqlx::query(&format!("{} WHERE block_hash = $1", query))
-            .bind(identifier.to_lowercase())
-            .fetch_one(&pool)
-            .await?
+/// List Blocks
+///
+/// Retrieves blocks newest-first. Without a range this returns the latest `limit` blocks;
+/// `fromBlock`/`toBlock` and `fromTimestamp`/`toTimestamp` restrict the range. Pass `cursorBlock` from a previous response
+/// to continue with older blocks.
+#[utoipa::path(
+    get,
+    path = "/blocks",
+    params(GetBlocksQuery),
+    responses(
+        (status = 200, description = "Successfully retrieved blocks", body = BlocksResponse),
+        (status = 400, description = "Bad request due to invalid parameters", body = GenericErrorResponse),
+        (status = 500, description = "Internal server error", body = GenericErrorResponse),
+    )
+)]
+pub async fn get_blocks_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Query(params): Query<GetBlocksQuery>,
+) -> Result<Json<BlocksResponse>, ApiError> {
+    let limit = params.limit.clamp(1, MAX_PAGE_SIZE);
+    let blocks = storage.get_blocks(&params, limit).await?;
+
+    // Only hand out a cursor when the page was full; a short page means we reached the end.
+    let next_cursor_block = if blocks.len() as u64 == limit {
+        blocks.last().map(|b| b.block_number.as_u64() as i64)
     } else {
-        let block_number = identifier
-            .parse::<i64>()
-            .map_err(|_| ApiError::BadRequest("Invalid block number format".to_string()))?;
-        sqlx::query(&format!("{} WHERE block_number = $1", query))
-            .bind(block_number)
-            .fetch_one(&pool)
-            .await?
+        None
     };
 
-    let my_block = MyBlock {
-        block_number: U64::from(SqlxRow::try_get::<i64, _>(&row, "block_number")?),
-        block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(&row, "block_hash")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid block_hash: {}", e)))?,
-        parent_hash: H256::from_str(&SqlxRow::try_get::<String, _>(&row, "parent_hash")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid parent_hash: {}", e)))?,
-        timestamp: U256::from(SqlxRow::try_get::<i64, _>(&row, "timestamp")?),
-        gas_used: U256::from_dec_str(&SqlxRow::try_get::<String, _>(&row, "gas_used")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas_used: {}", e)))?,
-        gas_limit: U256::from_dec_str(&SqlxRow::try_get::<String, _>(&row, "gas_limit")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas_limit: {}", e)))?,
-        base_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(&row, "base_fee_per_gas")?
-            .and_then(|s| U256::from_dec_str(&s).ok()),
-    };
+    Ok(Json(BlocksResponse {
+        blocks,
+        next_cursor_block,
+    }))
+}
+
+/// Get Latest Block
+///
+/// Retrieves the highest indexed block.
+#[utoipa::path(
+    get,
+    path = "/blocks/latest",
+    responses(
+        (status = 200, description = "Block found", body = MyBlock),
+        (status = 404, description = "No blocks indexed yet", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_latest_block_handler(
+    State(storage): State<Arc<dyn Storage>>,
+) -> Result<Json<MyBlock>, ApiError> {
+    Ok(Json(storage.get_latest_block().await?))
+}
 
-    Ok(Json(my_block))
+/// Get Block by Timestamp
+///
+/// Retrieves the last indexed block at or before `ts` (`closest=before`, the default) or the
+/// first indexed block at or after it (`closest=after`), by binary search over block timestamps.
+#[utoipa::path(
+    get,
+    path = "/block/by-timestamp",
+    params(BlockByTimestampQuery),
+    responses(
+        (status = 200, description = "Block found", body = MyBlock),
+        (status = 404, description = "No indexed block on that side of the timestamp", body = GenericErrorResponse),
+        (status = 400, description = "Invalid parameters", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_block_by_timestamp_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Query(params): Query<BlockByTimestampQuery>,
+) -> Result<Json<MyBlock>, ApiError> {
+    let block_number =
+        crate::storage::find_block_by_timestamp(storage.as_ref(), params.ts, params.closest)
+            .await?
+            .ok_or_else(|| {
+                ApiError::NotFound(format!(
+                    "No indexed block {} timestamp {}.",
+                    match params.closest {
+                        Closest::Before => "at or before",
+                        Closest::After => "at or after",
+                    },
+                    params.ts
+                ))
+            })?;
+    Ok(Json(storage.get_block(&block_number.to_string()).await?))
+}
+
+/// Get Block Transactions
+///
+/// Retrieves all transactions in a block, ordered by transaction index.
+#[utoipa::path(
+    get,
+    path = "/block/{identifier}/transactions",
+    params(
+        ("identifier" = String, Path, description = "Block number or hash", example = "18000000")
+    ),
+    responses(
+        (status = 200, description = "Transactions in the block", body = Vec<MyTransaction>),
+        (status = 404, description = "Block not found", body = GenericErrorResponse),
+        (status = 400, description = "Invalid identifier format", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_block_transactions_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Path(identifier): Path<String>,
+) -> Result<Json<Vec<MyTransaction>>, ApiError> {
+    let block = storage.get_block(&identifier).await?;
+    Ok(Json(storage.get_block_transactions(&block).await?))
+}
+
+/// Get Block Logs
+///
+/// Retrieves all event logs emitted in a block, in emission order.
+#[utoipa::path(
+    get,
+    path = "/block/{identifier}/logs",
+    params(
+        ("identifier" = String, Path, description = "Block number or hash", example = "18000000")
+    ),
+    responses(
+        (status = 200, description = "Logs in the block", body = Vec<MyLog>),
+        (status = 404, description = "Block not found", body = GenericErrorResponse),
+        (status = 400, description = "Invalid identifier format", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_block_logs_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Path(identifier): Path<String>,
+) -> Result<Json<Vec<MyLog>>, ApiError> {
+    let block = storage.get_block(&identifier).await?;
+    Ok(Json(storage.get_block_logs(&block).await?))
 }
 
 /// Get Transaction by Hash
@@ -336,7 +818,7 @@ pub async fn get_block_handler(
     )
 )]
 pub async fn get_transaction_by_hash_handler(
-    State(pool): State<PgPool>,
+    State(storage): State<Arc<dyn Storage>>,
     Path(tx_hash_param): Path<String>,
 ) -> Result<Json<MyTransaction>, ApiError> {
     if !tx_hash_param.starts_with("0x") || tx_hash_param.len() != 66 {
@@ -345,69 +827,217 @@ pub async fn get_transaction_by_hash_handler(
         ));
     }
 
-    let row = sqlx::query(
-        "SELECT tx_hash, block_number, block_hash, transaction_index, \
-         from_address, to_address, value, gas_price, max_fee_per_gas, \
-         max_priority_fee_per_gas, gas_provided, input_data, status \
-         FROM transactions WHERE tx_hash = $1",
-    )
-    .bind(tx_hash_param.to_lowercase())
-    .fetch_one(&pool)
-    .await?;
+    Ok(Json(
+        storage
+            .get_transaction(&tx_hash_param.to_lowercase())
+            .await?,
+    ))
+}
 
-    let my_tx = MyTransaction {
-        tx_hash: H256::from_str(&SqlxRow::try_get::<String, _>(&row, "tx_hash")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid tx_hash: {}", e)))?,
-        block_number: U64::from(SqlxRow::try_get::<i64, _>(&row, "block_number")?),
-        block_hash: H256::from_str(&SqlxRow::try_get::<String, _>(&row, "block_hash")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid block_hash: {}", e)))?,
-        transaction_index: SqlxRow::try_get::<Option<i64>, _>(&row, "transaction_index")?
-            .map(U64::from),
-        from_address: Address::from_str(&SqlxRow::try_get::<String, _>(&row, "from_address")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid from_address: {}", e)))?,
-        to_address: SqlxRow::try_get::<Option<String>, _>(&row, "to_address")?
-            .and_then(|s| Address::from_str(&s).ok()),
-        value: U256::from_dec_str(&SqlxRow::try_get::<String, _>(&row, "value")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid value: {}", e)))?,
-        gas_price: SqlxRow::try_get::<Option<String>, _>(&row, "gas_price")?
-            .and_then(|s| U256::from_dec_str(&s).ok()),
-        max_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(&row, "max_fee_per_gas")?
-            .and_then(|s| U256::from_dec_str(&s).ok()),
-        max_priority_fee_per_gas: SqlxRow::try_get::<Option<String>, _>(
-            &row,
-            "max_priority_fee_per_gas",
-        )?
-        .and_then(|s| U256::from_dec_str(&s).ok()),
-        gas: U256::from_dec_str(&SqlxRow::try_get::<String, _>(&row, "gas_provided")?)
-            .map_err(|e| ApiError::InternalServerError(format!("Invalid gas: {}", e)))?,
-        input_data: SqlxRow::try_get(&row, "input_data")?,
-        status: SqlxRow::try_get::<Option<i16>, _>(&row, "status")?.map(|s| s as u64),
-    };
+/// Get Transaction Logs
+///
+/// Retrieves all event logs emitted by a transaction, in emission order.
+#[utoipa::path(
+    get,
+    path = "/transaction/{tx_hash}/logs",
+    params(
+        ("tx_hash" = String, Path, description = "The transaction hash", example = "0x...")
+    ),
+    responses(
+        (status = 200, description = "Logs emitted by the transaction", body = Vec<MyLog>),
+        (status = 400, description = "Invalid hash format", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_transaction_logs_handler(
+    State(storage): State<Arc<dyn Storage>>,
+    Path(tx_hash_param): Path<String>,
+) -> Result<Json<Vec<MyLog>>, ApiError> {
+    if !tx_hash_param.starts_with("0x") || tx_hash_param.len() != 66 {
+        return Err(ApiError::BadRequest(
+            "Invalid transaction hash format.".to_string(),
+        ));
+    }
 
-    Ok(Json(my_tx))
+    Ok(Json(
+        storage
+            .get_transaction_logs(&tx_hash_param.to_lowercase())
+            .await?,
+    ))
 }
 
-pub async fn run_api_server(pool: PgPool) -> eyre::Result<()> {
-    let app = Router::new()
+pub async fn run_api_server(
+    storage: Arc<dyn Storage>,
+    provider: Arc<Provider<Http>>,
+    chain_id: u64,
+    events: crate::events::EventSender,
+    auth: Option<crate::auth::AuthConfig>,
+    control: Arc<crate::control::IngesterControl>,
+    shutdown: crate::supervisor::Shutdown,
+) -> eyre::Result<()> {
+    let mut app = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .route("/", get(root_handler))
         .route("/stats", get(get_stats_handler))
         .route("/logs", post(get_logs_handler))
+        .route("/transactions", post(get_transactions_handler))
         // --- FIX: Use modern Axum path parameter syntax ---
+        .route("/blocks", get(get_blocks_handler))
+        .route("/blocks/latest", get(get_latest_block_handler))
+        .route("/block/by-timestamp", get(get_block_by_timestamp_handler))
         .route("/block/{identifier}", get(get_block_handler))
+        .route(
+            "/block/{identifier}/transactions",
+            get(get_block_transactions_handler),
+        )
+        .route("/block/{identifier}/logs", get(get_block_logs_handler))
         .route(
             "/transaction/{tx_hash}",
             get(get_transaction_by_hash_handler),
         )
-        .with_state(pool.clone());
+        .route(
+            "/transaction/{tx_hash}/logs",
+            get(get_transaction_logs_handler),
+        )
+        .with_state(storage.clone())
+        .merge(crate::control::health_router(control.clone()));
+
+    // The remaining surfaces are written directly against Postgres.
+    let postgres = storage.postgres_pool();
+    if let Some(pool) = &postgres {
+        app = app
+            .merge(crate::rpc::router(pool.clone(), chain_id))
+            .merge(crate::graphql::router(pool.clone()))
+            .merge(crate::stream::router(pool.clone(), events))
+            .merge(crate::handlers::router(pool.clone()))
+            .merge(crate::dex::router(pool.clone()))
+            .merge(crate::pricing::router(pool.clone()))
+            .merge(crate::contracts::router(pool.clone()))
+            .merge(crate::gas::router(pool.clone()))
+            .merge(crate::stats::router(pool.clone()))
+            .merge(crate::search::router(pool.clone(), provider))
+            .merge(crate::export::router(pool.clone()));
+    } else {
+        tracing::info!(
+            "API: embedded storage; JSON-RPC, GraphQL, streaming, webhooks, event handlers, DEX, pricing, contract, gas, activity time series, search and export endpoints are disabled."
+        );
+    }
+
+    // Layered last so the middleware sees every route, including the admin ones. Routes that
+    // steer the ingester, run scripts in it or make it send requests elsewhere (webhook rules)
+    // exist only behind API keys.
+    match (auth, &postgres) {
+        (Some(config), Some(pool)) => {
+            let state = crate::auth::AuthState::new(pool.clone(), config);
+            crate::auth::spawn_usage_flusher(state.clone());
+            app = app
+                .merge(crate::auth::router(state.clone()))
+                .merge(crate::control::router(control, storage.clone()))
+                .merge(crate::handlers::admin_router(pool.clone()))
+                .merge(crate::webhooks::router(pool.clone()))
+                .layer(axum::middleware::from_fn_with_state(
+                    state,
+                    crate::auth::authenticate,
+                ));
+            tracing::info!("API: API key authentication on.");
+        }
+        (Some(_), None) => eyre::bail!("API_AUTH=true requires PostgreSQL storage."),
+        (None, Some(_)) => tracing::warn!(
+            "API: API KEY AUTHENTICATION IS OFF (API_AUTH=false). Every route is public to anyone who can reach port 3000; the /admin and /webhooks routes and handler changes are disabled. Unset API_AUTH to turn it back on."
+        ),
+        (None, None) => tracing::warn!(
+            "API: API KEY AUTHENTICATION IS OFF: SQLite storage has no API keys. Every route is public to anyone who can reach port 3000; the /admin and /webhooks routes and handler changes are disabled."
+        ),
+    }
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     println!("API: Server listening on http://{}", addr);
     println!("API: View Swagger UI at http://{}/swagger-ui", addr);
+    if postgres.is_some() {
+        println!("API: GraphiQL explorer at http://{}/graphql", addr);
+    }
 
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app)
-        .await
-        .map_err(|e| eyre::eyre!("API server error: {}", e))?;
+    // On shutdown, stop accepting connections and let open requests finish. Streams never
+    // finish on their own, so they get `SHUTDOWN_GRACE` before the server stops waiting.
+    let server = axum::serve(tokio::net::TcpListener::bind(addr).await?, app)
+        .with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move { shutdown.requested().await }
+        });
+    tokio::select! {
+        result = server => result.map_err(|e| eyre::eyre!("API server error: {}", e))?,
+        _ = async {
+            shutdown.requested().await;
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } => tracing::warn!(
+            "API: connections still open {}s after shutdown; closing them.",
+            SHUTDOWN_GRACE.as_secs()
+        ),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_filter_is_zero_or_one() {
+        assert_eq!(transaction_status(0).unwrap(), 0);
+        assert_eq!(transaction_status(1).unwrap(), 1);
+        for status in [2, 65_536, 65_537, u64::MAX] {
+            assert!(matches!(
+                transaction_status(status),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn transaction_addresses_are_validated() {
+        let address = "0xA0b86991c6218b36c1d19d4a2e9eb0ce3606eB48";
+        assert_eq!(
+            normalize_address(address, "from").unwrap(),
+            address.to_lowercase()
+        );
+        let filters = |value: serde_json::Value| {
+            serde_json::from_value::<GetTransactionsFilter>(value).unwrap()
+        };
+        for invalid in [
+            filters(serde_json::json!({ "from": "0x1234" })),
+            filters(serde_json::json!({ "to": "vitalik.eth" })),
+            filters(serde_json::json!({ "to": format!("{}'", &address[..41]) })),
+        ] {
+            let mut query_builder = QueryBuilder::new("SELECT 1 WHERE TRUE");
+            assert!(matches!(
+                push_transaction_filters(&mut query_builder, &invalid),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+        let mut query_builder = QueryBuilder::new("SELECT 1 WHERE TRUE");
+        let filters = filters(serde_json::json!({ "from": address, "to": address }));
+        push_transaction_filters(&mut query_builder, &filters).unwrap();
+        assert_eq!(
+            query_builder.sql(),
+            "SELECT 1 WHERE TRUE AND from_address = $1 AND to_address = $2"
+        );
+    }
+
+    #[test]
+    fn method_id_is_a_hex_selector() {
+        assert_eq!(normalize_method_id("0xA9059CBB").unwrap(), "0xa9059cbb");
+        for method_id in [
+            "a9059cbb",
+            "0xa9059cb",
+            "0xa9059cbbb",
+            "0xzz059cbb",
+            "0x'--;xxx",
+        ] {
+            assert!(
+                matches!(normalize_method_id(method_id), Err(ApiError::BadRequest(_))),
+                "{}",
+                method_id
+            );
+        }
+    }
+}
@@ -1,5 +1,6 @@
 // src/api_models.rs
-use serde::{Deserialize, Serialize}; // Add Serialize
+use serde::{Deserialize, Deserializer, Serialize}; // Add Serialize
+use std::str::FromStr;
 use utoipa::{IntoParams, ToSchema}; // Import IntoParams and ToSchema
 
 // Helper function to provide default for page
@@ -12,23 +13,191 @@ fn default_page_size() -> u64 {
     25
 }
 
+/// A JSON value that may be given either as a single item or as an array of items.
+/// Arrays are OR-ed together, mirroring the `eth_getLogs` filter object.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    pub fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_mut(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// A block bound as accepted by JSON-RPC: a number (decimal or 0x-hex) or a named tag.
+/// Tags are resolved against the indexer's own view of the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTag {
+    Number(u64),
+    Earliest,
+    Latest,
+    Safe,
+    Finalized,
+    Pending,
+}
+
+impl FromStr for BlockTag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "earliest" => Ok(BlockTag::Earliest),
+            "latest" => Ok(BlockTag::Latest),
+            "safe" => Ok(BlockTag::Safe),
+            "finalized" => Ok(BlockTag::Finalized),
+            "pending" => Ok(BlockTag::Pending),
+            _ => {
+                let parsed = match s.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => s.parse::<u64>(),
+                };
+                parsed
+                    .map(BlockTag::Number)
+                    .map_err(|_| format!("Invalid block number or tag: {}", s))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BlockTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(n) => Ok(BlockTag::Number(n)),
+            Raw::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
 // NOTE: This struct is used as the REQUEST BODY for the POST /logs endpoint.
-#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+// Field semantics follow the JSON-RPC `eth_getLogs` filter object.
+#[derive(Debug, Clone, Deserialize, ToSchema, IntoParams)]
 #[serde(rename_all = "camelCase")]
 pub struct GetLogsFilter {
+    /// Block number (decimal or 0x-hex) or one of `earliest`, `latest`, `safe`, `finalized`
+    #[schema(value_type = Option<String>, example = "18000000")]
+    #[param(value_type = Option<String>)]
+    pub from_block: Option<BlockTag>,
+    #[schema(value_type = Option<String>, example = "latest")]
+    #[param(value_type = Option<String>)]
+    pub to_block: Option<BlockTag>,
+    /// Unix timestamp (seconds); resolved to the first indexed block at or after it
+    #[schema(example = 1694035835)]
+    pub from_timestamp: Option<i64>,
+    /// Unix timestamp (seconds); resolved to the last indexed block at or before it
+    #[schema(example = 1694122235)]
+    pub to_timestamp: Option<i64>,
+    /// A single contract address or an array of addresses (OR)
+    #[schema(value_type = Option<Vec<String>>, example = json!(["0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"]))]
+    #[param(value_type = Option<Vec<String>>)]
+    pub address: Option<OneOrMany<String>>,
+    /// Positional topic filters; each position is null, a single value or an array (OR)
+    #[schema(value_type = Option<Vec<Vec<String>>>, example = json!([["0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"]]))]
+    #[param(value_type = Option<Vec<Vec<String>>>)]
+    pub topics: Option<Vec<Option<OneOrMany<String>>>>,
+    #[schema(value_type = Option<Vec<String>>, example = json!(["0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"]))]
+    #[param(value_type = Option<Vec<String>>)]
+    pub topic0: Option<OneOrMany<String>>,
+    #[schema(value_type = Option<Vec<String>>)]
+    #[param(value_type = Option<Vec<String>>)]
+    pub topic1: Option<OneOrMany<String>>,
+    #[schema(value_type = Option<Vec<String>>)]
+    #[param(value_type = Option<Vec<String>>)]
+    pub topic2: Option<OneOrMany<String>>,
+    #[schema(value_type = Option<Vec<String>>)]
+    #[param(value_type = Option<Vec<String>>)]
+    pub topic3: Option<OneOrMany<String>>,
+    #[schema(example = "0x...")]
+    pub block_hash: Option<String>,
+
+    // Pagination Fields
+    #[serde(default = "default_page")]
+    #[param(example = 1)]
+    pub page: u64,
+
+    #[serde(default = "default_page_size", alias = "limit")]
+    #[param(example = 25)]
+    pub page_size: u64,
+
+    // Cursor-based pagination fields (preferred over page/offset at scale)
+    // Use values from `next_cursor` in a previous response.
+    #[schema(example = 18000000)]
+    pub cursor_block: Option<i64>,
+    #[schema(example = 42)]
+    pub cursor_log_id: Option<i64>,
+
+    /// Add `addressEnsName` to each log, the emitting contract's primary ENS name (Postgres only)
+    #[serde(default)]
+    pub include_ens_names: bool,
+}
+
+impl GetLogsFilter {
+    /// Returns the filter for topic `position`, taking the `topics` array over `topicN`.
+    pub fn topic_filter(&self, position: usize) -> Option<&OneOrMany<String>> {
+        match &self.topics {
+            Some(topics) => topics.get(position).and_then(|t| t.as_ref()),
+            None => match position {
+                0 => self.topic0.as_ref(),
+                1 => self.topic1.as_ref(),
+                2 => self.topic2.as_ref(),
+                3 => self.topic3.as_ref(),
+                _ => None,
+            },
+        }
+    }
+}
+
+// NOTE: This struct is used as the REQUEST BODY for the POST /transactions endpoint.
+#[derive(Debug, Clone, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTransactionsFilter {
     #[schema(example = 18000000)]
     pub from_block: Option<u64>,
     #[schema(example = 18000100)]
     pub to_block: Option<u64>,
+    /// Unix timestamp (seconds); resolved to the first indexed block at or after it
+    #[schema(example = 1694035835)]
+    pub from_timestamp: Option<i64>,
+    /// Unix timestamp (seconds); resolved to the last indexed block at or before it
+    #[schema(example = 1694122235)]
+    pub to_timestamp: Option<i64>,
+    #[schema(example = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045")]
+    pub from: Option<String>,
     #[schema(example = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")]
-    pub address: Option<String>,
-    #[schema(example = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")]
-    pub topic0: Option<String>,
-    pub topic1: Option<String>,
-    pub topic2: Option<String>,
-    pub topic3: Option<String>,
-    #[schema(example = "0x...")]
-    pub block_hash: Option<String>,
+    pub to: Option<String>,
+    /// Receipt status: 1 = success, 0 = reverted
+    #[schema(example = 1)]
+    pub status: Option<u64>,
+    /// Minimum value in wei (inclusive, decimal string)
+    #[schema(example = "1000000000000000000")]
+    pub min_value: Option<String>,
+    /// Maximum value in wei (inclusive, decimal string)
+    pub max_value: Option<String>,
+    /// 4-byte function selector matched against the start of the input data
+    #[schema(example = "0xa9059cbb")]
+    pub method_id: Option<String>,
+    /// true = only contract creations (no `to`), false = exclude them
+    pub contract_creation: Option<bool>,
 
     // Pagination Fields
     #[serde(default = "default_page")]
@@ -39,14 +208,147 @@ pub struct GetLogsFilter {
     #[param(example = 25)]
     pub page_size: u64,
 
-    // Cursor-based pagination fields (preferred over page/offset at scale)
-    // Use values from `next_cursor` in a previous response.
+    // Cursor-based pagination fields, same semantics as GetLogsFilter.
     #[schema(example = 18000000)]
     pub cursor_block: Option<i64>,
     #[schema(example = 42)]
+    pub cursor_tx_index: Option<i64>,
+
+    /// Add `valueUsd` to each transaction, priced at ETH/USD in its block (Postgres only)
+    #[serde(default)]
+    pub include_usd: bool,
+
+    /// Add `fromEnsName`/`toEnsName`, the primary ENS names of both parties (Postgres only)
+    #[serde(default)]
+    pub include_ens_names: bool,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /stream/logs (Server-Sent Events).
+// Lists are comma-separated because query strings cannot carry the nested JSON filter.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct StreamLogsQuery {
+    /// Replay matching logs from this block before switching to live updates
+    #[param(example = "18000000")]
+    pub from_block: Option<String>,
+    /// Comma-separated contract addresses (OR)
+    pub address: Option<String>,
+    /// Comma-separated topic values for each position (OR)
+    pub topic0: Option<String>,
+    pub topic1: Option<String>,
+    pub topic2: Option<String>,
+    pub topic3: Option<String>,
+    /// Resume after this cursor; the `Last-Event-ID` header takes precedence when present
+    pub cursor_block: Option<i64>,
     pub cursor_log_id: Option<i64>,
 }
 
+impl StreamLogsQuery {
+    pub fn into_filter(self) -> Result<GetLogsFilter, String> {
+        let list = |v: Option<String>| {
+            v.map(|s| OneOrMany::Many(s.split(',').map(|p| p.trim().to_string()).collect()))
+        };
+        Ok(GetLogsFilter {
+            from_block: self.from_block.map(|b| b.parse()).transpose()?,
+            to_block: None,
+            from_timestamp: None,
+            to_timestamp: None,
+            address: list(self.address),
+            topics: None,
+            topic0: list(self.topic0),
+            topic1: list(self.topic1),
+            topic2: list(self.topic2),
+            topic3: list(self.topic3),
+            block_hash: None,
+            page: default_page(),
+            page_size: default_page_size(),
+            cursor_block: self.cursor_block,
+            cursor_log_id: self.cursor_log_id,
+            include_ens_names: false,
+        })
+    }
+}
+
+/// A message pushed to `/stream/logs` subscribers (SSE event data or WebSocket text frame).
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum StreamMessage {
+    /// A newly committed log matching the subscription; the cursor resumes after it.
+    #[serde(rename_all = "camelCase")]
+    Log {
+        cursor_block: i64,
+        cursor_log_id: i64,
+        log: Box<crate::models::MyLog>,
+    },
+    /// Data at or above `removed_from_block` was rolled back by a reorg; discard it.
+    #[serde(rename_all = "camelCase")]
+    Reorg { removed_from_block: u64 },
+    /// Data in `removed_from_block..=removed_to_block` was deleted to be re-indexed; discard
+    /// it. Its logs are sent again right after, while data above the range stays valid.
+    #[serde(rename_all = "camelCase")]
+    Retract {
+        removed_from_block: u64,
+        removed_to_block: u64,
+    },
+    /// The subscription failed and the stream is closing.
+    Error { message: String },
+}
+
+// Helper function to provide default for block list limit
+fn default_block_limit() -> u64 {
+    10
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /blocks endpoint.
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct GetBlocksQuery {
+    #[param(example = 18000000)]
+    pub from_block: Option<u64>,
+    #[param(example = 18000100)]
+    pub to_block: Option<u64>,
+    /// Only blocks at or after this Unix timestamp (seconds)
+    #[param(example = 1694035835)]
+    pub from_timestamp: Option<i64>,
+    /// Only blocks at or before this Unix timestamp (seconds)
+    #[param(example = 1694122235)]
+    pub to_timestamp: Option<i64>,
+    /// Number of blocks to return (newest first)
+    #[serde(default = "default_block_limit")]
+    #[param(example = 10)]
+    pub limit: u64,
+    /// Return blocks strictly below this number; use `next_cursor_block` from a previous response.
+    #[param(example = 18000090)]
+    pub cursor_block: Option<i64>,
+}
+
+/// Which side of a timestamp `GET /block/by-timestamp` resolves to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Closest {
+    /// The last indexed block at or before the timestamp
+    #[default]
+    Before,
+    /// The first indexed block at or after the timestamp
+    After,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /block/by-timestamp endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct BlockByTimestampQuery {
+    /// Unix timestamp (seconds)
+    #[param(example = 1694035835)]
+    pub ts: i64,
+    /// before (default) or after
+    #[serde(default)]
+    #[param(value_type = Option<String>, example = "before")]
+    pub closest: Closest,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct IndexerStats {
     pub total_blocks: i64,
@@ -65,6 +367,21 @@ pub struct LogsResponse {
     pub next_cursor_log_id: Option<i64>,
 }
 
+/// Cursor returned with paginated transaction responses for stable iteration.
+#[derive(Serialize, ToSchema)]
+pub struct TransactionsResponse {
+    pub transactions: Vec<crate::models::MyTransaction>,
+    pub next_cursor_block: Option<i64>,
+    pub next_cursor_tx_index: Option<i64>,
+}
+
+/// Cursor returned with block listings; pass it back as `cursorBlock` for older blocks.
+#[derive(Serialize, ToSchema)]
+pub struct BlocksResponse {
+    pub blocks: Vec<crate::models::MyBlock>,
+    pub next_cursor_block: Option<i64>,
+}
+
 // A generic, serializable error response struct for consistent API errors.
 #[derive(Serialize, ToSchema)]
 pub struct GenericErrorResponse {
@@ -74,3 +391,896 @@ pub struct GenericErrorResponse {
     #[schema(example = "Resource not found")]
     pub message: String,
 }
+
+/// Body of `POST /webhooks/rules` and `PUT /webhooks/rules/{id}`.
+/// At least one of `address`, `topic0` or `methodId` must be set; set fields are AND-ed.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRuleRequest {
+    #[schema(example = "Treasury outflows")]
+    pub name: String,
+    /// Matches transactions sent from or to this address, and logs emitted by it.
+    #[schema(example = "0xdac17f958d2ee523a2206206994597c13d831ec7")]
+    pub address: Option<String>,
+    /// Matches logs whose event signature (topic0) equals this value.
+    #[schema(example = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")]
+    pub topic0: Option<String>,
+    /// Matches transactions whose calldata starts with this 4-byte selector.
+    #[schema(example = "0xa9059cbb")]
+    pub method_id: Option<String>,
+    #[schema(example = "https://ops.example.com/hooks/indexer")]
+    pub target_url: String,
+    /// HMAC-SHA256 key for the `X-Webhook-Signature` header. Required on create;
+    /// omit on update to keep the current secret.
+    pub secret: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A stored webhook rule. The signing secret is never returned.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRule {
+    pub id: i64,
+    pub name: String,
+    pub address: Option<String>,
+    pub topic0: Option<String>,
+    pub method_id: Option<String>,
+    pub target_url: String,
+    pub enabled: bool,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+}
+
+/// A delivery that exhausted its retries.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDeadLetter {
+    pub id: i64,
+    pub delivery_id: i64,
+    pub rule_id: i64,
+    pub target_url: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+}
+
+// NOTE: This struct is used as the REQUEST BODY for POST /handlers and PUT /handlers/{id}.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EventHandlerRequest {
+    #[schema(example = "usdc-balances")]
+    pub name: String,
+    /// Contract whose logs the handler receives.
+    #[schema(example = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")]
+    pub address: String,
+    /// Event declaration with `indexed` markers and parameter names.
+    #[schema(example = "Transfer(address indexed from, address indexed to, uint256 value)")]
+    pub event_signature: String,
+    /// Rhai source defining `fn handle(event)`.
+    #[schema(
+        example = "fn handle(event) {\n  let to = load(\"balances\", event.params.to) ?? #{ amount: \"0\" };\n  to.amount = big_add(to.amount, event.params.value);\n  upsert(\"balances\", event.params.to, to);\n}"
+    )]
+    pub script: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// A registered event handler. `lastError` is set when the script failed; the handler is
+/// skipped until it is updated.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EventHandler {
+    pub id: i64,
+    pub name: String,
+    pub address: String,
+    pub event_signature: String,
+    pub topic0: String,
+    pub enabled: bool,
+    pub failed_at_block: Option<i64>,
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+}
+
+/// The current version of one row in a handler's entity table.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HandlerEntity {
+    pub key: String,
+    /// Block in which this version was written.
+    pub block_number: i64,
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /handlers/{id}/entities/{entity} endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetHandlerEntitiesQuery {
+    /// Return keys after this one (the last key of the previous page)
+    pub after: Option<String>,
+    #[serde(default = "default_dead_letter_limit")]
+    #[param(example = 50, maximum = 100)]
+    pub limit: u64,
+}
+
+/// A pool in the DEX registry. Reserves come from the pool's latest Sync event and are only
+/// reported for V2-style pools.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DexPool {
+    #[schema(example = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc")]
+    pub pool_address: String,
+    /// `uniswap_v2` or `uniswap_v3`; forks are listed under the protocol they mirror.
+    #[schema(example = "uniswap_v2")]
+    pub protocol: String,
+    pub factory_address: Option<String>,
+    pub token0: String,
+    pub token1: String,
+    /// V3 fee tier in hundredths of a basis point.
+    pub fee: Option<i32>,
+    pub tick_spacing: Option<i32>,
+    /// Block of the creation event, when it was indexed.
+    pub created_block: Option<i64>,
+    pub reserve0: Option<String>,
+    pub reserve1: Option<String>,
+    pub reserves_block: Option<i64>,
+}
+
+/// A decoded swap. `amount0`/`amount1` are signed raw token units from the pool's side
+/// (positive flowed into the pool); `tokenIn`/`tokenOut` restate them from the trader's side.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DexSwap {
+    pub log_id: i64,
+    pub block_number: i64,
+    /// Unix timestamp (seconds) of the block.
+    pub block_timestamp: i64,
+    pub tx_hash: String,
+    pub log_index: Option<i64>,
+    pub pool_address: String,
+    pub protocol: String,
+    pub token0: String,
+    pub token1: String,
+    /// Sender of the transaction.
+    pub trader: String,
+    /// `sender` from the event, usually a router contract.
+    pub sender: String,
+    pub recipient: String,
+    #[schema(example = "-2500000000")]
+    pub amount0: String,
+    #[schema(example = "1000000000000000000")]
+    pub amount1: String,
+    pub token_in: String,
+    pub amount_in: String,
+    pub token_out: String,
+    pub amount_out: String,
+    /// V3 only: pool state after the swap.
+    pub sqrt_price_x96: Option<String>,
+    pub liquidity: Option<String>,
+    pub tick: Option<i32>,
+}
+
+/// Cursor returned with swap listings; pass both values back to continue.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DexSwapsResponse {
+    pub swaps: Vec<DexSwap>,
+    pub next_cursor_block: Option<i64>,
+    pub next_cursor_log_id: Option<i64>,
+}
+
+/// A Mint, Burn or Sync. For `sync` the amounts are the pool's reserves after the event.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DexLiquidityEvent {
+    pub log_id: i64,
+    pub block_number: i64,
+    pub block_timestamp: i64,
+    pub tx_hash: String,
+    pub log_index: Option<i64>,
+    pub pool_address: String,
+    #[schema(example = "mint")]
+    pub kind: String,
+    /// V3 position owner.
+    pub owner: Option<String>,
+    pub sender: Option<String>,
+    /// V2 burns: where the withdrawn tokens went.
+    pub recipient: Option<String>,
+    pub amount0: String,
+    pub amount1: String,
+    /// V3 only: liquidity added or removed, and the position's tick range.
+    pub liquidity: Option<String>,
+    pub tick_lower: Option<i32>,
+    pub tick_upper: Option<i32>,
+}
+
+/// Cursor returned with liquidity event listings.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DexLiquidityResponse {
+    pub events: Vec<DexLiquidityEvent>,
+    pub next_cursor_block: Option<i64>,
+    pub next_cursor_log_id: Option<i64>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /dex/pools endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDexPoolsQuery {
+    /// Only pools with this token on either side
+    pub token: Option<String>,
+    /// `uniswap_v2` or `uniswap_v3`
+    pub protocol: Option<String>,
+    /// Return pools after this address (the last one of the previous page)
+    pub after: Option<String>,
+    #[serde(default = "default_page_size")]
+    #[param(example = 25, maximum = 100)]
+    pub limit: u64,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the swap and liquidity listings under /dex.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDexEventsQuery {
+    #[param(example = 18000000)]
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    /// Unix timestamp (seconds), inclusive
+    #[param(example = 1694035835)]
+    pub from_time: Option<i64>,
+    pub to_time: Option<i64>,
+    /// `nextCursorBlock` from the previous page
+    pub cursor_block: Option<i64>,
+    /// `nextCursorLogId` from the previous page
+    pub cursor_log_id: Option<i64>,
+    #[serde(default = "default_page_size")]
+    #[param(example = 25, maximum = 100)]
+    pub limit: u64,
+}
+
+/// A token's ETH price as of a block, taken from the deepest WETH pool that traded in that
+/// block (or minute). `priceUsd` multiplies it by the ETH/USD price at the same point.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPrice {
+    #[schema(example = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")]
+    pub token_address: String,
+    #[schema(example = 0.00041)]
+    pub price_eth: f64,
+    #[schema(example = 1.0)]
+    pub price_usd: Option<f64>,
+    /// Block of the observation; later blocks reuse it until the token trades again.
+    pub block_number: i64,
+    pub block_timestamp: i64,
+    pub pool_address: String,
+    /// WETH depth of the pool at the time, in ETH.
+    pub liquidity_eth: f64,
+}
+
+/// ETH/USD as of a block, from the configured stablecoin pools (stablecoins count as $1).
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EthUsdPrice {
+    #[schema(example = 2450.12)]
+    pub price_usd: f64,
+    pub block_number: i64,
+    pub block_timestamp: i64,
+    pub pool_address: String,
+    pub liquidity_eth: f64,
+}
+
+/// Cursor returned with price history; pass it back as `cursorBlock`.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPriceHistoryResponse {
+    pub prices: Vec<TokenPrice>,
+    pub next_cursor_block: Option<i64>,
+}
+
+/// An ERC-20 Transfer to or from the account. `amount` is in raw token units.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenTransfer {
+    pub log_id: i64,
+    pub block_number: i64,
+    pub block_timestamp: Option<i64>,
+    pub tx_hash: String,
+    pub log_index: Option<i64>,
+    pub token_address: String,
+    pub from: String,
+    pub to: String,
+    /// `in`, `out` or `self`
+    #[schema(example = "in")]
+    pub direction: String,
+    #[schema(example = "2500000000")]
+    pub amount: String,
+    /// Known for tokens that trade against WETH in an indexed pool.
+    pub decimals: Option<i32>,
+    /// With `usd=true`: the amount valued at the token's price in the transfer's block.
+    pub amount_usd: Option<f64>,
+}
+
+/// Cursor returned with transfer listings; pass both values back to continue.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenTransfersResponse {
+    pub transfers: Vec<TokenTransfer>,
+    pub next_cursor_block: Option<i64>,
+    pub next_cursor_log_id: Option<i64>,
+}
+
+/// Net amount of one token received minus sent by the account in indexed Transfer events.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenBalance {
+    pub token_address: String,
+    #[schema(example = "1250000000")]
+    pub balance: String,
+    pub decimals: Option<i32>,
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountBalancesResponse {
+    pub address: String,
+    /// Balances include transfers up to and including this block.
+    pub block_number: i64,
+    /// With `usd=true`: sum of the balances that have a price.
+    pub total_usd: Option<f64>,
+    pub balances: Vec<TokenBalance>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /prices endpoints.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPriceQuery {
+    /// Price as of this block (default: latest indexed block)
+    #[param(example = 18000000)]
+    pub block: Option<i64>,
+    /// Price as of the last block at or before this Unix timestamp (seconds)
+    pub time: Option<i64>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /prices/tokens/{address}/history.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPriceHistoryQuery {
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    /// Unix timestamp (seconds), inclusive
+    pub from_time: Option<i64>,
+    pub to_time: Option<i64>,
+    /// `nextCursorBlock` from the previous page
+    pub cursor_block: Option<i64>,
+    #[serde(default = "default_page_size")]
+    #[param(example = 25, maximum = 100)]
+    pub limit: u64,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /accounts/{address}/transfers.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTransfersQuery {
+    /// Only transfers of this token
+    pub token: Option<String>,
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    /// Unix timestamp (seconds), resolved to the first indexed block at or after it
+    pub from_timestamp: Option<i64>,
+    /// Unix timestamp (seconds), resolved to the last indexed block at or before it
+    pub to_timestamp: Option<i64>,
+    /// `nextCursorBlock` from the previous page
+    pub cursor_block: Option<i64>,
+    /// `nextCursorLogId` from the previous page
+    pub cursor_log_id: Option<i64>,
+    #[serde(default = "default_page_size")]
+    #[param(example = 25, maximum = 100)]
+    pub limit: u64,
+    /// Value each transfer in USD
+    #[serde(default)]
+    pub usd: bool,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /accounts/{address}/balances.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBalancesQuery {
+    /// Balances as of this block (default: latest indexed block)
+    pub block: Option<i64>,
+    /// Balances as of the last block at or before this Unix timestamp (seconds)
+    pub time: Option<i64>,
+    /// Value each balance in USD at the same block
+    #[serde(default)]
+    pub usd: bool,
+}
+
+/// One block, hour or day of fee-market history. Wei amounts are decimal strings; for hours
+/// and days `baseFeePerGas` is the mean over the bucket's blocks.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GasHistoryEntry {
+    /// Block number, or the Unix timestamp the hour or day starts at.
+    pub bucket: i64,
+    pub first_block: i64,
+    pub last_block: i64,
+    pub blocks: i64,
+    #[schema(example = "12000000000")]
+    pub base_fee_per_gas: Option<String>,
+    pub base_fee_min: Option<String>,
+    pub base_fee_max: Option<String>,
+    pub gas_used: String,
+    /// Gas used over gas limit, 0 to 1.
+    pub utilization: f64,
+    pub tx_count: i64,
+    /// Priority fees at `rewardPercentiles`, weighted by gas used. Null when the block's
+    /// receipts were not all indexed.
+    pub priority_fees: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GasHistoryResponse {
+    #[schema(example = "block")]
+    pub resolution: String,
+    #[schema(example = json!([10, 25, 50, 75, 90]))]
+    pub reward_percentiles: Vec<u8>,
+    pub entries: Vec<GasHistoryEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GasSuggestion {
+    pub max_priority_fee_per_gas: String,
+    /// Twice the next base fee plus the priority fee.
+    pub max_fee_per_gas: String,
+}
+
+/// Suggested fees for the next block. Suggestions are null until a sampled block has
+/// priority fees.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GasOracle {
+    /// Latest indexed block.
+    pub block_number: i64,
+    pub base_fee_per_gas: Option<String>,
+    /// EIP-1559 projection from the latest block's base fee and gas used.
+    pub next_base_fee_per_gas: Option<String>,
+    /// Recent blocks with priority fees the suggestions were taken from.
+    pub sampled_blocks: u64,
+    pub slow: Option<GasSuggestion>,
+    pub standard: Option<GasSuggestion>,
+    pub fast: Option<GasSuggestion>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractGasUsage {
+    pub address: String,
+    pub gas_used: String,
+    /// Fees paid by the transactions, in wei.
+    pub fees_paid: String,
+    pub tx_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GasContractsResponse {
+    pub from_time: i64,
+    pub to_time: i64,
+    pub contracts: Vec<ContractGasUsage>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /gas/history.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetGasHistoryQuery {
+    /// `block` (default), `hour` or `day`
+    #[param(example = "hour")]
+    pub resolution: Option<String>,
+    /// Newest block to include (block resolution; default: latest)
+    pub to_block: Option<i64>,
+    /// Unix timestamp in the newest hour or day to include (default: latest)
+    pub to_time: Option<i64>,
+    /// Number of buckets (default 100)
+    #[param(maximum = 1024)]
+    pub limit: Option<u64>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /gas/oracle.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetGasOracleQuery {
+    /// Recent blocks to sample (default 20)
+    #[param(maximum = 1024)]
+    pub blocks: Option<u64>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /gas/contracts.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetGasContractsQuery {
+    /// Unix timestamp (seconds); default 24 hours before `toTime`
+    pub from_time: Option<i64>,
+    /// Unix timestamp (seconds), exclusive; default the end of the latest indexed hour
+    pub to_time: Option<i64>,
+    #[param(example = 25, maximum = 100)]
+    pub limit: Option<u64>,
+}
+
+/// Activity in one hour or day. The chain-wide fields are null when the series is filtered by
+/// contract or topic0.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsTimeseriesEntry {
+    /// Unix timestamp the hour or day (UTC) starts at.
+    pub bucket: i64,
+    pub blocks: Option<i64>,
+    pub tx_count: Option<i64>,
+    /// Distinct transaction senders within the bucket.
+    pub unique_senders: Option<i64>,
+    /// Value moved by successful transactions, in wei.
+    #[schema(example = "1523000000000000000000")]
+    pub eth_volume: Option<String>,
+    pub log_count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsTimeseriesResponse {
+    #[schema(example = "hour")]
+    pub resolution: String,
+    pub contract: Option<String>,
+    pub topic0: Option<String>,
+    pub entries: Vec<StatsTimeseriesEntry>,
+}
+
+// NOTE: This struct is used as the QUERY STRING for GET /stats/timeseries.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetStatsTimeseriesQuery {
+    /// `hour` (default) or `day`
+    #[param(example = "day")]
+    pub resolution: Option<String>,
+    /// Unix timestamp in the oldest bucket to include
+    pub from_time: Option<i64>,
+    /// Unix timestamp in the newest bucket to include (default: latest)
+    pub to_time: Option<i64>,
+    /// Number of buckets (default 100)
+    #[param(maximum = 1024)]
+    pub limit: Option<u64>,
+    /// Count only logs emitted by this contract
+    #[param(example = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48")]
+    pub contract: Option<String>,
+    /// Count only logs with this topic0
+    #[param(example = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")]
+    pub topic0: Option<String>,
+}
+
+/// A contract deployed in an indexed block. `implementationAddress` follows the proxy's
+/// indexed `Upgraded` events (its beacon's, for beacon proxies) and falls back to what was
+/// read at deployment.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Contract {
+    #[schema(example = "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984")]
+    pub address: String,
+    /// Sender of the creating transaction.
+    pub deployer: String,
+    /// The contract that ran CREATE/CREATE2, for contracts deployed by another contract.
+    pub factory_address: Option<String>,
+    pub creation_tx_hash: String,
+    pub block_number: i64,
+    /// Unix timestamp (seconds) of the block.
+    pub block_timestamp: i64,
+    /// keccak256 of the runtime bytecode; null when no code was left at the address.
+    pub code_hash: Option<String>,
+    /// Runtime bytecode size in bytes.
+    pub code_size: i32,
+    /// `eip1167`, `eip1967` or `eip1967_beacon`; null for contracts not recognised as proxies.
+    #[schema(example = "eip1967")]
+    pub proxy_type: Option<String>,
+    pub implementation_address: Option<String>,
+    pub beacon_address: Option<String>,
+    /// The contract's own `Upgraded`/`BeaconUpgraded` events, oldest first.
+    pub upgrades: Vec<ContractUpgrade>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractUpgrade {
+    pub log_id: i64,
+    pub block_number: i64,
+    pub block_timestamp: i64,
+    pub tx_hash: String,
+    /// `implementation` (Upgraded) or `beacon` (BeaconUpgraded).
+    #[schema(example = "implementation")]
+    pub kind: String,
+    /// The new implementation or beacon.
+    pub target_address: String,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /search endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Block number, block or transaction hash, address, token symbol or ENS name
+    #[param(example = "vitalik.eth")]
+    pub q: String,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    /// The query as searched, trimmed.
+    pub query: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    /// `block`, `transaction`, `contract`, `token`, `address` or `ens`.
+    #[schema(example = "ens")]
+    pub kind: String,
+    /// Block hash, transaction hash or address of the match.
+    #[schema(example = "0xd8da6bf26964af9d7eed9e03e53415d37aa96045")]
+    pub value: String,
+    /// Token symbol or ENS name, where there is one.
+    #[schema(example = "vitalik.eth")]
+    pub label: Option<String>,
+    /// The block, or the block a transaction or contract deployment is in.
+    pub block_number: Option<i64>,
+    /// Endpoint serving the matched resource.
+    #[schema(example = "/accounts/0xd8da6bf26964af9d7eed9e03e53415d37aa96045/transfers")]
+    pub link: String,
+}
+
+/// Output format for the export endpoints and CLI.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+// NOTE: This struct is used as the QUERY STRING for the POST /export/* endpoints.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportParams {
+    /// csv (default), ndjson or parquet
+    #[serde(default)]
+    #[param(value_type = String, example = "parquet")]
+    pub format: ExportFormat,
+}
+
+// NOTE: This struct is used as the REQUEST BODY for the POST /export/blocks endpoint.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportBlocksFilter {
+    #[schema(example = 18000000)]
+    pub from_block: Option<u64>,
+    #[schema(example = 18010000)]
+    pub to_block: Option<u64>,
+    /// Unix timestamp (seconds); resolved to the first indexed block at or after it
+    #[schema(example = 1694035835)]
+    pub from_timestamp: Option<i64>,
+    /// Unix timestamp (seconds); resolved to the last indexed block at or before it
+    #[schema(example = 1694122235)]
+    pub to_timestamp: Option<i64>,
+}
+
+// Helper function to provide default for dead-letter list limit
+fn default_dead_letter_limit() -> u64 {
+    50
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /webhooks/dead-letters endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDeadLettersQuery {
+    /// Only dead letters for this rule
+    pub rule_id: Option<i64>,
+    #[serde(default = "default_dead_letter_limit")]
+    #[param(example = 50, maximum = 100)]
+    pub limit: u64,
+}
+
+/// JSON body POSTed to a rule's `targetUrl`.
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum WebhookPayload {
+    /// Transactions and logs in one committed block that matched the rule.
+    #[serde(rename_all = "camelCase")]
+    Match {
+        rule_id: i64,
+        rule_name: String,
+        block_number: u64,
+        block_hash: String,
+        transactions: Vec<crate::models::MyTransaction>,
+        logs: Vec<crate::models::MyLog>,
+    },
+    /// A previously delivered match was rolled back by a reorg and should be discarded.
+    #[serde(rename_all = "camelCase")]
+    Retraction {
+        rule_id: i64,
+        rule_name: String,
+        block_number: u64,
+        block_hash: String,
+        retracted_delivery_id: i64,
+    },
+}
+
+// NOTE: This struct is used as the REQUEST BODY for the POST /admin/keys endpoint.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyRequest {
+    /// Who the key is for, e.g. the partner team.
+    #[schema(example = "analytics-team")]
+    pub name: String,
+    /// Any of `read`, `export` and `admin`; `admin` includes the other two.
+    #[serde(default = "default_api_key_scopes")]
+    #[schema(example = json!(["read", "export"]))]
+    pub scopes: Vec<String>,
+    /// Requests per minute; null for no limit.
+    #[serde(default = "default_api_key_rate_limit")]
+    #[schema(example = 600)]
+    pub rate_limit_per_minute: Option<i32>,
+    /// Requests per UTC day; null for no quota.
+    #[schema(example = 100000)]
+    pub daily_quota: Option<i64>,
+}
+
+fn default_api_key_scopes() -> Vec<String> {
+    vec!["read".to_string()]
+}
+
+fn default_api_key_rate_limit() -> Option<i32> {
+    Some(600)
+}
+
+/// An issued API key. Only its hash is stored, so the key itself is returned once.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// The first characters of the key, to tell keys apart.
+    #[schema(example = "evmi_3f9a1c2")]
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_per_minute: Option<i32>,
+    pub daily_quota: Option<i64>,
+    /// Unix timestamp (seconds).
+    pub created_at: i64,
+    /// Unix timestamp (seconds) of the last recorded use.
+    pub last_used_at: Option<i64>,
+    /// Unix timestamp (seconds); revoked keys are rejected.
+    pub revoked_at: Option<i64>,
+    /// The key to send in `X-API-Key` or `Authorization: Bearer`. Only set on creation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+// Helper function to provide default for the usage window
+fn default_usage_days() -> u32 {
+    7
+}
+
+// NOTE: This struct is used as the QUERY STRING for the GET /admin/keys/{id}/usage endpoint.
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct GetApiKeyUsageQuery {
+    /// UTC days to include, counting today
+    #[serde(default = "default_usage_days")]
+    #[param(example = 7, maximum = 90)]
+    pub days: u32,
+}
+
+/// Requests one key made to one endpoint on one UTC day.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyUsage {
+    #[schema(example = "2024-05-01")]
+    pub day: String,
+    /// Method and route, e.g. `POST /logs`.
+    #[schema(example = "POST /logs")]
+    pub endpoint: String,
+    pub requests: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyUsageResponse {
+    pub key_id: i64,
+    /// Requests today across all endpoints, checked against `dailyQuota`.
+    pub requests_today: i64,
+    /// Newest day first.
+    pub usage: Vec<ApiKeyUsage>,
+}
+
+/// A block range, both ends inclusive.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockRange {
+    #[schema(example = 19000000)]
+    pub from_block: u64,
+    #[schema(example = 19000099)]
+    pub to_block: u64,
+}
+
+/// What the ingester is doing and how it is tuned.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IngesterStatus {
+    /// One of `starting`, `syncing`, `idle`, `paused`, `rewinding`, `reindexing`,
+    /// `retrying`, `stopping` and `down`.
+    #[schema(example = "syncing")]
+    pub phase: String,
+    /// Set by `POST /admin/ingester/pause`. A paused ingester still runs queued rewinds and
+    /// re-indexes.
+    pub paused: bool,
+    /// Upper bound on blocks pushed through the pipeline per cycle.
+    #[schema(example = 50)]
+    pub batch_size: u64,
+    /// Blocks whose headers and receipts are fetched at once.
+    #[schema(example = 4)]
+    pub blocks_in_flight: usize,
+    pub last_synced_block: Option<u64>,
+    /// Chain head as of the last cycle.
+    pub chain_head: Option<u64>,
+    /// Blocks being synced, rewound or re-indexed.
+    pub current_range: Option<BlockRange>,
+    /// Rewinds and re-indexes waiting to run.
+    pub pending_commands: usize,
+    /// The last error that made the ingester retry, cleared by the next successful cycle.
+    pub last_error: Option<String>,
+    /// Unix timestamp (seconds) the current phase started.
+    pub phase_since: i64,
+}
+
+// NOTE: This struct is used as the REQUEST BODY for the POST /admin/ingester/rewind endpoint.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RewindRequest {
+    /// First block to delete; the ingester re-syncs from here.
+    #[schema(example = 19000000)]
+    pub height: u64,
+}
+
+// NOTE: This struct is used as the REQUEST BODY for the PUT /admin/ingester/config endpoint.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IngesterConfigRequest {
+    /// Blocks per cycle, 1 to 1000; unchanged when omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 100)]
+    pub batch_size: Option<u64>,
+    /// Blocks fetched at once, 1 to 64; unchanged when omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 8)]
+    pub blocks_in_flight: Option<usize>,
+}
+
+/// Returned by `GET /ready`.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    pub ready: bool,
+    /// The ingester's phase, as in `GET /admin/ingester`.
+    #[schema(example = "syncing")]
+    pub phase: String,
+    pub shutting_down: bool,
+}
@@ -0,0 +1,1004 @@
+// src/auth.rs
+//
+// API key authentication, on by default with Postgres storage and turned off only by an
+// explicit `API_AUTH=false`. Every route except the welcome page, the health probes and the
+// API docs then needs a key, sent as `X-API-Key`, `Authorization: Bearer`
+// or, for WebSocket clients that cannot set headers, an `apiKey` query parameter. Keys are
+// issued and revoked through `/admin/keys` and only their SHA-256 hashes are stored in
+// `api_keys`.
+//
+// Each key has scopes (`read`, `export`, `admin`), a per-minute rate limit and a daily quota.
+// Requests are counted in memory per key and endpoint and flushed to `api_key_usage` every few
+// seconds; the same counts back the quota, seeded from the table at the start of each day.
+// Rate limits are per API process. `ADMIN_API_KEY` is an unmetered admin key kept outside the
+// database, for issuing the first keys.
+
+use crate::api::{ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{
+    ApiKey, ApiKeyRequest, ApiKeyUsage, ApiKeyUsageResponse, GenericErrorResponse,
+    GetApiKeyUsageQuery,
+};
+use axum::{
+    extract::{MatchedPath, Path, Query, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get},
+    Router,
+};
+use ethers::core::rand::{thread_rng, RngCore};
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, PgPool, Row as SqlxRow};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const KEY_PREFIX: &str = "evmi_";
+/// Hex characters after `KEY_PREFIX`: 32 random bytes.
+const KEY_SECRET_HEX_LEN: usize = 64;
+/// Characters of a key kept in `key_prefix`.
+const DISPLAY_PREFIX_LEN: usize = 12;
+const MIN_ADMIN_KEY_LEN: usize = 32;
+/// How long a looked-up key is trusted before it is read again; bounds how long a key revoked
+/// through another API process keeps working.
+const KEY_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Keys kept in the cache at once. Only keys that exist are cached, so this is only reached
+/// with that many keys in active use.
+const MAX_CACHED_KEYS: usize = 10_000;
+const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_USAGE_DAYS: u32 = 90;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+const KEY_COLUMNS: &str = "id, name, key_prefix, scopes, rate_limit_per_minute, daily_quota, \
+     EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at, \
+     EXTRACT(EPOCH FROM last_used_at)::BIGINT AS last_used_at, \
+     EXTRACT(EPOCH FROM revoked_at)::BIGINT AS revoked_at";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Read,
+    Export,
+    Admin,
+}
+
+impl Scope {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Scope::Read),
+            "export" => Some(Scope::Export),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Export => "export",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+/// The scope a request needs, or `None` for public routes. Webhook rules and changes to event
+/// handlers alter what the server does, so they are admin routes like key management.
+fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    let under = |prefix: &str| {
+        path == prefix
+            || path
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('/'))
+    };
+    if path == "/"
+        || path == "/health"
+        || path == "/ready"
+        || under("/swagger-ui")
+        || under("/api-docs")
+    {
+        None
+    } else if under("/admin") || under("/webhooks") || (under("/handlers") && method != Method::GET)
+    {
+        Some(Scope::Admin)
+    } else if under("/export") {
+        Some(Scope::Export)
+    } else {
+        Some(Scope::Read)
+    }
+}
+
+/// Read from the environment at startup.
+pub struct AuthConfig {
+    admin_key_hash: Option<String>,
+}
+
+impl AuthConfig {
+    /// `None` when authentication is off: see `auth_enabled`. `ADMIN_API_KEY`, if set, must be
+    /// at least 32 characters.
+    pub fn from_env(postgres: bool) -> eyre::Result<Option<Self>> {
+        if !auth_enabled(env::var("API_AUTH").ok().as_deref(), postgres)? {
+            return Ok(None);
+        }
+        let admin_key_hash = match env::var("ADMIN_API_KEY") {
+            Ok(key) if key.is_empty() => None,
+            Ok(key) if key.len() < MIN_ADMIN_KEY_LEN => eyre::bail!(
+                "ADMIN_API_KEY must be at least {} characters.",
+                MIN_ADMIN_KEY_LEN
+            ),
+            Ok(key) => Some(hash_key(&key)),
+            Err(_) => None,
+        };
+        Ok(Some(AuthConfig { admin_key_hash }))
+    }
+}
+
+/// Whether authentication is on for an `API_AUTH` value (`None` when unset). It defaults to on
+/// with Postgres storage; SQLite storage has no key tables, so it defaults to off there.
+fn auth_enabled(api_auth: Option<&str>, postgres: bool) -> eyre::Result<bool> {
+    match api_auth.unwrap_or("") {
+        "" => Ok(postgres),
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        other => eyre::bail!("API_AUTH must be true or false, not '{}'.", other),
+    }
+}
+
+/// Whether `key` has the shape of an issued key, so malformed ones are refused without a
+/// database lookup.
+fn is_well_formed_key(key: &str) -> bool {
+    key.strip_prefix(KEY_PREFIX).is_some_and(|secret| {
+        secret.len() == KEY_SECRET_HEX_LEN
+            && secret
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    })
+}
+
+/// A key as checked on each request.
+#[derive(Clone)]
+struct KeyGrant {
+    id: i64,
+    scopes: Vec<Scope>,
+    rate_limit_per_minute: Option<i32>,
+    daily_quota: Option<i64>,
+}
+
+impl KeyGrant {
+    fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+/// Requests counted against a key in the current minute and day.
+#[derive(Default)]
+struct KeyCounters {
+    minute: u64,
+    minute_requests: i64,
+    day: u64,
+    /// `None` until seeded from `api_key_usage` for `day`.
+    day_requests: Option<i64>,
+}
+
+pub struct AuthState {
+    pool: PgPool,
+    admin_key_hash: Option<String>,
+    /// Key hash to grant and when it was read. Unknown and revoked keys are not cached, so
+    /// made-up keys cannot grow it.
+    keys: Mutex<HashMap<String, (KeyGrant, Instant)>>,
+    counters: Mutex<HashMap<i64, KeyCounters>>,
+    /// Requests not yet written to `api_key_usage`, by key, day and endpoint.
+    pending_usage: Mutex<HashMap<(i64, u64, String), i64>>,
+}
+
+impl AuthState {
+    pub fn new(pool: PgPool, config: AuthConfig) -> Arc<Self> {
+        Arc::new(AuthState {
+            pool,
+            admin_key_hash: config.admin_key_hash,
+            keys: Mutex::new(HashMap::new()),
+            counters: Mutex::new(HashMap::new()),
+            pending_usage: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn grant(&self, key_hash: &str) -> Result<Option<KeyGrant>, sqlx::Error> {
+        if let Some((grant, read_at)) = self.keys.lock().unwrap().get(key_hash) {
+            if read_at.elapsed() < KEY_CACHE_TTL {
+                return Ok(Some(grant.clone()));
+            }
+        }
+        let row = sqlx::query(
+            "SELECT id, scopes, rate_limit_per_minute, daily_quota FROM api_keys \
+             WHERE key_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        let grant = match row {
+            Some(row) => Some(KeyGrant {
+                id: row.try_get("id")?,
+                scopes: row
+                    .try_get::<Vec<String>, _>("scopes")?
+                    .iter()
+                    .filter_map(|s| Scope::parse(s))
+                    .collect(),
+                rate_limit_per_minute: row.try_get("rate_limit_per_minute")?,
+                daily_quota: row.try_get("daily_quota")?,
+            }),
+            None => None,
+        };
+        let mut keys = self.keys.lock().unwrap();
+        match &grant {
+            Some(grant) => cache_key(&mut keys, key_hash, grant.clone()),
+            None => {
+                keys.remove(key_hash);
+            }
+        }
+        Ok(grant)
+    }
+
+    /// Counts one request against the key's limits, or says why it is refused.
+    async fn admit(&self, grant: &KeyGrant) -> Result<(), Response> {
+        let now = unix_now();
+        let (minute, day) = (now / 60, now / SECONDS_PER_DAY);
+        let seeded = self
+            .counters
+            .lock()
+            .unwrap()
+            .get(&grant.id)
+            .is_some_and(|c| c.day == day && c.day_requests.is_some());
+        // Before the key's first request of the day, pick up what is already recorded.
+        let recorded_today = match (grant.daily_quota, seeded) {
+            (Some(_), false) => {
+                let recorded: Option<i64> = sqlx::query_scalar(
+                    "SELECT SUM(requests)::BIGINT FROM api_key_usage \
+                     WHERE key_id = $1 AND day = DATE '1970-01-01' + $2::INT",
+                )
+                .bind(grant.id)
+                .bind(day as i32)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| ApiError::from(e).into_response())?;
+                let pending: i64 = self
+                    .pending_usage
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|((id, d, _), _)| *id == grant.id && *d == day)
+                    .map(|(_, n)| *n)
+                    .sum();
+                Some(recorded.unwrap_or(0) + pending)
+            }
+            _ => None,
+        };
+
+        let mut counters = self.counters.lock().unwrap();
+        let counter = counters.entry(grant.id).or_default();
+        if counter.minute != minute {
+            counter.minute = minute;
+            counter.minute_requests = 0;
+        }
+        if counter.day != day {
+            counter.day = day;
+            counter.day_requests = None;
+        }
+        if let Some(recorded) = recorded_today {
+            counter.day_requests.get_or_insert(recorded);
+        }
+        if let Some(limit) = grant.rate_limit_per_minute {
+            if counter.minute_requests >= limit as i64 {
+                return Err(too_many_requests(
+                    format!("Rate limit of {} requests per minute exceeded.", limit),
+                    60 - now % 60,
+                ));
+            }
+        }
+        if let Some(quota) = grant.daily_quota {
+            if counter.day_requests.unwrap_or(0) >= quota {
+                return Err(too_many_requests(
+                    format!("Daily quota of {} requests exceeded.", quota),
+                    SECONDS_PER_DAY - now % SECONDS_PER_DAY,
+                ));
+            }
+        }
+        counter.minute_requests += 1;
+        if let Some(day_requests) = counter.day_requests.as_mut() {
+            *day_requests += 1;
+        }
+        Ok(())
+    }
+
+    fn record(&self, key_id: i64, endpoint: String) {
+        let day = unix_now() / SECONDS_PER_DAY;
+        *self
+            .pending_usage
+            .lock()
+            .unwrap()
+            .entry((key_id, day, endpoint))
+            .or_insert(0) += 1;
+    }
+
+    /// Writes the pending usage counts; they are put back if the write fails.
+    async fn flush_usage(&self) {
+        let pending = std::mem::take(&mut *self.pending_usage.lock().unwrap());
+        if pending.is_empty() {
+            return;
+        }
+        let (mut key_ids, mut days, mut endpoints, mut requests) =
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for ((key_id, day, endpoint), count) in &pending {
+            key_ids.push(*key_id);
+            days.push(*day as i32);
+            endpoints.push(endpoint.clone());
+            requests.push(*count);
+        }
+        let result = async {
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(
+                r#"
+                INSERT INTO api_key_usage (key_id, day, endpoint, requests)
+                SELECT k, DATE '1970-01-01' + d, e, r
+                FROM UNNEST($1::BIGINT[], $2::INT[], $3::TEXT[], $4::BIGINT[]) AS u(k, d, e, r)
+                ON CONFLICT (key_id, day, endpoint)
+                DO UPDATE SET requests = api_key_usage.requests + EXCLUDED.requests
+                "#,
+            )
+            .bind(&key_ids)
+            .bind(&days)
+            .bind(&endpoints)
+            .bind(&requests)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = ANY($1)")
+                .bind(&key_ids)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("AUTH: Failed to record API key usage: {}. Retrying.", e);
+            let mut current = self.pending_usage.lock().unwrap();
+            for (entry, count) in pending {
+                *current.entry(entry).or_insert(0) += count;
+            }
+        }
+    }
+}
+
+/// Flushes usage counts for as long as the server runs.
+pub fn spawn_usage_flusher(state: Arc<AuthState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            state.flush_usage().await;
+        }
+    });
+}
+
+/// Caches `grant`, making room when the cache is full: expired entries go first, then the
+/// oldest one.
+fn cache_key(keys: &mut HashMap<String, (KeyGrant, Instant)>, key_hash: &str, grant: KeyGrant) {
+    if keys.len() >= MAX_CACHED_KEYS && !keys.contains_key(key_hash) {
+        keys.retain(|_, (_, read_at)| read_at.elapsed() < KEY_CACHE_TTL);
+        if keys.len() >= MAX_CACHED_KEYS {
+            let oldest = keys
+                .iter()
+                .min_by_key(|(_, (_, read_at))| *read_at)
+                .map(|(hash, _)| hash.clone());
+            if let Some(oldest) = oldest {
+                keys.remove(&oldest);
+            }
+        }
+    }
+    keys.insert(key_hash.to_string(), (grant, Instant::now()));
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn reject(status: StatusCode, message: String) -> Response {
+    let body = GenericErrorResponse {
+        status: "fail".to_string(),
+        status_code: status.as_u16(),
+        message,
+    };
+    (status, Json(body)).into_response()
+}
+
+fn too_many_requests(message: String, retry_after_secs: u64) -> Response {
+    let mut response = reject(StatusCode::TOO_MANY_REQUESTS, message);
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    response
+}
+
+/// The key sent with a request, from the headers or the `apiKey` query parameter.
+fn presented_key(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(key) = headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    if let Some(key) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        return Some(key.trim().to_string());
+    }
+    request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("apiKey="))
+            .map(str::to_string)
+    })
+}
+
+/// Middleware: checks the key, its scopes and limits, and counts the request.
+pub async fn authenticate(
+    State(state): State<Arc<AuthState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(scope) = required_scope(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let Some(key) = presented_key(&request) else {
+        return reject(
+            StatusCode::UNAUTHORIZED,
+            "An API key is required: send it in the X-API-Key header.".to_string(),
+        );
+    };
+    let key_hash = hash_key(&key);
+    if state.admin_key_hash.as_deref() == Some(key_hash.as_str()) {
+        return next.run(request).await;
+    }
+    if !is_well_formed_key(&key) {
+        return reject(
+            StatusCode::UNAUTHORIZED,
+            "Unknown or revoked API key.".to_string(),
+        );
+    }
+
+    let grant = match state.grant(&key_hash).await {
+        Ok(Some(grant)) => grant,
+        Ok(None) => {
+            return reject(
+                StatusCode::UNAUTHORIZED,
+                "Unknown or revoked API key.".to_string(),
+            )
+        }
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    if !grant.allows(scope) {
+        return reject(
+            StatusCode::FORBIDDEN,
+            format!("This API key lacks the '{}' scope.", scope.as_str()),
+        );
+    }
+    if let Err(response) = state.admit(&grant).await {
+        return response;
+    }
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str());
+    state.record(grant.id, format!("{} {}", request.method(), route));
+    next.run(request).await
+}
+
+fn row_to_key(row: &PgRow) -> Result<ApiKey, ApiError> {
+    Ok(ApiKey {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        key_prefix: row.try_get("key_prefix")?,
+        scopes: row.try_get("scopes")?,
+        rate_limit_per_minute: row.try_get("rate_limit_per_minute")?,
+        daily_quota: row.try_get("daily_quota")?,
+        created_at: row.try_get("created_at")?,
+        last_used_at: row.try_get("last_used_at")?,
+        revoked_at: row.try_get("revoked_at")?,
+        api_key: None,
+    })
+}
+
+/// List API Keys
+///
+/// All issued keys, revoked ones included. Keys themselves are never returned.
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    responses(
+        (status = 200, description = "All API keys", body = Vec<ApiKey>)
+    )
+)]
+pub async fn list_keys_handler(
+    State(state): State<Arc<AuthState>>,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM api_keys ORDER BY id", KEY_COLUMNS))
+        .fetch_all(&state.pool)
+        .await?;
+    let keys = rows.iter().map(row_to_key).collect::<Result<_, _>>()?;
+    Ok(Json(keys))
+}
+
+/// Issue API Key
+///
+/// Returns the new key in `apiKey`. It is not stored and cannot be shown again.
+#[utoipa::path(
+    post,
+    path = "/admin/keys",
+    request_body = ApiKeyRequest,
+    responses(
+        (status = 201, description = "Key issued", body = ApiKey),
+        (status = 400, description = "Invalid scopes or limits", body = GenericErrorResponse)
+    )
+)]
+pub async fn create_key_handler(
+    State(state): State<Arc<AuthState>>,
+    Json(req): Json<ApiKeyRequest>,
+) -> Result<(StatusCode, Json<ApiKey>), ApiError> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty.".to_string()));
+    }
+    if req.scopes.is_empty() {
+        return Err(ApiError::BadRequest(
+            "scopes must name at least one scope.".to_string(),
+        ));
+    }
+    if let Some(scope) = req.scopes.iter().find(|s| Scope::parse(s).is_none()) {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown scope '{}'; expected read, export or admin.",
+            scope
+        )));
+    }
+    if req.rate_limit_per_minute.is_some_and(|n| n < 1) || req.daily_quota.is_some_and(|n| n < 1) {
+        return Err(ApiError::BadRequest(
+            "rateLimitPerMinute and dailyQuota must be positive or null.".to_string(),
+        ));
+    }
+
+    let mut secret = [0u8; 32];
+    thread_rng().fill_bytes(&mut secret);
+    let key = format!("{}{}", KEY_PREFIX, hex::encode(secret));
+    let row = sqlx::query(&format!(
+        "INSERT INTO api_keys (name, key_prefix, key_hash, scopes, rate_limit_per_minute, \
+         daily_quota) VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        KEY_COLUMNS
+    ))
+    .bind(name)
+    .bind(&key[..DISPLAY_PREFIX_LEN])
+    .bind(hash_key(&key))
+    .bind(&req.scopes)
+    .bind(req.rate_limit_per_minute)
+    .bind(req.daily_quota)
+    .fetch_one(&state.pool)
+    .await?;
+    let mut issued = row_to_key(&row)?;
+    issued.api_key = Some(key);
+    Ok((StatusCode::CREATED, Json(issued)))
+}
+
+/// Revoke API Key
+///
+/// The key stops working at once on this server, and within 30 seconds on other API
+/// processes. Its usage history is kept.
+#[utoipa::path(
+    delete,
+    path = "/admin/keys/{id}",
+    params(("id" = i64, Path, description = "Key id")),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 404, description = "No active key with this id", body = GenericErrorResponse)
+    )
+)]
+pub async fn revoke_key_handler(
+    State(state): State<Arc<AuthState>>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let result =
+        sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+            .bind(id)
+            .execute(&state.pool)
+            .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("No active API key {}.", id)));
+    }
+    state
+        .keys
+        .lock()
+        .unwrap()
+        .retain(|_, (grant, _)| grant.id != id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get API Key Usage
+///
+/// Requests per endpoint and UTC day. Counts are written every 10 seconds, so the latest
+/// requests may be missing.
+#[utoipa::path(
+    get,
+    path = "/admin/keys/{id}/usage",
+    params(("id" = i64, Path, description = "Key id"), GetApiKeyUsageQuery),
+    responses(
+        (status = 200, description = "Usage of the key", body = ApiKeyUsageResponse),
+        (status = 404, description = "Key not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn key_usage_handler(
+    State(state): State<Arc<AuthState>>,
+    Path(id): Path<i64>,
+    Query(params): Query<GetApiKeyUsageQuery>,
+) -> Result<Json<ApiKeyUsageResponse>, ApiError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM api_keys WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::NotFound(format!("API key {} not found.", id)));
+    }
+    let days = params.days.clamp(1, MAX_USAGE_DAYS) as i32;
+    let rows = sqlx::query(
+        "SELECT TO_CHAR(day, 'YYYY-MM-DD') AS day, endpoint, requests, \
+                day = (NOW() AT TIME ZONE 'UTC')::DATE AS today \
+         FROM api_key_usage WHERE key_id = $1 AND day > (NOW() AT TIME ZONE 'UTC')::DATE - $2::INT \
+         ORDER BY day DESC, requests DESC, endpoint LIMIT $3",
+    )
+    .bind(id)
+    .bind(days)
+    .bind((MAX_PAGE_SIZE * MAX_USAGE_DAYS as u64) as i64)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let mut requests_today = 0;
+    let mut usage = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let requests: i64 = row.try_get("requests")?;
+        if row.try_get("today")? {
+            requests_today += requests;
+        }
+        usage.push(ApiKeyUsage {
+            day: row.try_get("day")?,
+            endpoint: row.try_get("endpoint")?,
+            requests,
+        });
+    }
+    Ok(Json(ApiKeyUsageResponse {
+        key_id: id,
+        requests_today,
+        usage,
+    }))
+}
+
+pub fn router(state: Arc<AuthState>) -> Router {
+    Router::new()
+        .route(
+            "/admin/keys",
+            get(list_keys_handler).post(create_key_handler),
+        )
+        .route("/admin/keys/{id}", delete(revoke_key_handler))
+        .route("/admin/keys/{id}/usage", get(key_usage_handler))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_scope_by_route() {
+        use Scope::{Admin, Export, Read};
+        let cases: &[(Method, &str, Option<Scope>)] = &[
+            // Public
+            (Method::GET, "/", None),
+            (Method::GET, "/health", None),
+            (Method::GET, "/ready", None),
+            (Method::GET, "/swagger-ui", None),
+            (Method::GET, "/swagger-ui/index.html", None),
+            (Method::GET, "/api-docs/openapi.json", None),
+            // Key management and the ingester
+            (Method::GET, "/admin/keys", Some(Admin)),
+            (Method::POST, "/admin/keys", Some(Admin)),
+            (Method::DELETE, "/admin/keys/1", Some(Admin)),
+            (Method::GET, "/admin/keys/1/usage", Some(Admin)),
+            (Method::GET, "/admin/ingester", Some(Admin)),
+            (Method::POST, "/admin/ingester/pause", Some(Admin)),
+            (Method::POST, "/admin/ingester/resume", Some(Admin)),
+            (Method::POST, "/admin/ingester/rewind", Some(Admin)),
+            (Method::POST, "/admin/ingester/reindex", Some(Admin)),
+            (Method::PUT, "/admin/ingester/config", Some(Admin)),
+            // Webhooks, reads included: rules hold target URLs
+            (Method::GET, "/webhooks/rules", Some(Admin)),
+            (Method::POST, "/webhooks/rules", Some(Admin)),
+            (Method::GET, "/webhooks/rules/1", Some(Admin)),
+            (Method::PUT, "/webhooks/rules/1", Some(Admin)),
+            (Method::DELETE, "/webhooks/rules/1", Some(Admin)),
+            (Method::GET, "/webhooks/dead-letters", Some(Admin)),
+            // Handlers: reading is open to read keys, changing them is not
+            (Method::GET, "/handlers", Some(Read)),
+            (Method::POST, "/handlers", Some(Admin)),
+            (Method::GET, "/handlers/1", Some(Read)),
+            (Method::PUT, "/handlers/1", Some(Admin)),
+            (Method::DELETE, "/handlers/1", Some(Admin)),
+            (Method::GET, "/handlers/1/entities/balances", Some(Read)),
+            (
+                Method::GET,
+                "/handlers/1/entities/balances/0xab",
+                Some(Read),
+            ),
+            // Export
+            (Method::POST, "/export/logs", Some(Export)),
+            (Method::POST, "/export/transactions", Some(Export)),
+            (Method::POST, "/export/blocks", Some(Export)),
+            // Everything else
+            (Method::GET, "/stats", Some(Read)),
+            (Method::POST, "/logs", Some(Read)),
+            (Method::POST, "/transactions", Some(Read)),
+            (Method::GET, "/block/18000000", Some(Read)),
+            (Method::POST, "/rpc", Some(Read)),
+            (Method::POST, "/graphql", Some(Read)),
+            (Method::GET, "/stream/logs/ws", Some(Read)),
+            // Prefixes match whole path segments only
+            (Method::GET, "/healthz", Some(Read)),
+            (Method::GET, "/readyz", Some(Read)),
+            (Method::GET, "/administrator", Some(Read)),
+            (Method::GET, "/webhooksx", Some(Read)),
+            (Method::POST, "/exports", Some(Read)),
+            (Method::POST, "/handlersx", Some(Read)),
+            (Method::GET, "/swagger-uix", Some(Read)),
+        ];
+        for (method, path, expected) in cases {
+            assert_eq!(
+                required_scope(method, path),
+                *expected,
+                "{} {}",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn admin_grants_include_the_other_scopes() {
+        let grant = |scopes: Vec<Scope>| KeyGrant {
+            id: 1,
+            scopes,
+            rate_limit_per_minute: None,
+            daily_quota: None,
+        };
+        let admin = grant(vec![Scope::Admin]);
+        let read = grant(vec![Scope::Read]);
+        let export = grant(vec![Scope::Read, Scope::Export]);
+        for scope in [Scope::Read, Scope::Export, Scope::Admin] {
+            assert!(admin.allows(scope));
+        }
+        assert!(read.allows(Scope::Read));
+        assert!(!read.allows(Scope::Export) && !read.allows(Scope::Admin));
+        assert!(export.allows(Scope::Export) && !export.allows(Scope::Admin));
+        for scope in [Scope::Read, Scope::Export, Scope::Admin] {
+            assert_eq!(Scope::parse(scope.as_str()), Some(scope));
+        }
+        assert_eq!(Scope::parse("write"), None);
+    }
+
+    #[test]
+    fn keys_are_stored_as_sha256_hex() {
+        assert_eq!(
+            hash_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let key = format!("{}{}", KEY_PREFIX, "a".repeat(43));
+        assert_eq!(hash_key(&key).len(), 64);
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(
+            hash_key(&key),
+            hash_key(&format!("{}b", &key[..key.len() - 1]))
+        );
+        assert_eq!(hash_key(&key), hash_key(&key).to_lowercase());
+    }
+
+    #[test]
+    fn auth_is_on_by_default_with_postgres() {
+        assert!(auth_enabled(None, true).unwrap());
+        assert!(auth_enabled(Some(""), true).unwrap());
+        assert!(!auth_enabled(None, false).unwrap());
+        for off in ["false", "0"] {
+            assert!(!auth_enabled(Some(off), true).unwrap());
+        }
+        for on in ["true", "1"] {
+            assert!(auth_enabled(Some(on), true).unwrap());
+            assert!(auth_enabled(Some(on), false).unwrap());
+        }
+        assert!(auth_enabled(Some("off"), true).is_err());
+        assert!(auth_enabled(Some("TRUE"), true).is_err());
+    }
+
+    #[test]
+    fn only_issued_key_shapes_are_looked_up() {
+        let mut secret = [0u8; 32];
+        thread_rng().fill_bytes(&mut secret);
+        let issued = format!("{}{}", KEY_PREFIX, hex::encode(secret));
+        assert!(is_well_formed_key(&issued));
+        assert!(is_well_formed_key(&format!(
+            "{}{}",
+            KEY_PREFIX,
+            "0".repeat(64)
+        )));
+        for key in [
+            String::new(),
+            KEY_PREFIX.to_string(),
+            "0".repeat(64),
+            format!("evmx_{}", "0".repeat(64)),
+            format!("{}{}", KEY_PREFIX, "0".repeat(63)),
+            format!("{}{}", KEY_PREFIX, "0".repeat(65)),
+            format!("{}{}", KEY_PREFIX, "A".repeat(64)),
+            format!("{}{}", KEY_PREFIX, "g".repeat(64)),
+            format!("{}{}é", KEY_PREFIX, "0".repeat(62)),
+        ] {
+            assert!(!is_well_formed_key(&key), "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn the_key_cache_is_bounded() {
+        let grant = |id| KeyGrant {
+            id,
+            scopes: vec![Scope::Read],
+            rate_limit_per_minute: None,
+            daily_quota: None,
+        };
+        let expired = Instant::now() - KEY_CACHE_TTL * 2;
+        let mut keys: HashMap<String, (KeyGrant, Instant)> = (0..MAX_CACHED_KEYS)
+            .map(|i| (i.to_string(), (grant(i as i64), expired)))
+            .collect();
+        // Expired entries make room first.
+        cache_key(&mut keys, "new", grant(-1));
+        assert_eq!(keys.len(), 1);
+
+        let start = Instant::now();
+        let mut keys: HashMap<String, (KeyGrant, Instant)> = (0..MAX_CACHED_KEYS)
+            .map(|i| {
+                let read_at = start + Duration::from_millis(i as u64 % 1_000 + 1);
+                (i.to_string(), (grant(i as i64), read_at))
+            })
+            .collect();
+        keys.insert("oldest".to_string(), (grant(-2), start));
+        keys.remove("0");
+        // Then the oldest one.
+        cache_key(&mut keys, "new", grant(-1));
+        assert_eq!(keys.len(), MAX_CACHED_KEYS);
+        assert!(keys.contains_key("new") && !keys.contains_key("oldest"));
+        // Refreshing a cached key takes no room.
+        cache_key(&mut keys, "new", grant(-1));
+        assert_eq!(keys.len(), MAX_CACHED_KEYS);
+    }
+
+    fn state() -> Arc<AuthState> {
+        AuthState::new(
+            PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+            AuthConfig {
+                admin_key_hash: None,
+            },
+        )
+    }
+
+    /// A refusal with status 429 and a `Retry-After` of at most `max_secs`.
+    fn is_too_many(outcome: Result<(), Response>, max_secs: u64) -> bool {
+        let Err(response) = outcome else {
+            return false;
+        };
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+        response.status() == StatusCode::TOO_MANY_REQUESTS
+            && retry_after.is_some_and(|secs| (1..=max_secs).contains(&secs))
+    }
+
+    /// Runs `check` again if a minute boundary passed while it ran, since the counters start
+    /// over then.
+    async fn within_one_minute<F, Fut>(check: F)
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = bool>,
+    {
+        for _ in 0..2 {
+            let minute = unix_now() / 60;
+            let passed = check().await;
+            if unix_now() / 60 == minute {
+                assert!(passed);
+                return;
+            }
+        }
+        panic!("a minute boundary passed during both runs");
+    }
+
+    #[tokio::test]
+    async fn rate_limit_counts_requests_per_key_and_minute() {
+        within_one_minute(|| async {
+            let state = state();
+            let limited = KeyGrant {
+                id: 1,
+                scopes: vec![Scope::Read],
+                rate_limit_per_minute: Some(3),
+                daily_quota: None,
+            };
+            let other = KeyGrant {
+                id: 2,
+                ..limited.clone()
+            };
+            let mut admitted = 0;
+            while state.admit(&limited).await.is_ok() && admitted < 10 {
+                admitted += 1;
+            }
+            admitted == 3
+                && is_too_many(state.admit(&limited).await, 60)
+                // Other keys have their own budget.
+                && state.admit(&other).await.is_ok()
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn daily_quota_counts_from_the_recorded_usage() {
+        within_one_minute(|| async {
+            let state = state();
+            let grant = KeyGrant {
+                id: 1,
+                scopes: vec![Scope::Read],
+                rate_limit_per_minute: None,
+                daily_quota: Some(10),
+            };
+            // As if 8 requests were already recorded today, so no database is needed.
+            state.counters.lock().unwrap().insert(
+                grant.id,
+                KeyCounters {
+                    day: unix_now() / SECONDS_PER_DAY,
+                    day_requests: Some(8),
+                    ..Default::default()
+                },
+            );
+            state.admit(&grant).await.is_ok()
+                && state.admit(&grant).await.is_ok()
+                && is_too_many(state.admit(&grant).await, SECONDS_PER_DAY)
+                // Refused requests don't count.
+                && is_too_many(state.admit(&grant).await, SECONDS_PER_DAY)
+                && state.counters.lock().unwrap()[&grant.id].day_requests == Some(10)
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn usage_is_counted_per_key_and_endpoint() {
+        let state = state();
+        state.record(1, "POST /logs".to_string());
+        state.record(1, "POST /logs".to_string());
+        state.record(1, "GET /blocks".to_string());
+        state.record(2, "POST /logs".to_string());
+        let pending = state.pending_usage.lock().unwrap();
+        let count = |key: i64, endpoint: &str| -> i64 {
+            pending
+                .iter()
+                .filter(|((k, _, e), _)| *k == key && e == endpoint)
+                .map(|(_, n)| *n)
+                .sum()
+        };
+        assert_eq!(count(1, "POST /logs"), 2);
+        assert_eq!(count(1, "GET /blocks"), 1);
+        assert_eq!(count(2, "POST /logs"), 1);
+        assert_eq!(count(2, "GET /blocks"), 0);
+    }
+}
@@ -0,0 +1,225 @@
+// src/bench.rs
+//
+// `evm_indexer bench-writes`: measures ingester write throughput against the configured
+// database with synthetic blocks, comparing row-at-a-time inserts, batched inserts, and
+// batched inserts with several blocks per commit. Synthetic rows are written far above any
+// real height and deleted afterwards, but run it against a scratch database anyway.
+
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::storage::Storage;
+use ethers::types::{Address, H256, U256, U64};
+use std::time::{Duration, Instant};
+
+const CLI_USAGE: &str = "usage: evm_indexer bench-writes [--blocks N] [--txs-per-block N] \
+     [--logs-per-block N] [--blocks-per-commit N]";
+// Synthetic blocks start here so the cleanup rollback never touches indexed data.
+const BENCH_START_BLOCK: u64 = 1 << 40;
+
+struct BenchOptions {
+    blocks: u64,
+    txs_per_block: u64,
+    logs_per_block: u64,
+    blocks_per_commit: u64,
+}
+
+fn parse_cli(args: &[String]) -> eyre::Result<BenchOptions> {
+    let mut options = BenchOptions {
+        blocks: 20,
+        txs_per_block: 300,
+        logs_per_block: 1000,
+        blocks_per_commit: 10,
+    };
+    let mut rest = args.iter();
+    while let Some(flag) = rest.next() {
+        let value: u64 = rest
+            .next()
+            .ok_or_else(|| eyre::eyre!("missing value for {}\n{}", flag, CLI_USAGE))?
+            .parse()?;
+        match flag.as_str() {
+            "--blocks" => options.blocks = value.max(1),
+            "--txs-per-block" => options.txs_per_block = value.max(1),
+            "--logs-per-block" => options.logs_per_block = value,
+            "--blocks-per-commit" => options.blocks_per_commit = value.max(1),
+            _ => eyre::bail!("unknown option {}\n{}", flag, CLI_USAGE),
+        }
+    }
+    Ok(options)
+}
+
+fn hash(kind: u64, block: u64, index: u64) -> H256 {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&kind.to_be_bytes());
+    bytes[8..16].copy_from_slice(&block.to_be_bytes());
+    bytes[16..24].copy_from_slice(&index.to_be_bytes());
+    H256(bytes)
+}
+
+/// A block shaped like a busy mainnet block: ERC-20 transfer calls and Transfer events.
+fn synthetic_block(
+    number: u64,
+    options: &BenchOptions,
+) -> (MyBlock, Vec<MyTransaction>, Vec<MyLog>) {
+    let block_hash = hash(1, number, 0);
+    let block = MyBlock {
+        block_number: U64::from(number),
+        block_hash,
+        parent_hash: hash(1, number - 1, 0),
+        timestamp: U256::from(1_700_000_000u64),
+        gas_used: U256::from(15_000_000u64),
+        gas_limit: U256::from(30_000_000u64),
+        base_fee_per_gas: Some(U256::from(20_000_000_000u64)),
+    };
+    let transactions: Vec<MyTransaction> = (0..options.txs_per_block)
+        .map(|i| MyTransaction {
+            tx_hash: hash(2, number, i),
+            block_number: U64::from(number),
+            block_hash,
+            block_timestamp: Some(1_700_000_000),
+            transaction_index: Some(U64::from(i)),
+            from_address: Address::from_low_u64_be(i + 1),
+            to_address: Some(Address::from_low_u64_be(0xc0ffee)),
+            value: U256::from(i) * U256::exp10(15),
+            gas_price: Some(U256::from(25_000_000_000u64)),
+            max_fee_per_gas: Some(U256::from(30_000_000_000u64)),
+            max_priority_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            gas: U256::from(65_000u64),
+            nonce: Some(U256::from(number)),
+            input_data: format!("0xa9059cbb{:0>128}", format!("{:x}", i)),
+            status: Some(1),
+            gas_used: Some(U256::from(50_000u64)),
+            effective_gas_price: Some(U256::from(21_000_000_000u64)),
+            transaction_type: Some(2),
+            contract_address: None,
+            value_usd: None,
+            from_ens_name: None,
+            to_ens_name: None,
+        })
+        .collect();
+    let logs = (0..options.logs_per_block)
+        .map(|i| {
+            let tx_index = i * options.txs_per_block / options.logs_per_block.max(1);
+            MyLog {
+                log_index: Some(U256::from(i)),
+                transaction_hash: hash(2, number, tx_index),
+                transaction_index: Some(tx_index),
+                block_number: number,
+                block_hash,
+                block_timestamp: Some(1_700_000_000),
+                address: Address::from_low_u64_be(0xc0ffee),
+                data: format!("0x{:064x}", i),
+                topics: vec![
+                    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef".into(),
+                    format!("{:#x}", H256::from_low_u64_be(i + 1)),
+                    format!("{:#x}", H256::from_low_u64_be(i + 2)),
+                ],
+                address_ens_name: None,
+            }
+        })
+        .collect();
+    (block, transactions, logs)
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    RowByRow,
+    Batched { blocks_per_commit: u64 },
+}
+
+async fn run_mode(
+    storage: &dyn Storage,
+    options: &BenchOptions,
+    first_block: u64,
+    mode: Mode,
+) -> eyre::Result<Duration> {
+    let blocks_per_commit = match mode {
+        Mode::RowByRow => 1,
+        Mode::Batched { blocks_per_commit } => blocks_per_commit,
+    };
+    let data: Vec<_> = (first_block..first_block + options.blocks)
+        .map(|n| synthetic_block(n, options))
+        .collect();
+
+    let started = Instant::now();
+    for group in data.chunks(blocks_per_commit as usize) {
+        let mut db_tx = storage.begin().await?;
+        for (block, transactions, logs) in group {
+            db_tx.insert_block_data(block).await?;
+            match mode {
+                Mode::RowByRow => {
+                    for tx in transactions {
+                        db_tx.insert_transaction_data(tx).await?;
+                    }
+                    for log in logs {
+                        db_tx.insert_log_data(log).await?;
+                    }
+                }
+                Mode::Batched { .. } => {
+                    db_tx.insert_transactions_batch(transactions).await?;
+                    db_tx.insert_logs_batch(logs).await?;
+                }
+            }
+            // Keeps the /stats totals right across the cleanup rollback.
+            db_tx
+                .insert_block_stats(&crate::stats::summarize_block(
+                    block,
+                    transactions,
+                    logs,
+                    false,
+                ))
+                .await?;
+        }
+        db_tx.commit().await?;
+    }
+    Ok(started.elapsed())
+}
+
+pub async fn run_cli(storage: &dyn Storage, args: &[String]) -> eyre::Result<()> {
+    let options = parse_cli(args)?;
+    let rows_per_block = 1 + options.txs_per_block + options.logs_per_block;
+    let total_rows = options.blocks * rows_per_block;
+    println!(
+        "bench-writes: {} blocks x ({} txs + {} logs) = {} rows per mode",
+        options.blocks, options.txs_per_block, options.logs_per_block, total_rows
+    );
+
+    let modes = [
+        ("row-by-row INSERT".to_string(), Mode::RowByRow),
+        (
+            "batched (1 block/commit)".to_string(),
+            Mode::Batched {
+                blocks_per_commit: 1,
+            },
+        ),
+        (
+            format!("batched ({} blocks/commit)", options.blocks_per_commit),
+            Mode::Batched {
+                blocks_per_commit: options.blocks_per_commit,
+            },
+        ),
+    ];
+
+    let mut baseline = None;
+    let mut result = Ok(());
+    for (i, (label, mode)) in modes.into_iter().enumerate() {
+        let first_block = BENCH_START_BLOCK + i as u64 * options.blocks;
+        let elapsed = match run_mode(storage, &options, first_block, mode).await {
+            Ok(elapsed) => elapsed,
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        let secs = elapsed.as_secs_f64();
+        let baseline_secs = *baseline.get_or_insert(secs);
+        println!(
+            "  {:<28} {:>8.3}s  {:>10.0} rows/s  {:>6.1}x",
+            label,
+            secs,
+            total_rows as f64 / secs,
+            baseline_secs / secs
+        );
+    }
+
+    storage.rollback_from_height(BENCH_START_BLOCK).await?;
+    result
+}
@@ -0,0 +1,480 @@
+// src/contracts.rs
+//
+// Contract deployment index. The receipt stage collects the contracts a block created: the
+// `contractAddress` of successful creation receipts and, with CONTRACT_TRACES=true, the
+// CREATE/CREATE2 frames of `trace_block`, which also covers contracts deployed by factories.
+// Each new contract's runtime bytecode is read with `eth_getCode` (traces already carry it)
+// and hashed, and the contract is checked for the two common proxy layouts: an EIP-1167
+// minimal proxy is recognised from its bytecode, an EIP-1967 proxy from its implementation or
+// beacon storage slot.
+//
+// Code and storage are read as of the creation block. Nodes that have pruned that state answer
+// with an error, in which case the latest state is used instead.
+//
+// The transform stage picks up `Upgraded`/`BeaconUpgraded` events, which the writer keeps for
+// contracts already in the index; they move a proxy's implementation after deployment. Rows
+// carry their block number and are removed by a reorg rollback like the logs they came with.
+
+use crate::api::ApiError;
+use crate::api_models::{Contract, ContractUpgrade, GenericErrorResponse};
+use crate::dex::{call_word, word_to_address};
+use crate::ens::resolve_address;
+use crate::models::MyBlock;
+use crate::pipeline::retry_rpc;
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::providers::{Http, Middleware, Provider, ProviderError, RpcError};
+use ethers::types::{
+    Action, Address, BlockId, BlockNumber, Bytes, Log, Res, Transaction, TransactionReceipt, H256,
+};
+use ethers::utils::keccak256;
+use futures::stream::StreamExt;
+use sqlx::{postgres::PgRow, PgPool, Row as SqlxRow};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::future::Future;
+
+/// Contracts of one block inspected over RPC at once.
+const MAX_CONTRACT_LOOKUPS_CONCURRENT: usize = 8;
+
+/// `keccak256("eip1967.proxy.implementation") - 1`
+const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+/// `keccak256("eip1967.proxy.beacon") - 1`
+const EIP1967_BEACON_SLOT: &str =
+    "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+/// Runtime code of an EIP-1167 minimal proxy, around the 20-byte implementation address.
+const EIP1167_PREFIX: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const EIP1167_SUFFIX: [u8; 15] = [
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+
+const CONTRACT_COLUMNS: &str = "c.address, c.deployer, c.factory_address, c.creation_tx_hash, \
+     c.block_number, c.block_timestamp, c.code_hash, c.code_size, c.proxy_type";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxyKind {
+    Eip1167,
+    Eip1967,
+    Eip1967Beacon,
+}
+
+impl ProxyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProxyKind::Eip1167 => "eip1167",
+            ProxyKind::Eip1967 => "eip1967",
+            ProxyKind::Eip1967Beacon => "eip1967_beacon",
+        }
+    }
+}
+
+/// What an upgrade event points the contract at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpgradeKind {
+    Implementation,
+    Beacon,
+}
+
+impl UpgradeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UpgradeKind::Implementation => "implementation",
+            UpgradeKind::Beacon => "beacon",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Deployment {
+    pub address: Address,
+    /// Sender of the creating transaction.
+    pub deployer: Address,
+    /// The contract that ran CREATE/CREATE2; only known from traces.
+    pub factory: Option<Address>,
+    pub tx_hash: H256,
+    /// `None` when the address holds no code, e.g. a contract that self-destructed.
+    pub code_hash: Option<H256>,
+    pub code_size: usize,
+    pub proxy: Option<ProxyKind>,
+    pub implementation: Option<Address>,
+    pub beacon: Option<Address>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Upgrade {
+    /// Position of the event in the block's logs, as for DEX rows.
+    pub log_position: usize,
+    pub tx_hash: H256,
+    pub contract: Address,
+    pub kind: UpgradeKind,
+    pub target: Address,
+}
+
+/// One block's contract rows, handed to the writer.
+#[derive(Clone, Debug)]
+pub struct ContractBlock {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub deployments: Vec<Deployment>,
+    pub upgrades: Vec<Upgrade>,
+}
+
+/// A creation found in a receipt or a trace, before its code has been inspected.
+struct Creation {
+    address: Address,
+    deployer: Address,
+    factory: Option<Address>,
+    tx_hash: H256,
+    /// Runtime code, when a trace already returned it.
+    code: Option<Bytes>,
+}
+
+/// Shared by the receipt and transform stages for the lifetime of the ingester.
+pub struct ContractIndexer {
+    traces: bool,
+    upgraded_topic: H256,
+    beacon_upgraded_topic: H256,
+}
+
+impl ContractIndexer {
+    /// Reads `CONTRACT_INDEXING` (on unless `false`) and `CONTRACT_TRACES` (off unless `true`).
+    pub fn from_env() -> Option<Self> {
+        if env::var("CONTRACT_INDEXING").is_ok_and(|v| v == "false" || v == "0") {
+            return None;
+        }
+        let traces = env::var("CONTRACT_TRACES").is_ok_and(|v| v == "true" || v == "1");
+        Some(ContractIndexer {
+            traces,
+            upgraded_topic: H256::from(keccak256("Upgraded(address)")),
+            beacon_upgraded_topic: H256::from(keccak256("BeaconUpgraded(address)")),
+        })
+    }
+
+    pub fn traces(&self) -> bool {
+        self.traces
+    }
+
+    /// The contracts created by a block's successful transactions, with their code inspected.
+    /// Only transactions present in `receipts` are considered, so in selective mode these are
+    /// the contracts created by the indexed transactions.
+    pub async fn collect_deployments(
+        &self,
+        provider: &Provider<Http>,
+        block: &MyBlock,
+        receipts: &[(Transaction, Option<TransactionReceipt>)],
+    ) -> eyre::Result<Vec<Deployment>> {
+        let block_number = block.block_number.as_u64();
+        // Pre-Byzantium receipts have no status; those creations are kept and show up with
+        // no code if they failed.
+        let succeeded: HashMap<H256, Address> = receipts
+            .iter()
+            .filter(|(_, receipt)| {
+                receipt
+                    .as_ref()
+                    .is_some_and(|r| r.status.is_none_or(|s| !s.is_zero()))
+            })
+            .map(|(tx, _)| (tx.hash, tx.from))
+            .collect();
+        let mut creations: Vec<Creation> = receipts
+            .iter()
+            .filter(|(tx, _)| succeeded.contains_key(&tx.hash))
+            .filter_map(|(tx, receipt)| {
+                Some(Creation {
+                    address: receipt.as_ref()?.contract_address?,
+                    deployer: tx.from,
+                    factory: None,
+                    tx_hash: tx.hash,
+                    code: None,
+                })
+            })
+            .collect();
+
+        if self.traces && !succeeded.is_empty() {
+            let traces = retry_rpc(&format!("trace_block #{}", block_number), || {
+                provider.trace_block(BlockNumber::Number(block_number.into()))
+            })
+            .await?;
+            // A frame that reverted undoes every contract created beneath it.
+            let reverted: HashSet<(H256, &[usize])> = traces
+                .iter()
+                .filter(|trace| trace.error.is_some())
+                .filter_map(|trace| Some((trace.transaction_hash?, trace.trace_address.as_slice())))
+                .collect();
+            for trace in &traces {
+                let (Action::Create(create), Some(Res::Create(created))) =
+                    (&trace.action, &trace.result)
+                else {
+                    continue;
+                };
+                let Some(tx_hash) = trace.transaction_hash else {
+                    continue;
+                };
+                let Some(&sender) = succeeded.get(&tx_hash) else {
+                    continue;
+                };
+                let path = trace.trace_address.as_slice();
+                if (0..=path.len()).any(|depth| reverted.contains(&(tx_hash, &path[..depth]))) {
+                    continue;
+                }
+                if path.is_empty() {
+                    if let Some(creation) = creations
+                        .iter_mut()
+                        .find(|creation| creation.address == created.address)
+                    {
+                        creation.code = Some(created.code.clone());
+                        continue;
+                    }
+                }
+                creations.push(Creation {
+                    address: created.address,
+                    deployer: sender,
+                    factory: (!path.is_empty()).then_some(create.from),
+                    tx_hash,
+                    code: Some(created.code.clone()),
+                });
+            }
+        }
+
+        futures::stream::iter(creations)
+            .map(|creation| inspect(provider, block_number, creation))
+            .buffered(MAX_CONTRACT_LOOKUPS_CONCURRENT)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Pairs the block's deployments with its proxy upgrade events. `logs` must be in the
+    /// order they are written. Returns `None` when the block has neither.
+    pub fn decode_block(
+        &self,
+        block: &MyBlock,
+        deployments: Vec<Deployment>,
+        logs: &[Log],
+    ) -> Option<ContractBlock> {
+        let upgrades: Vec<Upgrade> = logs
+            .iter()
+            .enumerate()
+            .filter_map(|(position, log)| {
+                let kind = match log.topics.first()? {
+                    topic if *topic == self.upgraded_topic => UpgradeKind::Implementation,
+                    topic if *topic == self.beacon_upgraded_topic => UpgradeKind::Beacon,
+                    _ => return None,
+                };
+                // Both events have a single indexed address and no data.
+                if log.topics.len() != 2 || !log.data.is_empty() {
+                    return None;
+                }
+                Some(Upgrade {
+                    log_position: position,
+                    tx_hash: log.transaction_hash.unwrap_or_default(),
+                    contract: log.address,
+                    kind,
+                    target: word_to_address(log.topics[1].0)?,
+                })
+            })
+            .collect();
+        if deployments.is_empty() && upgrades.is_empty() {
+            return None;
+        }
+        Some(ContractBlock {
+            block_number: block.block_number.as_u64(),
+            block_timestamp: block.timestamp.as_u64(),
+            deployments,
+            upgrades,
+        })
+    }
+}
+
+/// Reads state as of `block`, or as of `latest` when the node no longer has it.
+async fn read_state<T, F, Fut>(what: &str, block: u64, read: F) -> eyre::Result<T>
+where
+    F: Fn(Option<BlockId>) -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let read = &read;
+    retry_rpc(what, || async move {
+        match read(Some(BlockId::Number(block.into()))).await {
+            Err(e) if e.as_error_response().is_some() => read(None).await,
+            result => result,
+        }
+    })
+    .await
+}
+
+async fn inspect(
+    provider: &Provider<Http>,
+    block_number: u64,
+    creation: Creation,
+) -> eyre::Result<Deployment> {
+    let address = creation.address;
+    let code = match creation.code {
+        Some(code) => code,
+        None => {
+            read_state(
+                &format!("eth_getCode for {:?}", address),
+                block_number,
+                |at| provider.get_code(address, at),
+            )
+            .await?
+        }
+    };
+    let mut deployment = Deployment {
+        address,
+        deployer: creation.deployer,
+        factory: creation.factory,
+        tx_hash: creation.tx_hash,
+        code_hash: (!code.is_empty()).then(|| H256::from(keccak256(&code))),
+        code_size: code.len(),
+        proxy: None,
+        implementation: None,
+        beacon: None,
+    };
+    if code.is_empty() {
+        return Ok(deployment);
+    }
+    if let Some(implementation) = minimal_proxy_target(&code) {
+        deployment.proxy = Some(ProxyKind::Eip1167);
+        deployment.implementation = Some(implementation);
+        return Ok(deployment);
+    }
+
+    if let Some(implementation) =
+        read_slot(provider, address, block_number, EIP1967_IMPLEMENTATION_SLOT).await?
+    {
+        deployment.proxy = Some(ProxyKind::Eip1967);
+        deployment.implementation = Some(implementation);
+    } else if let Some(beacon) =
+        read_slot(provider, address, block_number, EIP1967_BEACON_SLOT).await?
+    {
+        deployment.proxy = Some(ProxyKind::Eip1967Beacon);
+        deployment.beacon = Some(beacon);
+        deployment.implementation = call_word(provider, beacon, "implementation()")
+            .await?
+            .and_then(word_to_address);
+    }
+    Ok(deployment)
+}
+
+/// The address kept in an EIP-1967 slot, if one is set.
+async fn read_slot(
+    provider: &Provider<Http>,
+    address: Address,
+    block_number: u64,
+    slot: &str,
+) -> eyre::Result<Option<Address>> {
+    let slot: H256 = slot.parse()?;
+    let word = read_state(
+        &format!("storage slot {:#x} of {:?}", slot, address),
+        block_number,
+        |at| provider.get_storage_at(address, slot, at),
+    )
+    .await?;
+    Ok(word_to_address(word.0))
+}
+
+/// The implementation an EIP-1167 minimal proxy delegates to.
+fn minimal_proxy_target(code: &[u8]) -> Option<Address> {
+    let target = code
+        .strip_prefix(&EIP1167_PREFIX)?
+        .strip_suffix(&EIP1167_SUFFIX)?;
+    (target.len() == 20).then(|| Address::from_slice(target))
+}
+
+fn row_to_upgrade(row: &PgRow) -> Result<ContractUpgrade, ApiError> {
+    Ok(ContractUpgrade {
+        log_id: row.try_get("log_id")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        tx_hash: row.try_get("tx_hash")?,
+        kind: row.try_get("kind")?,
+        target_address: row.try_get("target_address")?,
+    })
+}
+
+/// Get Contract
+///
+/// Deployment details of a contract created in an indexed block, with its proxy
+/// implementation and upgrade history.
+#[utoipa::path(
+    get,
+    path = "/contract/{address}",
+    params(("address" = String, Path, description = "Contract address or ENS name")),
+    responses(
+        (status = 200, description = "Contract found", body = Contract),
+        (status = 400, description = "Invalid address", body = GenericErrorResponse),
+        (status = 404, description = "No indexed deployment at this address", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_contract_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+) -> Result<Json<Contract>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    // A beacon proxy follows its beacon's upgrades; any other contract follows its own.
+    let row = sqlx::query(&format!(
+        "SELECT {}, \
+                COALESCE(b.target_address, c.beacon_address) AS beacon_address, \
+                COALESCE(i.target_address, c.implementation_address) AS implementation_address \
+         FROM contracts c \
+         LEFT JOIN LATERAL ( \
+             SELECT target_address FROM contract_upgrades \
+             WHERE contract_address = c.address AND kind = 'beacon' \
+             ORDER BY block_number DESC, log_id DESC LIMIT 1 \
+         ) b ON TRUE \
+         LEFT JOIN LATERAL ( \
+             SELECT target_address FROM contract_upgrades \
+             WHERE contract_address = COALESCE(b.target_address, c.beacon_address, c.address) \
+               AND kind = 'implementation' \
+             ORDER BY block_number DESC, log_id DESC LIMIT 1 \
+         ) i ON TRUE \
+         WHERE c.address = $1",
+        CONTRACT_COLUMNS
+    ))
+    .bind(&address)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "No deployment of {} in the indexed blocks.",
+            address
+        ))
+    })?;
+    let upgrades = sqlx::query(
+        "SELECT log_id, block_number, block_timestamp, tx_hash, kind, target_address \
+         FROM contract_upgrades WHERE contract_address = $1 ORDER BY block_number, log_id",
+    )
+    .bind(&address)
+    .fetch_all(&pool)
+    .await?
+    .iter()
+    .map(row_to_upgrade)
+    .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Json(Contract {
+        address: row.try_get("address")?,
+        deployer: row.try_get("deployer")?,
+        factory_address: row.try_get("factory_address")?,
+        creation_tx_hash: row.try_get("creation_tx_hash")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        code_hash: row.try_get("code_hash")?,
+        code_size: row.try_get("code_size")?,
+        proxy_type: row.try_get("proxy_type")?,
+        implementation_address: row.try_get("implementation_address")?,
+        beacon_address: row.try_get("beacon_address")?,
+        upgrades,
+    }))
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/contract/{address}", get(get_contract_handler))
+        .with_state(pool)
+}
@@ -0,0 +1,704 @@
+// src/control.rs
+//
+// Ingester control plane. The ingester loop in main.rs reads its tuning and pause flag from an
+// `IngesterControl` shared with the API, reports its phase back into it, and runs the rewinds
+// and re-indexes queued through it between cycles. The `/admin/ingester` routes are admin
+// routes, so they are only served with API key authentication on; `evm_indexer admin ...` is
+// a thin client for them.
+//
+// A rewind deletes everything from a height on through `rollback_from_height` and publishes a
+// `Reorg` event, so the normal sync loop fetches those blocks again. A re-index deletes a range
+// below the sync point, publishes a `Retract` for it so streams, webhooks and sinks drop what
+// they hold for the range, and ingests it again in place, publishing `BlockCommitted` for every
+// re-ingested block. If a block of the range fails to commit, everything from it on is rolled
+// back as for a rewind, so no gap is left below the sync point. Event handlers run again over
+// the range, against entity state that already includes the blocks above it.
+//
+// `GET /health` and `GET /ready` are public probes: the first answers while the process is up,
+// the second only while the ingester is running and no shutdown is under way.
+
+use crate::api::ApiError;
+use crate::api_models::{
+    BlockRange, GenericErrorResponse, IngesterConfigRequest, IngesterStatus, Readiness,
+    RewindRequest,
+};
+use crate::storage::Storage;
+use crate::supervisor::Shutdown;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    routing::{get, post, put},
+    Router,
+};
+use std::collections::VecDeque;
+use std::env;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+const MAX_BATCH_SIZE: u64 = 1000;
+const MAX_BLOCKS_IN_FLIGHT: usize = 64;
+/// Rewinds and re-indexes that may wait at once.
+const MAX_PENDING_COMMANDS: usize = 16;
+const DEFAULT_ADMIN_URL: &str = "http://localhost:3000";
+
+const CLI_USAGE: &str = "usage: evm_indexer admin status | pause | resume | rewind HEIGHT | \
+     reindex FROM TO | set [--batch-size N] [--blocks-in-flight N]\n\
+     Talks to ADMIN_URL (default http://localhost:3000) with the key in ADMIN_API_KEY.";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Starting,
+    Syncing,
+    Idle,
+    Paused,
+    Rewinding,
+    Reindexing,
+    Retrying,
+    /// Finishing the current commit after a shutdown request.
+    Stopping,
+    /// Not running: stopped, or waiting to be restarted by its supervisor.
+    Down,
+}
+
+impl Phase {
+    fn as_str(self) -> &'static str {
+        match self {
+            Phase::Starting => "starting",
+            Phase::Syncing => "syncing",
+            Phase::Idle => "idle",
+            Phase::Paused => "paused",
+            Phase::Rewinding => "rewinding",
+            Phase::Reindexing => "reindexing",
+            Phase::Retrying => "retrying",
+            Phase::Stopping => "stopping",
+            Phase::Down => "down",
+        }
+    }
+}
+
+/// Work the ingester runs between cycles, in the order it was queued.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Rewind { height: u64 },
+    Reindex { from: u64, to: u64 },
+}
+
+struct PhaseState {
+    phase: Phase,
+    range: Option<BlockRange>,
+    since: i64,
+    chain_head: Option<u64>,
+    last_error: Option<String>,
+}
+
+pub struct IngesterControl {
+    paused: AtomicBool,
+    batch_size: AtomicU64,
+    blocks_in_flight: AtomicUsize,
+    state: Mutex<PhaseState>,
+    commands: Mutex<VecDeque<Command>>,
+    /// Cuts the ingester's waits short when there is something new to do.
+    wake: Notify,
+    shutdown: Shutdown,
+}
+
+/// Marks the ingester as running while held; see `IngesterControl::running`.
+pub struct RunningGuard(Arc<IngesterControl>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.set_phase(Phase::Down, None);
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+impl IngesterControl {
+    pub fn new(batch_size: u64, blocks_in_flight: usize, shutdown: Shutdown) -> Arc<Self> {
+        Arc::new(IngesterControl {
+            paused: AtomicBool::new(false),
+            batch_size: AtomicU64::new(batch_size),
+            blocks_in_flight: AtomicUsize::new(blocks_in_flight),
+            state: Mutex::new(PhaseState {
+                phase: Phase::Starting,
+                range: None,
+                since: unix_now(),
+                chain_head: None,
+                last_error: None,
+            }),
+            commands: Mutex::new(VecDeque::new()),
+            wake: Notify::new(),
+            shutdown,
+        })
+    }
+
+    /// Called at the start of each ingester run. The phase drops to `down` when the guard is
+    /// dropped, which also happens when the run panics.
+    pub fn running(self: &Arc<Self>) -> RunningGuard {
+        self.set_phase(Phase::Starting, None);
+        RunningGuard(self.clone())
+    }
+
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    fn is_ready(&self) -> bool {
+        let phase = self.state.lock().unwrap().phase;
+        !self.shutdown.is_requested() && !matches!(phase, Phase::Stopping | Phase::Down)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn batch_size(&self) -> u64 {
+        self.batch_size.load(Ordering::SeqCst)
+    }
+
+    pub fn blocks_in_flight(&self) -> usize {
+        self.blocks_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Pausing takes effect at the end of the current cycle; blocks already in the pipeline
+    /// are committed first.
+    fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    fn enqueue(&self, command: Command) -> Result<(), ApiError> {
+        let mut commands = self.commands.lock().unwrap();
+        if commands.len() >= MAX_PENDING_COMMANDS {
+            return Err(ApiError::BadRequest(format!(
+                "{} commands are already queued; wait for them to finish.",
+                MAX_PENDING_COMMANDS
+            )));
+        }
+        commands.push_back(command);
+        drop(commands);
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Puts a command back at the front of the queue, e.g. a rollback that must not be lost.
+    pub fn enqueue_first(&self, command: Command) {
+        self.commands.lock().unwrap().push_front(command);
+        self.wake.notify_one();
+    }
+
+    /// Puts a command back at the front of the queue without waking the ingester, for one
+    /// that could not run yet and should wait out the poll interval.
+    pub fn requeue(&self, command: Command) {
+        self.commands.lock().unwrap().push_front(command);
+    }
+
+    pub fn next_command(&self) -> Option<Command> {
+        self.commands.lock().unwrap().pop_front()
+    }
+
+    pub fn set_phase(&self, phase: Phase, range: Option<RangeInclusive<u64>>) {
+        let mut state = self.state.lock().unwrap();
+        if state.phase != phase {
+            state.phase = phase;
+            state.since = unix_now();
+        }
+        state.range = range.map(|range| BlockRange {
+            from_block: *range.start(),
+            to_block: *range.end(),
+        });
+    }
+
+    pub fn set_chain_head(&self, chain_head: u64) {
+        self.state.lock().unwrap().chain_head = Some(chain_head);
+    }
+
+    pub fn chain_head(&self) -> Option<u64> {
+        self.state.lock().unwrap().chain_head
+    }
+
+    /// Records why the ingester is retrying; `None` once a cycle goes through.
+    pub fn set_error(&self, error: Option<String>) {
+        self.state.lock().unwrap().last_error = error;
+    }
+
+    /// Sleeps for `duration`, or until a command, a pause change, a tuning change or a
+    /// shutdown request arrives.
+    pub async fn wait(&self, duration: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.wake.notified() => {}
+            _ = self.shutdown.requested() => {}
+        }
+    }
+
+    fn snapshot(&self, last_synced_block: Option<u64>) -> IngesterStatus {
+        let pending_commands = self.commands.lock().unwrap().len();
+        let state = self.state.lock().unwrap();
+        IngesterStatus {
+            phase: state.phase.as_str().to_string(),
+            paused: self.is_paused(),
+            batch_size: self.batch_size(),
+            blocks_in_flight: self.blocks_in_flight(),
+            last_synced_block,
+            chain_head: state.chain_head,
+            current_range: state.range,
+            pending_commands,
+            last_error: state.last_error.clone(),
+            phase_since: state.since,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ControlState {
+    pub control: Arc<IngesterControl>,
+    pub storage: Arc<dyn Storage>,
+}
+
+impl ControlState {
+    async fn status(&self) -> Result<IngesterStatus, ApiError> {
+        let last_synced_block = self.storage.get_last_synced_block().await?;
+        Ok(self.control.snapshot(last_synced_block))
+    }
+
+    /// Rewinds and re-indexes only touch blocks that are already indexed.
+    async fn require_synced(&self, height: u64, field: &str) -> Result<(), ApiError> {
+        match self.storage.get_last_synced_block().await? {
+            Some(last) if height <= last => Ok(()),
+            Some(last) => Err(ApiError::BadRequest(format!(
+                "{} {} is above the last synced block {}.",
+                field, height, last
+            ))),
+            None => Err(ApiError::BadRequest("Nothing is indexed yet.".to_string())),
+        }
+    }
+}
+
+/// Get Ingester Status
+///
+/// The ingester's phase, tuning, sync position and queued commands.
+#[utoipa::path(
+    get,
+    path = "/admin/ingester",
+    responses(
+        (status = 200, description = "Ingester status", body = IngesterStatus),
+        (status = 500, description = "Internal server error", body = GenericErrorResponse)
+    )
+)]
+pub async fn status_handler(
+    State(state): State<ControlState>,
+) -> Result<Json<IngesterStatus>, ApiError> {
+    Ok(Json(state.status().await?))
+}
+
+/// Pause Ingester
+///
+/// Stops syncing new blocks once the current cycle is committed. Queued rewinds and
+/// re-indexes still run.
+#[utoipa::path(
+    post,
+    path = "/admin/ingester/pause",
+    responses(
+        (status = 200, description = "Ingester status", body = IngesterStatus)
+    )
+)]
+pub async fn pause_handler(
+    State(state): State<ControlState>,
+) -> Result<Json<IngesterStatus>, ApiError> {
+    state.control.set_paused(true);
+    Ok(Json(state.status().await?))
+}
+
+/// Resume Ingester
+#[utoipa::path(
+    post,
+    path = "/admin/ingester/resume",
+    responses(
+        (status = 200, description = "Ingester status", body = IngesterStatus)
+    )
+)]
+pub async fn resume_handler(
+    State(state): State<ControlState>,
+) -> Result<Json<IngesterStatus>, ApiError> {
+    state.control.set_paused(false);
+    Ok(Json(state.status().await?))
+}
+
+/// Rewind Ingester
+///
+/// Queues a rollback of everything at or above `height`, as for a chain reorg: the rows are
+/// deleted, a `reorg` event is published and the ingester syncs forward again from `height`.
+#[utoipa::path(
+    post,
+    path = "/admin/ingester/rewind",
+    request_body = RewindRequest,
+    responses(
+        (status = 202, description = "Rewind queued", body = IngesterStatus),
+        (status = 400, description = "Height not indexed or queue full", body = GenericErrorResponse)
+    )
+)]
+pub async fn rewind_handler(
+    State(state): State<ControlState>,
+    Json(req): Json<RewindRequest>,
+) -> Result<(StatusCode, Json<IngesterStatus>), ApiError> {
+    state.require_synced(req.height, "height").await?;
+    state
+        .control
+        .enqueue(Command::Rewind { height: req.height })?;
+    Ok((StatusCode::ACCEPTED, Json(state.status().await?)))
+}
+
+/// Re-index Block Range
+///
+/// Queues a re-index of `fromBlock..=toBlock`: the range is deleted and fetched again from
+/// the node while the blocks above it stay in place. If the node now has different blocks at
+/// the top of the range, everything above it is rolled back as for a reorg.
+#[utoipa::path(
+    post,
+    path = "/admin/ingester/reindex",
+    request_body = BlockRange,
+    responses(
+        (status = 202, description = "Re-index queued", body = IngesterStatus),
+        (status = 400, description = "Invalid range or queue full", body = GenericErrorResponse)
+    )
+)]
+pub async fn reindex_handler(
+    State(state): State<ControlState>,
+    Json(req): Json<BlockRange>,
+) -> Result<(StatusCode, Json<IngesterStatus>), ApiError> {
+    if req.from_block > req.to_block {
+        return Err(ApiError::BadRequest(
+            "fromBlock must not be after toBlock.".to_string(),
+        ));
+    }
+    state.require_synced(req.to_block, "toBlock").await?;
+    state.control.enqueue(Command::Reindex {
+        from: req.from_block,
+        to: req.to_block,
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(state.status().await?)))
+}
+
+/// Tune Ingester
+///
+/// Changes the batch size and blocks in flight. Takes effect from the next cycle and lasts
+/// until the process restarts.
+#[utoipa::path(
+    put,
+    path = "/admin/ingester/config",
+    request_body = IngesterConfigRequest,
+    responses(
+        (status = 200, description = "Ingester status", body = IngesterStatus),
+        (status = 400, description = "Value out of range", body = GenericErrorResponse)
+    )
+)]
+pub async fn config_handler(
+    State(state): State<ControlState>,
+    Json(req): Json<IngesterConfigRequest>,
+) -> Result<Json<IngesterStatus>, ApiError> {
+    if req
+        .batch_size
+        .is_some_and(|n| !(1..=MAX_BATCH_SIZE).contains(&n))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "batchSize must be between 1 and {}.",
+            MAX_BATCH_SIZE
+        )));
+    }
+    if req
+        .blocks_in_flight
+        .is_some_and(|n| !(1..=MAX_BLOCKS_IN_FLIGHT).contains(&n))
+    {
+        return Err(ApiError::BadRequest(format!(
+            "blocksInFlight must be between 1 and {}.",
+            MAX_BLOCKS_IN_FLIGHT
+        )));
+    }
+    if let Some(batch_size) = req.batch_size {
+        state.control.batch_size.store(batch_size, Ordering::SeqCst);
+    }
+    if let Some(blocks_in_flight) = req.blocks_in_flight {
+        state
+            .control
+            .blocks_in_flight
+            .store(blocks_in_flight, Ordering::SeqCst);
+    }
+    state.control.wake.notify_one();
+    Ok(Json(state.status().await?))
+}
+
+pub fn router(control: Arc<IngesterControl>, storage: Arc<dyn Storage>) -> Router {
+    Router::new()
+        .route("/admin/ingester", get(status_handler))
+        .route("/admin/ingester/pause", post(pause_handler))
+        .route("/admin/ingester/resume", post(resume_handler))
+        .route("/admin/ingester/rewind", post(rewind_handler))
+        .route("/admin/ingester/reindex", post(reindex_handler))
+        .route("/admin/ingester/config", put(config_handler))
+        .with_state(ControlState { control, storage })
+}
+
+/// Liveness Probe
+///
+/// Answers 200 while the process is up, whatever the ingester is doing.
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "The process is up", body = String)
+    )
+)]
+pub async fn health_handler() -> &'static str {
+    "ok"
+}
+
+/// Readiness Probe
+///
+/// 200 while the ingester is running, 503 while it is down (waiting to be restarted after a
+/// crash) and from the moment a shutdown is requested.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    responses(
+        (status = 200, description = "Ready", body = Readiness),
+        (status = 503, description = "Ingester down or shutting down", body = Readiness)
+    )
+)]
+pub async fn ready_handler(
+    State(control): State<Arc<IngesterControl>>,
+) -> (StatusCode, Json<Readiness>) {
+    let ready = control.is_ready();
+    let readiness = Readiness {
+        ready,
+        phase: control.state.lock().unwrap().phase.as_str().to_string(),
+        shutting_down: control.shutdown.is_requested(),
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+/// The probes, served with or without `API_AUTH`.
+pub fn health_router(control: Arc<IngesterControl>) -> Router {
+    Router::new()
+        .route("/health", get(health_handler))
+        .route("/ready", get(ready_handler))
+        .with_state(control)
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<&String>, name: &str) -> eyre::Result<T> {
+    value
+        .ok_or_else(|| eyre::eyre!("missing {}\n{}", name, CLI_USAGE))?
+        .parse()
+        .map_err(|_| eyre::eyre!("{} must be a number\n{}", name, CLI_USAGE))
+}
+
+/// `evm_indexer admin ...`: sends one request to a running server and prints its response.
+pub async fn run_cli(args: &[String]) -> eyre::Result<()> {
+    let (method, path, body) = match args.first().map(String::as_str) {
+        Some("status") => (reqwest::Method::GET, "", None),
+        Some("pause") => (reqwest::Method::POST, "/pause", None),
+        Some("resume") => (reqwest::Method::POST, "/resume", None),
+        Some("rewind") => {
+            let height = parse_number(args.get(1), "HEIGHT")?;
+            let body = serde_json::to_string(&RewindRequest { height })?;
+            (reqwest::Method::POST, "/rewind", Some(body))
+        }
+        Some("reindex") => {
+            let range = BlockRange {
+                from_block: parse_number(args.get(1), "FROM")?,
+                to_block: parse_number(args.get(2), "TO")?,
+            };
+            (
+                reqwest::Method::POST,
+                "/reindex",
+                Some(serde_json::to_string(&range)?),
+            )
+        }
+        Some("set") => {
+            let mut config = IngesterConfigRequest::default();
+            let mut rest = args[1..].iter();
+            while let Some(flag) = rest.next() {
+                match flag.as_str() {
+                    "--batch-size" => config.batch_size = Some(parse_number(rest.next(), flag)?),
+                    "--blocks-in-flight" => {
+                        config.blocks_in_flight = Some(parse_number(rest.next(), flag)?)
+                    }
+                    _ => eyre::bail!("unknown option {}\n{}", flag, CLI_USAGE),
+                }
+            }
+            if config.batch_size.is_none() && config.blocks_in_flight.is_none() {
+                eyre::bail!(
+                    "set needs --batch-size or --blocks-in-flight\n{}",
+                    CLI_USAGE
+                );
+            }
+            (
+                reqwest::Method::PUT,
+                "/config",
+                Some(serde_json::to_string(&config)?),
+            )
+        }
+        _ => eyre::bail!("{}", CLI_USAGE),
+    };
+
+    let base_url = env::var("ADMIN_URL").unwrap_or_else(|_| DEFAULT_ADMIN_URL.to_string());
+    let url = format!("{}/admin/ingester{}", base_url.trim_end_matches('/'), path);
+    let mut request = reqwest::Client::new().request(method, &url);
+    if let Ok(key) = env::var("ADMIN_API_KEY") {
+        request = request.header("X-API-Key", key);
+    }
+    if let Some(body) = body {
+        request = request
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    let text = response.text().await?;
+    if !status.is_success() {
+        eyre::bail!("{} {}: {}", status, url, text);
+    }
+    println!("{}", text);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::supervisor::shutdown_channel;
+
+    fn drain(control: &IngesterControl) -> Vec<Command> {
+        std::iter::from_fn(|| control.next_command()).collect()
+    }
+
+    /// Whether `wait` returns well before its timeout, i.e. the ingester was woken.
+    async fn woken(control: &IngesterControl) -> bool {
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            control.wait(Duration::from_secs(60)),
+        )
+        .await
+        .is_ok()
+    }
+
+    #[tokio::test]
+    async fn commands_run_in_queue_order_with_retries_first() {
+        let (_trigger, shutdown) = shutdown_channel();
+        let control = IngesterControl::new(50, 4, shutdown);
+        control.enqueue(Command::Rewind { height: 10 }).unwrap();
+        control
+            .enqueue(Command::Reindex { from: 20, to: 30 })
+            .unwrap();
+        control.enqueue_first(Command::Rewind { height: 5 });
+        control.requeue(Command::Reindex { from: 1, to: 2 });
+        assert_eq!(
+            drain(&control),
+            vec![
+                Command::Reindex { from: 1, to: 2 },
+                Command::Rewind { height: 5 },
+                Command::Rewind { height: 10 },
+                Command::Reindex { from: 20, to: 30 },
+            ]
+        );
+        assert_eq!(control.next_command(), None);
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_rejects_new_commands_but_keeps_retries() {
+        let (_trigger, shutdown) = shutdown_channel();
+        let control = IngesterControl::new(50, 4, shutdown);
+        for height in 0..MAX_PENDING_COMMANDS as u64 {
+            control.enqueue(Command::Rewind { height }).unwrap();
+        }
+        assert!(matches!(
+            control.enqueue(Command::Rewind { height: 99 }),
+            Err(ApiError::BadRequest(_))
+        ));
+        // A rollback that must not be lost goes in regardless.
+        control.enqueue_first(Command::Rewind { height: 7 });
+        let queued = drain(&control);
+        assert_eq!(queued.len(), MAX_PENDING_COMMANDS + 1);
+        assert_eq!(queued[0], Command::Rewind { height: 7 });
+        assert_eq!(queued[1], Command::Rewind { height: 0 });
+        assert!(!queued.contains(&Command::Rewind { height: 99 }));
+    }
+
+    #[tokio::test]
+    async fn enqueueing_wakes_the_ingester_and_requeueing_does_not() {
+        let (_trigger, shutdown) = shutdown_channel();
+        let control = IngesterControl::new(50, 4, shutdown);
+        control.requeue(Command::Rewind { height: 1 });
+        assert!(!woken(&control).await);
+        control.enqueue_first(Command::Rewind { height: 2 });
+        assert!(woken(&control).await);
+        control.enqueue(Command::Rewind { height: 3 }).unwrap();
+        assert!(woken(&control).await);
+    }
+
+    /// Status, phase and `shuttingDown` of `/ready`, whose `ready` must match the status.
+    async fn readiness(control: &Arc<IngesterControl>) -> (StatusCode, String, bool) {
+        let (status, Json(readiness)) = ready_handler(State(control.clone())).await;
+        assert_eq!(readiness.ready, status == StatusCode::OK);
+        (status, readiness.phase, readiness.shutting_down)
+    }
+
+    #[tokio::test]
+    async fn readiness_follows_the_ingester_and_shutdown() {
+        let (trigger, shutdown) = shutdown_channel();
+        let control = IngesterControl::new(50, 4, shutdown);
+        let ready = |phase: &str| (StatusCode::OK, phase.to_string(), false);
+        let down = |phase: &str| (StatusCode::SERVICE_UNAVAILABLE, phase.to_string(), false);
+
+        let guard = control.running();
+        assert_eq!(readiness(&control).await, ready("starting"));
+        control.set_phase(Phase::Syncing, Some(1..=10));
+        assert_eq!(readiness(&control).await, ready("syncing"));
+        // A run that ends drops its guard and marks the ingester down until the next one.
+        drop(guard);
+        assert_eq!(readiness(&control).await, down("down"));
+        let _guard = control.running();
+        assert_eq!(readiness(&control).await, ready("starting"));
+        control.set_phase(Phase::Stopping, None);
+        assert_eq!(readiness(&control).await, down("stopping"));
+        control.set_phase(Phase::Idle, None);
+        assert_eq!(readiness(&control).await, ready("idle"));
+
+        // A requested shutdown is not ready whatever the phase.
+        trigger.trigger();
+        assert_eq!(
+            readiness(&control).await,
+            (StatusCode::SERVICE_UNAVAILABLE, "idle".to_string(), true)
+        );
+    }
+
+    #[tokio::test]
+    async fn a_panicking_run_marks_the_ingester_down() {
+        let (_trigger, shutdown) = shutdown_channel();
+        let control = IngesterControl::new(50, 4, shutdown);
+        let run = control.clone();
+        let outcome = tokio::spawn(async move {
+            let _guard = run.running();
+            run.set_phase(Phase::Syncing, None);
+            panic!("ingester crashed");
+        })
+        .await;
+        assert!(outcome.unwrap_err().is_panic());
+        assert_eq!(
+            readiness(&control).await,
+            (StatusCode::SERVICE_UNAVAILABLE, "down".to_string(), false)
+        );
+    }
+}
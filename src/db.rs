@@ -1,5 +1,12 @@
 // src/db.rs
+use crate::contracts::ContractBlock;
+use crate::dex::DexBlock;
+use crate::ens::EnsBlock;
+use crate::gas::{GasBlock, HOUR_SECONDS};
+use crate::handlers::EntityWrite;
 use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::pricing::BlockPrices;
+use crate::stats::{BlockStats, RESOLUTIONS};
 use sqlx::{PgPool, Postgres, Transaction};
 
 const INDEXER_NAME: &str = "evm_main_sync";
@@ -29,27 +36,318 @@ pub async fn get_canonical_block_hash_at_height(
     Ok(row.map(|r| r.0))
 }
 
-/// Deletes all blocks, transactions, and logs at or above `fork_height`.
+/// Deletes all blocks, transactions, and logs at or above `fork_height` and moves the sync
+/// point back below it, so the ingester fetches them again.
 /// Used during chain reorg rollback to restore a clean canonical state.
 pub async fn rollback_from_height(pool: &PgPool, fork_height: u64) -> Result<(), sqlx::Error> {
-    let height = fork_height as i64;
+    delete_blocks(pool, fork_height, None).await
+}
+
+/// Deletes the blocks in `from..=to` and everything derived from them, leaving the sync point
+/// alone; the caller ingests the range again. Used to re-index a range below the head.
+pub async fn delete_block_range(pool: &PgPool, from: u64, to: u64) -> Result<(), sqlx::Error> {
+    delete_blocks(pool, from, Some(to)).await
+}
+
+/// Deletes `from..=to`, or everything from `from` on when `to` is `None`, in which case the
+/// sync point moves back to `from - 1`.
+async fn delete_blocks(pool: &PgPool, from: u64, to: Option<u64>) -> Result<(), sqlx::Error> {
+    let height = from as i64;
+    let upper = to.map_or(i64::MAX, |to| to as i64);
+    let mut tx = pool.begin().await?;
+    // The first and last hours whose gas rollups include a deleted block; rebuilt at the end.
+    let gas_hours: (Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT MIN(block_timestamp) / $3 * $3, MAX(block_timestamp) / $3 * $3 \
+         FROM gas_blocks WHERE block_number BETWEEN $1 AND $2",
+    )
+    .bind(height)
+    .bind(upper)
+    .bind(HOUR_SECONDS)
+    .fetch_one(&mut *tx)
+    .await?;
+    // Counters and rollups are adjusted from the rows about to be deleted.
+    rollback_stats(&mut tx, height, upper).await?;
     // Order matters: delete dependent rows first
-    sqlx::query("DELETE FROM logs WHERE block_number >= $1")
+    for (table, column) in [
+        ("handler_entities", "block_number"),
+        ("dex_swaps", "block_number"),
+        ("dex_liquidity_events", "block_number"),
+        ("dex_pools", "first_seen_block"),
+        ("gas_blocks", "block_number"),
+        ("ens_names", "block_number"),
+        ("contract_upgrades", "block_number"),
+        ("contracts", "block_number"),
+        ("token_prices", "block_number"),
+        ("eth_usd_prices", "block_number"),
+        ("logs", "block_number"),
+        ("transactions", "block_number"),
+        ("blocks", "block_number"),
+    ] {
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE {} BETWEEN $1 AND $2",
+            table, column
+        ))
         .bind(height)
-        .execute(pool)
+        .bind(upper)
+        .execute(&mut *tx)
         .await?;
-    sqlx::query("DELETE FROM transactions WHERE block_number >= $1")
+    }
+    if to.is_none() {
+        if height == 0 {
+            sqlx::query("DELETE FROM indexer_status WHERE indexer_name = $1")
+                .bind(INDEXER_NAME)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            sqlx::query(
+                "UPDATE indexer_status SET last_processed_block = $2 \
+                 WHERE indexer_name = $3 AND last_processed_block >= $1",
+            )
+            .bind(height)
+            .bind(height - 1)
+            .bind(INDEXER_NAME)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    if let (Some(from_hour), Some(to_hour)) = gas_hours {
+        rebuild_gas_hours(&mut tx, from_hour, to_hour).await?;
+    }
+    tx.commit().await
+}
+
+/// Recomputes the `gas_hourly` rows of the hours in `[$1, $2)` from `gas_blocks`.
+const GAS_HOURLY_UPSERT: &str = r#"
+    INSERT INTO gas_hourly (
+        bucket, first_block, last_block, blocks, base_fee_avg, base_fee_min, base_fee_max,
+        gas_used, utilization, tx_count, priority_fee_p10, priority_fee_p25, priority_fee_p50,
+        priority_fee_p75, priority_fee_p90
+    )
+    SELECT block_timestamp / $3 * $3, MIN(block_number), MAX(block_number), COUNT(*),
+           ROUND(AVG(base_fee_per_gas)), MIN(base_fee_per_gas), MAX(base_fee_per_gas),
+           SUM(gas_used), AVG(utilization), SUM(tx_count),
+           percentile_disc(0.5) WITHIN GROUP (ORDER BY priority_fee_p10),
+           percentile_disc(0.5) WITHIN GROUP (ORDER BY priority_fee_p25),
+           percentile_disc(0.5) WITHIN GROUP (ORDER BY priority_fee_p50),
+           percentile_disc(0.5) WITHIN GROUP (ORDER BY priority_fee_p75),
+           percentile_disc(0.5) WITHIN GROUP (ORDER BY priority_fee_p90)
+    FROM gas_blocks
+    WHERE block_timestamp >= $1 AND block_timestamp < $2
+    GROUP BY 1
+    ON CONFLICT (bucket) DO UPDATE SET
+        first_block = EXCLUDED.first_block,
+        last_block = EXCLUDED.last_block,
+        blocks = EXCLUDED.blocks,
+        base_fee_avg = EXCLUDED.base_fee_avg,
+        base_fee_min = EXCLUDED.base_fee_min,
+        base_fee_max = EXCLUDED.base_fee_max,
+        gas_used = EXCLUDED.gas_used,
+        utilization = EXCLUDED.utilization,
+        tx_count = EXCLUDED.tx_count,
+        priority_fee_p10 = EXCLUDED.priority_fee_p10,
+        priority_fee_p25 = EXCLUDED.priority_fee_p25,
+        priority_fee_p50 = EXCLUDED.priority_fee_p50,
+        priority_fee_p75 = EXCLUDED.priority_fee_p75,
+        priority_fee_p90 = EXCLUDED.priority_fee_p90
+"#;
+
+/// Rebuilds the hourly gas rollups of the hours `from_hour..=to_hour` after their blocks were
+/// deleted, in the deletion's transaction. The contract rankings are summed again from the
+/// remaining transactions of those hours.
+async fn rebuild_gas_hours(
+    tx: &mut Transaction<'_, Postgres>,
+    from_hour: i64,
+    to_hour: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM gas_hourly WHERE bucket BETWEEN $1 AND $2")
+        .bind(from_hour)
+        .bind(to_hour)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(GAS_HOURLY_UPSERT)
+        .bind(from_hour)
+        .bind(to_hour + HOUR_SECONDS)
+        .bind(HOUR_SECONDS)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query("DELETE FROM gas_contract_hourly WHERE bucket BETWEEN $1 AND $2")
+        .bind(from_hour)
+        .bind(to_hour)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO gas_contract_hourly (bucket, contract_address, gas_used, fees_paid, tx_count)
+        SELECT g.block_timestamp / $2 * $2, t.to_address, SUM(t.gas_used::NUMERIC),
+               SUM(t.gas_used::NUMERIC * t.effective_gas_price::NUMERIC), COUNT(*)
+        FROM transactions t
+        JOIN gas_blocks g ON g.block_number = t.block_number
+        WHERE g.block_timestamp >= $1 AND g.block_timestamp < $3 AND t.to_address IS NOT NULL
+          AND length(t.input_data) > 2
+          AND t.gas_used IS NOT NULL AND t.effective_gas_price IS NOT NULL
+          AND g.priority_fee_p50 IS NOT NULL
+        GROUP BY 1, 2
+        "#,
+    )
+    .bind(from_hour)
+    .bind(HOUR_SECONDS)
+    .bind(to_hour + HOUR_SECONDS)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Subtracts the blocks in `height..=upper` from the counters and, for the blocks that were
+/// rolled up, from the hourly and daily activity. Runs before their rows are deleted.
+async fn rollback_stats(
+    tx: &mut Transaction<'_, Postgres>,
+    height: i64,
+    upper: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE stats_counters SET
+            blocks = blocks - (SELECT COUNT(*) FROM blocks WHERE block_number BETWEEN $1 AND $2),
+            transactions = transactions - (SELECT COUNT(*) FROM transactions WHERE block_number BETWEEN $1 AND $2),
+            logs = logs - (SELECT COUNT(*) FROM logs WHERE block_number BETWEEN $1 AND $2)
+        "#,
+    )
+    .bind(height)
+    .bind(upper)
+    .execute(&mut **tx)
+    .await?;
+
+    let (first_timestamp,): (Option<i64>,) = sqlx::query_as(
+        "SELECT MIN(block_timestamp) FROM stats_blocks WHERE block_number BETWEEN $1 AND $2",
+    )
+    .bind(height)
+    .bind(upper)
+    .fetch_one(&mut **tx)
+    .await?;
+    let Some(first_timestamp) = first_timestamp else {
+        return Ok(());
+    };
+    for (resolution, seconds) in RESOLUTIONS {
+        // Each sender of a rolled-back transaction is recounted in its bucket: it stays, with
+        // its first block moved to its earliest transaction outside the range, or leaves when
+        // it has none. A re-indexed range need not sit at the top of its bucket, and the
+        // blocks written in its place need not have the same senders.
+        sqlx::query(
+            r#"
+            WITH rolled_blocks AS (
+                SELECT block_number, block_timestamp / $3 * $3 AS bucket
+                FROM stats_blocks WHERE block_number BETWEEN $1 AND $4
+            ),
+            block_counts AS (
+                SELECT bucket, COUNT(*) AS blocks FROM rolled_blocks GROUP BY bucket
+            ),
+            tx_counts AS (
+                SELECT r.bucket, COUNT(*) AS tx_count,
+                       COALESCE(SUM(t.value::NUMERIC) FILTER (WHERE t.status IS DISTINCT FROM 0), 0)
+                           AS eth_volume
+                FROM transactions t JOIN rolled_blocks r ON r.block_number = t.block_number
+                GROUP BY r.bucket
+            ),
+            log_counts AS (
+                SELECT r.bucket, COUNT(*) AS log_count
+                FROM logs l JOIN rolled_blocks r ON r.block_number = l.block_number
+                GROUP BY r.bucket
+            ),
+            touched_senders AS (
+                SELECT DISTINCT r.bucket, t.from_address AS address
+                FROM transactions t JOIN rolled_blocks r ON r.block_number = t.block_number
+            ),
+            remaining_senders AS (
+                SELECT u.bucket, u.address, (
+                    SELECT MIN(t.block_number)
+                    FROM transactions t JOIN stats_blocks b ON b.block_number = t.block_number
+                    WHERE t.from_address = u.address
+                      AND b.block_timestamp >= u.bucket AND b.block_timestamp < u.bucket + $3
+                      AND t.block_number NOT BETWEEN $1 AND $4
+                ) AS first_block
+                FROM touched_senders u
+            ),
+            moved_senders AS (
+                UPDATE stats_senders s SET first_block = r.first_block
+                FROM remaining_senders r
+                WHERE s.resolution = $2 AND s.bucket = r.bucket AND s.address = r.address
+                  AND r.first_block IS NOT NULL
+            ),
+            removed_senders AS (
+                DELETE FROM stats_senders s USING remaining_senders r
+                WHERE s.resolution = $2 AND s.bucket = r.bucket AND s.address = r.address
+                  AND r.first_block IS NULL
+                RETURNING s.bucket
+            ),
+            sender_counts AS (
+                SELECT bucket, COUNT(*) AS senders FROM removed_senders GROUP BY bucket
+            )
+            UPDATE stats_rollups s SET
+                blocks = s.blocks - b.blocks,
+                tx_count = s.tx_count - COALESCE(t.tx_count, 0),
+                unique_senders = s.unique_senders - COALESCE(u.senders, 0),
+                eth_volume = s.eth_volume - COALESCE(t.eth_volume, 0),
+                log_count = s.log_count - COALESCE(l.log_count, 0)
+            FROM block_counts b
+            LEFT JOIN tx_counts t ON t.bucket = b.bucket
+            LEFT JOIN log_counts l ON l.bucket = b.bucket
+            LEFT JOIN sender_counts u ON u.bucket = b.bucket
+            WHERE s.resolution = $2 AND s.bucket = b.bucket
+            "#,
+        )
         .bind(height)
-        .execute(pool)
+        .bind(resolution)
+        .bind(seconds)
+        .bind(upper)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE stats_log_rollups s SET log_count = s.log_count - d.log_count
+            FROM (
+                SELECT b.block_timestamp / $3 * $3 AS bucket, l.contract_address, l.topic0,
+                       COUNT(*) AS log_count
+                FROM logs l JOIN stats_blocks b ON b.block_number = l.block_number
+                WHERE b.block_number BETWEEN $1 AND $4
+                GROUP BY 1, 2, 3
+            ) d
+            WHERE s.resolution = $2 AND s.bucket = d.bucket
+              AND s.contract_address = d.contract_address AND s.topic0 IS NOT DISTINCT FROM d.topic0
+            "#,
+        )
+        .bind(height)
+        .bind(resolution)
+        .bind(seconds)
+        .bind(upper)
+        .execute(&mut **tx)
+        .await?;
+        let from_bucket = first_timestamp / seconds * seconds;
+        sqlx::query(
+            "DELETE FROM stats_rollups WHERE resolution = $1 AND bucket >= $2 AND blocks = 0",
+        )
+        .bind(resolution)
+        .bind(from_bucket)
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query(
+            "DELETE FROM stats_log_rollups WHERE resolution = $1 AND bucket >= $2 AND log_count = 0",
+        )
+        .bind(resolution)
+        .bind(from_bucket)
+        .execute(&mut **tx)
         .await?;
-    sqlx::query("DELETE FROM blocks WHERE block_number >= $1")
+    }
+    sqlx::query("DELETE FROM stats_blocks WHERE block_number BETWEEN $1 AND $2")
         .bind(height)
-        .execute(pool)
+        .bind(upper)
+        .execute(&mut **tx)
         .await?;
     Ok(())
 }
 
-/// Updates indexer status: last synced block and the chain head at time of poll.
+/// Updates indexer status: last synced block and the chain head at time of poll. The sync
+/// point only moves forward here, so re-indexing a range below it leaves it in place; only a
+/// rollback moves it back.
 pub async fn set_last_synced_block(
     executor: &mut Transaction<'_, Postgres>,
     block_number: u64,
@@ -63,7 +361,9 @@ pub async fn set_last_synced_block(
         INSERT INTO indexer_status (indexer_name, last_processed_block, chain_head_at_last_poll)
         VALUES ($1, $2, $3)
         ON CONFLICT (indexer_name) DO UPDATE SET
-            last_processed_block = EXCLUDED.last_processed_block,
+            last_processed_block = GREATEST(
+                indexer_status.last_processed_block, EXCLUDED.last_processed_block
+            ),
             chain_head_at_last_poll = EXCLUDED.chain_head_at_last_poll;
         "#,
     )
@@ -125,14 +425,22 @@ pub async fn insert_transaction_data(
     let block_number_val = tx.block_number.as_u64() as i64;
     let transaction_index_val = tx.transaction_index.map(|idx| idx.as_u64() as i64);
     let status_val = tx.status.map(|s| s as i16);
+    let gas_used_str = tx.gas_used.map(|val| val.to_string());
+    let effective_gas_price_str = tx.effective_gas_price.map(|val| val.to_string());
+    let nonce_str = tx.nonce.map(|val| val.to_string());
+    let transaction_type_val = tx.transaction_type.map(|t| t as i16);
+    let contract_address_str = tx.contract_address.map(|addr| format!("{:#x}", addr));
 
     sqlx::query(
         r#"
         INSERT INTO transactions (
             tx_hash, block_number, block_hash, transaction_index,
             from_address, to_address, value, gas_price, max_fee_per_gas,
-            max_priority_fee_per_gas, gas_provided, input_data, status
-        ) VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13 )
+            max_priority_fee_per_gas, gas_provided, input_data, status, gas_used,
+            effective_gas_price, nonce, transaction_type, contract_address
+        ) VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18
+        )
         ON CONFLICT (tx_hash) DO NOTHING;
         "#,
     )
@@ -149,15 +457,21 @@ pub async fn insert_transaction_data(
     .bind(gas_provided_str)
     .bind(tx.input_data.as_bytes())
     .bind(status_val)
+    .bind(gas_used_str)
+    .bind(effective_gas_price_str)
+    .bind(nonce_str)
+    .bind(transaction_type_val)
+    .bind(contract_address_str)
     .execute(&mut **executor)
     .await?;
     Ok(())
 }
 
+/// Inserts a log and returns its generated `id` (the second half of the pagination cursor).
 pub async fn insert_log_data(
     executor: &mut Transaction<'_, Postgres>,
     log: &MyLog,
-) -> Result<(), sqlx::Error> {
+) -> Result<i64, sqlx::Error> {
     let tx_hash_str = format!("{:#x}", log.transaction_hash);
     let block_hash_str = format!("{:#x}", log.block_hash);
     let contract_address_str = format!("{:#x}", log.address);
@@ -169,13 +483,14 @@ pub async fn insert_log_data(
     let transaction_index_val = log.transaction_index.map(|ti| ti as i64);
     let block_number_val = log.block_number as i64;
 
-    sqlx::query(
+    let (id,): (i64,) = sqlx::query_as(
         r#"
         INSERT INTO logs (
             log_index_in_tx, transaction_hash, transaction_index_in_block,
             block_number, block_hash, contract_address, data,
             topic0, topic1, topic2, topic3, all_topics
         ) VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12 )
+        RETURNING id
         "#,
     )
     .bind(log_index_val)
@@ -190,7 +505,253 @@ pub async fn insert_log_data(
     .bind(topic2)
     .bind(topic3)
     .bind(&log.topics)
-    .execute(&mut **executor)
+    .fetch_one(&mut **executor)
+    .await?;
+    Ok(id)
+}
+
+/// Upper bound on rows per batched statement, keeping each bound array a manageable size.
+const MAX_BATCH_ROWS: usize = 5000;
+
+/// Inserts many transactions with one `INSERT ... SELECT FROM UNNEST` per chunk: each column
+/// is bound as a single array parameter, so a block costs one round trip instead of one per
+/// transaction.
+pub async fn insert_transactions_batch(
+    executor: &mut Transaction<'_, Postgres>,
+    txs: &[MyTransaction],
+) -> Result<(), sqlx::Error> {
+    for chunk in txs.chunks(MAX_BATCH_ROWS) {
+        sqlx::query(
+            r#"
+            INSERT INTO transactions (
+                tx_hash, block_number, block_hash, transaction_index,
+                from_address, to_address, value, gas_price, max_fee_per_gas,
+                max_priority_fee_per_gas, gas_provided, input_data, status, gas_used,
+                effective_gas_price, nonce, transaction_type, contract_address
+            )
+            SELECT * FROM UNNEST(
+                $1::TEXT[], $2::BIGINT[], $3::TEXT[], $4::BIGINT[],
+                $5::TEXT[], $6::TEXT[], $7::TEXT[], $8::TEXT[], $9::TEXT[],
+                $10::TEXT[], $11::TEXT[], $12::BYTEA[], $13::SMALLINT[], $14::TEXT[], $15::TEXT[],
+                $16::TEXT[], $17::SMALLINT[], $18::TEXT[]
+            )
+            ON CONFLICT (tx_hash) DO NOTHING;
+            "#,
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| format!("{:#x}", tx.tx_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.block_number.as_u64() as i64)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| format!("{:#x}", tx.block_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.transaction_index.map(|idx| idx.as_u64() as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| format!("{:#x}", tx.from_address))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.to_address.map(|addr| format!("{:#x}", addr)))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.value.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.gas_price.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.max_fee_per_gas.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.max_priority_fee_per_gas.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.gas.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.input_data.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.status.map(|s| s as i16))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.gas_used.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.effective_gas_price.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.nonce.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.transaction_type.map(|t| t as i16))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|tx| tx.contract_address.map(|addr| format!("{:#x}", addr)))
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Inserts many logs with one `INSERT ... SELECT FROM UNNEST` per chunk and returns their ids
+/// in input order. Rows are inserted in ordinality order, so the BIGSERIAL ids ascend with the
+/// input. `all_topics` is rebuilt from the topic columns since UNNEST cannot take ragged arrays.
+pub async fn insert_logs_batch(
+    executor: &mut Transaction<'_, Postgres>,
+    logs: &[MyLog],
+) -> Result<Vec<i64>, sqlx::Error> {
+    let mut ids = Vec::with_capacity(logs.len());
+    for chunk in logs.chunks(MAX_BATCH_ROWS) {
+        let topic = |position: usize| -> Vec<Option<String>> {
+            chunk
+                .iter()
+                .map(|log| log.topics.get(position).cloned())
+                .collect()
+        };
+        let mut chunk_ids: Vec<(i64,)> = sqlx::query_as(
+            r#"
+            INSERT INTO logs (
+                log_index_in_tx, transaction_hash, transaction_index_in_block,
+                block_number, block_hash, contract_address, data,
+                topic0, topic1, topic2, topic3, all_topics
+            )
+            SELECT l, h, ti, bn, bh, a, d, t0, t1, t2, t3,
+                   ARRAY_REMOVE(ARRAY[t0, t1, t2, t3], NULL)
+            FROM UNNEST(
+                $1::BIGINT[], $2::TEXT[], $3::BIGINT[], $4::BIGINT[], $5::TEXT[],
+                $6::TEXT[], $7::BYTEA[], $8::TEXT[], $9::TEXT[], $10::TEXT[], $11::TEXT[]
+            ) WITH ORDINALITY AS u(l, h, ti, bn, bh, a, d, t0, t1, t2, t3, ord)
+            ORDER BY ord
+            RETURNING id
+            "#,
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| log.log_index.map(|li| li.as_u64() as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| format!("{:#x}", log.transaction_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| log.transaction_index.map(|ti| ti as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| log.block_number as i64)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| format!("{:#x}", log.block_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| format!("{:#x}", log.address))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|log| log.data.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+        )
+        .bind(topic(0))
+        .bind(topic(1))
+        .bind(topic(2))
+        .bind(topic(3))
+        .fetch_all(&mut **executor)
+        .await?;
+        // RETURNING order is not guaranteed, but id assignment follows insertion order.
+        chunk_ids.sort_unstable();
+        ids.extend(chunk_ids.into_iter().map(|(id,)| id));
+    }
+    Ok(ids)
+}
+
+/// Seeds `stats_counters` from the tables when it is empty, i.e. on the first start with a
+/// database created before the counters existed. That one start pays for the full count.
+pub async fn init_stats_counters(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO stats_counters (id, blocks, transactions, logs)
+        SELECT TRUE, (SELECT COUNT(*) FROM blocks), (SELECT COUNT(*) FROM transactions),
+               (SELECT COUNT(*) FROM logs)
+        WHERE NOT EXISTS (SELECT 1 FROM stats_counters)
+        ON CONFLICT (id) DO NOTHING
+        "#,
+    )
+    .execute(pool)
     .await?;
     Ok(())
 }
@@ -205,3 +766,774 @@ pub async fn get_indexer_status(pool: &PgPool) -> Result<Option<(i64, i64)>, sql
     .await?;
     Ok(row)
 }
+
+/// Writes one block's handler entity versions. Callers pass at most one row per
+/// (handler, entity, key), since a single upsert cannot touch the same row twice.
+pub async fn upsert_handler_entities(
+    executor: &mut Transaction<'_, Postgres>,
+    rows: &[EntityWrite],
+) -> Result<(), sqlx::Error> {
+    for chunk in rows.chunks(MAX_BATCH_ROWS) {
+        sqlx::query(
+            r#"
+            INSERT INTO handler_entities (handler_id, entity, entity_key, block_number, data)
+            SELECT h, e, k, b, d::JSONB
+            FROM UNNEST($1::BIGINT[], $2::TEXT[], $3::TEXT[], $4::BIGINT[], $5::TEXT[])
+                AS u(h, e, k, b, d)
+            ON CONFLICT (handler_id, entity, entity_key, block_number)
+            DO UPDATE SET data = EXCLUDED.data
+            "#,
+        )
+        .bind(chunk.iter().map(|r| r.handler_id).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|r| r.entity.clone()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|r| r.key.clone()).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|r| r.block_number).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|r| r.data.clone()).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Disables a handler whose script failed at `block_number` until its script is updated.
+pub async fn mark_handler_failed(
+    executor: &mut Transaction<'_, Postgres>,
+    handler_id: i64,
+    block_number: u64,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE event_handlers SET last_error = $2, failed_at_block = $3 WHERE id = $1")
+        .bind(handler_id)
+        .bind(error)
+        .bind(block_number as i64)
+        .execute(&mut **executor)
+        .await?;
+    Ok(())
+}
+
+/// Writes one block's DEX rows. `log_ids` are the ids of the block's logs in insertion order;
+/// decoded events refer to their log by position. Pools are upserted on every block that
+/// touches them, which re-registers a pool whose first block was rolled back.
+pub async fn insert_dex_block(
+    executor: &mut Transaction<'_, Postgres>,
+    dex: &DexBlock,
+    log_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    let hex = |address: &ethers::types::Address| format!("{:#x}", address);
+    let log_id = |position: usize| {
+        log_ids.get(position).copied().ok_or_else(|| {
+            sqlx::Error::Protocol(format!("DEX row refers to missing log #{}", position))
+        })
+    };
+    let block_number = dex.block_number as i64;
+    let block_timestamp = dex.block_timestamp as i64;
+
+    if !dex.pools.is_empty() {
+        let pools = &dex.pools;
+        sqlx::query(
+            r#"
+            INSERT INTO dex_pools (
+                pool_address, protocol, factory_address, token0, token1, fee, tick_spacing,
+                created_block, first_seen_block
+            )
+            SELECT a, p, f, t0, t1, fee, ts, cb, $9
+            FROM UNNEST(
+                $1::TEXT[], $2::TEXT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::INT[],
+                $7::INT[], $8::BIGINT[]
+            ) AS u(a, p, f, t0, t1, fee, ts, cb)
+            ON CONFLICT (pool_address) DO UPDATE SET
+                factory_address = EXCLUDED.factory_address,
+                created_block = EXCLUDED.created_block
+            WHERE dex_pools.created_block IS NULL AND EXCLUDED.created_block IS NOT NULL
+            "#,
+        )
+        .bind(pools.iter().map(|p| hex(&p.address)).collect::<Vec<_>>())
+        .bind(
+            pools
+                .iter()
+                .map(|p| p.protocol.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            pools
+                .iter()
+                .map(|p| p.factory.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(pools.iter().map(|p| hex(&p.token0)).collect::<Vec<_>>())
+        .bind(pools.iter().map(|p| hex(&p.token1)).collect::<Vec<_>>())
+        .bind(
+            pools
+                .iter()
+                .map(|p| p.fee.map(|fee| fee as i32))
+                .collect::<Vec<_>>(),
+        )
+        .bind(pools.iter().map(|p| p.tick_spacing).collect::<Vec<_>>())
+        .bind(
+            pools
+                .iter()
+                .map(|p| p.created_block.map(|b| b as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(block_number)
+        .execute(&mut **executor)
+        .await?;
+    }
+
+    for chunk in dex.swaps.chunks(MAX_BATCH_ROWS) {
+        sqlx::query(
+            r#"
+            INSERT INTO dex_swaps (
+                log_id, block_number, block_timestamp, tx_hash, log_index, pool_address,
+                protocol, token0, token1, trader, sender, recipient, amount0, amount1,
+                sqrt_price_x96, liquidity, tick
+            )
+            SELECT id, $1, $2, h, li, a, p, t0, t1, tr, s, r, a0::NUMERIC, a1::NUMERIC,
+                   sp::NUMERIC, l::NUMERIC, tk
+            FROM UNNEST(
+                $3::BIGINT[], $4::TEXT[], $5::BIGINT[], $6::TEXT[], $7::TEXT[], $8::TEXT[],
+                $9::TEXT[], $10::TEXT[], $11::TEXT[], $12::TEXT[], $13::TEXT[], $14::TEXT[],
+                $15::TEXT[], $16::TEXT[], $17::INT[]
+            ) AS u(id, h, li, a, p, t0, t1, tr, s, r, a0, a1, sp, l, tk)
+            ON CONFLICT (log_id) DO NOTHING
+            "#,
+        )
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(
+            chunk
+                .iter()
+                .map(|s| log_id(s.log_position))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|s| format!("{:#x}", s.tx_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.log_index.map(|i| i as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|s| hex(&s.pool)).collect::<Vec<_>>())
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.protocol.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|s| hex(&s.token0)).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|s| hex(&s.token1)).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|s| hex(&s.trader)).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|s| hex(&s.sender)).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|s| hex(&s.recipient)).collect::<Vec<_>>())
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.amount0.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.amount1.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.sqrt_price_x96.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|s| s.liquidity.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|s| s.tick).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+
+    for chunk in dex.liquidity.chunks(MAX_BATCH_ROWS) {
+        sqlx::query(
+            r#"
+            INSERT INTO dex_liquidity_events (
+                log_id, block_number, block_timestamp, tx_hash, log_index, pool_address, kind,
+                owner, sender, recipient, amount0, amount1, liquidity, tick_lower, tick_upper
+            )
+            SELECT id, $1, $2, h, li, a, k, o, s, r, a0::NUMERIC, a1::NUMERIC, l::NUMERIC,
+                   tl, tu
+            FROM UNNEST(
+                $3::BIGINT[], $4::TEXT[], $5::BIGINT[], $6::TEXT[], $7::TEXT[], $8::TEXT[],
+                $9::TEXT[], $10::TEXT[], $11::TEXT[], $12::TEXT[], $13::TEXT[], $14::INT[],
+                $15::INT[]
+            ) AS u(id, h, li, a, k, o, s, r, a0, a1, l, tl, tu)
+            ON CONFLICT (log_id) DO NOTHING
+            "#,
+        )
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(
+            chunk
+                .iter()
+                .map(|e| log_id(e.log_position))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| format!("{:#x}", e.tx_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.log_index.map(|i| i as i64))
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|e| hex(&e.pool)).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.kind).collect::<Vec<_>>())
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.owner.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.sender.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.recipient.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.amount0.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.amount1.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            chunk
+                .iter()
+                .map(|e| e.liquidity.map(|v| v.to_string()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(chunk.iter().map(|e| e.tick_lower).collect::<Vec<_>>())
+        .bind(chunk.iter().map(|e| e.tick_upper).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Writes one block's prices. Rows are keyed by bucket, so with per-minute buckets a later
+/// block in the same minute overwrites the earlier observation. Token decimals and symbols
+/// are immutable and only inserted once.
+pub async fn insert_prices(
+    executor: &mut Transaction<'_, Postgres>,
+    prices: &BlockPrices,
+) -> Result<(), sqlx::Error> {
+    let hex = |address: &ethers::types::Address| format!("{:#x}", address);
+    let block_number = prices.block_number as i64;
+    let block_timestamp = prices.block_timestamp as i64;
+
+    if !prices.tokens_meta.is_empty() {
+        let tokens_meta = &prices.tokens_meta;
+        sqlx::query(
+            r#"
+            INSERT INTO tokens (token_address, decimals, symbol)
+            SELECT * FROM UNNEST($1::TEXT[], $2::SMALLINT[], $3::TEXT[])
+            ON CONFLICT (token_address) DO NOTHING
+            "#,
+        )
+        .bind(
+            tokens_meta
+                .iter()
+                .map(|(token, _)| hex(token))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            tokens_meta
+                .iter()
+                .map(|(_, meta)| meta.decimals.map(i16::from))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            tokens_meta
+                .iter()
+                .map(|(_, meta)| meta.symbol.clone())
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut **executor)
+        .await?;
+    }
+
+    if !prices.tokens.is_empty() {
+        let quotes = &prices.tokens;
+        sqlx::query(
+            r#"
+            INSERT INTO token_prices (
+                token_address, bucket, block_number, block_timestamp, price_eth, pool_address,
+                liquidity_eth
+            )
+            SELECT t, $1, $2, $3, p, a, l
+            FROM UNNEST($4::TEXT[], $5::FLOAT8[], $6::TEXT[], $7::FLOAT8[]) AS u(t, p, a, l)
+            ON CONFLICT (token_address, bucket) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_timestamp = EXCLUDED.block_timestamp,
+                price_eth = EXCLUDED.price_eth,
+                pool_address = EXCLUDED.pool_address,
+                liquidity_eth = EXCLUDED.liquidity_eth
+            "#,
+        )
+        .bind(prices.bucket)
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(quotes.iter().map(|q| hex(&q.token)).collect::<Vec<_>>())
+        .bind(quotes.iter().map(|q| q.price_eth).collect::<Vec<_>>())
+        .bind(quotes.iter().map(|q| hex(&q.pool)).collect::<Vec<_>>())
+        .bind(quotes.iter().map(|q| q.liquidity_eth).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+
+    if let Some(eth_usd) = &prices.eth_usd {
+        sqlx::query(
+            r#"
+            INSERT INTO eth_usd_prices (
+                bucket, block_number, block_timestamp, price_usd, pool_address, liquidity_eth
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (bucket) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_timestamp = EXCLUDED.block_timestamp,
+                price_usd = EXCLUDED.price_usd,
+                pool_address = EXCLUDED.pool_address,
+                liquidity_eth = EXCLUDED.liquidity_eth
+            "#,
+        )
+        .bind(prices.bucket)
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(eth_usd.price_usd)
+        .bind(hex(&eth_usd.pool))
+        .bind(eth_usd.liquidity_eth)
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Writes one block's deployments and proxy upgrades. A redeployment at the same address
+/// (CREATE2 after a self-destruct) replaces the earlier row. Upgrade events are only kept for
+/// contracts in the index, which includes the block's own deployments: proxies commonly emit
+/// `Upgraded` from their constructor.
+pub async fn insert_contracts(
+    executor: &mut Transaction<'_, Postgres>,
+    contracts: &ContractBlock,
+    log_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    let hex = |address: &ethers::types::Address| format!("{:#x}", address);
+    let block_number = contracts.block_number as i64;
+    let block_timestamp = contracts.block_timestamp as i64;
+
+    if !contracts.deployments.is_empty() {
+        let deployments = &contracts.deployments;
+        sqlx::query(
+            r#"
+            INSERT INTO contracts (
+                address, deployer, factory_address, creation_tx_hash, block_number,
+                block_timestamp, code_hash, code_size, proxy_type, implementation_address,
+                beacon_address
+            )
+            SELECT a, d, f, h, $1, $2, ch, cs, pt, i, b
+            FROM UNNEST(
+                $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[], $8::INT[],
+                $9::TEXT[], $10::TEXT[], $11::TEXT[]
+            ) AS u(a, d, f, h, ch, cs, pt, i, b)
+            ON CONFLICT (address) DO UPDATE SET
+                deployer = EXCLUDED.deployer,
+                factory_address = EXCLUDED.factory_address,
+                creation_tx_hash = EXCLUDED.creation_tx_hash,
+                block_number = EXCLUDED.block_number,
+                block_timestamp = EXCLUDED.block_timestamp,
+                code_hash = EXCLUDED.code_hash,
+                code_size = EXCLUDED.code_size,
+                proxy_type = EXCLUDED.proxy_type,
+                implementation_address = EXCLUDED.implementation_address,
+                beacon_address = EXCLUDED.beacon_address
+            "#,
+        )
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(
+            deployments
+                .iter()
+                .map(|d| hex(&d.address))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| hex(&d.deployer))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.factory.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| format!("{:#x}", d.tx_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.code_hash.map(|h| format!("{:#x}", h)))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.code_size as i32)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.proxy.map(|p| p.as_str()))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.implementation.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            deployments
+                .iter()
+                .map(|d| d.beacon.as_ref().map(hex))
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut **executor)
+        .await?;
+    }
+
+    if !contracts.upgrades.is_empty() {
+        let upgrades = &contracts.upgrades;
+        let log_ids = upgrades
+            .iter()
+            .map(|u| {
+                log_ids.get(u.log_position).copied().ok_or_else(|| {
+                    sqlx::Error::Protocol(format!(
+                        "contract upgrade refers to missing log #{}",
+                        u.log_position
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        sqlx::query(
+            r#"
+            INSERT INTO contract_upgrades (
+                log_id, block_number, block_timestamp, tx_hash, contract_address, kind,
+                target_address
+            )
+            SELECT id, $1, $2, h, a, k, t
+            FROM UNNEST($3::BIGINT[], $4::TEXT[], $5::TEXT[], $6::TEXT[], $7::TEXT[])
+                AS u(id, h, a, k, t)
+            WHERE EXISTS (SELECT 1 FROM contracts c WHERE c.address = u.a)
+            ON CONFLICT (log_id) DO NOTHING
+            "#,
+        )
+        .bind(block_number)
+        .bind(block_timestamp)
+        .bind(log_ids)
+        .bind(
+            upgrades
+                .iter()
+                .map(|u| format!("{:#x}", u.tx_hash))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            upgrades
+                .iter()
+                .map(|u| hex(&u.contract))
+                .collect::<Vec<_>>(),
+        )
+        .bind(upgrades.iter().map(|u| u.kind.as_str()).collect::<Vec<_>>())
+        .bind(upgrades.iter().map(|u| hex(&u.target)).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Appends one block's ENS events. Earlier rows are kept: reads take each node's latest.
+pub async fn insert_ens(
+    executor: &mut Transaction<'_, Postgres>,
+    ens: &EnsBlock,
+    log_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    if ens.records.is_empty() {
+        return Ok(());
+    }
+    let records = &ens.records;
+    let log_ids = records
+        .iter()
+        .map(|r| {
+            log_ids.get(r.log_position).copied().ok_or_else(|| {
+                sqlx::Error::Protocol(format!(
+                    "ENS record refers to missing log #{}",
+                    r.log_position
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    sqlx::query(
+        r#"
+        INSERT INTO ens_names (log_id, block_number, node, kind, value, source)
+        SELECT id, $1, n, k, v, s
+        FROM UNNEST($2::BIGINT[], $3::TEXT[], $4::TEXT[], $5::TEXT[], $6::TEXT[])
+            AS u(id, n, k, v, s)
+        ON CONFLICT (log_id) DO NOTHING
+        "#,
+    )
+    .bind(ens.block_number as i64)
+    .bind(log_ids)
+    .bind(
+        records
+            .iter()
+            .map(|r| format!("{:#x}", r.node))
+            .collect::<Vec<_>>(),
+    )
+    .bind(records.iter().map(|r| r.kind.as_str()).collect::<Vec<_>>())
+    .bind(records.iter().map(|r| r.value.clone()).collect::<Vec<_>>())
+    .bind(
+        records
+            .iter()
+            .map(|r| format!("{:#x}", r.source))
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut **executor)
+    .await?;
+    Ok(())
+}
+
+/// Writes one block's gas rollup, recomputes its hour and adds its per-contract gas to the
+/// hour's rankings.
+pub async fn insert_gas_block(
+    executor: &mut Transaction<'_, Postgres>,
+    gas: &GasBlock,
+) -> Result<(), sqlx::Error> {
+    let block_timestamp = gas.block_timestamp as i64;
+    let hour = block_timestamp / HOUR_SECONDS * HOUR_SECONDS;
+    let fee = |i: usize| {
+        gas.priority_fees
+            .as_ref()
+            .and_then(|fees| fees.get(i))
+            .map(|fee| fee.to_string())
+    };
+    let utilization = if gas.gas_limit.is_zero() {
+        0.0
+    } else {
+        crate::pricing::to_f64(gas.gas_used) / crate::pricing::to_f64(gas.gas_limit)
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO gas_blocks (
+            block_number, block_timestamp, base_fee_per_gas, gas_used, gas_limit, utilization,
+            tx_count, priority_fee_p10, priority_fee_p25, priority_fee_p50, priority_fee_p75,
+            priority_fee_p90
+        )
+        VALUES ($1, $2, $3::NUMERIC, $4::NUMERIC, $5::NUMERIC, $6, $7, $8::NUMERIC,
+                $9::NUMERIC, $10::NUMERIC, $11::NUMERIC, $12::NUMERIC)
+        ON CONFLICT (block_number) DO NOTHING
+        "#,
+    )
+    .bind(gas.block_number as i64)
+    .bind(block_timestamp)
+    .bind(gas.base_fee_per_gas.map(|fee| fee.to_string()))
+    .bind(gas.gas_used.to_string())
+    .bind(gas.gas_limit.to_string())
+    .bind(utilization)
+    .bind(gas.tx_count as i32)
+    .bind(fee(0))
+    .bind(fee(1))
+    .bind(fee(2))
+    .bind(fee(3))
+    .bind(fee(4))
+    .execute(&mut **executor)
+    .await?;
+
+    sqlx::query(GAS_HOURLY_UPSERT)
+        .bind(hour)
+        .bind(hour + HOUR_SECONDS)
+        .bind(HOUR_SECONDS)
+        .execute(&mut **executor)
+        .await?;
+
+    if !gas.contracts.is_empty() {
+        let contracts = &gas.contracts;
+        sqlx::query(
+            r#"
+            INSERT INTO gas_contract_hourly (bucket, contract_address, gas_used, fees_paid, tx_count)
+            SELECT $1, a, g::NUMERIC, f::NUMERIC, n
+            FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[], $5::BIGINT[]) AS u(a, g, f, n)
+            ON CONFLICT (bucket, contract_address) DO UPDATE SET
+                gas_used = gas_contract_hourly.gas_used + EXCLUDED.gas_used,
+                fees_paid = gas_contract_hourly.fees_paid + EXCLUDED.fees_paid,
+                tx_count = gas_contract_hourly.tx_count + EXCLUDED.tx_count
+            "#,
+        )
+        .bind(hour)
+        .bind(
+            contracts
+                .iter()
+                .map(|c| format!("{:#x}", c.address))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            contracts
+                .iter()
+                .map(|c| c.gas_used.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            contracts
+                .iter()
+                .map(|c| c.fees_paid.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(contracts.iter().map(|c| c.tx_count as i64).collect::<Vec<_>>())
+        .execute(&mut **executor)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Adds one block to the counters and, with rollups on, to its hour and day. A block already
+/// in `stats_blocks` is not rolled up twice.
+pub async fn insert_block_stats(
+    executor: &mut Transaction<'_, Postgres>,
+    stats: &BlockStats,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE stats_counters SET blocks = blocks + 1, transactions = transactions + $1, \
+         logs = logs + $2",
+    )
+    .bind(stats.tx_count as i64)
+    .bind(stats.log_count as i64)
+    .execute(&mut **executor)
+    .await?;
+
+    let Some(rollup) = &stats.rollup else {
+        return Ok(());
+    };
+    let block_number = stats.block_number as i64;
+    let block_timestamp = stats.block_timestamp as i64;
+    let inserted = sqlx::query(
+        "INSERT INTO stats_blocks (block_number, block_timestamp) VALUES ($1, $2) \
+         ON CONFLICT (block_number) DO NOTHING",
+    )
+    .bind(block_number)
+    .bind(block_timestamp)
+    .execute(&mut **executor)
+    .await?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(());
+    }
+
+    for (resolution, seconds) in RESOLUTIONS {
+        let bucket = block_timestamp / seconds * seconds;
+        // Only senders new to the bucket add to its unique count. A re-indexed block can come
+        // before a sender's first block in the bucket, which then moves down to it.
+        sqlx::query(
+            r#"
+            WITH earlier_senders AS (
+                UPDATE stats_senders SET first_block = $3
+                WHERE resolution = $1 AND bucket = $2 AND address = ANY($4::TEXT[])
+                  AND first_block > $3
+            ),
+            new_senders AS (
+                INSERT INTO stats_senders (resolution, bucket, address, first_block)
+                SELECT $1, $2, address, $3 FROM UNNEST($4::TEXT[]) AS address
+                ON CONFLICT (resolution, bucket, address) DO NOTHING
+                RETURNING 1
+            )
+            INSERT INTO stats_rollups (
+                resolution, bucket, blocks, tx_count, unique_senders, eth_volume, log_count
+            )
+            SELECT $1, $2, 1, $5, COUNT(*), $6::NUMERIC, $7 FROM new_senders
+            ON CONFLICT (resolution, bucket) DO UPDATE SET
+                blocks = stats_rollups.blocks + 1,
+                tx_count = stats_rollups.tx_count + EXCLUDED.tx_count,
+                unique_senders = stats_rollups.unique_senders + EXCLUDED.unique_senders,
+                eth_volume = stats_rollups.eth_volume + EXCLUDED.eth_volume,
+                log_count = stats_rollups.log_count + EXCLUDED.log_count
+            "#,
+        )
+        .bind(resolution)
+        .bind(bucket)
+        .bind(block_number)
+        .bind(&rollup.senders)
+        .bind(stats.tx_count as i64)
+        .bind(rollup.eth_volume.to_string())
+        .bind(stats.log_count as i64)
+        .execute(&mut **executor)
+        .await?;
+
+        if !rollup.logs.is_empty() {
+            sqlx::query(
+                r#"
+                INSERT INTO stats_log_rollups (resolution, bucket, contract_address, topic0, log_count)
+                SELECT $1, $2, a, t, n
+                FROM UNNEST($3::TEXT[], $4::TEXT[], $5::BIGINT[]) AS u(a, t, n)
+                ON CONFLICT (resolution, bucket, contract_address, topic0) DO UPDATE SET
+                    log_count = stats_log_rollups.log_count + EXCLUDED.log_count
+                "#,
+            )
+            .bind(resolution)
+            .bind(bucket)
+            .bind(
+                rollup
+                    .logs
+                    .iter()
+                    .map(|l| l.contract_address.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .bind(
+                rollup
+                    .logs
+                    .iter()
+                    .map(|l| l.topic0.as_deref())
+                    .collect::<Vec<_>>(),
+            )
+            .bind(rollup.logs.iter().map(|l| l.count).collect::<Vec<_>>())
+            .execute(&mut **executor)
+            .await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,1464 @@
+// src/dex.rs
+//
+// Built-in decoding of Uniswap V2 and V3 pool events. Logs are recognised by topic0 alone, so
+// forks that kept the Uniswap event signatures (SushiSwap, PancakeSwap and friends) are
+// decoded as well; `factory_address` tells them apart.
+//
+// The work follows the pipeline stages. The receipt stage resolves every pool a block touches:
+// from creation events in the block itself, an in-memory cache, the `dex_pools` table or, as a
+// last resort, `token0()`/`token1()` calls against the pool, kept only when the pool's
+// `factory()` confirms it with `getPair`/`getPool`. The transform stage decodes Swap,
+// Mint, Burn and Sync logs into rows, and the writer inserts them inside the block's DB
+// transaction. Every row carries its block number, so a reorg rollback removes it together
+// with the logs it was decoded from.
+
+use crate::api::{normalize_hex_values, ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{
+    DexLiquidityEvent, DexLiquidityResponse, DexPool, DexSwap, DexSwapsResponse,
+    GenericErrorResponse, GetDexEventsQuery, GetDexPoolsQuery,
+};
+use crate::ens::resolve_address;
+use crate::models::MyBlock;
+use crate::pipeline::retry_rpc;
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::abi::{Event, HumanReadableParser, RawLog, Token};
+use ethers::providers::{Http, Middleware, Provider, RpcError};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes, Log, TransactionRequest, H256, I256, U256};
+use futures::stream::StreamExt;
+use sqlx::{postgres::PgRow, PgPool, Postgres, QueryBuilder, Row as SqlxRow};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::debug;
+
+/// Pools (and non-pool addresses) remembered between blocks before the cache starts over.
+const MAX_POOL_CACHE_ENTRIES: usize = 100_000;
+/// Unknown pools looked up over RPC at once for a single block.
+const MAX_POOL_LOOKUPS_CONCURRENT: usize = 8;
+
+const POOL_COLUMNS: &str = "p.pool_address, p.protocol, p.factory_address, p.token0, p.token1, \
+     p.fee, p.tick_spacing, p.created_block";
+
+const SWAP_COLUMNS: &str = "log_id, block_number, block_timestamp, tx_hash, log_index, \
+     pool_address, protocol, token0, token1, trader, sender, recipient, \
+     amount0::TEXT AS amount0, amount1::TEXT AS amount1, \
+     CASE WHEN amount0 > 0 THEN token0 ELSE token1 END AS token_in, \
+     GREATEST(amount0, amount1)::TEXT AS amount_in, \
+     CASE WHEN amount0 < 0 THEN token0 ELSE token1 END AS token_out, \
+     (-LEAST(amount0, amount1))::TEXT AS amount_out, \
+     sqrt_price_x96::TEXT AS sqrt_price_x96, liquidity::TEXT AS liquidity, tick";
+
+const LIQUIDITY_COLUMNS: &str = "log_id, block_number, block_timestamp, tx_hash, log_index, \
+     pool_address, kind, owner, sender, recipient, amount0::TEXT AS amount0, \
+     amount1::TEXT AS amount1, liquidity::TEXT AS liquidity, tick_lower, tick_upper";
+
+/// The ABI family of a pool. Forks are recorded under the protocol whose events they emit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DexProtocol {
+    UniswapV2,
+    UniswapV3,
+}
+
+impl DexProtocol {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DexProtocol::UniswapV2 => "uniswap_v2",
+            DexProtocol::UniswapV3 => "uniswap_v3",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "uniswap_v2" => Some(DexProtocol::UniswapV2),
+            "uniswap_v3" => Some(DexProtocol::UniswapV3),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DexEventKind {
+    PairCreated,
+    PoolCreated,
+    SwapV2,
+    MintV2,
+    BurnV2,
+    Sync,
+    SwapV3,
+    MintV3,
+    BurnV3,
+}
+
+impl DexEventKind {
+    /// Protocol of the pool that emits this event; `None` for factory events.
+    fn pool_protocol(self) -> Option<DexProtocol> {
+        match self {
+            DexEventKind::PairCreated | DexEventKind::PoolCreated => None,
+            DexEventKind::SwapV2
+            | DexEventKind::MintV2
+            | DexEventKind::BurnV2
+            | DexEventKind::Sync => Some(DexProtocol::UniswapV2),
+            DexEventKind::SwapV3 | DexEventKind::MintV3 | DexEventKind::BurnV3 => {
+                Some(DexProtocol::UniswapV3)
+            }
+        }
+    }
+}
+
+const DEX_EVENTS: [(DexEventKind, &str); 9] = [
+    (
+        DexEventKind::PairCreated,
+        "event PairCreated(address indexed token0, address indexed token1, address pair, uint256 allPairsLength)",
+    ),
+    (
+        DexEventKind::PoolCreated,
+        "event PoolCreated(address indexed token0, address indexed token1, uint24 indexed fee, int24 tickSpacing, address pool)",
+    ),
+    (
+        DexEventKind::SwapV2,
+        "event Swap(address indexed sender, uint256 amount0In, uint256 amount1In, uint256 amount0Out, uint256 amount1Out, address indexed to)",
+    ),
+    (
+        DexEventKind::MintV2,
+        "event Mint(address indexed sender, uint256 amount0, uint256 amount1)",
+    ),
+    (
+        DexEventKind::BurnV2,
+        "event Burn(address indexed sender, uint256 amount0, uint256 amount1, address indexed to)",
+    ),
+    (
+        DexEventKind::Sync,
+        "event Sync(uint112 reserve0, uint112 reserve1)",
+    ),
+    (
+        DexEventKind::SwapV3,
+        "event Swap(address indexed sender, address indexed recipient, int256 amount0, int256 amount1, uint160 sqrtPriceX96, uint128 liquidity, int24 tick)",
+    ),
+    (
+        DexEventKind::MintV3,
+        "event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)",
+    ),
+    (
+        DexEventKind::BurnV3,
+        "event Burn(address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)",
+    ),
+];
+
+/// Pool metadata. Token addresses are fixed at deployment, so a resolved pool never changes.
+#[derive(Clone, Debug)]
+pub struct PoolInfo {
+    pub address: Address,
+    pub protocol: DexProtocol,
+    pub factory: Option<Address>,
+    pub token0: Address,
+    pub token1: Address,
+    /// V3 fee tier in hundredths of a basis point.
+    pub fee: Option<u32>,
+    pub tick_spacing: Option<i32>,
+    /// Only known when the creation event itself was indexed.
+    pub created_block: Option<u64>,
+}
+
+/// A decoded swap. Amounts are signed from the pool's side: positive flowed into the pool,
+/// negative flowed out (V3's convention; V2 in/out amounts are netted into it).
+pub struct DecodedSwap {
+    /// Index into the block's log list, mapped to the stored log id by the writer.
+    pub log_position: usize,
+    pub pool: Address,
+    pub protocol: DexProtocol,
+    pub token0: Address,
+    pub token1: Address,
+    pub tx_hash: H256,
+    pub log_index: Option<u64>,
+    /// The transaction sender; `sender` is usually a router.
+    pub trader: Address,
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount0: I256,
+    pub amount1: I256,
+    pub sqrt_price_x96: Option<U256>,
+    pub liquidity: Option<U256>,
+    pub tick: Option<i32>,
+}
+
+/// A decoded Mint, Burn or Sync. For Sync the amounts are the pool's new reserves.
+pub struct DecodedLiquidity {
+    pub log_position: usize,
+    pub pool: Address,
+    pub kind: &'static str,
+    pub tx_hash: H256,
+    pub log_index: Option<u64>,
+    pub owner: Option<Address>,
+    pub sender: Option<Address>,
+    pub recipient: Option<Address>,
+    pub amount0: U256,
+    pub amount1: U256,
+    pub liquidity: Option<U256>,
+    pub tick_lower: Option<i32>,
+    pub tick_upper: Option<i32>,
+}
+
+/// Everything the writer stores for one block: the pools the block touched (upserted so the
+/// registry survives rollbacks of the block that first recorded them) and the decoded events.
+pub struct DexBlock {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub pools: Vec<PoolInfo>,
+    pub swaps: Vec<DecodedSwap>,
+    pub liquidity: Vec<DecodedLiquidity>,
+}
+
+type Params = HashMap<String, Token>;
+
+fn parse_params(event: &Event, log: &Log) -> Option<Params> {
+    let raw = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    match event.parse_log(raw) {
+        Ok(parsed) => Some(
+            parsed
+                .params
+                .into_iter()
+                .map(|param| (param.name, param.value))
+                .collect(),
+        ),
+        Err(e) => {
+            // Same topic0, different layout (e.g. an ERC-20 with its own Mint event).
+            debug!(
+                "DEX: {} log from {:?} does not decode: {}",
+                event.name, log.address, e
+            );
+            None
+        }
+    }
+}
+
+fn address_param(params: &Params, name: &str) -> Option<Address> {
+    match params.get(name)? {
+        Token::Address(address) => Some(*address),
+        _ => None,
+    }
+}
+
+fn uint_param(params: &Params, name: &str) -> Option<U256> {
+    match params.get(name)? {
+        Token::Uint(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn int_param(params: &Params, name: &str) -> Option<I256> {
+    match params.get(name)? {
+        Token::Int(value) => Some(I256::from_raw(*value)),
+        _ => None,
+    }
+}
+
+/// Narrows an `int24` tick.
+fn tick_param(params: &Params, name: &str) -> Option<i32> {
+    int_param(params, name).map(|tick| tick.low_i32())
+}
+
+fn decode_creation(kind: DexEventKind, params: &Params, log: &Log) -> Option<PoolInfo> {
+    let token0 = address_param(params, "token0")?;
+    let token1 = address_param(params, "token1")?;
+    let created_block = log.block_number.map(|n| n.as_u64());
+    match kind {
+        DexEventKind::PairCreated => Some(PoolInfo {
+            address: address_param(params, "pair")?,
+            protocol: DexProtocol::UniswapV2,
+            factory: Some(log.address),
+            token0,
+            token1,
+            fee: None,
+            tick_spacing: None,
+            created_block,
+        }),
+        DexEventKind::PoolCreated => Some(PoolInfo {
+            address: address_param(params, "pool")?,
+            protocol: DexProtocol::UniswapV3,
+            factory: Some(log.address),
+            token0,
+            token1,
+            fee: uint_param(params, "fee").map(|fee| fee.low_u32()),
+            tick_spacing: tick_param(params, "tickSpacing"),
+            created_block,
+        }),
+        _ => None,
+    }
+}
+
+/// Calls a no-argument getter and returns its raw output. `Ok(None)` when the call reverts,
+/// i.e. the contract has no such getter. Transport errors are retried and then fail the
+/// block, so a flaky node can't drop a pool's swaps.
+pub(crate) async fn call_output(
+    provider: &Provider<Http>,
+    to: Address,
+    signature: &str,
+) -> eyre::Result<Option<Bytes>> {
+    call_with_args(provider, to, signature, &[]).await
+}
+
+/// `call_output` for a function taking `args`, which must match `signature`.
+async fn call_with_args(
+    provider: &Provider<Http>,
+    to: Address,
+    signature: &str,
+    args: &[Token],
+) -> eyre::Result<Option<Bytes>> {
+    let mut data = ethers::utils::id(signature).to_vec();
+    data.extend(ethers::abi::encode(args));
+    let call: TypedTransaction = TransactionRequest::new().to(to).data(data).into();
+    let call = &call;
+    retry_rpc(&format!("{} on {:?}", signature, to), || async move {
+        match provider.call(call, None).await {
+            Ok(output) => Ok(Some(output)),
+            Err(e) if e.as_error_response().is_some() => Ok(None),
+            Err(e) => Err(e),
+        }
+    })
+    .await
+}
+
+/// `call_output` narrowed to the first word; `Ok(None)` also when the call returns nothing.
+pub(crate) async fn call_word(
+    provider: &Provider<Http>,
+    to: Address,
+    signature: &str,
+) -> eyre::Result<Option<[u8; 32]>> {
+    Ok(call_output(provider, to, signature)
+        .await?
+        .and_then(|output| {
+            output.get(..32).map(|word| {
+                let mut out = [0u8; 32];
+                out.copy_from_slice(word);
+                out
+            })
+        }))
+}
+
+/// An ABI-encoded address: 12 zero bytes, then 20 non-zero ones.
+pub(crate) fn word_to_address(word: [u8; 32]) -> Option<Address> {
+    let address = Address::from_slice(&word[12..]);
+    (word[..12].iter().all(|b| *b == 0) && !address.is_zero()).then_some(address)
+}
+
+/// Whether the pool's factory returns the pool's address from `getPair(token0, token1)` (V2)
+/// or `getPool(token0, token1, fee)` (V3). Any contract can claim a factory and a token pair;
+/// only the factory's own registry tells its pools apart from look-alikes.
+pub(crate) async fn factory_confirms(
+    provider: &Provider<Http>,
+    pool: &PoolInfo,
+) -> eyre::Result<bool> {
+    let Some(factory) = pool.factory else {
+        return Ok(false);
+    };
+    let tokens = [Token::Address(pool.token0), Token::Address(pool.token1)];
+    let output = match pool.protocol {
+        DexProtocol::UniswapV2 => {
+            call_with_args(provider, factory, "getPair(address,address)", &tokens).await?
+        }
+        DexProtocol::UniswapV3 => {
+            let Some(fee) = pool.fee else {
+                return Ok(false);
+            };
+            let [token0, token1] = tokens;
+            let args = [token0, token1, Token::Uint(U256::from(fee))];
+            call_with_args(provider, factory, "getPool(address,address,uint24)", &args).await?
+        }
+    };
+    Ok(output
+        .and_then(|output| <[u8; 32]>::try_from(output.get(..32)?).ok())
+        .and_then(word_to_address)
+        == Some(pool.address))
+}
+
+/// Asks a contract that emitted a pool event for its tokens and has its factory confirm it.
+/// Contracts that don't answer like a Uniswap pool, or whose factory doesn't know them, are
+/// not pools.
+async fn discover_pool(
+    provider: &Provider<Http>,
+    address: Address,
+    protocol: DexProtocol,
+) -> eyre::Result<Option<PoolInfo>> {
+    let Some(token0) = call_word(provider, address, "token0()")
+        .await?
+        .and_then(word_to_address)
+    else {
+        return Ok(None);
+    };
+    let Some(token1) = call_word(provider, address, "token1()")
+        .await?
+        .and_then(word_to_address)
+    else {
+        return Ok(None);
+    };
+    let Some(factory) = call_word(provider, address, "factory()")
+        .await?
+        .and_then(word_to_address)
+    else {
+        return Ok(None);
+    };
+    let (fee, tick_spacing) = match protocol {
+        DexProtocol::UniswapV2 => (None, None),
+        DexProtocol::UniswapV3 => (
+            call_word(provider, address, "fee()")
+                .await?
+                .map(|word| U256::from_big_endian(&word).low_u32()),
+            call_word(provider, address, "tickSpacing()")
+                .await?
+                .map(|word| I256::from_raw(U256::from_big_endian(&word)).low_i32()),
+        ),
+    };
+    let pool = PoolInfo {
+        address,
+        protocol,
+        factory: Some(factory),
+        token0,
+        token1,
+        fee,
+        tick_spacing,
+        created_block: None,
+    };
+    Ok(factory_confirms(provider, &pool).await?.then_some(pool))
+}
+
+fn row_to_pool_info(row: &PgRow) -> Option<PoolInfo> {
+    let address =
+        |column: &str| -> Option<Address> { row.try_get::<String, _>(column).ok()?.parse().ok() };
+    Some(PoolInfo {
+        address: address("pool_address")?,
+        protocol: DexProtocol::parse(&row.try_get::<String, _>("protocol").ok()?)?,
+        factory: address("factory_address"),
+        token0: address("token0")?,
+        token1: address("token1")?,
+        fee: row
+            .try_get::<Option<i32>, _>("fee")
+            .ok()?
+            .map(|fee| fee as u32),
+        tick_spacing: row.try_get("tick_spacing").ok()?,
+        created_block: row
+            .try_get::<Option<i64>, _>("created_block")
+            .ok()?
+            .map(|block| block as u64),
+    })
+}
+
+/// Shared by the receipt and transform stages for the lifetime of the ingester.
+pub struct DexDecoder {
+    pool: PgPool,
+    events: HashMap<H256, (DexEventKind, Event)>,
+    /// Resolved pools by address; `None` marks a contract that turned out not to be a pool.
+    cache: Mutex<HashMap<Address, Option<PoolInfo>>>,
+}
+
+impl DexDecoder {
+    pub fn new(pool: PgPool) -> Self {
+        let events = DEX_EVENTS
+            .iter()
+            .map(|(kind, declaration)| {
+                let event = HumanReadableParser::parse_event(declaration)
+                    .expect("built-in DEX event declarations parse");
+                (event.signature(), (*kind, event))
+            })
+            .collect();
+        DexDecoder {
+            pool,
+            events,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn event_for(&self, log: &Log) -> Option<&(DexEventKind, Event)> {
+        log.topics
+            .first()
+            .and_then(|topic0| self.events.get(topic0))
+    }
+
+    /// Blocks resolve concurrently, so a creation event can land after a lookup for the same
+    /// address already finished; a known pool is never replaced by a negative result.
+    fn remember(&self, address: Address, pool: Option<PoolInfo>) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_POOL_CACHE_ENTRIES {
+            cache.clear();
+        }
+        match (cache.get(&address), &pool) {
+            (Some(Some(_)), None) => {}
+            _ => {
+                cache.insert(address, pool);
+            }
+        }
+    }
+
+    /// Pools touched by `logs`, keyed by address. Fails only when the database or the node
+    /// can't be reached; the receipt stage then ends the range and the block is retried.
+    pub async fn resolve_pools(
+        &self,
+        provider: &Provider<Http>,
+        logs: &[Log],
+    ) -> eyre::Result<HashMap<Address, PoolInfo>> {
+        let mut pools: HashMap<Address, PoolInfo> = HashMap::new();
+        let mut wanted: HashMap<Address, DexProtocol> = HashMap::new();
+        for log in logs {
+            let Some((kind, event)) = self.event_for(log) else {
+                continue;
+            };
+            match kind.pool_protocol() {
+                Some(protocol) => {
+                    wanted.entry(log.address).or_insert(protocol);
+                }
+                None => {
+                    if let Some(pool) =
+                        parse_params(event, log).and_then(|p| decode_creation(*kind, &p, log))
+                    {
+                        self.remember(pool.address, Some(pool.clone()));
+                        pools.insert(pool.address, pool);
+                    }
+                }
+            }
+        }
+        wanted.retain(|address, _| !pools.contains_key(address));
+
+        let mut missing: Vec<(Address, DexProtocol)> = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap();
+            for (address, protocol) in wanted {
+                match cache.get(&address) {
+                    Some(Some(pool)) => {
+                        pools.insert(address, pool.clone());
+                    }
+                    Some(None) => {}
+                    None => missing.push((address, protocol)),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(pools);
+        }
+
+        let addresses: Vec<String> = missing
+            .iter()
+            .map(|(address, _)| format!("{:#x}", address))
+            .collect();
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM dex_pools p WHERE p.pool_address = ANY($1)",
+            POOL_COLUMNS
+        ))
+        .bind(addresses)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| eyre::eyre!("DB: load DEX pools: {}", e))?;
+        for pool in rows.iter().filter_map(row_to_pool_info) {
+            self.remember(pool.address, Some(pool.clone()));
+            pools.insert(pool.address, pool);
+        }
+        missing.retain(|(address, _)| !pools.contains_key(address));
+
+        let discovered = futures::stream::iter(missing)
+            .map(|(address, protocol)| async move {
+                let pool = discover_pool(provider, address, protocol).await?;
+                Ok::<_, eyre::Report>((address, pool))
+            })
+            .buffer_unordered(MAX_POOL_LOOKUPS_CONCURRENT)
+            .collect::<Vec<_>>()
+            .await;
+        for result in discovered {
+            let (address, pool) = result?;
+            if pool.is_none() {
+                debug!("DEX: {:?} emits pool events but is not a pool", address);
+            }
+            self.remember(address, pool.clone());
+            if let Some(pool) = pool {
+                pools.insert(address, pool);
+            }
+        }
+        Ok(pools)
+    }
+
+    /// Decodes the block's pool events. `logs` must be in the order they are written, since
+    /// rows refer to them by position. `senders` maps transaction hashes to their senders.
+    pub fn decode_block(
+        &self,
+        block: &MyBlock,
+        logs: &[Log],
+        senders: &HashMap<H256, Address>,
+        pools: &HashMap<Address, PoolInfo>,
+    ) -> Option<DexBlock> {
+        if pools.is_empty() {
+            return None;
+        }
+        let mut pool_list: Vec<PoolInfo> = pools.values().cloned().collect();
+        pool_list.sort_by_key(|pool| pool.address);
+        let mut dex = DexBlock {
+            block_number: block.block_number.as_u64(),
+            block_timestamp: block.timestamp.as_u64(),
+            pools: pool_list,
+            swaps: Vec::new(),
+            liquidity: Vec::new(),
+        };
+
+        for (log_position, log) in logs.iter().enumerate() {
+            let Some((kind, event)) = self.event_for(log) else {
+                continue;
+            };
+            let Some(pool) = pools.get(&log.address) else {
+                continue;
+            };
+            if kind.pool_protocol() != Some(pool.protocol) {
+                continue;
+            }
+            let Some(params) = parse_params(event, log) else {
+                continue;
+            };
+            let tx_hash = log.transaction_hash.unwrap_or_default();
+            let log_index = log.log_index.map(|i| i.as_u64());
+            match kind {
+                DexEventKind::SwapV2 | DexEventKind::SwapV3 => {
+                    if let Some(swap) =
+                        decode_swap(*kind, &params, pool, senders, log_position, log)
+                    {
+                        dex.swaps.push(swap);
+                    }
+                }
+                _ => {
+                    if let Some(liquidity) = decode_liquidity(*kind, &params) {
+                        dex.liquidity.push(DecodedLiquidity {
+                            log_position,
+                            pool: pool.address,
+                            tx_hash,
+                            log_index,
+                            ..liquidity
+                        });
+                    }
+                }
+            }
+        }
+        Some(dex)
+    }
+}
+
+fn decode_swap(
+    kind: DexEventKind,
+    params: &Params,
+    pool: &PoolInfo,
+    senders: &HashMap<H256, Address>,
+    log_position: usize,
+    log: &Log,
+) -> Option<DecodedSwap> {
+    let tx_hash = log.transaction_hash.unwrap_or_default();
+    let sender = address_param(params, "sender")?;
+    let net = |into_pool: &str, out_of_pool: &str| -> Option<I256> {
+        let into_pool = I256::try_from(uint_param(params, into_pool)?).ok()?;
+        let out_of_pool = I256::try_from(uint_param(params, out_of_pool)?).ok()?;
+        into_pool.checked_sub(out_of_pool)
+    };
+    let (recipient, amount0, amount1, sqrt_price_x96, liquidity, tick) = match kind {
+        DexEventKind::SwapV2 => (
+            address_param(params, "to")?,
+            net("amount0In", "amount0Out")?,
+            net("amount1In", "amount1Out")?,
+            None,
+            None,
+            None,
+        ),
+        DexEventKind::SwapV3 => (
+            address_param(params, "recipient")?,
+            int_param(params, "amount0")?,
+            int_param(params, "amount1")?,
+            uint_param(params, "sqrtPriceX96"),
+            uint_param(params, "liquidity"),
+            tick_param(params, "tick"),
+        ),
+        _ => return None,
+    };
+    Some(DecodedSwap {
+        log_position,
+        pool: pool.address,
+        protocol: pool.protocol,
+        token0: pool.token0,
+        token1: pool.token1,
+        tx_hash,
+        log_index: log.log_index.map(|i| i.as_u64()),
+        trader: senders.get(&tx_hash).copied().unwrap_or(sender),
+        sender,
+        recipient,
+        amount0,
+        amount1,
+        sqrt_price_x96,
+        liquidity,
+        tick,
+    })
+}
+
+/// Decodes the event-specific fields; the caller fills in where the log came from.
+fn decode_liquidity(kind: DexEventKind, params: &Params) -> Option<DecodedLiquidity> {
+    let empty = DecodedLiquidity {
+        log_position: 0,
+        pool: Address::zero(),
+        kind: "",
+        tx_hash: H256::zero(),
+        log_index: None,
+        owner: None,
+        sender: None,
+        recipient: None,
+        amount0: U256::zero(),
+        amount1: U256::zero(),
+        liquidity: None,
+        tick_lower: None,
+        tick_upper: None,
+    };
+    match kind {
+        DexEventKind::MintV2 | DexEventKind::BurnV2 => Some(DecodedLiquidity {
+            kind: if kind == DexEventKind::MintV2 {
+                "mint"
+            } else {
+                "burn"
+            },
+            sender: Some(address_param(params, "sender")?),
+            recipient: address_param(params, "to"),
+            amount0: uint_param(params, "amount0")?,
+            amount1: uint_param(params, "amount1")?,
+            ..empty
+        }),
+        DexEventKind::Sync => Some(DecodedLiquidity {
+            kind: "sync",
+            amount0: uint_param(params, "reserve0")?,
+            amount1: uint_param(params, "reserve1")?,
+            ..empty
+        }),
+        DexEventKind::MintV3 | DexEventKind::BurnV3 => Some(DecodedLiquidity {
+            kind: if kind == DexEventKind::MintV3 {
+                "mint"
+            } else {
+                "burn"
+            },
+            owner: Some(address_param(params, "owner")?),
+            sender: address_param(params, "sender"),
+            amount0: uint_param(params, "amount0")?,
+            amount1: uint_param(params, "amount1")?,
+            liquidity: uint_param(params, "amount"),
+            tick_lower: tick_param(params, "tickLower"),
+            tick_upper: tick_param(params, "tickUpper"),
+            ..empty
+        }),
+        _ => None,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// REST API
+// ---------------------------------------------------------------------------
+
+pub(crate) fn normalize_address(value: &str, field: &str) -> Result<String, ApiError> {
+    let mut values = normalize_hex_values(&[value.to_string()], 20, field)?;
+    Ok(values.remove(0))
+}
+
+fn row_to_pool(row: &PgRow) -> Result<DexPool, ApiError> {
+    Ok(DexPool {
+        pool_address: row.try_get("pool_address")?,
+        protocol: row.try_get("protocol")?,
+        factory_address: row.try_get("factory_address")?,
+        token0: row.try_get("token0")?,
+        token1: row.try_get("token1")?,
+        fee: row.try_get("fee")?,
+        tick_spacing: row.try_get("tick_spacing")?,
+        created_block: row.try_get("created_block")?,
+        reserve0: row.try_get("reserve0")?,
+        reserve1: row.try_get("reserve1")?,
+        reserves_block: row.try_get("reserves_block")?,
+    })
+}
+
+fn row_to_swap(row: &PgRow) -> Result<DexSwap, ApiError> {
+    Ok(DexSwap {
+        log_id: row.try_get("log_id")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        tx_hash: row.try_get("tx_hash")?,
+        log_index: row.try_get("log_index")?,
+        pool_address: row.try_get("pool_address")?,
+        protocol: row.try_get("protocol")?,
+        token0: row.try_get("token0")?,
+        token1: row.try_get("token1")?,
+        trader: row.try_get("trader")?,
+        sender: row.try_get("sender")?,
+        recipient: row.try_get("recipient")?,
+        amount0: row.try_get("amount0")?,
+        amount1: row.try_get("amount1")?,
+        token_in: row.try_get("token_in")?,
+        amount_in: row.try_get("amount_in")?,
+        token_out: row.try_get("token_out")?,
+        amount_out: row.try_get("amount_out")?,
+        sqrt_price_x96: row.try_get("sqrt_price_x96")?,
+        liquidity: row.try_get("liquidity")?,
+        tick: row.try_get("tick")?,
+    })
+}
+
+fn row_to_liquidity_event(row: &PgRow) -> Result<DexLiquidityEvent, ApiError> {
+    Ok(DexLiquidityEvent {
+        log_id: row.try_get("log_id")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        tx_hash: row.try_get("tx_hash")?,
+        log_index: row.try_get("log_index")?,
+        pool_address: row.try_get("pool_address")?,
+        kind: row.try_get("kind")?,
+        owner: row.try_get("owner")?,
+        sender: row.try_get("sender")?,
+        recipient: row.try_get("recipient")?,
+        amount0: row.try_get("amount0")?,
+        amount1: row.try_get("amount1")?,
+        liquidity: row.try_get("liquidity")?,
+        tick_lower: row.try_get("tick_lower")?,
+        tick_upper: row.try_get("tick_upper")?,
+    })
+}
+
+/// Pool columns plus the reserves from the pool's latest Sync (V2 pools only).
+fn pools_query() -> QueryBuilder<'static, Postgres> {
+    QueryBuilder::new(format!(
+        "SELECT {}, r.amount0::TEXT AS reserve0, r.amount1::TEXT AS reserve1, \
+         r.block_number AS reserves_block FROM dex_pools p LEFT JOIN LATERAL ( \
+             SELECT amount0, amount1, block_number FROM dex_liquidity_events e \
+             WHERE e.pool_address = p.pool_address AND e.kind = 'sync' \
+             ORDER BY e.block_number DESC, e.log_id DESC LIMIT 1 \
+         ) r ON TRUE WHERE TRUE",
+        POOL_COLUMNS
+    ))
+}
+
+/// Appends the block/time range, keyset cursor, ordering and limit shared by the event lists.
+fn push_event_range(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    params: &GetDexEventsQuery,
+) -> Result<i64, ApiError> {
+    if params.cursor_block.is_some() != params.cursor_log_id.is_some() {
+        return Err(ApiError::BadRequest(
+            "cursorBlock and cursorLogId must be passed together.".to_string(),
+        ));
+    }
+    if let Some(from_block) = params.from_block {
+        query_builder.push(" AND block_number >= ");
+        query_builder.push_bind(from_block);
+    }
+    if let Some(to_block) = params.to_block {
+        query_builder.push(" AND block_number <= ");
+        query_builder.push_bind(to_block);
+    }
+    if let Some(from_time) = params.from_time {
+        query_builder.push(" AND block_timestamp >= ");
+        query_builder.push_bind(from_time);
+    }
+    if let Some(to_time) = params.to_time {
+        query_builder.push(" AND block_timestamp <= ");
+        query_builder.push_bind(to_time);
+    }
+    if let (Some(block), Some(log_id)) = (params.cursor_block, params.cursor_log_id) {
+        query_builder.push(" AND (block_number, log_id) > (");
+        query_builder.push_bind(block);
+        query_builder.push(", ");
+        query_builder.push_bind(log_id);
+        query_builder.push(")");
+    }
+    let limit = params.limit.clamp(1, MAX_PAGE_SIZE) as i64;
+    query_builder.push(" ORDER BY block_number, log_id LIMIT ");
+    query_builder.push_bind(limit);
+    Ok(limit)
+}
+
+/// Which column set a swap listing is scoped to.
+enum SwapScope {
+    Pool,
+    Trader,
+    Token,
+}
+
+async fn list_swaps(
+    pool: &PgPool,
+    scope: SwapScope,
+    address: String,
+    params: &GetDexEventsQuery,
+) -> Result<Json<DexSwapsResponse>, ApiError> {
+    let mut query_builder =
+        QueryBuilder::new(format!("SELECT {} FROM dex_swaps WHERE ", SWAP_COLUMNS));
+    match scope {
+        SwapScope::Pool => {
+            query_builder.push("pool_address = ");
+            query_builder.push_bind(address);
+        }
+        SwapScope::Trader => {
+            query_builder.push("trader = ");
+            query_builder.push_bind(address);
+        }
+        SwapScope::Token => {
+            query_builder.push("(token0 = ");
+            query_builder.push_bind(address.clone());
+            query_builder.push(" OR token1 = ");
+            query_builder.push_bind(address);
+            query_builder.push(")");
+        }
+    }
+    let limit = push_event_range(&mut query_builder, params)?;
+    let rows = query_builder.build().fetch_all(pool).await?;
+    let swaps: Vec<DexSwap> = rows.iter().map(row_to_swap).collect::<Result<_, _>>()?;
+    let last = swaps.last().filter(|_| swaps.len() as i64 == limit);
+    Ok(Json(DexSwapsResponse {
+        next_cursor_block: last.map(|swap| swap.block_number),
+        next_cursor_log_id: last.map(|swap| swap.log_id),
+        swaps,
+    }))
+}
+
+/// List DEX Pools
+///
+/// Pools in the registry, ordered by address. Pass the last address of a page as `after` to
+/// get the next one.
+#[utoipa::path(
+    get,
+    path = "/dex/pools",
+    params(GetDexPoolsQuery),
+    responses(
+        (status = 200, description = "Pools", body = Vec<DexPool>),
+        (status = 400, description = "Invalid filter", body = GenericErrorResponse)
+    )
+)]
+pub async fn list_pools_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetDexPoolsQuery>,
+) -> Result<Json<Vec<DexPool>>, ApiError> {
+    let mut query_builder = pools_query();
+    if let Some(token) = &params.token {
+        let token = resolve_address(&pool, token, "token").await?;
+        query_builder.push(" AND (p.token0 = ");
+        query_builder.push_bind(token.clone());
+        query_builder.push(" OR p.token1 = ");
+        query_builder.push_bind(token);
+        query_builder.push(")");
+    }
+    if let Some(protocol) = &params.protocol {
+        if DexProtocol::parse(protocol).is_none() {
+            return Err(ApiError::BadRequest(format!(
+                "Unknown protocol '{}'; expected uniswap_v2 or uniswap_v3.",
+                protocol
+            )));
+        }
+        query_builder.push(" AND p.protocol = ");
+        query_builder.push_bind(protocol.clone());
+    }
+    if let Some(after) = &params.after {
+        query_builder.push(" AND p.pool_address > ");
+        query_builder.push_bind(after.to_lowercase());
+    }
+    query_builder.push(" ORDER BY p.pool_address LIMIT ");
+    query_builder.push_bind(params.limit.clamp(1, MAX_PAGE_SIZE) as i64);
+    let rows = query_builder.build().fetch_all(&pool).await?;
+    let pools = rows.iter().map(row_to_pool).collect::<Result<_, _>>()?;
+    Ok(Json(pools))
+}
+
+/// Get DEX Pool
+#[utoipa::path(
+    get,
+    path = "/dex/pools/{address}",
+    params(("address" = String, Path, description = "Pool address")),
+    responses(
+        (status = 200, description = "Pool found", body = DexPool),
+        (status = 400, description = "Invalid address", body = GenericErrorResponse),
+        (status = 404, description = "Pool not indexed", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_pool_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+) -> Result<Json<DexPool>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    let mut query_builder = pools_query();
+    query_builder.push(" AND p.pool_address = ");
+    query_builder.push_bind(address.clone());
+    let row = query_builder
+        .build()
+        .fetch_optional(&pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Pool {} is not indexed.", address)))?;
+    Ok(Json(row_to_pool(&row)?))
+}
+
+/// Get Pool Swaps
+///
+/// Swaps in one pool, oldest first, optionally limited to a block or time range.
+#[utoipa::path(
+    get,
+    path = "/dex/pools/{address}/swaps",
+    params(("address" = String, Path, description = "Pool address"), GetDexEventsQuery),
+    responses(
+        (status = 200, description = "Swaps", body = DexSwapsResponse),
+        (status = 400, description = "Invalid address or range", body = GenericErrorResponse)
+    )
+)]
+pub async fn pool_swaps_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetDexEventsQuery>,
+) -> Result<Json<DexSwapsResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    list_swaps(&pool, SwapScope::Pool, address, &params).await
+}
+
+/// Get Pool Liquidity Events
+///
+/// Mints, burns and (V2) reserve syncs in one pool, oldest first.
+#[utoipa::path(
+    get,
+    path = "/dex/pools/{address}/liquidity",
+    params(("address" = String, Path, description = "Pool address"), GetDexEventsQuery),
+    responses(
+        (status = 200, description = "Liquidity events", body = DexLiquidityResponse),
+        (status = 400, description = "Invalid address or range", body = GenericErrorResponse)
+    )
+)]
+pub async fn pool_liquidity_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetDexEventsQuery>,
+) -> Result<Json<DexLiquidityResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    let mut query_builder = QueryBuilder::new(format!(
+        "SELECT {} FROM dex_liquidity_events WHERE pool_address = ",
+        LIQUIDITY_COLUMNS
+    ));
+    query_builder.push_bind(address);
+    let limit = push_event_range(&mut query_builder, &params)?;
+    let rows = query_builder.build().fetch_all(&pool).await?;
+    let events: Vec<DexLiquidityEvent> = rows
+        .iter()
+        .map(row_to_liquidity_event)
+        .collect::<Result<_, _>>()?;
+    let last = events.last().filter(|_| events.len() as i64 == limit);
+    Ok(Json(DexLiquidityResponse {
+        next_cursor_block: last.map(|event| event.block_number),
+        next_cursor_log_id: last.map(|event| event.log_id),
+        events,
+    }))
+}
+
+/// Get Trader Swaps
+///
+/// Swaps in transactions sent by `address`, across all pools.
+#[utoipa::path(
+    get,
+    path = "/dex/traders/{address}/swaps",
+    params(("address" = String, Path, description = "Transaction sender, as an address or ENS name"), GetDexEventsQuery),
+    responses(
+        (status = 200, description = "Swaps", body = DexSwapsResponse),
+        (status = 400, description = "Invalid address or range", body = GenericErrorResponse)
+    )
+)]
+pub async fn trader_swaps_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetDexEventsQuery>,
+) -> Result<Json<DexSwapsResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    list_swaps(&pool, SwapScope::Trader, address, &params).await
+}
+
+/// Get Token Swaps
+///
+/// Swaps in any pool that has `address` as token0 or token1.
+#[utoipa::path(
+    get,
+    path = "/dex/tokens/{address}/swaps",
+    params(("address" = String, Path, description = "Token address"), GetDexEventsQuery),
+    responses(
+        (status = 200, description = "Swaps", body = DexSwapsResponse),
+        (status = 400, description = "Invalid address or range", body = GenericErrorResponse)
+    )
+)]
+pub async fn token_swaps_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetDexEventsQuery>,
+) -> Result<Json<DexSwapsResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    list_swaps(&pool, SwapScope::Token, address, &params).await
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/dex/pools", get(list_pools_handler))
+        .route("/dex/pools/{address}", get(get_pool_handler))
+        .route("/dex/pools/{address}/swaps", get(pool_swaps_handler))
+        .route(
+            "/dex/pools/{address}/liquidity",
+            get(pool_liquidity_handler),
+        )
+        .route("/dex/traders/{address}/swaps", get(trader_swaps_handler))
+        .route("/dex/tokens/{address}/swaps", get(token_swaps_handler))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+
+    // Fixtures follow the mainnet log layouts of the Uniswap V2 pair and V3 pool contracts:
+    // topic0, then the indexed arguments as topics, then one 32-byte word per other argument.
+    const PAIR_CREATED: &str = "0x0d3648bd0f6ba80134a33ba9275ac585d9d315f0ad8355cddefde31afa28d0e9";
+    const POOL_CREATED: &str = "0x783cca1c0412dd0d695e784568c96da2e9c22ff989357a2e8b1d9b2b4e6b7118";
+    const SWAP_V2: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+    const MINT_V2: &str = "0x4c209b5fc8ad50758f13e2e1088ba56a560dff690a1c6fef26394f4c03821c4f";
+    const BURN_V2: &str = "0xdccd412f0b1252819cb1fd330b93224ca42612892bb3f4f789976e6d81936496";
+    const SYNC: &str = "0x1c411e9a96e071241c2f21f7726b17ae89e3cab4c78be50e062b03a9fffbbad1";
+    const SWAP_V3: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+    const MINT_V3: &str = "0x7a53080ba414158be7ec69b987b5fb7d07dee101fe85488f0853ae16239d0bde";
+    const BURN_V3: &str = "0x0c396cd989a39f4459b5fa1aed6a9a8dcdbc45908acfd67e028cd568da98982c";
+
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+    const USDC_WETH_V2: &str = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
+    const USDC_WETH_V3: &str = "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640";
+    const V2_ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+    const V3_ROUTER: &str = "0xe592427a0aece92de3edee1f18e0157c05861564";
+    const V3_POSITIONS: &str = "0xc36442b4a4522e871399cd717abdd847ab11fe88";
+    const TRADER: &str = "0x3fc91a3afd70395cd496c647d5a6cc9d4b2b7fad";
+
+    const USDC_1000: &str = "000000000000000000000000000000000000000000000000000000003b9aca00";
+    const USDC_2000: &str = "0000000000000000000000000000000000000000000000000000000077359400";
+    const USDC_MINUS_1000: &str =
+        "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffc4653600";
+    const WETH_HALF: &str = "00000000000000000000000000000000000000000000000006f05b59d3b20000";
+    const WETH_1: &str = "0000000000000000000000000000000000000000000000000de0b6b3a7640000";
+    const ZERO: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    fn address(value: &str) -> Address {
+        value.parse().unwrap()
+    }
+
+    fn topic(value: &str) -> H256 {
+        value.parse().unwrap()
+    }
+
+    fn address_topic(value: &str) -> H256 {
+        H256::from(address(value))
+    }
+
+    /// An indexed `int24`, sign-extended to a full word like every ABI-encoded int.
+    fn int_topic(value: i64) -> H256 {
+        let mut word = [0u8; 32];
+        I256::from(value).into_raw().to_big_endian(&mut word);
+        H256(word)
+    }
+
+    fn address_word(value: &str) -> String {
+        format!("{:0>64}", value.trim_start_matches("0x"))
+    }
+
+    fn log(pool: &str, topics: Vec<H256>, words: &[&str], log_index: u64) -> Log {
+        Log {
+            address: address(pool),
+            topics,
+            data: hex::decode(words.concat()).unwrap().into(),
+            block_number: Some(U64::from(18_000_000)),
+            transaction_hash: Some(H256::repeat_byte(0x11)),
+            log_index: Some(U256::from(log_index)),
+            ..Default::default()
+        }
+    }
+
+    fn pools() -> HashMap<Address, PoolInfo> {
+        let v2 = PoolInfo {
+            address: address(USDC_WETH_V2),
+            protocol: DexProtocol::UniswapV2,
+            factory: Some(address("0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f")),
+            token0: address(USDC),
+            token1: address(WETH),
+            fee: None,
+            tick_spacing: None,
+            created_block: None,
+        };
+        let v3 = PoolInfo {
+            address: address(USDC_WETH_V3),
+            protocol: DexProtocol::UniswapV3,
+            factory: Some(address("0x1f98431c8ad98523631ae4a59f267346ea31f984")),
+            fee: Some(500),
+            tick_spacing: Some(10),
+            ..v2.clone()
+        };
+        [(v2.address, v2), (v3.address, v3)].into_iter().collect()
+    }
+
+    fn decoder() -> DexDecoder {
+        DexDecoder::new(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+    }
+
+    fn decode(logs: &[Log]) -> DexBlock {
+        let block = MyBlock {
+            block_number: U64::from(18_000_000),
+            block_hash: H256::zero(),
+            parent_hash: H256::zero(),
+            timestamp: U256::from(1_693_066_895),
+            gas_used: U256::zero(),
+            gas_limit: U256::zero(),
+            base_fee_per_gas: None,
+        };
+        let senders = [(H256::repeat_byte(0x11), address(TRADER))]
+            .into_iter()
+            .collect();
+        decoder()
+            .decode_block(&block, logs, &senders, &pools())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn topic0_selects_the_mainnet_event() {
+        let decoder = decoder();
+        for (topic0, kind) in [
+            (PAIR_CREATED, DexEventKind::PairCreated),
+            (POOL_CREATED, DexEventKind::PoolCreated),
+            (SWAP_V2, DexEventKind::SwapV2),
+            (MINT_V2, DexEventKind::MintV2),
+            (BURN_V2, DexEventKind::BurnV2),
+            (SYNC, DexEventKind::Sync),
+            (SWAP_V3, DexEventKind::SwapV3),
+            (MINT_V3, DexEventKind::MintV3),
+            (BURN_V3, DexEventKind::BurnV3),
+        ] {
+            let decoded = decoder.events.get(&topic(topic0)).map(|(kind, _)| *kind);
+            assert_eq!(decoded, Some(kind), "{}", topic0);
+        }
+        assert_eq!(decoder.events.len(), DEX_EVENTS.len());
+    }
+
+    #[tokio::test]
+    async fn decodes_v2_swap_netting_in_and_out() {
+        // 0.5 WETH in, 1,000 USDC out.
+        let swap = log(
+            USDC_WETH_V2,
+            vec![
+                topic(SWAP_V2),
+                address_topic(V2_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[ZERO, WETH_HALF, USDC_1000, ZERO],
+            7,
+        );
+        let dex = decode(&[swap]);
+        assert!(dex.liquidity.is_empty());
+        let [swap] = &dex.swaps[..] else {
+            panic!("expected one swap");
+        };
+        assert_eq!(swap.protocol, DexProtocol::UniswapV2);
+        assert_eq!(swap.pool, address(USDC_WETH_V2));
+        assert_eq!(swap.log_position, 0);
+        assert_eq!(swap.log_index, Some(7));
+        assert_eq!(swap.sender, address(V2_ROUTER));
+        assert_eq!(swap.recipient, address(TRADER));
+        assert_eq!(swap.trader, address(TRADER));
+        assert_eq!(swap.amount0, I256::from(-1_000_000_000i64));
+        assert_eq!(swap.amount1, I256::from(500_000_000_000_000_000i64));
+        assert_eq!(
+            (swap.sqrt_price_x96, swap.liquidity, swap.tick),
+            (None, None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_v2_sync_mint_and_burn() {
+        let sync = log(
+            USDC_WETH_V2,
+            vec![topic(SYNC)],
+            &[
+                "00000000000000000000000000000000000000000000000000001b48eb57e000",
+                "00000000000000000000000000000000000000000000032d26d12e980b600000",
+            ],
+            0,
+        );
+        let mint = log(
+            USDC_WETH_V2,
+            vec![topic(MINT_V2), address_topic(V2_ROUTER)],
+            &[USDC_2000, WETH_1],
+            1,
+        );
+        let burn = log(
+            USDC_WETH_V2,
+            vec![
+                topic(BURN_V2),
+                address_topic(V2_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[USDC_2000, WETH_1],
+            2,
+        );
+        let dex = decode(&[sync, mint, burn]);
+        assert!(dex.swaps.is_empty());
+        let [sync, mint, burn] = &dex.liquidity[..] else {
+            panic!("expected three liquidity events");
+        };
+
+        assert_eq!(sync.kind, "sync");
+        assert_eq!(sync.amount0, U256::from(30_000_000_000_000u64));
+        assert_eq!(sync.amount1, U256::exp10(22) * 15 / 10);
+        assert_eq!((sync.sender, sync.recipient), (None, None));
+
+        assert_eq!(mint.kind, "mint");
+        assert_eq!(mint.log_position, 1);
+        assert_eq!(mint.sender, Some(address(V2_ROUTER)));
+        assert_eq!(mint.recipient, None);
+        assert_eq!(mint.amount0, U256::from(2_000_000_000u64));
+        assert_eq!(mint.amount1, U256::exp10(18));
+
+        assert_eq!(burn.kind, "burn");
+        assert_eq!(burn.sender, Some(address(V2_ROUTER)));
+        assert_eq!(burn.recipient, Some(address(TRADER)));
+        assert_eq!(burn.amount0, U256::from(2_000_000_000u64));
+        assert_eq!(
+            (burn.owner, burn.liquidity, burn.tick_lower),
+            (None, None, None)
+        );
+    }
+
+    #[tokio::test]
+    async fn decodes_v3_swap_with_signed_amounts() {
+        // 1,000 USDC out, 0.5 WETH in, at about 2,000 USDC per WETH.
+        let swap = log(
+            USDC_WETH_V3,
+            vec![
+                topic(SWAP_V3),
+                address_topic(V3_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[
+                USDC_MINUS_1000,
+                WETH_HALF,
+                "0000000000000000000000000000000000005758ae05bbf89b1e32f83635685c",
+                "000000000000000000000000000000000000000000000000a688906bd8b00000",
+                "0000000000000000000000000000000000000000000000000000000000030e77",
+            ],
+            3,
+        );
+        let dex = decode(&[swap]);
+        let [swap] = &dex.swaps[..] else {
+            panic!("expected one swap");
+        };
+        assert_eq!(swap.protocol, DexProtocol::UniswapV3);
+        assert_eq!(swap.sender, address(V3_ROUTER));
+        assert_eq!(swap.recipient, address(TRADER));
+        assert_eq!(swap.amount0, I256::from(-1_000_000_000i64));
+        assert_eq!(swap.amount1, I256::from(500_000_000_000_000_000i64));
+        assert_eq!(
+            swap.sqrt_price_x96,
+            Some(U256::from_dec_str("1771595571142957102961017161607260").unwrap())
+        );
+        assert_eq!(swap.liquidity, Some(U256::exp10(18) * 12));
+        assert_eq!(swap.tick, Some(200_311));
+    }
+
+    #[tokio::test]
+    async fn v3_int256_and_int24_keep_their_sign_at_the_extremes() {
+        let swap = log(
+            USDC_WETH_V3,
+            vec![
+                topic(SWAP_V3),
+                address_topic(V3_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[
+                // int256 minimum, then -1.
+                "8000000000000000000000000000000000000000000000000000000000000000",
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                "00000000000000000000000000000000000000000000000000000001000276a4",
+                ZERO,
+                // MIN_TICK, -887272.
+                "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffff27618",
+            ],
+            0,
+        );
+        let dex = decode(&[swap]);
+        let [swap] = &dex.swaps[..] else {
+            panic!("expected one swap");
+        };
+        assert_eq!(swap.amount0, I256::MIN);
+        assert_eq!(swap.amount1, I256::minus_one());
+        assert_eq!(swap.sqrt_price_x96, Some(U256::from(4_295_128_740u64)));
+        assert_eq!(swap.tick, Some(-887_272));
+    }
+
+    #[tokio::test]
+    async fn decodes_v3_mint_and_burn_with_negative_ticks() {
+        // A full-range position of the 0.05% pool: ticks -887270 and 887270.
+        let ticks = [int_topic(-887_270), int_topic(887_270)];
+        let liquidity = "00000000000000000000000000000000000000000000000000b1a2bc2ec50000";
+        let mint = log(
+            USDC_WETH_V3,
+            [topic(MINT_V3), address_topic(V3_POSITIONS)]
+                .into_iter()
+                .chain(ticks)
+                .collect(),
+            &[&address_word(V3_POSITIONS), liquidity, USDC_2000, WETH_1],
+            4,
+        );
+        let burn = log(
+            USDC_WETH_V3,
+            [topic(BURN_V3), address_topic(V3_POSITIONS)]
+                .into_iter()
+                .chain(ticks)
+                .collect(),
+            &[liquidity, USDC_2000, WETH_1],
+            5,
+        );
+        let dex = decode(&[mint, burn]);
+        let [mint, burn] = &dex.liquidity[..] else {
+            panic!("expected two liquidity events");
+        };
+        for (event, kind) in [(mint, "mint"), (burn, "burn")] {
+            assert_eq!(event.kind, kind);
+            assert_eq!(event.owner, Some(address(V3_POSITIONS)));
+            assert_eq!(event.tick_lower, Some(-887_270));
+            assert_eq!(event.tick_upper, Some(887_270));
+            assert_eq!(event.liquidity, Some(U256::exp10(16) * 5));
+            assert_eq!(event.amount0, U256::from(2_000_000_000u64));
+            assert_eq!(event.amount1, U256::exp10(18));
+        }
+        assert_eq!(mint.sender, Some(address(V3_POSITIONS)));
+        assert_eq!(burn.sender, None);
+    }
+
+    #[tokio::test]
+    async fn skips_other_protocols_events_and_foreign_layouts() {
+        // A V2 Swap emitted by the V3 pool, and a V3 Swap cut short like an unrelated event
+        // that happens to share the topic0.
+        let v2_swap_on_v3 = log(
+            USDC_WETH_V3,
+            vec![
+                topic(SWAP_V2),
+                address_topic(V2_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[ZERO, WETH_HALF, USDC_1000, ZERO],
+            0,
+        );
+        let short_v3_swap = log(
+            USDC_WETH_V3,
+            vec![
+                topic(SWAP_V3),
+                address_topic(V3_ROUTER),
+                address_topic(TRADER),
+            ],
+            &[USDC_MINUS_1000, WETH_HALF],
+            1,
+        );
+        let unknown_pool = log(WETH, vec![topic(SYNC)], &[USDC_1000, WETH_1], 2);
+        let dex = decode(&[v2_swap_on_v3, short_v3_swap, unknown_pool]);
+        assert!(dex.swaps.is_empty());
+        assert!(dex.liquidity.is_empty());
+    }
+}
@@ -1,5 +1,16 @@
 // src/docs.rs
-use crate::api_models::{GenericErrorResponse, GetLogsFilter, IndexerStats, LogsResponse};
+use crate::api_models::{
+    AccountBalancesResponse, ApiKey, ApiKeyRequest, ApiKeyUsage, ApiKeyUsageResponse, BlockRange,
+    BlocksResponse, Closest, Contract, ContractGasUsage, ContractUpgrade, DexLiquidityEvent,
+    DexLiquidityResponse, DexPool, DexSwap, DexSwapsResponse, EthUsdPrice, EventHandler,
+    EventHandlerRequest, ExportBlocksFilter, ExportFormat, GasContractsResponse, GasHistoryEntry,
+    GasHistoryResponse, GasOracle, GasSuggestion, GenericErrorResponse, GetBlocksQuery,
+    GetLogsFilter, GetTransactionsFilter, HandlerEntity, IndexerStats, IngesterConfigRequest,
+    IngesterStatus, LogsResponse, Readiness, RewindRequest, SearchMatch, SearchResponse,
+    StatsTimeseriesEntry, StatsTimeseriesResponse, StreamMessage, TokenBalance, TokenPrice,
+    TokenPriceHistoryResponse, TokenTransfer, TokenTransfersResponse, TransactionsResponse,
+    WebhookDeadLetter, WebhookPayload, WebhookRule, WebhookRuleRequest,
+};
 use crate::models::{MyBlock, MyLog, MyTransaction};
 use utoipa::OpenApi;
 
@@ -9,16 +20,120 @@ use utoipa::OpenApi;
         crate::api::root_handler,
         crate::api::get_stats_handler,
         crate::api::get_logs_handler,
+        crate::api::get_transactions_handler,
+        crate::api::get_blocks_handler,
+        crate::api::get_latest_block_handler,
+        crate::api::get_block_by_timestamp_handler,
         crate::api::get_block_handler,
+        crate::api::get_block_transactions_handler,
+        crate::api::get_block_logs_handler,
         crate::api::get_transaction_by_hash_handler,
+        crate::api::get_transaction_logs_handler,
+        crate::search::search_handler,
+        crate::rpc::rpc_handler,
+        crate::graphql::graphql_handler,
+        crate::stream::stream_logs_sse_handler,
+        crate::stream::stream_logs_ws_handler,
+        crate::webhooks::list_rules_handler,
+        crate::webhooks::create_rule_handler,
+        crate::webhooks::get_rule_handler,
+        crate::webhooks::update_rule_handler,
+        crate::webhooks::delete_rule_handler,
+        crate::webhooks::list_dead_letters_handler,
+        crate::auth::list_keys_handler,
+        crate::auth::create_key_handler,
+        crate::auth::revoke_key_handler,
+        crate::auth::key_usage_handler,
+        crate::control::health_handler,
+        crate::control::ready_handler,
+        crate::control::status_handler,
+        crate::control::pause_handler,
+        crate::control::resume_handler,
+        crate::control::rewind_handler,
+        crate::control::reindex_handler,
+        crate::control::config_handler,
+        crate::handlers::list_handlers_handler,
+        crate::handlers::create_handler_handler,
+        crate::handlers::get_handler_handler,
+        crate::handlers::update_handler_handler,
+        crate::handlers::delete_handler_handler,
+        crate::handlers::list_entities_handler,
+        crate::handlers::get_entity_handler,
+        crate::dex::list_pools_handler,
+        crate::dex::get_pool_handler,
+        crate::dex::pool_swaps_handler,
+        crate::dex::pool_liquidity_handler,
+        crate::dex::trader_swaps_handler,
+        crate::dex::token_swaps_handler,
+        crate::pricing::eth_price_handler,
+        crate::pricing::token_price_handler,
+        crate::pricing::token_price_history_handler,
+        crate::pricing::account_transfers_handler,
+        crate::pricing::account_balances_handler,
+        crate::contracts::get_contract_handler,
+        crate::gas::gas_history_handler,
+        crate::gas::gas_oracle_handler,
+        crate::gas::gas_contracts_handler,
+        crate::stats::stats_timeseries_handler,
+        crate::export::export_logs_handler,
+        crate::export::export_transactions_handler,
+        crate::export::export_blocks_handler,
     ),
     components(
         schemas(
             // API Models
             GetLogsFilter,
+            GetTransactionsFilter,
+            GetBlocksQuery,
+            Closest,
             GenericErrorResponse,
             IndexerStats,
             LogsResponse,
+            TransactionsResponse,
+            BlocksResponse,
+            StreamMessage,
+            WebhookRuleRequest,
+            WebhookRule,
+            WebhookDeadLetter,
+            WebhookPayload,
+            ApiKeyRequest,
+            ApiKey,
+            ApiKeyUsage,
+            ApiKeyUsageResponse,
+            IngesterStatus,
+            Readiness,
+            BlockRange,
+            RewindRequest,
+            IngesterConfigRequest,
+            EventHandlerRequest,
+            EventHandler,
+            HandlerEntity,
+            DexPool,
+            DexSwap,
+            DexSwapsResponse,
+            DexLiquidityEvent,
+            DexLiquidityResponse,
+            TokenPrice,
+            EthUsdPrice,
+            TokenPriceHistoryResponse,
+            TokenTransfer,
+            TokenTransfersResponse,
+            TokenBalance,
+            AccountBalancesResponse,
+            Contract,
+            ContractUpgrade,
+            GasHistoryEntry,
+            GasHistoryResponse,
+            GasSuggestion,
+            GasOracle,
+            ContractGasUsage,
+            GasContractsResponse,
+            StatsTimeseriesEntry,
+            StatsTimeseriesResponse,
+            SearchResponse,
+            SearchMatch,
+            ExportFormat,
+            ExportBlocksFilter,
             // Core DB Models
             MyBlock,
             MyTransaction,
@@ -0,0 +1,539 @@
+// src/ens.rs
+//
+// ENS indexing. The transform stage decodes the ENS events in each block into `ens_names`
+// rows, one per event: owner and resolver changes (`NewOwner`, `Transfer`, `NewResolver`) from
+// the registry, address and name records (`AddrChanged`, `NameChanged`) from resolvers, and
+// the plaintext of `.eth` names from the registrar controllers' `NameRegistered`.
+//
+// Rows are never updated. A node's current record of each kind is its latest row, and a reorg
+// rollback deletes the rows of the dropped blocks, which brings back the previous values.
+//
+// Anyone can deploy a contract that emits resolver events, so a record only counts when it
+// came from the resolver the registry currently names for the node. Registrations are checked
+// against their label hash instead. A primary name is the `NameChanged` record of the
+// address's reverse node (`<address>.addr.reverse`), kept only when the name resolves back to
+// the address.
+//
+// Only events in indexed blocks are known: a name whose resolver was set before the start
+// block does not resolve. Names are lowercased but not otherwise normalized.
+
+use crate::api::ApiError;
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use ethers::abi::{ParamType, Token};
+use ethers::providers::ens::{namehash, reverse_address};
+use ethers::types::{Address, Log, H256};
+use ethers::utils::keccak256;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+/// The ENS registry, deployed at the same address on mainnet and the public testnets.
+const DEFAULT_ENS_REGISTRY: &str = "0x00000000000c2e074ec69a0dfb2997ba6c7d2e1e";
+/// Mainnet, Sepolia and Holesky.
+const DEFAULT_REGISTRY_CHAINS: [u64; 3] = [1, 11_155_111, 17_000];
+/// namehash("eth")
+const ETH_NODE: &str = "0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae";
+
+/// Current record of one kind for each node; the resolver record itself is taken from the
+/// registry rows, the others only from the node's current resolver.
+const RESOLVER_RECORDS_QUERY: &str = r#"
+    SELECT n.node, rec.value
+    FROM UNNEST($1::TEXT[]) AS n(node)
+    CROSS JOIN LATERAL (
+        SELECT x.value FROM ens_names x
+        WHERE x.node = n.node AND x.kind = $2 AND x.source = (
+            SELECT r.value FROM ens_names r
+            WHERE r.node = n.node AND r.kind = 'resolver'
+            ORDER BY r.block_number DESC, r.log_id DESC LIMIT 1
+        )
+        ORDER BY x.block_number DESC, x.log_id DESC LIMIT 1
+    ) rec
+"#;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnsKind {
+    /// A `.eth` name registered through a controller; the value is the full name.
+    Registered,
+    Owner,
+    Resolver,
+    /// The address record; the zero address clears it.
+    Addr,
+    /// The name record, set on reverse nodes for primary names.
+    Name,
+}
+
+impl EnsKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EnsKind::Registered => "registered",
+            EnsKind::Owner => "owner",
+            EnsKind::Resolver => "resolver",
+            EnsKind::Addr => "addr",
+            EnsKind::Name => "name",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EnsRecord {
+    /// Position of the event in the block's logs, as for DEX rows.
+    pub log_position: usize,
+    pub node: H256,
+    pub kind: EnsKind,
+    /// A lowercase hex address, or a name for `Registered` and `Name`.
+    pub value: String,
+    /// The contract that emitted the event.
+    pub source: Address,
+}
+
+/// One block's ENS rows, handed to the writer.
+#[derive(Clone, Debug)]
+pub struct EnsBlock {
+    pub block_number: u64,
+    pub records: Vec<EnsRecord>,
+}
+
+/// Shared by the transform stage for the lifetime of the ingester.
+pub struct EnsIndexer {
+    registry: Address,
+    eth_node: H256,
+    new_owner_topic: H256,
+    transfer_topic: H256,
+    new_resolver_topic: H256,
+    addr_changed_topic: H256,
+    name_changed_topic: H256,
+    /// Controller versions up to 2023 report `cost`; later ones `baseCost` and `premium`.
+    name_registered_topics: [H256; 2],
+}
+
+impl EnsIndexer {
+    /// Reads `ENS_INDEXING` (on unless `false`) and `ENS_REGISTRY`, which defaults to the
+    /// registry on mainnet, Sepolia and Holesky. `None` on other chains without a registry.
+    pub fn from_env(chain_id: u64) -> eyre::Result<Option<Self>> {
+        if env::var("ENS_INDEXING").is_ok_and(|v| v == "false" || v == "0") {
+            return Ok(None);
+        }
+        let registry = match env::var("ENS_REGISTRY") {
+            Ok(value) => value
+                .trim()
+                .parse::<Address>()
+                .map_err(|e| eyre::eyre!("ENS_REGISTRY: invalid address {}: {}", value, e))?,
+            Err(_) if DEFAULT_REGISTRY_CHAINS.contains(&chain_id) => {
+                DEFAULT_ENS_REGISTRY.parse()?
+            }
+            Err(_) => return Ok(None),
+        };
+        let topic = |signature: &str| H256::from(keccak256(signature));
+        Ok(Some(EnsIndexer {
+            registry,
+            eth_node: ETH_NODE.parse()?,
+            new_owner_topic: topic("NewOwner(bytes32,bytes32,address)"),
+            transfer_topic: topic("Transfer(bytes32,address)"),
+            new_resolver_topic: topic("NewResolver(bytes32,address)"),
+            addr_changed_topic: topic("AddrChanged(bytes32,address)"),
+            name_changed_topic: topic("NameChanged(bytes32,string)"),
+            name_registered_topics: [
+                topic("NameRegistered(string,bytes32,address,uint256,uint256)"),
+                topic("NameRegistered(string,bytes32,address,uint256,uint256,uint256)"),
+            ],
+        }))
+    }
+
+    pub fn registry(&self) -> Address {
+        self.registry
+    }
+
+    /// The ENS rows of a block's logs. `None` when the block has none.
+    pub fn decode_block(&self, block: &MyBlock, logs: &[Log]) -> Option<EnsBlock> {
+        let records: Vec<EnsRecord> = logs
+            .iter()
+            .enumerate()
+            .filter_map(|(position, log)| {
+                let (node, kind, value) = self.decode_log(log)?;
+                Some(EnsRecord {
+                    log_position: position,
+                    node,
+                    kind,
+                    value,
+                    source: log.address,
+                })
+            })
+            .collect();
+        (!records.is_empty()).then(|| EnsBlock {
+            block_number: block.block_number.as_u64(),
+            records,
+        })
+    }
+
+    fn decode_log(&self, log: &Log) -> Option<(H256, EnsKind, String)> {
+        let topic0 = *log.topics.first()?;
+        let from_registry = log.address == self.registry;
+        let address_word = || -> Option<String> {
+            let word = log.data.get(..32)?;
+            // Unlike `word_to_address`, the zero address is kept: it clears the record.
+            (word[..12].iter().all(|b| *b == 0))
+                .then(|| format!("{:#x}", Address::from_slice(&word[12..])))
+        };
+
+        if from_registry && topic0 == self.new_owner_topic && log.topics.len() == 3 {
+            let subnode = keccak256([log.topics[1].0, log.topics[2].0].concat());
+            return Some((H256::from(subnode), EnsKind::Owner, address_word()?));
+        }
+        if from_registry && topic0 == self.transfer_topic && log.topics.len() == 2 {
+            return Some((log.topics[1], EnsKind::Owner, address_word()?));
+        }
+        if from_registry && topic0 == self.new_resolver_topic && log.topics.len() == 2 {
+            return Some((log.topics[1], EnsKind::Resolver, address_word()?));
+        }
+        if topic0 == self.addr_changed_topic && log.topics.len() == 2 {
+            return Some((log.topics[1], EnsKind::Addr, address_word()?));
+        }
+        if topic0 == self.name_changed_topic && log.topics.len() == 2 {
+            let name = decode_string(&log.data, &[ParamType::String])?;
+            return Some((log.topics[1], EnsKind::Name, name.to_lowercase()));
+        }
+        if self.name_registered_topics.contains(&topic0) && log.topics.len() == 3 {
+            let params: &[ParamType] = if topic0 == self.name_registered_topics[0] {
+                &[
+                    ParamType::String,
+                    ParamType::Uint(256),
+                    ParamType::Uint(256),
+                ]
+            } else {
+                &[
+                    ParamType::String,
+                    ParamType::Uint(256),
+                    ParamType::Uint(256),
+                    ParamType::Uint(256),
+                ]
+            };
+            let label = decode_string(&log.data, params)?;
+            // Only a name that hashes to the indexed label is the one that was registered.
+            if keccak256(label.as_bytes()) != log.topics[1].0 {
+                return None;
+            }
+            let node = keccak256([self.eth_node.0, log.topics[1].0].concat());
+            return Some((
+                H256::from(node),
+                EnsKind::Registered,
+                format!("{}.eth", label.to_lowercase()),
+            ));
+        }
+        None
+    }
+}
+
+/// The leading string of ABI-encoded event data.
+fn decode_string(data: &[u8], params: &[ParamType]) -> Option<String> {
+    match ethers::abi::decode(params, data).ok()?.into_iter().next()? {
+        Token::String(value) if !value.is_empty() => Some(value),
+        _ => None,
+    }
+}
+
+/// `name.tld`: at least two non-empty labels and a letter-only top-level label.
+pub fn is_ens_name(value: &str) -> bool {
+    value.split('.').count() >= 2
+        && value.split('.').all(|label| !label.is_empty())
+        && value
+            .rsplit('.')
+            .next()
+            .is_some_and(|tld| tld.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// The current record of `kind` for each node that has one, keyed by node.
+async fn resolver_records(
+    pool: &PgPool,
+    nodes: &[H256],
+    kind: EnsKind,
+) -> Result<HashMap<H256, String>, ApiError> {
+    if nodes.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<(String, String)> = sqlx::query_as(RESOLVER_RECORDS_QUERY)
+        .bind(
+            nodes
+                .iter()
+                .map(|node| format!("{:#x}", node))
+                .collect::<Vec<_>>(),
+        )
+        .bind(kind.as_str())
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(node, value)| Some((node.parse().ok()?, value)))
+        .collect())
+}
+
+/// The address `name` resolves to in the indexed records, if any.
+pub(crate) async fn resolve_name(pool: &PgPool, name: &str) -> Result<Option<Address>, ApiError> {
+    let node = namehash(&name.to_lowercase());
+    let records = resolver_records(pool, &[node], EnsKind::Addr).await?;
+    Ok(records
+        .get(&node)
+        .and_then(|value| value.parse::<Address>().ok())
+        .filter(|address| !address.is_zero()))
+}
+
+/// Verified primary names of `addresses`, for those that have one.
+pub(crate) async fn primary_names(
+    pool: &PgPool,
+    addresses: &HashSet<Address>,
+) -> Result<HashMap<Address, String>, ApiError> {
+    let reverse_nodes: HashMap<H256, Address> = addresses
+        .iter()
+        .map(|address| (namehash(&reverse_address(*address)), *address))
+        .collect();
+    let nodes: Vec<H256> = reverse_nodes.keys().copied().collect();
+    let claimed: Vec<(Address, String)> = resolver_records(pool, &nodes, EnsKind::Name)
+        .await?
+        .into_iter()
+        .filter_map(|(node, name)| Some((*reverse_nodes.get(&node)?, name)))
+        .collect();
+
+    // A reverse record can claim any name; keep it only if the name points back.
+    let forward_nodes: Vec<H256> = claimed.iter().map(|(_, name)| namehash(name)).collect();
+    let forward = resolver_records(pool, &forward_nodes, EnsKind::Addr).await?;
+    Ok(claimed
+        .into_iter()
+        .filter(|(address, name)| {
+            forward
+                .get(&namehash(name))
+                .and_then(|value| value.parse::<Address>().ok())
+                == Some(*address)
+        })
+        .collect())
+}
+
+/// An address parameter that may also be given as an ENS name: names are resolved from the
+/// index, anything else is validated as a hex address.
+pub(crate) async fn resolve_address(
+    pool: &PgPool,
+    value: &str,
+    field: &str,
+) -> Result<String, ApiError> {
+    if !is_ens_name(value) {
+        return crate::dex::normalize_address(value, field);
+    }
+    match resolve_name(pool, value).await? {
+        Some(address) => Ok(format!("{:#x}", address)),
+        None => Err(unresolved(value)),
+    }
+}
+
+/// Replaces an ENS name in a filter value with its address, leaving other values for the
+/// filter's own validation. Names need the Postgres index.
+pub(crate) async fn resolve_filter_value(
+    pool: Option<&PgPool>,
+    value: &mut String,
+) -> Result<(), ApiError> {
+    if !is_ens_name(value) {
+        return Ok(());
+    }
+    let pool = pool
+        .ok_or_else(|| ApiError::BadRequest("ENS names require PostgreSQL storage.".to_string()))?;
+    let address = resolve_name(pool, value)
+        .await?
+        .ok_or_else(|| unresolved(value))?;
+    *value = format!("{:#x}", address);
+    Ok(())
+}
+
+fn unresolved(name: &str) -> ApiError {
+    ApiError::NotFound(format!(
+        "{} does not resolve to an address in the indexed ENS records.",
+        name
+    ))
+}
+
+/// Sets `fromEnsName`/`toEnsName` on transactions whose addresses have a primary name.
+pub(crate) async fn fill_transaction_names(
+    pool: &PgPool,
+    transactions: &mut [MyTransaction],
+) -> Result<(), ApiError> {
+    let addresses: HashSet<Address> = transactions
+        .iter()
+        .flat_map(|tx| std::iter::once(tx.from_address).chain(tx.to_address))
+        .collect();
+    let names = primary_names(pool, &addresses).await?;
+    for tx in transactions {
+        tx.from_ens_name = names.get(&tx.from_address).cloned();
+        tx.to_ens_name = tx.to_address.and_then(|to| names.get(&to).cloned());
+    }
+    Ok(())
+}
+
+/// Sets `addressEnsName` on logs whose emitting contract has a primary name.
+pub(crate) async fn fill_log_names(pool: &PgPool, logs: &mut [MyLog]) -> Result<(), ApiError> {
+    let addresses: HashSet<Address> = logs.iter().map(|log| log.address).collect();
+    let names = primary_names(pool, &addresses).await?;
+    for log in logs {
+        log.address_ens_name = names.get(&log.address).cloned();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::encode;
+    use ethers::types::U256;
+
+    fn indexer() -> EnsIndexer {
+        EnsIndexer::from_env(1).unwrap().unwrap()
+    }
+
+    fn hash(value: &str) -> H256 {
+        value.parse().unwrap()
+    }
+
+    fn label(value: &str) -> H256 {
+        H256::from(keccak256(value))
+    }
+
+    fn log(address: Address, topics: Vec<H256>, data: Vec<u8>) -> Log {
+        Log {
+            address,
+            topics,
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn namehash_matches_eip137() {
+        assert_eq!(namehash(""), H256::zero());
+        assert_eq!(
+            namehash("eth"),
+            hash("0x93cdeb708b7545dc668eb9280176169d1c33cfd8ed6f04690a0bcc88a93fc4ae")
+        );
+        assert_eq!(
+            namehash("foo.eth"),
+            hash("0xde9b09fd7c5f901e23a3f19fecc54828e9c848539801e86591bd9801b019f84f")
+        );
+        assert_eq!(
+            namehash("addr.reverse"),
+            hash("0x91d1777781884d03a6757a803996e38de2a42967fb37eeaca72729271025a9e2")
+        );
+        assert_eq!(namehash("eth"), hash(ETH_NODE));
+    }
+
+    #[test]
+    fn new_owner_nodes_are_namehashes() {
+        let indexer = indexer();
+        let owner = Address::repeat_byte(0xab);
+        let new_owner = log(
+            indexer.registry(),
+            vec![
+                H256::from(keccak256("NewOwner(bytes32,bytes32,address)")),
+                hash(ETH_NODE),
+                label("foo"),
+            ],
+            encode(&[Token::Address(owner)]),
+        );
+        let (node, kind, value) = indexer.decode_log(&new_owner).unwrap();
+        assert_eq!(node, namehash("foo.eth"));
+        assert_eq!(kind, EnsKind::Owner);
+        assert_eq!(value, format!("{:#x}", owner));
+
+        // Only the registry assigns owners.
+        let spoofed = Log {
+            address: Address::repeat_byte(0x01),
+            ..new_owner
+        };
+        assert!(indexer.decode_log(&spoofed).is_none());
+    }
+
+    #[test]
+    fn decodes_registered_names_of_both_controller_versions() {
+        let indexer = indexer();
+        let owner = H256::from(Address::repeat_byte(0xab));
+        let amount = Token::Uint(U256::exp10(16));
+        for (signature, data) in [
+            (
+                "NameRegistered(string,bytes32,address,uint256,uint256)",
+                encode(&[
+                    Token::String("vitalik".to_string()),
+                    amount.clone(),
+                    amount.clone(),
+                ]),
+            ),
+            (
+                "NameRegistered(string,bytes32,address,uint256,uint256,uint256)",
+                encode(&[
+                    Token::String("vitalik".to_string()),
+                    amount.clone(),
+                    amount.clone(),
+                    amount.clone(),
+                ]),
+            ),
+        ] {
+            let topic0 = H256::from(keccak256(signature));
+            let registered = log(
+                Address::repeat_byte(0x02),
+                vec![topic0, label("vitalik"), owner],
+                data.clone(),
+            );
+            let (node, kind, value) = indexer.decode_log(&registered).unwrap();
+            assert_eq!(kind, EnsKind::Registered);
+            assert_eq!(value, "vitalik.eth");
+            assert_eq!(node, namehash("vitalik.eth"));
+
+            // A plaintext that doesn't hash to the indexed label is not the registered name.
+            let mislabeled = log(
+                Address::repeat_byte(0x02),
+                vec![topic0, label("vitalik2"), owner],
+                data,
+            );
+            assert!(indexer.decode_log(&mislabeled).is_none());
+        }
+    }
+
+    #[test]
+    fn decodes_resolver_name_and_addr_records() {
+        let indexer = indexer();
+        let resolver = Address::repeat_byte(0x03);
+        let node = namehash("foo.eth");
+        let name_changed = log(
+            resolver,
+            vec![H256::from(keccak256("NameChanged(bytes32,string)")), node],
+            encode(&[Token::String("Foo.ETH".to_string())]),
+        );
+        assert_eq!(
+            indexer.decode_log(&name_changed),
+            Some((node, EnsKind::Name, "foo.eth".to_string()))
+        );
+        let empty_name = Log {
+            data: encode(&[Token::String(String::new())]).into(),
+            ..name_changed
+        };
+        assert!(indexer.decode_log(&empty_name).is_none());
+
+        // The zero address is kept: it clears the record.
+        let addr_changed = log(
+            resolver,
+            vec![H256::from(keccak256("AddrChanged(bytes32,address)")), node],
+            encode(&[Token::Address(Address::zero())]),
+        );
+        assert_eq!(
+            indexer.decode_log(&addr_changed),
+            Some((node, EnsKind::Addr, format!("{:#x}", Address::zero())))
+        );
+        let not_an_address = Log {
+            data: vec![0xff; 32].into(),
+            ..addr_changed
+        };
+        assert!(indexer.decode_log(&not_an_address).is_none());
+    }
+
+    #[test]
+    fn recognises_ens_names() {
+        for name in ["vitalik.eth", "sub.vitalik.eth", "name.xyz"] {
+            assert!(is_ens_name(name), "{}", name);
+        }
+        for name in ["eth", "vitalik.", ".eth", "a..eth", "vitalik.eth2", ""] {
+            assert!(!is_ens_name(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// src/events.rs
+//
+// In-process notifications published by the ingester after it changes the database.
+// Subscribers (API streams and other consumers) receive every committed block and every
+// reorg rollback in order, without polling Postgres.
+
+use crate::api::{row_to_log, row_to_transaction, LOG_COLUMNS, TRANSACTION_COLUMNS};
+use crate::models::{MyLog, MyTransaction};
+use ethers::types::H256;
+use sqlx::{PgPool, Row as SqlxRow};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+// Slow subscribers that fall further behind than this are told they lagged and
+// are expected to catch up from the database.
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// A log together with its `logs.id`, which forms the `(block_number, id)` cursor.
+#[derive(Debug, Clone)]
+pub struct IndexedLog {
+    pub id: i64,
+    pub log: MyLog,
+}
+
+#[derive(Debug, Clone)]
+pub enum ChainEvent {
+    /// A block and all of its transactions and logs were committed.
+    BlockCommitted {
+        block_number: u64,
+        block_hash: H256,
+        transactions: Vec<MyTransaction>,
+        logs: Vec<IndexedLog>,
+    },
+    /// All data at or above `fork_height` was removed by `rollback_from_height`.
+    Reorg { fork_height: u64 },
+    /// The blocks in `from_block..=to_block` were deleted to be re-indexed, and are committed
+    /// again next. Blocks above the range are untouched and are not published again.
+    Retract { from_block: u64, to_block: u64 },
+}
+
+impl ChainEvent {
+    /// Whether this is a `BlockCommitted` for a block inside `range`, the range of the last
+    /// `Retract` a consumer saw; such blocks are below the consumer's position but new to it.
+    pub fn is_reindexed(&self, range: Option<(u64, u64)>) -> bool {
+        match (self, range) {
+            (ChainEvent::BlockCommitted { block_number, .. }, Some((from, to))) => {
+                (from..=to).contains(block_number)
+            }
+            _ => false,
+        }
+    }
+}
+
+pub type EventSender = broadcast::Sender<Arc<ChainEvent>>;
+
+pub fn channel() -> EventSender {
+    broadcast::channel(EVENT_CHANNEL_CAPACITY).0
+}
+
+/// Publishes an event; having no subscribers is not an error.
+pub fn publish(sender: &EventSender, event: ChainEvent) {
+    let _ = sender.send(Arc::new(event));
+}
+
+/// Rebuilds the `BlockCommitted` event for a stored block, for consumers that fell behind
+/// the channel and catch up from Postgres. Returns `None` if the block is not indexed.
+pub async fn load_block_committed(
+    pool: &PgPool,
+    block_number: u64,
+) -> eyre::Result<Option<ChainEvent>> {
+    let height = block_number as i64;
+    let block_hash: Option<String> =
+        sqlx::query_scalar("SELECT block_hash FROM blocks WHERE block_number = $1")
+            .bind(height)
+            .fetch_optional(pool)
+            .await?;
+    let Some(block_hash) = block_hash else {
+        return Ok(None);
+    };
+
+    let tx_rows = sqlx::query(&format!(
+        "SELECT {} FROM transactions WHERE block_number = $1 ORDER BY transaction_index",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(height)
+    .fetch_all(pool)
+    .await?;
+    let log_rows = sqlx::query(&format!(
+        "SELECT {} FROM logs WHERE block_number = $1 ORDER BY id",
+        LOG_COLUMNS
+    ))
+    .bind(height)
+    .fetch_all(pool)
+    .await?;
+
+    let transactions = tx_rows
+        .iter()
+        .map(|row| row_to_transaction(row).map_err(|e| eyre::eyre!("{:?}", e)))
+        .collect::<eyre::Result<_>>()?;
+    let logs = log_rows
+        .iter()
+        .map(|row| {
+            Ok(IndexedLog {
+                id: row.try_get("id")?,
+                log: row_to_log(row).map_err(|e| eyre::eyre!("{:?}", e))?,
+            })
+        })
+        .collect::<eyre::Result<_>>()?;
+
+    Ok(Some(ChainEvent::BlockCommitted {
+        block_number,
+        block_hash: H256::from_str(&block_hash)?,
+        transactions,
+        logs,
+    }))
+}
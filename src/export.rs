@@ -0,0 +1,928 @@
+// src/export.rs
+//
+// Bulk export of logs, transactions and blocks as CSV, NDJSON or Parquet.
+// Rows are read through a server-side Postgres cursor in fixed-size batches and encoded
+// batch by batch, so memory stays constant no matter how many rows match. The same code
+// backs the `POST /export/*` endpoints (one streamed file per request) and the
+// `evm_indexer export` CLI command (one file per block range).
+
+use crate::api::{
+    push_log_filters, push_transaction_filters, resolve_log_time_range,
+    resolve_transaction_time_range, ApiError,
+};
+use crate::api_models::{
+    BlockTag, ExportBlocksFilter, ExportFormat, ExportParams, GenericErrorResponse, GetLogsFilter,
+    GetTransactionsFilter,
+};
+use crate::storage::{resolve_time_range, PostgresStorage};
+use arrow_array::builder::{Int64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::post,
+    Router,
+};
+use futures::stream;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use sqlx::{postgres::PgRow, PgPool, Postgres, QueryBuilder, Row as SqlxRow};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{error, info};
+
+// Rows per FETCH; also the Parquet row group size.
+const EXPORT_FETCH_SIZE: usize = 10_000;
+const DEFAULT_BLOCKS_PER_FILE: u64 = 10_000;
+// Each HTTP export holds a pooled connection for as long as the client reads, and the pool
+// is shared with the ingester, so only a few run at once.
+const MAX_CONCURRENT_EXPORTS: usize = 2;
+// How long a client may stop reading before its export is dropped.
+const EXPORT_SEND_TIMEOUT: Duration = Duration::from_secs(60);
+// Backstop for a connection left idle inside the export transaction; Postgres ends the
+// session if nothing runs on it for this long.
+const EXPORT_IDLE_TIMEOUT: &str = "120s";
+
+#[derive(Clone, Copy)]
+enum ColumnKind {
+    Int,
+    Text,
+}
+
+struct Column {
+    name: &'static str,
+    expr: &'static str,
+    kind: ColumnKind,
+}
+
+const fn col(name: &'static str, expr: &'static str, kind: ColumnKind) -> Column {
+    Column { name, expr, kind }
+}
+
+// Flat column layouts for exported files. Hashes, addresses and wei amounts stay strings,
+// matching how they are stored.
+const LOG_EXPORT_COLUMNS: &[Column] = &[
+    col("id", "id", ColumnKind::Int),
+    col("block_number", "block_number", ColumnKind::Int),
+    col("block_hash", "block_hash", ColumnKind::Text),
+    col("transaction_hash", "transaction_hash", ColumnKind::Text),
+    col(
+        "transaction_index",
+        "transaction_index_in_block",
+        ColumnKind::Int,
+    ),
+    col("log_index", "log_index_in_tx", ColumnKind::Int),
+    col("address", "contract_address", ColumnKind::Text),
+    col("topic0", "topic0", ColumnKind::Text),
+    col("topic1", "topic1", ColumnKind::Text),
+    col("topic2", "topic2", ColumnKind::Text),
+    col("topic3", "topic3", ColumnKind::Text),
+    col("data", "ENCODE(data, 'escape')", ColumnKind::Text),
+];
+
+const TRANSACTION_EXPORT_COLUMNS: &[Column] = &[
+    col("tx_hash", "tx_hash", ColumnKind::Text),
+    col("block_number", "block_number", ColumnKind::Int),
+    col("block_hash", "block_hash", ColumnKind::Text),
+    col("transaction_index", "transaction_index", ColumnKind::Int),
+    col("from_address", "from_address", ColumnKind::Text),
+    col("to_address", "to_address", ColumnKind::Text),
+    col("value", "value", ColumnKind::Text),
+    col("gas_price", "gas_price", ColumnKind::Text),
+    col("max_fee_per_gas", "max_fee_per_gas", ColumnKind::Text),
+    col(
+        "max_priority_fee_per_gas",
+        "max_priority_fee_per_gas",
+        ColumnKind::Text,
+    ),
+    col("gas", "gas_provided", ColumnKind::Text),
+    col(
+        "input_data",
+        "ENCODE(input_data, 'escape')",
+        ColumnKind::Text,
+    ),
+    col("status", "status::BIGINT", ColumnKind::Int),
+    col("gas_used", "gas_used", ColumnKind::Text),
+    col(
+        "effective_gas_price",
+        "effective_gas_price",
+        ColumnKind::Text,
+    ),
+];
+
+const BLOCK_EXPORT_COLUMNS: &[Column] = &[
+    col("block_number", "block_number", ColumnKind::Int),
+    col("block_hash", "block_hash", ColumnKind::Text),
+    col("parent_hash", "parent_hash", ColumnKind::Text),
+    col("timestamp", "timestamp", ColumnKind::Int),
+    col("gas_used", "gas_used", ColumnKind::Text),
+    col("gas_limit", "gas_limit", ColumnKind::Text),
+    col("base_fee_per_gas", "base_fee_per_gas", ColumnKind::Text),
+];
+
+/// An export: which table, with the same filters as the corresponding query endpoint.
+#[derive(Clone)]
+pub enum ExportRequest {
+    Logs(GetLogsFilter),
+    Transactions(GetTransactionsFilter),
+    Blocks(ExportBlocksFilter),
+}
+
+impl ExportRequest {
+    fn entity(&self) -> &'static str {
+        match self {
+            ExportRequest::Logs(_) => "logs",
+            ExportRequest::Transactions(_) => "transactions",
+            ExportRequest::Blocks(_) => "blocks",
+        }
+    }
+
+    fn columns(&self) -> &'static [Column] {
+        match self {
+            ExportRequest::Logs(_) => LOG_EXPORT_COLUMNS,
+            ExportRequest::Transactions(_) => TRANSACTION_EXPORT_COLUMNS,
+            ExportRequest::Blocks(_) => BLOCK_EXPORT_COLUMNS,
+        }
+    }
+
+    /// The numeric block range, when both ends are given as numbers.
+    fn block_range(&self) -> Option<(u64, u64)> {
+        match self {
+            ExportRequest::Logs(f) => match (f.from_block, f.to_block) {
+                (Some(BlockTag::Number(a)), Some(BlockTag::Number(b))) => Some((a, b)),
+                _ => None,
+            },
+            ExportRequest::Transactions(f) => f.from_block.zip(f.to_block),
+            ExportRequest::Blocks(f) => f.from_block.zip(f.to_block),
+        }
+    }
+
+    /// The same export restricted to `from..=to`, replacing any range in the filters.
+    fn with_block_range(&self, from: u64, to: u64) -> Self {
+        match self {
+            ExportRequest::Logs(f) => ExportRequest::Logs(GetLogsFilter {
+                from_block: Some(BlockTag::Number(from)),
+                to_block: Some(BlockTag::Number(to)),
+                from_timestamp: None,
+                to_timestamp: None,
+                ..f.clone()
+            }),
+            ExportRequest::Transactions(f) => ExportRequest::Transactions(GetTransactionsFilter {
+                from_block: Some(from),
+                to_block: Some(to),
+                from_timestamp: None,
+                to_timestamp: None,
+                ..f.clone()
+            }),
+            ExportRequest::Blocks(_) => ExportRequest::Blocks(ExportBlocksFilter {
+                from_block: Some(from),
+                to_block: Some(to),
+                from_timestamp: None,
+                to_timestamp: None,
+            }),
+        }
+    }
+
+    /// Replaces `fromTimestamp`/`toTimestamp` with the block bounds they resolve to, as the
+    /// query endpoints do.
+    async fn resolve_time_range(&mut self, pool: &PgPool) -> Result<(), ApiError> {
+        let storage = PostgresStorage::new(pool.clone());
+        match self {
+            ExportRequest::Logs(filters) => resolve_log_time_range(&storage, filters).await,
+            ExportRequest::Transactions(filters) => {
+                resolve_transaction_time_range(&storage, filters).await
+            }
+            ExportRequest::Blocks(filters) => {
+                if (filters.from_timestamp.is_some() && filters.from_block.is_some())
+                    || (filters.to_timestamp.is_some() && filters.to_block.is_some())
+                {
+                    return Err(ApiError::BadRequest(
+                        "fromTimestamp/toTimestamp cannot be combined with the matching \
+                         fromBlock/toBlock."
+                            .to_string(),
+                    ));
+                }
+                let (from_block, to_block) = resolve_time_range(
+                    &storage,
+                    filters.from_timestamp.take(),
+                    filters.to_timestamp.take(),
+                )
+                .await?;
+                if let Some(fb) = from_block {
+                    filters.from_block = Some(fb as u64);
+                }
+                if let Some(tb) = to_block {
+                    filters.to_block = Some(tb as u64);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn file_name(&self, format: ExportFormat) -> String {
+        match self.block_range() {
+            Some((from, to)) => format!(
+                "{}_{:012}_{:012}.{}",
+                self.entity(),
+                from,
+                to,
+                format.extension()
+            ),
+            None => format!("{}.{}", self.entity(), format.extension()),
+        }
+    }
+
+    /// Builds the `DECLARE ... CURSOR` statement for this export. Pagination fields in the
+    /// filters are ignored: an export always covers every matching row.
+    async fn declare_cursor(
+        &self,
+        pool: &PgPool,
+    ) -> Result<QueryBuilder<'static, Postgres>, ApiError> {
+        let select = self
+            .columns()
+            .iter()
+            .map(|c| format!("{} AS {}", c.expr, c.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+            "DECLARE export_cursor NO SCROLL CURSOR FOR SELECT {} FROM {} WHERE 1=1",
+            select,
+            self.entity()
+        ));
+
+        match self {
+            ExportRequest::Logs(filters) => {
+                push_log_filters(pool, &mut query_builder, filters).await?;
+                query_builder.push(" ORDER BY block_number ASC, id ASC");
+            }
+            ExportRequest::Transactions(filters) => {
+                push_transaction_filters(&mut query_builder, filters)?;
+                query_builder.push(" ORDER BY block_number ASC, transaction_index ASC");
+            }
+            ExportRequest::Blocks(filters) => {
+                if let Some(fb) = filters.from_block {
+                    query_builder.push(" AND block_number >= ");
+                    query_builder.push_bind(fb as i64);
+                }
+                if let Some(tb) = filters.to_block {
+                    query_builder.push(" AND block_number <= ");
+                    query_builder.push_bind(tb as i64);
+                }
+                query_builder.push(" ORDER BY block_number ASC");
+            }
+        }
+        Ok(query_builder)
+    }
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Encoders
+// ---------------------------------------------------------------------------
+
+/// One field of an exported row, read according to its column's kind.
+enum Value {
+    Int(Option<i64>),
+    Text(Option<String>),
+}
+
+fn row_values(row: &PgRow, columns: &[Column]) -> Result<Vec<Value>, sqlx::Error> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            Ok(match column.kind {
+                ColumnKind::Int => Value::Int(row.try_get(i)?),
+                ColumnKind::Text => Value::Text(row.try_get(i)?),
+            })
+        })
+        .collect()
+}
+
+/// Turns batches of rows into output bytes. Each call returns whatever is ready to send.
+trait Encoder: Send {
+    fn encode(&mut self, rows: &[Vec<Value>]) -> eyre::Result<Vec<u8>>;
+    fn finish(self: Box<Self>) -> eyre::Result<Vec<u8>>;
+}
+
+fn encoder(format: ExportFormat, columns: &'static [Column]) -> eyre::Result<Box<dyn Encoder>> {
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvEncoder {
+            columns,
+            header_written: false,
+        }),
+        ExportFormat::Ndjson => Box::new(NdjsonEncoder { columns }),
+        ExportFormat::Parquet => Box::new(ParquetEncoder::new(columns)?),
+    })
+}
+
+struct CsvEncoder {
+    columns: &'static [Column],
+    header_written: bool,
+}
+
+impl CsvEncoder {
+    fn header(&mut self, out: &mut Vec<u8>) {
+        if !self.header_written {
+            let names: Vec<&str> = self.columns.iter().map(|c| c.name).collect();
+            out.extend_from_slice(names.join(",").as_bytes());
+            out.push(b'\n');
+            self.header_written = true;
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl Encoder for CsvEncoder {
+    fn encode(&mut self, rows: &[Vec<Value>]) -> eyre::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.header(&mut out);
+        for row in rows {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|value| match value {
+                    Value::Int(v) => v.map(|v| v.to_string()).unwrap_or_default(),
+                    Value::Text(v) => v.as_deref().map(csv_field).unwrap_or_default(),
+                })
+                .collect();
+            out.extend_from_slice(fields.join(",").as_bytes());
+            out.push(b'\n');
+        }
+        Ok(out)
+    }
+
+    fn finish(mut self: Box<Self>) -> eyre::Result<Vec<u8>> {
+        // An export with no rows still gets its header.
+        let mut out = Vec::new();
+        self.header(&mut out);
+        Ok(out)
+    }
+}
+
+struct NdjsonEncoder {
+    columns: &'static [Column],
+}
+
+impl Encoder for NdjsonEncoder {
+    fn encode(&mut self, rows: &[Vec<Value>]) -> eyre::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for row in rows {
+            let object: serde_json::Map<String, serde_json::Value> = self
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| {
+                    let json = match value {
+                        Value::Int(v) => (*v).into(),
+                        Value::Text(v) => v.clone().into(),
+                    };
+                    (column.name.to_string(), json)
+                })
+                .collect();
+            serde_json::to_writer(&mut out, &object)?;
+            out.push(b'\n');
+        }
+        Ok(out)
+    }
+
+    fn finish(self: Box<Self>) -> eyre::Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+}
+
+struct ParquetEncoder {
+    columns: &'static [Column],
+    schema: Arc<Schema>,
+    writer: ArrowWriter<Vec<u8>>,
+}
+
+impl ParquetEncoder {
+    fn new(columns: &'static [Column]) -> eyre::Result<Self> {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|c| {
+                let data_type = match c.kind {
+                    ColumnKind::Int => DataType::Int64,
+                    ColumnKind::Text => DataType::Utf8,
+                };
+                Field::new(c.name, data_type, true)
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(EXPORT_FETCH_SIZE)
+            .build();
+        let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(props))?;
+        Ok(ParquetEncoder {
+            columns,
+            schema,
+            writer,
+        })
+    }
+}
+
+impl Encoder for ParquetEncoder {
+    fn encode(&mut self, rows: &[Vec<Value>]) -> eyre::Result<Vec<u8>> {
+        let mut arrays: Vec<ArrayRef> = Vec::with_capacity(self.columns.len());
+        for (i, column) in self.columns.iter().enumerate() {
+            let array: ArrayRef = match column.kind {
+                ColumnKind::Int => {
+                    let mut builder = Int64Builder::with_capacity(rows.len());
+                    for row in rows {
+                        let Value::Int(v) = row[i] else {
+                            eyre::bail!("column {} is not an integer", column.name);
+                        };
+                        builder.append_option(v);
+                    }
+                    Arc::new(builder.finish())
+                }
+                ColumnKind::Text => {
+                    let mut builder = StringBuilder::new();
+                    for row in rows {
+                        let Value::Text(v) = &row[i] else {
+                            eyre::bail!("column {} is not text", column.name);
+                        };
+                        builder.append_option(v.as_deref());
+                    }
+                    Arc::new(builder.finish())
+                }
+            };
+            arrays.push(array);
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        self.writer.write(&batch)?;
+        // Close the row group so its bytes can be sent now instead of held in memory.
+        self.writer.flush()?;
+        Ok(std::mem::take(self.writer.inner_mut()))
+    }
+
+    fn finish(self: Box<Self>) -> eyre::Result<Vec<u8>> {
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Cursor streaming
+// ---------------------------------------------------------------------------
+
+/// Where encoded chunks go: an HTTP response body or a file.
+#[async_trait]
+trait ChunkSink: Send {
+    async fn emit(&mut self, chunk: Vec<u8>) -> eyre::Result<()>;
+}
+
+#[async_trait]
+impl ChunkSink for mpsc::Sender<Result<Bytes, std::io::Error>> {
+    async fn emit(&mut self, chunk: Vec<u8>) -> eyre::Result<()> {
+        tokio::time::timeout(EXPORT_SEND_TIMEOUT, self.send(Ok(Bytes::from(chunk))))
+            .await
+            .map_err(|_| eyre::eyre!("client stopped reading"))?
+            .map_err(|_| eyre::eyre!("client disconnected"))
+    }
+}
+
+#[async_trait]
+impl ChunkSink for tokio::fs::File {
+    async fn emit(&mut self, chunk: Vec<u8>) -> eyre::Result<()> {
+        self.write_all(&chunk).await?;
+        Ok(())
+    }
+}
+
+/// Runs the declared cursor to completion, passing encoded chunks to `out` in order.
+/// Returns the number of rows exported.
+async fn run_export(
+    pool: &PgPool,
+    mut declare: QueryBuilder<'static, Postgres>,
+    columns: &'static [Column],
+    format: ExportFormat,
+    out: &mut dyn ChunkSink,
+) -> eyre::Result<u64> {
+    // Cursors live inside a transaction; a read-only one also sees a consistent snapshot.
+    let mut db_tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *db_tx)
+        .await?;
+    sqlx::query(&format!(
+        "SET LOCAL idle_in_transaction_session_timeout = '{}'",
+        EXPORT_IDLE_TIMEOUT
+    ))
+    .execute(&mut *db_tx)
+    .await?;
+    declare.build().execute(&mut *db_tx).await?;
+
+    let fetch = format!("FETCH FORWARD {} FROM export_cursor", EXPORT_FETCH_SIZE);
+    let mut encoder = encoder(format, columns)?;
+    let mut total = 0u64;
+    loop {
+        let rows = sqlx::query(&fetch).fetch_all(&mut *db_tx).await?;
+        if rows.is_empty() {
+            break;
+        }
+        total += rows.len() as u64;
+        let rows = rows
+            .iter()
+            .map(|row| row_values(row, columns))
+            .collect::<Result<Vec<_>, _>>()?;
+        let chunk = encoder.encode(&rows)?;
+        if !chunk.is_empty() {
+            out.emit(chunk).await?;
+        }
+    }
+    let tail = encoder.finish()?;
+    if !tail.is_empty() {
+        out.emit(tail).await?;
+    }
+    db_tx.commit().await?;
+    Ok(total)
+}
+
+// ---------------------------------------------------------------------------
+// HTTP endpoints
+// ---------------------------------------------------------------------------
+
+#[derive(Clone)]
+pub struct ExportState {
+    pub pool: PgPool,
+    /// One permit per export that may run at a time.
+    pub permits: Arc<Semaphore>,
+}
+
+async fn export_response(
+    state: ExportState,
+    mut request: ExportRequest,
+    format: ExportFormat,
+) -> Result<Response, ApiError> {
+    let Ok(permit) = state.permits.clone().try_acquire_owned() else {
+        let status = StatusCode::SERVICE_UNAVAILABLE;
+        let body = GenericErrorResponse {
+            status: "error".to_string(),
+            status_code: status.as_u16(),
+            message: format!(
+                "At most {} exports run at a time; retry later",
+                MAX_CONCURRENT_EXPORTS
+            ),
+        };
+        return Ok((status, Json(body)).into_response());
+    };
+    let pool = state.pool;
+    request.resolve_time_range(&pool).await?;
+    // Build (and validate) the query before the response starts, so bad filters get a 400.
+    let declare = request.declare_cursor(&pool).await?;
+    let file_name = request.file_name(format);
+    let entity = request.entity();
+    let columns = request.columns();
+
+    // A small channel applies backpressure: the cursor only advances as the client reads.
+    let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(4);
+    tokio::spawn(async move {
+        let mut sender = tx.clone();
+        let result = run_export(&pool, declare, columns, format, &mut sender).await;
+        drop(permit);
+        if let Err(e) = result {
+            error!("EXPORT: {} export failed: {}", entity, e);
+            // Abort the response so the client sees a truncated transfer, not a short file.
+            let error = Err(std::io::Error::other(e.to_string()));
+            let _ = tokio::time::timeout(EXPORT_SEND_TIMEOUT, tx.send(error)).await;
+        }
+    });
+
+    let body = Body::from_stream(stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    }));
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Export Logs
+///
+/// Streams every log matching a `POST /logs` filter (pagination fields are ignored),
+/// ordered by block number and log id.
+#[utoipa::path(
+    post,
+    path = "/export/logs",
+    params(ExportParams),
+    request_body = GetLogsFilter,
+    responses(
+        (status = 200, description = "Streamed export file (CSV, NDJSON or Parquet)", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid filter", body = GenericErrorResponse),
+        (status = 503, description = "Too many exports running", body = GenericErrorResponse)
+    )
+)]
+pub async fn export_logs_handler(
+    State(state): State<ExportState>,
+    Query(params): Query<ExportParams>,
+    Json(filters): Json<GetLogsFilter>,
+) -> Result<Response, ApiError> {
+    export_response(state, ExportRequest::Logs(filters), params.format).await
+}
+
+/// Export Transactions
+///
+/// Streams every transaction matching a `POST /transactions` filter (pagination fields are
+/// ignored), ordered by block number and transaction index.
+#[utoipa::path(
+    post,
+    path = "/export/transactions",
+    params(ExportParams),
+    request_body = GetTransactionsFilter,
+    responses(
+        (status = 200, description = "Streamed export file (CSV, NDJSON or Parquet)", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid filter", body = GenericErrorResponse),
+        (status = 503, description = "Too many exports running", body = GenericErrorResponse)
+    )
+)]
+pub async fn export_transactions_handler(
+    State(state): State<ExportState>,
+    Query(params): Query<ExportParams>,
+    Json(filters): Json<GetTransactionsFilter>,
+) -> Result<Response, ApiError> {
+    export_response(state, ExportRequest::Transactions(filters), params.format).await
+}
+
+/// Export Blocks
+///
+/// Streams every block in the range, ordered by block number.
+#[utoipa::path(
+    post,
+    path = "/export/blocks",
+    params(ExportParams),
+    request_body = ExportBlocksFilter,
+    responses(
+        (status = 200, description = "Streamed export file (CSV, NDJSON or Parquet)", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid filter", body = GenericErrorResponse),
+        (status = 503, description = "Too many exports running", body = GenericErrorResponse)
+    )
+)]
+pub async fn export_blocks_handler(
+    State(state): State<ExportState>,
+    Query(params): Query<ExportParams>,
+    Json(filters): Json<ExportBlocksFilter>,
+) -> Result<Response, ApiError> {
+    export_response(state, ExportRequest::Blocks(filters), params.format).await
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/export/logs", post(export_logs_handler))
+        .route("/export/transactions", post(export_transactions_handler))
+        .route("/export/blocks", post(export_blocks_handler))
+        .with_state(ExportState {
+            pool,
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_EXPORTS)),
+        })
+}
+
+// ---------------------------------------------------------------------------
+// CLI
+// ---------------------------------------------------------------------------
+
+const CLI_USAGE: &str = "usage: evm_indexer export <logs|transactions|blocks> \
+    [--format csv|ndjson|parquet] [--from-block N] [--to-block N] \
+    [--blocks-per-file N] [--out-dir DIR] [--filter JSON]";
+
+struct CliOptions {
+    request: ExportRequest,
+    format: ExportFormat,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    blocks_per_file: u64,
+    out_dir: PathBuf,
+}
+
+fn parse_cli(args: &[String]) -> eyre::Result<CliOptions> {
+    let entity = args
+        .first()
+        .ok_or_else(|| eyre::eyre!("missing export target\n{}", CLI_USAGE))?;
+    let mut format = ExportFormat::Csv;
+    let mut from_block = None;
+    let mut to_block = None;
+    let mut blocks_per_file = DEFAULT_BLOCKS_PER_FILE;
+    let mut out_dir = PathBuf::from("export");
+    let mut filter = "{}".to_string();
+
+    let mut rest = args[1..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest
+            .next()
+            .ok_or_else(|| eyre::eyre!("missing value for {}\n{}", flag, CLI_USAGE))?;
+        match flag.as_str() {
+            "--format" => format = serde_json::from_value(serde_json::json!(value))?,
+            "--from-block" => from_block = Some(value.parse()?),
+            "--to-block" => to_block = Some(value.parse()?),
+            "--blocks-per-file" => blocks_per_file = value.parse::<u64>()?.max(1),
+            "--out-dir" => out_dir = PathBuf::from(value),
+            "--filter" => filter = value.clone(),
+            _ => eyre::bail!("unknown option {}\n{}", flag, CLI_USAGE),
+        }
+    }
+
+    let request = match entity.as_str() {
+        "logs" => ExportRequest::Logs(serde_json::from_str(&filter)?),
+        "transactions" => ExportRequest::Transactions(serde_json::from_str(&filter)?),
+        "blocks" => ExportRequest::Blocks(serde_json::from_str(&filter)?),
+        other => eyre::bail!("unknown export target {}\n{}", other, CLI_USAGE),
+    };
+    Ok(CliOptions {
+        request,
+        format,
+        from_block,
+        to_block,
+        blocks_per_file,
+        out_dir,
+    })
+}
+
+/// `evm_indexer export ...`: writes one file per `--blocks-per-file` block range into
+/// `--out-dir`, named `<entity>_<from>_<to>.<ext>` so a directory glob loads the whole set.
+/// The range defaults to everything indexed; filter ranges are replaced by the CLI range.
+pub async fn run_cli(pool: PgPool, args: &[String]) -> eyre::Result<()> {
+    let options = parse_cli(args)?;
+
+    let from_block = match options.from_block {
+        Some(b) => b,
+        None => sqlx::query_scalar::<_, Option<i64>>("SELECT MIN(block_number) FROM blocks")
+            .fetch_one(&pool)
+            .await?
+            .map_or(0, |b| b as u64),
+    };
+    let to_block = match options.to_block {
+        Some(b) => b,
+        None => crate::db::get_last_synced_block(&pool)
+            .await?
+            .ok_or_else(|| eyre::eyre!("nothing indexed yet; pass --to-block"))?,
+    };
+    if from_block > to_block {
+        eyre::bail!(
+            "--from-block {} is after --to-block {}",
+            from_block,
+            to_block
+        );
+    }
+    tokio::fs::create_dir_all(&options.out_dir).await?;
+
+    let mut start = from_block;
+    while start <= to_block {
+        let end = start
+            .saturating_add(options.blocks_per_file - 1)
+            .min(to_block);
+        let request = options.request.with_block_range(start, end);
+        let declare = request
+            .declare_cursor(&pool)
+            .await
+            .map_err(|e| eyre::eyre!("invalid export: {:?}", e))?;
+
+        let path = options.out_dir.join(request.file_name(options.format));
+        let mut file = tokio::fs::File::create(&path).await?;
+        let rows = run_export(&pool, declare, request.columns(), options.format, &mut file).await?;
+        file.sync_all().await?;
+        info!("EXPORT: wrote {} rows to {}", rows, path.display());
+
+        start = end + 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    const COLUMNS: &[Column] = &[
+        col("block_number", "block_number", ColumnKind::Int),
+        col("data", "data", ColumnKind::Text),
+    ];
+
+    fn row(number: Option<i64>, data: Option<&str>) -> Vec<Value> {
+        vec![Value::Int(number), Value::Text(data.map(str::to_string))]
+    }
+
+    /// Everything an encoder produces for `batches`, in order.
+    fn encode_all(format: ExportFormat, batches: &[Vec<Vec<Value>>]) -> Vec<u8> {
+        let mut encoder = encoder(format, COLUMNS).unwrap();
+        let mut out = Vec::new();
+        for batch in batches {
+            out.extend(encoder.encode(batch).unwrap());
+        }
+        out.extend(encoder.finish().unwrap());
+        out
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("0xabc"), "0xabc");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn csv_has_one_header_and_empty_nulls() {
+        let batches = [
+            vec![row(Some(1), Some("a,b")), row(None, None)],
+            vec![row(Some(2), Some("plain"))],
+        ];
+        let csv = String::from_utf8(encode_all(ExportFormat::Csv, &batches)).unwrap();
+        assert_eq!(csv, "block_number,data\n1,\"a,b\"\n,\n2,plain\n");
+
+        let empty = String::from_utf8(encode_all(ExportFormat::Csv, &[])).unwrap();
+        assert_eq!(empty, "block_number,data\n");
+    }
+
+    #[test]
+    fn parquet_output_reads_back_with_one_row_group_per_batch() {
+        let batches = [
+            vec![row(Some(1), Some("a,b")), row(None, None)],
+            vec![row(Some(2), Some("plain"))],
+        ];
+        let file = Bytes::from(encode_all(ExportFormat::Parquet, &batches));
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(builder.metadata().num_row_groups(), 2);
+        let fields: Vec<(&str, &DataType)> = builder
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name().as_str(), f.data_type()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("block_number", &DataType::Int64),
+                ("data", &DataType::Utf8)
+            ]
+        );
+
+        let mut numbers = Vec::new();
+        let mut data = Vec::new();
+        for batch in builder.build().unwrap() {
+            let batch = batch.unwrap();
+            numbers.extend(batch.column(0).as_primitive::<Int64Type>().iter());
+            data.extend(
+                batch
+                    .column(1)
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|v| v.map(str::to_string)),
+            );
+        }
+        assert_eq!(numbers, [Some(1), None, Some(2)]);
+        assert_eq!(
+            data,
+            [Some("a,b".to_string()), None, Some("plain".to_string())]
+        );
+    }
+
+    #[test]
+    fn parquet_with_no_rows_is_still_a_file() {
+        let file = Bytes::from(encode_all(ExportFormat::Parquet, &[]));
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(builder.metadata().file_metadata().num_rows(), 0);
+        assert_eq!(builder.schema().fields().len(), COLUMNS.len());
+    }
+}
@@ -0,0 +1,473 @@
+// src/gas.rs
+//
+// Gas and fee-market analytics. The transform stage rolls each block up into a `gas_blocks`
+// row: base fee, utilization and the gas-weighted percentiles of the priority fees its
+// transactions paid, computed the way `eth_feeHistory` computes `reward` (each transaction's
+// effective gas price minus the base fee, weighted by the gas it used). The writer stores it
+// in the block's transaction, recomputes the block's hour in `gas_hourly` from the block rows,
+// and adds the block's per-contract gas to `gas_contract_hourly`.
+//
+// The endpoints read only those tables. Daily history is summed from the hourly rows; its
+// percentiles are the block-weighted mean of the hourly ones, an approximation. A reorg
+// rollback deletes the block rows and rebuilds every hour from the fork onward.
+//
+// In selective indexing mode only the indexed transactions are known, so blocks get base fee
+// and utilization but no priority fees, and nothing is added to the contract rankings.
+
+use crate::api::ApiError;
+use crate::api_models::{
+    ContractGasUsage, GasContractsResponse, GasHistoryEntry, GasHistoryResponse, GasOracle,
+    GasSuggestion, GenericErrorResponse, GetGasContractsQuery, GetGasHistoryQuery,
+    GetGasOracleQuery,
+};
+use crate::models::{MyBlock, MyTransaction};
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::types::{Address, U256};
+use sqlx::{postgres::PgRow, PgPool, Row as SqlxRow};
+use std::collections::HashMap;
+
+/// Percentiles of the priority fee stored per block, as in `eth_feeHistory`'s `rewardPercentiles`.
+pub const PRIORITY_FEE_PERCENTILES: [u8; 5] = [10, 25, 50, 75, 90];
+pub const HOUR_SECONDS: i64 = 3600;
+const DAY_SECONDS: i64 = 86_400;
+/// Same cap as `eth_feeHistory`'s block count.
+const MAX_HISTORY_BUCKETS: u64 = 1024;
+const DEFAULT_HISTORY_BUCKETS: u64 = 100;
+const DEFAULT_ORACLE_BLOCKS: u64 = 20;
+const MAX_ORACLE_BLOCKS: u64 = 1024;
+const DEFAULT_CONTRACTS_WINDOW_SECONDS: i64 = DAY_SECONDS;
+/// EIP-1559: the base fee moves by at most 1/8 per block, towards a target of half the limit.
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// One block's fee-market figures, handed to the writer.
+#[derive(Clone, Debug)]
+pub struct GasBlock {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub base_fee_per_gas: Option<U256>,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    pub tx_count: usize,
+    /// Priority fees at `PRIORITY_FEE_PERCENTILES`; `None` when the block's transactions or
+    /// their receipts are incomplete.
+    pub priority_fees: Option<Vec<U256>>,
+    /// Gas spent calling each contract in this block.
+    pub contracts: Vec<ContractGas>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContractGas {
+    pub address: Address,
+    pub gas_used: U256,
+    /// `gas_used * effective_gas_price`, in wei.
+    pub fees_paid: U256,
+    pub tx_count: u64,
+}
+
+/// Rolls a block up. `all_transactions` is false in selective mode, where `transactions` is
+/// only the indexed subset.
+pub fn rollup_block(
+    block: &MyBlock,
+    transactions: &[MyTransaction],
+    all_transactions: bool,
+) -> GasBlock {
+    let base_fee = block.base_fee_per_gas;
+    // (priority fee, gas used) of every transaction, if every receipt was fetched.
+    let paid: Option<Vec<(U256, U256)>> = all_transactions
+        .then(|| {
+            transactions
+                .iter()
+                .map(|tx| {
+                    let price = tx.effective_gas_price?;
+                    let priority = base_fee.map_or(price, |base| price.saturating_sub(base));
+                    Some((priority, tx.gas_used?))
+                })
+                .collect()
+        })
+        .flatten();
+
+    let mut contracts: HashMap<Address, ContractGas> = HashMap::new();
+    if paid.is_some() {
+        // Plain ETH transfers carry no calldata and are left out of the rankings.
+        for tx in transactions.iter().filter(|tx| tx.input_data.len() > 2) {
+            let (Some(to), Some(gas_used), Some(price)) =
+                (tx.to_address, tx.gas_used, tx.effective_gas_price)
+            else {
+                continue;
+            };
+            let entry = contracts.entry(to).or_insert_with(|| ContractGas {
+                address: to,
+                gas_used: U256::zero(),
+                fees_paid: U256::zero(),
+                tx_count: 0,
+            });
+            entry.gas_used = entry.gas_used.saturating_add(gas_used);
+            entry.fees_paid = entry
+                .fees_paid
+                .saturating_add(gas_used.saturating_mul(price));
+            entry.tx_count += 1;
+        }
+    }
+
+    GasBlock {
+        block_number: block.block_number.as_u64(),
+        block_timestamp: block.timestamp.as_u64(),
+        base_fee_per_gas: base_fee,
+        gas_used: block.gas_used,
+        gas_limit: block.gas_limit,
+        tx_count: transactions.len(),
+        priority_fees: paid.map(weighted_percentiles),
+        contracts: contracts.into_values().collect(),
+    }
+}
+
+/// Gas-weighted percentiles, as `eth_feeHistory` computes rewards: walk the fees in ascending
+/// order until the gas seen covers the percentile of the block's gas. A block with no
+/// transactions reports zeros.
+fn weighted_percentiles(mut paid: Vec<(U256, U256)>) -> Vec<U256> {
+    if paid.is_empty() {
+        return vec![U256::zero(); PRIORITY_FEE_PERCENTILES.len()];
+    }
+    paid.sort_by_key(|(fee, _)| *fee);
+    let total = paid
+        .iter()
+        .fold(U256::zero(), |sum, (_, gas)| sum.saturating_add(*gas));
+    let mut fees = Vec::with_capacity(PRIORITY_FEE_PERCENTILES.len());
+    let mut index = 0;
+    let mut covered = paid[0].1;
+    for percentile in PRIORITY_FEE_PERCENTILES {
+        let threshold = total * U256::from(percentile) / U256::from(100);
+        while covered < threshold && index + 1 < paid.len() {
+            index += 1;
+            covered = covered.saturating_add(paid[index].1);
+        }
+        fees.push(paid[index].0);
+    }
+    fees
+}
+
+/// The next block's base fee under EIP-1559.
+fn next_base_fee(base_fee: U256, gas_used: U256, gas_limit: U256) -> U256 {
+    let target = gas_limit / U256::from(ELASTICITY_MULTIPLIER);
+    if target.is_zero() || gas_used == target {
+        return base_fee;
+    }
+    let denominator = target * U256::from(BASE_FEE_MAX_CHANGE_DENOMINATOR);
+    if gas_used > target {
+        let delta = base_fee * (gas_used - target) / denominator;
+        base_fee + delta.max(U256::one())
+    } else {
+        base_fee - base_fee * (target - gas_used) / denominator
+    }
+}
+
+fn parse_wei(value: Option<String>, column: &str) -> Result<Option<U256>, ApiError> {
+    value
+        .map(|v| {
+            U256::from_dec_str(&v).map_err(|e| {
+                ApiError::InternalServerError(format!("Invalid {} '{}': {}", column, v, e))
+            })
+        })
+        .transpose()
+}
+
+/// Upper median; `None` for an empty sample.
+fn median(mut values: Vec<U256>) -> Option<U256> {
+    values.sort();
+    values.get(values.len() / 2).copied()
+}
+
+const PRIORITY_FEE_COLUMNS: &str = "priority_fee_p10::TEXT AS p10, priority_fee_p25::TEXT AS p25, \
+     priority_fee_p50::TEXT AS p50, priority_fee_p75::TEXT AS p75, priority_fee_p90::TEXT AS p90";
+
+fn row_to_priority_fees(row: &PgRow) -> Result<Option<Vec<String>>, ApiError> {
+    let fees = ["p10", "p25", "p50", "p75", "p90"]
+        .iter()
+        .map(|column| row.try_get::<Option<String>, _>(*column))
+        .collect::<Result<Option<Vec<_>>, _>>()?;
+    Ok(fees)
+}
+
+fn row_to_history_entry(row: &PgRow) -> Result<GasHistoryEntry, ApiError> {
+    Ok(GasHistoryEntry {
+        bucket: row.try_get("bucket")?,
+        first_block: row.try_get("first_block")?,
+        last_block: row.try_get("last_block")?,
+        blocks: row.try_get("blocks")?,
+        base_fee_per_gas: row.try_get("base_fee")?,
+        base_fee_min: row.try_get("base_fee_min")?,
+        base_fee_max: row.try_get("base_fee_max")?,
+        gas_used: row.try_get("gas_used")?,
+        utilization: row.try_get("utilization")?,
+        tx_count: row.try_get("tx_count")?,
+        priority_fees: row_to_priority_fees(row)?,
+    })
+}
+
+/// Gas History
+///
+/// Base fee, utilization and priority-fee percentiles per block, hour or day, oldest first,
+/// ending at `toBlock` (block resolution) or the bucket containing `toTime`.
+#[utoipa::path(
+    get,
+    path = "/gas/history",
+    params(GetGasHistoryQuery),
+    responses(
+        (status = 200, description = "Fee history", body = GasHistoryResponse),
+        (status = 400, description = "Invalid resolution or range", body = GenericErrorResponse)
+    )
+)]
+pub async fn gas_history_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetGasHistoryQuery>,
+) -> Result<Json<GasHistoryResponse>, ApiError> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_BUCKETS)
+        .clamp(1, MAX_HISTORY_BUCKETS) as i64;
+    let resolution = params.resolution.as_deref().unwrap_or("block");
+    let rows = match resolution {
+        "block" => {
+            if params.to_time.is_some() {
+                return Err(ApiError::BadRequest(
+                    "toTime applies to the hour and day resolutions; use toBlock.".to_string(),
+                ));
+            }
+            sqlx::query(&format!(
+                "SELECT * FROM ( \
+                     SELECT block_number AS bucket, block_number AS first_block, \
+                            block_number AS last_block, 1::BIGINT AS blocks, \
+                            base_fee_per_gas::TEXT AS base_fee, \
+                            base_fee_per_gas::TEXT AS base_fee_min, \
+                            base_fee_per_gas::TEXT AS base_fee_max, gas_used::TEXT AS gas_used, \
+                            utilization, tx_count::BIGINT AS tx_count, {} \
+                     FROM gas_blocks WHERE block_number <= COALESCE($1, block_number) \
+                     ORDER BY block_number DESC LIMIT $2 \
+                 ) b ORDER BY bucket",
+                PRIORITY_FEE_COLUMNS
+            ))
+            .bind(params.to_block)
+            .bind(limit)
+            .fetch_all(&pool)
+            .await?
+        }
+        "hour" | "day" => {
+            if params.to_block.is_some() {
+                return Err(ApiError::BadRequest(
+                    "toBlock applies to the block resolution; use toTime.".to_string(),
+                ));
+            }
+            let seconds = if resolution == "hour" {
+                HOUR_SECONDS
+            } else {
+                DAY_SECONDS
+            };
+            let weighted = |column: &str| {
+                format!(
+                    "ROUND(SUM({c} * blocks) / NULLIF(SUM(blocks) FILTER (WHERE {c} IS NOT NULL), 0))",
+                    c = column
+                )
+            };
+            sqlx::query(&format!(
+                "SELECT * FROM ( \
+                     SELECT bucket / $1 * $1 AS bucket, MIN(first_block) AS first_block, \
+                            MAX(last_block) AS last_block, SUM(blocks)::BIGINT AS blocks, \
+                            {}::TEXT AS base_fee, MIN(base_fee_min)::TEXT AS base_fee_min, \
+                            MAX(base_fee_max)::TEXT AS base_fee_max, \
+                            SUM(gas_used)::TEXT AS gas_used, \
+                            SUM(utilization * blocks) / SUM(blocks) AS utilization, \
+                            SUM(tx_count)::BIGINT AS tx_count, \
+                            {}::TEXT AS p10, {}::TEXT AS p25, {}::TEXT AS p50, \
+                            {}::TEXT AS p75, {}::TEXT AS p90 \
+                     FROM gas_hourly WHERE $2::BIGINT IS NULL OR bucket / $1 * $1 <= $2 \
+                     GROUP BY 1 ORDER BY 1 DESC LIMIT $3 \
+                 ) b ORDER BY bucket",
+                weighted("base_fee_avg"),
+                weighted("priority_fee_p10"),
+                weighted("priority_fee_p25"),
+                weighted("priority_fee_p50"),
+                weighted("priority_fee_p75"),
+                weighted("priority_fee_p90"),
+            ))
+            .bind(seconds)
+            .bind(params.to_time)
+            .bind(limit)
+            .fetch_all(&pool)
+            .await?
+        }
+        other => {
+            return Err(ApiError::BadRequest(format!(
+                "Invalid resolution '{}'; expected block, hour or day.",
+                other
+            )))
+        }
+    };
+    Ok(Json(GasHistoryResponse {
+        resolution: resolution.to_string(),
+        reward_percentiles: PRIORITY_FEE_PERCENTILES.to_vec(),
+        entries: rows
+            .iter()
+            .map(row_to_history_entry)
+            .collect::<Result<_, _>>()?,
+    }))
+}
+
+/// Gas Oracle
+///
+/// Suggested fees for the next block: the EIP-1559 base fee projected from the latest indexed
+/// block, and priority fees taken as the median over recent blocks of each block's 10th
+/// (slow), 50th (standard) and 90th (fast) percentile.
+#[utoipa::path(
+    get,
+    path = "/gas/oracle",
+    params(GetGasOracleQuery),
+    responses(
+        (status = 200, description = "Fee suggestions", body = GasOracle),
+        (status = 404, description = "No gas data indexed yet", body = GenericErrorResponse)
+    )
+)]
+pub async fn gas_oracle_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetGasOracleQuery>,
+) -> Result<Json<GasOracle>, ApiError> {
+    let blocks = params
+        .blocks
+        .unwrap_or(DEFAULT_ORACLE_BLOCKS)
+        .clamp(1, MAX_ORACLE_BLOCKS) as i64;
+    let rows = sqlx::query(&format!(
+        "SELECT block_number, base_fee_per_gas::TEXT AS base_fee, gas_used::TEXT AS gas_used, \
+                gas_limit::TEXT AS gas_limit, {} \
+         FROM gas_blocks ORDER BY block_number DESC LIMIT $1",
+        PRIORITY_FEE_COLUMNS
+    ))
+    .bind(blocks)
+    .fetch_all(&pool)
+    .await?;
+    let latest = rows
+        .first()
+        .ok_or_else(|| ApiError::NotFound("No gas data indexed yet.".to_string()))?;
+
+    let base_fee = parse_wei(latest.try_get("base_fee")?, "base_fee_per_gas")?;
+    let next_base_fee = match base_fee {
+        Some(base_fee) => {
+            let gas_used = parse_wei(latest.try_get("gas_used")?, "gas_used")?.unwrap_or_default();
+            let gas_limit =
+                parse_wei(latest.try_get("gas_limit")?, "gas_limit")?.unwrap_or_default();
+            Some(next_base_fee(base_fee, gas_used, gas_limit))
+        }
+        None => None,
+    };
+    let mut samples: [Vec<U256>; 3] = Default::default();
+    let mut sampled = 0;
+    for row in &rows {
+        let Some(fees) = row_to_priority_fees(row)? else {
+            continue;
+        };
+        sampled += 1;
+        for (sample, column) in samples.iter_mut().zip([0, 2, 4]) {
+            if let Some(fee) = parse_wei(fees.get(column).cloned(), "priority fee")? {
+                sample.push(fee);
+            }
+        }
+    }
+    // Twice the projected base fee leaves room for six full blocks in a row.
+    let suggestion = |sample: Vec<U256>| {
+        median(sample).map(|priority| GasSuggestion {
+            max_priority_fee_per_gas: priority.to_string(),
+            max_fee_per_gas: next_base_fee
+                .map_or(priority, |base| base * U256::from(2) + priority)
+                .to_string(),
+        })
+    };
+    let [slow, standard, fast] = samples;
+
+    Ok(Json(GasOracle {
+        block_number: latest.try_get("block_number")?,
+        base_fee_per_gas: base_fee.map(|fee| fee.to_string()),
+        next_base_fee_per_gas: next_base_fee.map(|fee| fee.to_string()),
+        sampled_blocks: sampled,
+        slow: suggestion(slow),
+        standard: suggestion(standard),
+        fast: suggestion(fast),
+    }))
+}
+
+/// Gas by Contract
+///
+/// Contracts ranked by the gas their callers spent on them over a time window, in whole hours.
+/// The window defaults to the 24 hours up to the latest indexed block.
+#[utoipa::path(
+    get,
+    path = "/gas/contracts",
+    params(GetGasContractsQuery),
+    responses(
+        (status = 200, description = "Ranking", body = GasContractsResponse),
+        (status = 400, description = "Invalid window", body = GenericErrorResponse)
+    )
+)]
+pub async fn gas_contracts_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetGasContractsQuery>,
+) -> Result<Json<GasContractsResponse>, ApiError> {
+    let limit = params
+        .limit
+        .unwrap_or(crate::api::MAX_PAGE_SIZE)
+        .clamp(1, crate::api::MAX_PAGE_SIZE) as i64;
+    let to_time = match params.to_time {
+        Some(to_time) => to_time,
+        None => sqlx::query_scalar::<_, Option<i64>>("SELECT MAX(bucket) FROM gas_hourly")
+            .fetch_one(&pool)
+            .await?
+            .map_or(0, |bucket| bucket + HOUR_SECONDS),
+    };
+    let from_time = params
+        .from_time
+        .unwrap_or(to_time - DEFAULT_CONTRACTS_WINDOW_SECONDS);
+    if from_time >= to_time {
+        return Err(ApiError::BadRequest(
+            "fromTime must be before toTime.".to_string(),
+        ));
+    }
+    // Buckets are hours starting at their key; include every hour overlapping the window.
+    let rows = sqlx::query(
+        "SELECT contract_address, SUM(gas_used)::TEXT AS gas_used, \
+                SUM(fees_paid)::TEXT AS fees_paid, SUM(tx_count)::BIGINT AS tx_count \
+         FROM gas_contract_hourly WHERE bucket > $1 - $4 AND bucket < $2 \
+         GROUP BY contract_address ORDER BY SUM(gas_used) DESC, contract_address LIMIT $3",
+    )
+    .bind(from_time)
+    .bind(to_time)
+    .bind(limit)
+    .bind(HOUR_SECONDS)
+    .fetch_all(&pool)
+    .await?;
+    Ok(Json(GasContractsResponse {
+        from_time,
+        to_time,
+        contracts: rows
+            .iter()
+            .map(|row| {
+                Ok(ContractGasUsage {
+                    address: row.try_get("contract_address")?,
+                    gas_used: row.try_get("gas_used")?,
+                    fees_paid: row.try_get("fees_paid")?,
+                    tx_count: row.try_get("tx_count")?,
+                })
+            })
+            .collect::<Result<_, ApiError>>()?,
+    }))
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/gas/history", get(gas_history_handler))
+        .route("/gas/oracle", get(gas_oracle_handler))
+        .route("/gas/contracts", get(gas_contracts_handler))
+        .with_state(pool)
+}
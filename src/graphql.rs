@@ -0,0 +1,618 @@
+// src/graphql.rs
+//
+// GraphQL API over blocks, transactions and logs, served next to the REST router.
+// Relations (block -> transactions -> logs and back) resolve lazily, so clients fetch a
+// transaction, its block and its logs in one round trip. Depth and complexity limits keep
+// deeply nested or very wide queries from fanning out into expensive Postgres work.
+
+use crate::api::{
+    fetch_block_row, push_log_filters, push_transaction_filters, row_to_block, row_to_log,
+    row_to_transaction, ApiError, BLOCK_COLUMNS, LOG_COLUMNS, MAX_PAGE_SIZE, TRANSACTION_COLUMNS,
+};
+use crate::api_models::{
+    BlockTag, GenericErrorResponse, GetLogsFilter, GetTransactionsFilter, OneOrMany,
+};
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use async_graphql::{
+    connection::{Connection, Edge},
+    http::GraphiQLSource,
+    BatchRequest, BatchResponse, ComplexObject, Context, EmptyMutation, EmptySubscription,
+    InputObject, Object, Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    response::{Html, Json},
+    routing::get,
+    Router,
+};
+use sqlx::{PgPool, QueryBuilder, Row as SqlxRow};
+use std::str::FromStr;
+
+const GRAPHQL_MAX_DEPTH: usize = 8;
+const GRAPHQL_MAX_COMPLEXITY: usize = 5_000;
+// The depth and complexity limits apply per query, so batches are capped like JSON-RPC ones.
+const GRAPHQL_MAX_BATCH_SIZE: usize = 100;
+const DEFAULT_CONNECTION_SIZE: i32 = 25;
+// Complexity multiplier for unpaginated nested lists (a block's transactions or logs).
+const NESTED_LIST_COMPLEXITY: usize = 50;
+
+pub type IndexerSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+type GqlResult<T> = async_graphql::Result<T>;
+
+/// Converts API errors into GraphQL errors without leaking database internals.
+fn gql_error(err: impl Into<ApiError>) -> async_graphql::Error {
+    match err.into() {
+        ApiError::DatabaseError(db_err) => {
+            tracing::error!("GraphQL database error: {:?}", db_err);
+            async_graphql::Error::new("A database error occurred")
+        }
+        ApiError::NotFound(msg)
+        | ApiError::BadRequest(msg)
+        | ApiError::InternalServerError(msg) => async_graphql::Error::new(msg),
+    }
+}
+
+fn page_size(first: Option<i32>) -> i64 {
+    (first.unwrap_or(DEFAULT_CONNECTION_SIZE).max(1) as i64).min(MAX_PAGE_SIZE as i64)
+}
+
+/// Parses an opaque `a:b` cursor produced by one of the connections below.
+fn parse_cursor(cursor: &str) -> GqlResult<(i64, i64)> {
+    cursor
+        .split_once(':')
+        .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+        .ok_or_else(|| async_graphql::Error::new(format!("Invalid cursor: {}", cursor)))
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Block {
+    pub number: u64,
+    pub hash: String,
+    pub parent_hash: String,
+    pub timestamp: u64,
+    pub gas_used: String,
+    pub gas_limit: String,
+    pub base_fee_per_gas: Option<String>,
+}
+
+impl From<MyBlock> for Block {
+    fn from(b: MyBlock) -> Self {
+        Block {
+            number: b.block_number.as_u64(),
+            hash: format!("{:#x}", b.block_hash),
+            parent_hash: format!("{:#x}", b.parent_hash),
+            timestamp: b.timestamp.as_u64(),
+            gas_used: b.gas_used.to_string(),
+            gas_limit: b.gas_limit.to_string(),
+            base_fee_per_gas: b.base_fee_per_gas.map(|v| v.to_string()),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Block {
+    /// Transactions in this block, ordered by index.
+    #[graphql(complexity = "NESTED_LIST_COMPLEXITY * child_complexity")]
+    async fn transactions(&self, ctx: &Context<'_>) -> GqlResult<Vec<Transaction>> {
+        transactions_by(ctx.data::<PgPool>()?, "block_hash", &self.hash).await
+    }
+
+    /// Logs emitted in this block, in emission order.
+    #[graphql(complexity = "NESTED_LIST_COMPLEXITY * child_complexity")]
+    async fn logs(&self, ctx: &Context<'_>) -> GqlResult<Vec<Log>> {
+        logs_by(ctx.data::<PgPool>()?, "block_hash", &self.hash).await
+    }
+
+    /// The parent block, if it has been indexed.
+    async fn parent(&self, ctx: &Context<'_>) -> GqlResult<Option<Block>> {
+        block_by_identifier(ctx.data::<PgPool>()?, &self.parent_hash).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Transaction {
+    pub hash: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub transaction_index: Option<u64>,
+    pub from: String,
+    pub to: Option<String>,
+    pub value: String,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub gas: String,
+    pub input: String,
+    pub status: Option<u64>,
+    pub gas_used: Option<String>,
+    pub effective_gas_price: Option<String>,
+}
+
+impl From<MyTransaction> for Transaction {
+    fn from(tx: MyTransaction) -> Self {
+        Transaction {
+            hash: format!("{:#x}", tx.tx_hash),
+            block_number: tx.block_number.as_u64(),
+            block_hash: format!("{:#x}", tx.block_hash),
+            transaction_index: tx.transaction_index.map(|i| i.as_u64()),
+            from: format!("{:#x}", tx.from_address),
+            to: tx.to_address.map(|a| format!("{:#x}", a)),
+            value: tx.value.to_string(),
+            gas_price: tx.gas_price.map(|v| v.to_string()),
+            max_fee_per_gas: tx.max_fee_per_gas.map(|v| v.to_string()),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas.map(|v| v.to_string()),
+            gas: tx.gas.to_string(),
+            input: tx.input_data,
+            status: tx.status,
+            gas_used: tx.gas_used.map(|v| v.to_string()),
+            effective_gas_price: tx.effective_gas_price.map(|v| v.to_string()),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Transaction {
+    /// The block containing this transaction.
+    async fn block(&self, ctx: &Context<'_>) -> GqlResult<Option<Block>> {
+        block_by_identifier(ctx.data::<PgPool>()?, &self.block_hash).await
+    }
+
+    /// Logs emitted by this transaction, in emission order.
+    #[graphql(complexity = "NESTED_LIST_COMPLEXITY * child_complexity")]
+    async fn logs(&self, ctx: &Context<'_>) -> GqlResult<Vec<Log>> {
+        logs_by(ctx.data::<PgPool>()?, "transaction_hash", &self.hash).await
+    }
+}
+
+#[derive(SimpleObject, Clone)]
+#[graphql(complex)]
+pub struct Log {
+    pub log_index: Option<u64>,
+    pub transaction_hash: String,
+    pub transaction_index: Option<u64>,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub address: String,
+    pub data: String,
+    pub topics: Vec<String>,
+}
+
+impl From<MyLog> for Log {
+    fn from(log: MyLog) -> Self {
+        Log {
+            log_index: log.log_index.map(|i| i.as_u64()),
+            transaction_hash: format!("{:#x}", log.transaction_hash),
+            transaction_index: log.transaction_index,
+            block_number: log.block_number,
+            block_hash: format!("{:#x}", log.block_hash),
+            address: format!("{:#x}", log.address),
+            data: log.data,
+            topics: log.topics,
+        }
+    }
+}
+
+#[ComplexObject]
+impl Log {
+    /// The block this log was emitted in.
+    async fn block(&self, ctx: &Context<'_>) -> GqlResult<Option<Block>> {
+        block_by_identifier(ctx.data::<PgPool>()?, &self.block_hash).await
+    }
+
+    /// The transaction that emitted this log.
+    async fn transaction(&self, ctx: &Context<'_>) -> GqlResult<Option<Transaction>> {
+        let pool = ctx.data::<PgPool>()?;
+        Ok(transactions_by(pool, "tx_hash", &self.transaction_hash)
+            .await?
+            .into_iter()
+            .next())
+    }
+}
+
+/// Mirrors `GetLogsFilter`: addresses and each topic position are OR-lists,
+/// block bounds accept numbers or `latest`/`safe`/`finalized`.
+#[derive(InputObject, Default)]
+pub struct LogFilter {
+    pub from_block: Option<String>,
+    pub to_block: Option<String>,
+    pub address: Option<Vec<String>>,
+    pub topics: Option<Vec<Option<Vec<String>>>>,
+    pub block_hash: Option<String>,
+}
+
+impl LogFilter {
+    fn into_logs_filter(self) -> GqlResult<GetLogsFilter> {
+        let parse_tag = |tag: Option<String>| -> GqlResult<Option<BlockTag>> {
+            tag.map(|t| BlockTag::from_str(&t).map_err(async_graphql::Error::new))
+                .transpose()
+        };
+        Ok(GetLogsFilter {
+            from_block: parse_tag(self.from_block)?,
+            to_block: parse_tag(self.to_block)?,
+            from_timestamp: None,
+            to_timestamp: None,
+            address: self.address.map(OneOrMany::Many),
+            topics: self
+                .topics
+                .map(|ts| ts.into_iter().map(|t| t.map(OneOrMany::Many)).collect()),
+            topic0: None,
+            topic1: None,
+            topic2: None,
+            topic3: None,
+            block_hash: self.block_hash,
+            page: 1,
+            page_size: DEFAULT_CONNECTION_SIZE as u64,
+            cursor_block: None,
+            cursor_log_id: None,
+            include_ens_names: false,
+        })
+    }
+}
+
+/// Mirrors `GetTransactionsFilter`.
+#[derive(InputObject, Default)]
+pub struct TransactionFilter {
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub status: Option<u64>,
+    pub min_value: Option<String>,
+    pub max_value: Option<String>,
+    pub method_id: Option<String>,
+    pub contract_creation: Option<bool>,
+}
+
+impl From<TransactionFilter> for GetTransactionsFilter {
+    fn from(f: TransactionFilter) -> Self {
+        GetTransactionsFilter {
+            from_block: f.from_block,
+            to_block: f.to_block,
+            from_timestamp: None,
+            to_timestamp: None,
+            from: f.from,
+            to: f.to,
+            status: f.status,
+            min_value: f.min_value,
+            max_value: f.max_value,
+            method_id: f.method_id,
+            contract_creation: f.contract_creation,
+            page: 1,
+            page_size: DEFAULT_CONNECTION_SIZE as u64,
+            cursor_block: None,
+            cursor_tx_index: None,
+            include_usd: false,
+            include_ens_names: false,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Looks up a block by number or hash.
+    async fn block(
+        &self,
+        ctx: &Context<'_>,
+        number: Option<u64>,
+        hash: Option<String>,
+    ) -> GqlResult<Option<Block>> {
+        let identifier = match (number, hash) {
+            (Some(n), None) => n.to_string(),
+            (None, Some(h)) => h,
+            _ => return Err("Provide exactly one of `number` or `hash`".into()),
+        };
+        block_by_identifier(ctx.data::<PgPool>()?, &identifier).await
+    }
+
+    /// Blocks newest-first, optionally restricted to a range.
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> GqlResult<Connection<String, Block>> {
+        let pool = ctx.data::<PgPool>()?;
+        let limit = page_size(first);
+
+        let mut query_builder: QueryBuilder<sqlx::Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM blocks WHERE 1=1", BLOCK_COLUMNS));
+        if let Some(fb) = from_block {
+            query_builder.push(" AND block_number >= ");
+            query_builder.push_bind(fb as i64);
+        }
+        if let Some(tb) = to_block {
+            query_builder.push(" AND block_number <= ");
+            query_builder.push_bind(tb as i64);
+        }
+        if let Some(after) = &after {
+            let (cursor_block, _) = parse_cursor(after)?;
+            query_builder.push(" AND block_number < ");
+            query_builder.push_bind(cursor_block);
+        }
+        query_builder.push(" ORDER BY block_number DESC LIMIT ");
+        query_builder.push_bind(limit + 1);
+
+        let rows = query_builder
+            .build()
+            .fetch_all(pool)
+            .await
+            .map_err(gql_error)?;
+        let mut connection = Connection::new(after.is_some(), rows.len() as i64 > limit);
+        for row in rows.iter().take(limit as usize) {
+            let block = row_to_block(row).map_err(gql_error)?;
+            let cursor = format!("{}:0", block.block_number);
+            connection.edges.push(Edge::new(cursor, block.into()));
+        }
+        Ok(connection)
+    }
+
+    /// Looks up a transaction by hash.
+    async fn transaction(&self, ctx: &Context<'_>, hash: String) -> GqlResult<Option<Transaction>> {
+        let pool = ctx.data::<PgPool>()?;
+        Ok(transactions_by(pool, "tx_hash", &hash)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// Transactions matching `filter`, ordered by (block, index).
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TransactionFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> GqlResult<Connection<String, Transaction>> {
+        let pool = ctx.data::<PgPool>()?;
+        let limit = page_size(first);
+        let filter: GetTransactionsFilter = filter.unwrap_or_default().into();
+
+        let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new(format!(
+            "SELECT {} FROM transactions WHERE 1=1",
+            TRANSACTION_COLUMNS
+        ));
+        push_transaction_filters(&mut query_builder, &filter).map_err(gql_error)?;
+        if let Some(after) = &after {
+            let (cursor_block, cursor_index) = parse_cursor(after)?;
+            query_builder.push(" AND (block_number, transaction_index) > (");
+            query_builder.push_bind(cursor_block);
+            query_builder.push(", ");
+            query_builder.push_bind(cursor_index);
+            query_builder.push(")");
+        }
+        query_builder.push(" ORDER BY block_number ASC, transaction_index ASC LIMIT ");
+        query_builder.push_bind(limit + 1);
+
+        let rows = query_builder
+            .build()
+            .fetch_all(pool)
+            .await
+            .map_err(gql_error)?;
+        let mut connection = Connection::new(after.is_some(), rows.len() as i64 > limit);
+        for row in rows.iter().take(limit as usize) {
+            let tx = row_to_transaction(row).map_err(gql_error)?;
+            let cursor = format!(
+                "{}:{}",
+                tx.block_number,
+                tx.transaction_index.map_or(-1, |i| i.as_u64() as i64)
+            );
+            connection.edges.push(Edge::new(cursor, tx.into()));
+        }
+        Ok(connection)
+    }
+
+    /// Logs matching `filter`, ordered by (block, id).
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn logs(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<LogFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> GqlResult<Connection<String, Log>> {
+        let pool = ctx.data::<PgPool>()?;
+        let limit = page_size(first);
+        let filter = filter.unwrap_or_default().into_logs_filter()?;
+
+        let mut query_builder: QueryBuilder<sqlx::Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM logs WHERE 1=1", LOG_COLUMNS));
+        push_log_filters(pool, &mut query_builder, &filter)
+            .await
+            .map_err(gql_error)?;
+        if let Some(after) = &after {
+            let (cursor_block, cursor_id) = parse_cursor(after)?;
+            query_builder.push(" AND (block_number, id) > (");
+            query_builder.push_bind(cursor_block);
+            query_builder.push(", ");
+            query_builder.push_bind(cursor_id);
+            query_builder.push(")");
+        }
+        query_builder.push(" ORDER BY block_number ASC, id ASC LIMIT ");
+        query_builder.push_bind(limit + 1);
+
+        let rows = query_builder
+            .build()
+            .fetch_all(pool)
+            .await
+            .map_err(gql_error)?;
+        let mut connection = Connection::new(after.is_some(), rows.len() as i64 > limit);
+        for row in rows.iter().take(limit as usize) {
+            let log = row_to_log(row).map_err(gql_error)?;
+            let id: i64 = SqlxRow::try_get(row, "id").map_err(gql_error)?;
+            let cursor = format!("{}:{}", log.block_number, id);
+            connection.edges.push(Edge::new(cursor, log.into()));
+        }
+        Ok(connection)
+    }
+}
+
+async fn block_by_identifier(pool: &PgPool, identifier: &str) -> GqlResult<Option<Block>> {
+    match fetch_block_row(pool, identifier).await {
+        Ok(row) => Ok(Some(row_to_block(&row).map_err(gql_error)?.into())),
+        Err(ApiError::NotFound(_)) => Ok(None),
+        Err(e) => Err(gql_error(e)),
+    }
+}
+
+async fn transactions_by(pool: &PgPool, column: &str, value: &str) -> GqlResult<Vec<Transaction>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM transactions WHERE {} = $1 ORDER BY transaction_index ASC",
+        TRANSACTION_COLUMNS, column
+    ))
+    .bind(value.to_lowercase())
+    .fetch_all(pool)
+    .await
+    .map_err(gql_error)?;
+    rows.iter()
+        .map(|row| row_to_transaction(row).map(Into::into))
+        .collect::<Result<Vec<_>, ApiError>>()
+        .map_err(gql_error)
+}
+
+async fn logs_by(pool: &PgPool, column: &str, value: &str) -> GqlResult<Vec<Log>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM logs WHERE {} = $1 \
+         ORDER BY transaction_index_in_block ASC, log_index_in_tx ASC, id ASC",
+        LOG_COLUMNS, column
+    ))
+    .bind(value.to_lowercase())
+    .fetch_all(pool)
+    .await
+    .map_err(gql_error)?;
+    rows.iter()
+        .map(|row| row_to_log(row).map(Into::into))
+        .collect::<Result<Vec<_>, ApiError>>()
+        .map_err(gql_error)
+}
+
+/// GraphQL
+///
+/// Executes a GraphQL query (or batch) over blocks, transactions and logs.
+/// `GET /graphql` serves the GraphiQL explorer with the full schema.
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    request_body(content = Object, description = "A GraphQL request or batch"),
+    responses(
+        (status = 200, description = "GraphQL response", body = Object),
+        (status = 400, description = "Batch is empty or too large", body = GenericErrorResponse)
+    )
+)]
+pub async fn graphql_handler(
+    State(schema): State<IndexerSchema>,
+    Json(request): Json<BatchRequest>,
+) -> Result<Json<BatchResponse>, ApiError> {
+    if let BatchRequest::Batch(requests) = &request {
+        if requests.is_empty() || requests.len() > GRAPHQL_MAX_BATCH_SIZE {
+            return Err(ApiError::BadRequest(format!(
+                "Batch must contain 1 to {} requests",
+                GRAPHQL_MAX_BATCH_SIZE
+            )));
+        }
+    }
+    Ok(Json(schema.execute_batch(request).await))
+}
+
+async fn graphiql_handler() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+pub fn build_schema(pool: PgPool) -> IndexerSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .limit_depth(GRAPHQL_MAX_DEPTH)
+        .limit_complexity(GRAPHQL_MAX_COMPLEXITY)
+        .finish()
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/graphql", get(graphiql_handler).post(graphql_handler))
+        .with_state(build_schema(pool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::Request;
+
+    // Validation runs before any resolver, and the queries that pass it fail on their
+    // arguments before a connection is needed.
+    fn schema() -> IndexerSchema {
+        build_schema(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+    }
+
+    async fn errors(query: &str) -> Vec<String> {
+        let response = schema().execute(query).await;
+        response.errors.into_iter().map(|e| e.message).collect()
+    }
+
+    /// `block` with `parents` levels of `parent` below it: a query `parents + 2` deep.
+    fn nested_parents(parents: usize) -> String {
+        format!(
+            "{{ block {{ {}number{} }} }}",
+            "parent { ".repeat(parents),
+            " }".repeat(parents)
+        )
+    }
+
+    #[tokio::test]
+    async fn queries_deeper_than_the_limit_are_refused() {
+        let at_limit = errors(&nested_parents(GRAPHQL_MAX_DEPTH - 2)).await;
+        assert_eq!(at_limit, ["Provide exactly one of `number` or `hash`"]);
+
+        let too_deep = errors(&nested_parents(GRAPHQL_MAX_DEPTH - 1)).await;
+        assert_eq!(too_deep, ["Query is nested too deep."]);
+    }
+
+    #[tokio::test]
+    async fn queries_over_the_complexity_limit_are_refused() {
+        // Each block costs its transactions list (50 x 1) plus the edge and node fields.
+        let query = |first: u64| {
+            format!(
+                r#"{{ blocks(first: {}, after: "bad") {{ edges {{ node {{ transactions {{ hash }} }} }} }} }}"#,
+                first
+            )
+        };
+        let within = errors(&query(50)).await;
+        assert_eq!(within, ["Invalid cursor: bad"]);
+
+        let too_complex = errors(&query(MAX_PAGE_SIZE)).await;
+        assert_eq!(too_complex, ["Query is too complex."]);
+    }
+
+    #[tokio::test]
+    async fn batches_must_hold_one_to_max_requests() {
+        let batch = |len: usize| {
+            BatchRequest::Batch(
+                (0..len)
+                    .map(|_| Request::new("{ block { number } }"))
+                    .collect(),
+            )
+        };
+        for len in [0, GRAPHQL_MAX_BATCH_SIZE + 1] {
+            let result = graphql_handler(State(schema()), Json(batch(len))).await;
+            assert!(
+                matches!(result, Err(ApiError::BadRequest(_))),
+                "batch of {}",
+                len
+            );
+        }
+
+        let Ok(Json(BatchResponse::Batch(responses))) =
+            graphql_handler(State(schema()), Json(batch(GRAPHQL_MAX_BATCH_SIZE))).await
+        else {
+            panic!("a full batch should run");
+        };
+        assert_eq!(responses.len(), GRAPHQL_MAX_BATCH_SIZE);
+    }
+}
@@ -0,0 +1,980 @@
+// src/handlers.rs
+//
+// User-defined event handlers. A handler pairs a contract address and an event signature with
+// a Rhai script defining `fn handle(event)`. For every matching log the ingester writes, the
+// script receives the decoded event and can `load`, `upsert` and `remove` rows in its own
+// entity tables. Entity rows are versioned by block number and written inside the block's DB
+// transaction, so a failed block leaves no trace and a reorg rollback deletes the versions
+// written at or above the fork height, exposing the previous ones again.
+//
+// Scripts are synchronous, so a block's scripts run on a blocking thread; their `load` calls
+// are answered by the writer task, which queries Postgres asynchronously meanwhile.
+//
+// A script that throws (or exceeds its operation budget) is marked failed and skipped from
+// then on; ingestion itself never stops because of a handler. Fixing the script with PUT
+// resumes it from the next block.
+
+use crate::api::{normalize_hex_values, ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{
+    EventHandler, EventHandlerRequest, GenericErrorResponse, GetHandlerEntitiesQuery, HandlerEntity,
+};
+use crate::models::MyLog;
+use crate::storage::StorageTransaction;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post, put},
+    Router,
+};
+use ethers::abi::{Event, HumanReadableParser, RawLog, Token};
+use ethers::types::{H256, I256};
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use sqlx::{postgres::PgRow, PgPool, Row as SqlxRow};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+/// Rhai operations a single `handle` call may perform before it is aborted.
+const MAX_SCRIPT_OPERATIONS: u64 = 1_000_000;
+const MAX_ENTITY_NAME_LEN: usize = 64;
+const MAX_ENTITY_KEY_LEN: usize = 256;
+
+const HANDLER_COLUMNS: &str = "id, name, address, event_signature, topic0, enabled, \
+     failed_at_block, last_error, EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at";
+
+// ---------------------------------------------------------------------------
+// Registration helpers
+// ---------------------------------------------------------------------------
+
+/// Parses `Transfer(address indexed from, address indexed to, uint256 value)`, with or without
+/// a leading `event` keyword.
+fn parse_event_signature(signature: &str) -> Result<Event, String> {
+    let signature = signature.trim();
+    let declaration = if signature.starts_with("event ") {
+        signature.to_string()
+    } else {
+        format!("event {}", signature)
+    };
+    let event = HumanReadableParser::parse_event(&declaration).map_err(|e| e.to_string())?;
+    if event.anonymous {
+        return Err("anonymous events have no topic0 and cannot be matched".to_string());
+    }
+    Ok(event)
+}
+
+/// A fresh engine with resource limits and the helper functions every script can use.
+/// The entity functions are added per handler in `HandlerRuntime::session`.
+fn base_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    engine.set_max_call_levels(32);
+    engine.set_max_string_size(1 << 20);
+    engine.set_max_array_size(1 << 16);
+    engine.set_max_map_size(1 << 16);
+    engine.on_print(|text| info!("HANDLER print: {}", text));
+    engine.on_debug(|text, _, pos| info!("HANDLER debug {}: {}", pos, text));
+
+    // uint256/int256 values arrive as decimal strings; these keep the math exact.
+    fn big(value: &str) -> Result<I256, Box<EvalAltResult>> {
+        I256::from_dec_str(value).map_err(|e| format!("invalid integer '{}': {}", value, e).into())
+    }
+    fn checked(result: Option<I256>, op: &str) -> Result<String, Box<EvalAltResult>> {
+        result
+            .map(|v| v.to_string())
+            .ok_or_else(|| format!("{} overflowed int256", op).into())
+    }
+    engine.register_fn("big_add", |a: &str, b: &str| {
+        checked(big(a)?.checked_add(big(b)?), "big_add")
+    });
+    engine.register_fn("big_sub", |a: &str, b: &str| {
+        checked(big(a)?.checked_sub(big(b)?), "big_sub")
+    });
+    engine.register_fn("big_mul", |a: &str, b: &str| {
+        checked(big(a)?.checked_mul(big(b)?), "big_mul")
+    });
+    engine.register_fn("big_div", |a: &str, b: &str| {
+        checked(big(a)?.checked_div(big(b)?), "big_div")
+    });
+    engine.register_fn(
+        "big_cmp",
+        |a: &str, b: &str| -> Result<i64, Box<EvalAltResult>> { Ok(big(a)?.cmp(&big(b)?) as i64) },
+    );
+    engine
+}
+
+/// Compiles a script and checks that it defines `fn handle(event)`.
+fn compile_script(engine: &Engine, script: &str) -> Result<AST, String> {
+    let ast = engine.compile(script).map_err(|e| e.to_string())?;
+    if !ast
+        .iter_functions()
+        .any(|f| f.name == "handle" && f.params.len() == 1)
+    {
+        return Err("script must define fn handle(event)".to_string());
+    }
+    Ok(ast)
+}
+
+fn validate_entity_name(entity: &str) -> Result<(), String> {
+    if entity.is_empty()
+        || entity.len() > MAX_ENTITY_NAME_LEN
+        || !entity
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!(
+            "entity table name '{}' must be 1-{} characters of a-z, 0-9 and _",
+            entity, MAX_ENTITY_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+fn validate_entity_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_ENTITY_KEY_LEN {
+        return Err(format!(
+            "entity key must be 1-{} characters",
+            MAX_ENTITY_KEY_LEN
+        ));
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Event decoding
+// ---------------------------------------------------------------------------
+
+fn token_to_dynamic(token: Token) -> Dynamic {
+    match token {
+        Token::Address(address) => format!("{:#x}", address).into(),
+        Token::Uint(value) => value.to_string().into(),
+        Token::Int(value) => I256::from_raw(value).to_string().into(),
+        Token::Bool(value) => value.into(),
+        Token::String(value) => value.into(),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => {
+            format!("0x{}", hex::encode(bytes)).into()
+        }
+        Token::Array(items) | Token::FixedArray(items) | Token::Tuple(items) => {
+            Dynamic::from_array(items.into_iter().map(token_to_dynamic).collect())
+        }
+    }
+}
+
+/// The map passed to `handle(event)`. `params` holds named arguments, `args` all of them in
+/// declaration order.
+fn decode_event(event: &Event, log: &MyLog, log_id: i64) -> Result<Dynamic, String> {
+    let topics = log
+        .topics
+        .iter()
+        .map(|t| t.parse::<H256>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = hex::decode(log.data.trim_start_matches("0x")).map_err(|e| e.to_string())?;
+    let decoded = event
+        .parse_log(RawLog { topics, data })
+        .map_err(|e| format!("log does not match {}: {}", event.name, e))?;
+
+    let mut params = Map::new();
+    let mut args = Vec::with_capacity(decoded.params.len());
+    for param in decoded.params {
+        let value = token_to_dynamic(param.value);
+        if !param.name.is_empty() {
+            params.insert(param.name.into(), value.clone());
+        }
+        args.push(value);
+    }
+
+    let mut map = Map::new();
+    map.insert("name".into(), event.name.clone().into());
+    map.insert("address".into(), format!("{:#x}", log.address).into());
+    map.insert("block_number".into(), (log.block_number as i64).into());
+    map.insert("block_hash".into(), format!("{:#x}", log.block_hash).into());
+    map.insert(
+        "tx_hash".into(),
+        format!("{:#x}", log.transaction_hash).into(),
+    );
+    map.insert(
+        "log_index".into(),
+        log.log_index.map_or(-1, |i| i.as_u64() as i64).into(),
+    );
+    map.insert("log_id".into(), log_id.into());
+    map.insert("params".into(), params.into());
+    map.insert("args".into(), Dynamic::from_array(args));
+    Ok(map.into())
+}
+
+// ---------------------------------------------------------------------------
+// Entity store
+// ---------------------------------------------------------------------------
+
+/// One entity version to write for a block. `data` is JSON text, or `None` for a removal.
+pub struct EntityWrite {
+    pub handler_id: i64,
+    pub entity: String,
+    pub key: String,
+    pub block_number: i64,
+    pub data: Option<String>,
+}
+
+type EntityId = (String, String);
+
+type LoadResult = Result<Option<serde_json::Value>, String>;
+
+/// An entity lookup sent from a script to the writer task, which answers on `reply`.
+struct LoadRequest {
+    handler_id: i64,
+    entity: String,
+    key: String,
+    reply: oneshot::Sender<LoadResult>,
+}
+
+/// The latest stored version of an entity row; `None` when absent or removed.
+async fn load_entity(pool: &PgPool, request: &LoadRequest) -> LoadResult {
+    let stored: Option<Option<String>> = sqlx::query_scalar(
+        "SELECT data::TEXT FROM handler_entities \
+         WHERE handler_id = $1 AND entity = $2 AND entity_key = $3 \
+         ORDER BY block_number DESC LIMIT 1",
+    )
+    .bind(request.handler_id)
+    .bind(&request.entity)
+    .bind(&request.key)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("entity lookup failed: {}", e))?;
+    match stored.flatten() {
+        Some(text) => Ok(Some(
+            serde_json::from_str(&text).map_err(|e| e.to_string())?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// State shared between the writer and the entity functions registered on one handler's
+/// engine. `cache` holds every value read or written this session, which is also how a
+/// block sees writes from earlier blocks whose transaction hasn't committed yet.
+struct EntityStore {
+    handler_id: i64,
+    /// Where lookups go while a block's scripts run; `None` otherwise.
+    loader: Option<mpsc::Sender<LoadRequest>>,
+    cache: HashMap<EntityId, Option<serde_json::Value>>,
+    /// Writes from the `handle` call in progress; merged on success, dropped on error.
+    call_writes: HashMap<EntityId, Option<serde_json::Value>>,
+    /// Writes from successful calls in the current block, flushed by `run_block`.
+    block_writes: HashMap<EntityId, Option<serde_json::Value>>,
+}
+
+impl EntityStore {
+    fn load(&mut self, entity: &str, key: &str) -> Result<Option<serde_json::Value>, String> {
+        validate_entity_name(entity)?;
+        let id = (entity.to_string(), key.to_string());
+        if let Some(value) = self.call_writes.get(&id).or_else(|| self.cache.get(&id)) {
+            return Ok(value.clone());
+        }
+        // Runs on the blocking thread of `run_block`, which waits here for the writer task.
+        let loader = self
+            .loader
+            .as_ref()
+            .ok_or("entity lookups only run while a block is processed")?;
+        let (reply, response) = oneshot::channel();
+        loader
+            .blocking_send(LoadRequest {
+                handler_id: self.handler_id,
+                entity: entity.to_string(),
+                key: key.to_string(),
+                reply,
+            })
+            .map_err(|_| "entity loader stopped".to_string())?;
+        let value = response
+            .blocking_recv()
+            .map_err(|_| "entity loader stopped".to_string())??;
+        self.cache.insert(id, value.clone());
+        Ok(value)
+    }
+
+    fn write(
+        &mut self,
+        entity: &str,
+        key: &str,
+        value: Option<serde_json::Value>,
+    ) -> Result<(), String> {
+        validate_entity_name(entity)?;
+        validate_entity_key(key)?;
+        self.call_writes
+            .insert((entity.to_string(), key.to_string()), value);
+        Ok(())
+    }
+
+    fn finish_call(&mut self, succeeded: bool) {
+        let writes = std::mem::take(&mut self.call_writes);
+        if succeeded {
+            for (id, value) in writes {
+                self.cache.insert(id.clone(), value.clone());
+                self.block_writes.insert(id, value);
+            }
+        }
+    }
+}
+
+fn register_entity_functions(engine: &mut Engine, store: Arc<Mutex<EntityStore>>) {
+    let lookup = store.clone();
+    engine.register_fn(
+        "load",
+        move |entity: &str, key: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+            let value = lookup.lock().unwrap().load(entity, key)?;
+            match value {
+                Some(value) => rhai::serde::to_dynamic(value),
+                None => Ok(Dynamic::UNIT),
+            }
+        },
+    );
+    let upsert = store.clone();
+    engine.register_fn(
+        "upsert",
+        move |entity: &str, key: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let json: serde_json::Value = rhai::serde::from_dynamic(&value)?;
+            Ok(upsert.lock().unwrap().write(entity, key, Some(json))?)
+        },
+    );
+    engine.register_fn(
+        "remove",
+        move |entity: &str, key: &str| -> Result<(), Box<EvalAltResult>> {
+            Ok(store.lock().unwrap().write(entity, key, None)?)
+        },
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Runtime
+// ---------------------------------------------------------------------------
+
+struct LoadedHandler {
+    id: i64,
+    name: String,
+    address: String,
+    topic0: String,
+    event: Event,
+    engine: Engine,
+    ast: AST,
+    store: Arc<Mutex<EntityStore>>,
+    failed: bool,
+}
+
+/// Entry point used by the ingester. Handlers are reloaded for every block range, so
+/// registrations and edits take effect within one ingester cycle.
+pub struct HandlerRuntime {
+    pool: PgPool,
+}
+
+impl HandlerRuntime {
+    pub fn new(pool: PgPool) -> Self {
+        HandlerRuntime { pool }
+    }
+
+    /// Loads and compiles the enabled, healthy handlers. `None` when there are none, so the
+    /// writer skips handler work entirely.
+    pub async fn session(&self) -> Result<Option<HandlerSession>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, name, address, topic0, event_signature, script FROM event_handlers \
+             WHERE enabled AND last_error IS NULL ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut handlers = Vec::with_capacity(rows.len());
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            let name: String = row.try_get("name")?;
+            let signature: String = row.try_get("event_signature")?;
+            let script: String = row.try_get("script")?;
+            // Both were validated on registration; a failure here means the row was edited
+            // by hand, so treat it like a failed script.
+            let event = parse_event_signature(&signature);
+            let mut engine = base_engine();
+            let store = Arc::new(Mutex::new(EntityStore {
+                handler_id: id,
+                loader: None,
+                cache: HashMap::new(),
+                call_writes: HashMap::new(),
+                block_writes: HashMap::new(),
+            }));
+            register_entity_functions(&mut engine, store.clone());
+            let loaded =
+                event.and_then(|event| compile_script(&engine, &script).map(|ast| (event, ast)));
+            match loaded {
+                Ok((event, ast)) => handlers.push(LoadedHandler {
+                    id,
+                    name,
+                    address: row.try_get("address")?,
+                    topic0: row.try_get("topic0")?,
+                    event,
+                    engine,
+                    ast,
+                    store,
+                    failed: false,
+                }),
+                Err(e) => {
+                    warn!("HANDLER {} ({}) cannot be loaded: {}", id, name, e);
+                    mark_failed(&self.pool, id, &e).await?;
+                }
+            }
+        }
+        if handlers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(HandlerSession {
+            pool: self.pool.clone(),
+            handlers,
+        }))
+    }
+}
+
+/// Disables a handler that cannot be loaded. Failures while running a block are recorded in
+/// the block's transaction instead.
+async fn mark_failed(pool: &PgPool, handler_id: i64, error: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE event_handlers SET last_error = $2, failed_at_block = NULL WHERE id = $1")
+        .bind(handler_id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Runs `handle` for each matching log, in log order. Returns the handlers, and the id and
+/// error of each one that failed in this block.
+fn run_scripts(
+    mut handlers: Vec<LoadedHandler>,
+    block_number: u64,
+    logs: &[(MyLog, i64)],
+) -> (Vec<LoadedHandler>, Vec<(i64, String)>) {
+    let mut failures = Vec::new();
+    for (log, log_id) in logs {
+        let (Some(topic0), address) = (log.topics.first(), format!("{:#x}", log.address)) else {
+            continue;
+        };
+        for handler in handlers.iter_mut() {
+            if handler.failed || &handler.topic0 != topic0 || handler.address != address {
+                continue;
+            }
+            let result = decode_event(&handler.event, log, *log_id).and_then(|event| {
+                handler
+                    .engine
+                    .call_fn::<Dynamic>(&mut Scope::new(), &handler.ast, "handle", (event,))
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            });
+            handler.store.lock().unwrap().finish_call(result.is_ok());
+            if let Err(e) = result {
+                warn!(
+                    "HANDLER {} ({}) failed at block #{}: {}. Disabled until its script is updated.",
+                    handler.id, handler.name, block_number, e
+                );
+                handler.failed = true;
+                failures.push((handler.id, e));
+            }
+        }
+    }
+    (handlers, failures)
+}
+
+/// Handlers loaded for one block range.
+pub struct HandlerSession {
+    pool: PgPool,
+    handlers: Vec<LoadedHandler>,
+}
+
+impl HandlerSession {
+    /// Runs the matching handlers over one block's logs, in log order, and writes the
+    /// resulting entity versions and failures into `db_tx`. `log_ids` are the ids returned
+    /// for `logs`.
+    pub async fn run_block(
+        &mut self,
+        db_tx: &mut dyn StorageTransaction,
+        block_number: u64,
+        logs: &[MyLog],
+        log_ids: &[i64],
+    ) -> eyre::Result<()> {
+        let matching: Vec<(MyLog, i64)> = logs
+            .iter()
+            .zip(log_ids)
+            .filter(|(log, _)| {
+                let address = format!("{:#x}", log.address);
+                self.handlers.iter().any(|handler| {
+                    !handler.failed
+                        && log.topics.first() == Some(&handler.topic0)
+                        && handler.address == address
+                })
+            })
+            .map(|(log, &log_id)| (log.clone(), log_id))
+            .collect();
+        if matching.is_empty() {
+            return Ok(());
+        }
+
+        let (loader, mut requests) = mpsc::channel::<LoadRequest>(1);
+        for handler in &self.handlers {
+            handler.store.lock().unwrap().loader = Some(loader.clone());
+        }
+        drop(loader);
+        let handlers = std::mem::take(&mut self.handlers);
+        let mut scripts =
+            tokio::task::spawn_blocking(move || run_scripts(handlers, block_number, &matching));
+        let joined = loop {
+            tokio::select! {
+                Some(request) = requests.recv() => {
+                    let result = load_entity(&self.pool, &request).await;
+                    let _ = request.reply.send(result);
+                }
+                joined = &mut scripts => break joined,
+            }
+        };
+        let (handlers, failures) = joined.map_err(|e| {
+            eyre::eyre!(
+                "HANDLER scripts for block #{} panicked: {}",
+                block_number,
+                e
+            )
+        })?;
+        for handler in &handlers {
+            handler.store.lock().unwrap().loader = None;
+        }
+        self.handlers = handlers;
+
+        for (handler_id, error) in failures {
+            db_tx
+                .mark_handler_failed(handler_id, block_number, &error)
+                .await
+                .map_err(|e| {
+                    eyre::eyre!("DB: handler failure for block #{}: {}", block_number, e)
+                })?;
+        }
+
+        let mut writes = Vec::new();
+        for handler in &self.handlers {
+            let block_writes = std::mem::take(&mut handler.store.lock().unwrap().block_writes);
+            for ((entity, key), value) in block_writes {
+                writes.push(EntityWrite {
+                    handler_id: handler.id,
+                    entity,
+                    key,
+                    block_number: block_number as i64,
+                    data: value.map(|v| v.to_string()),
+                });
+            }
+        }
+        if !writes.is_empty() {
+            db_tx.upsert_handler_entities(&writes).await.map_err(|e| {
+                eyre::eyre!("DB: handler entities for block #{}: {}", block_number, e)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// REST API
+// ---------------------------------------------------------------------------
+
+/// Validated, normalized handler fields ready to be written.
+struct HandlerFields {
+    name: String,
+    address: String,
+    event_signature: String,
+    topic0: String,
+    script: String,
+    enabled: bool,
+}
+
+fn validate_handler(req: EventHandlerRequest) -> Result<HandlerFields, ApiError> {
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty.".to_string()));
+    }
+    let address = normalize_hex_values(&[req.address], 20, "address")?
+        .pop()
+        .unwrap_or_default();
+    let event = parse_event_signature(&req.event_signature)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid eventSignature: {}", e)))?;
+    compile_script(&base_engine(), &req.script)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid script: {}", e)))?;
+    Ok(HandlerFields {
+        name,
+        address,
+        event_signature: req.event_signature.trim().to_string(),
+        topic0: format!("{:#x}", event.signature()),
+        script: req.script,
+        enabled: req.enabled,
+    })
+}
+
+fn row_to_handler(row: &PgRow) -> Result<EventHandler, ApiError> {
+    Ok(EventHandler {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        address: row.try_get("address")?,
+        event_signature: row.try_get("event_signature")?,
+        topic0: row.try_get("topic0")?,
+        enabled: row.try_get("enabled")?,
+        failed_at_block: row.try_get("failed_at_block")?,
+        last_error: row.try_get("last_error")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn row_to_entity(row: &PgRow) -> Result<HandlerEntity, ApiError> {
+    let data: String = row.try_get("data")?;
+    Ok(HandlerEntity {
+        key: row.try_get("entity_key")?,
+        block_number: row.try_get("block_number")?,
+        data: serde_json::from_str(&data)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid stored entity: {}", e)))?,
+    })
+}
+
+/// List Event Handlers
+#[utoipa::path(
+    get,
+    path = "/handlers",
+    responses(
+        (status = 200, description = "All event handlers", body = Vec<EventHandler>)
+    )
+)]
+pub async fn list_handlers_handler(
+    State(pool): State<PgPool>,
+) -> Result<Json<Vec<EventHandler>>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM event_handlers ORDER BY id",
+        HANDLER_COLUMNS
+    ))
+    .fetch_all(&pool)
+    .await?;
+    let handlers = rows.iter().map(row_to_handler).collect::<Result<_, _>>()?;
+    Ok(Json(handlers))
+}
+
+/// Register Event Handler
+///
+/// The script runs for matching logs in blocks ingested from the next ingester cycle on.
+/// Earlier blocks are not replayed.
+#[utoipa::path(
+    post,
+    path = "/handlers",
+    request_body = EventHandlerRequest,
+    responses(
+        (status = 201, description = "Handler registered", body = EventHandler),
+        (status = 400, description = "Invalid handler or script", body = GenericErrorResponse)
+    )
+)]
+pub async fn create_handler_handler(
+    State(pool): State<PgPool>,
+    Json(req): Json<EventHandlerRequest>,
+) -> Result<(StatusCode, Json<EventHandler>), ApiError> {
+    let fields = validate_handler(req)?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO event_handlers (name, address, event_signature, topic0, script, enabled) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        HANDLER_COLUMNS
+    ))
+    .bind(fields.name)
+    .bind(fields.address)
+    .bind(fields.event_signature)
+    .bind(fields.topic0)
+    .bind(fields.script)
+    .bind(fields.enabled)
+    .fetch_one(&pool)
+    .await?;
+    Ok((StatusCode::CREATED, Json(row_to_handler(&row)?)))
+}
+
+/// Get Event Handler
+#[utoipa::path(
+    get,
+    path = "/handlers/{id}",
+    params(("id" = i64, Path, description = "Handler id")),
+    responses(
+        (status = 200, description = "Handler found", body = EventHandler),
+        (status = 404, description = "Handler not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_handler_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+) -> Result<Json<EventHandler>, ApiError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM event_handlers WHERE id = $1",
+        HANDLER_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("Event handler {} not found.", id)))?;
+    Ok(Json(row_to_handler(&row)?))
+}
+
+/// Update Event Handler
+///
+/// Replaces the handler and clears a recorded failure. Existing entity rows are kept; the
+/// new script picks up from the next ingested block.
+#[utoipa::path(
+    put,
+    path = "/handlers/{id}",
+    params(("id" = i64, Path, description = "Handler id")),
+    request_body = EventHandlerRequest,
+    responses(
+        (status = 200, description = "Handler updated", body = EventHandler),
+        (status = 400, description = "Invalid handler or script", body = GenericErrorResponse),
+        (status = 404, description = "Handler not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn update_handler_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+    Json(req): Json<EventHandlerRequest>,
+) -> Result<Json<EventHandler>, ApiError> {
+    let fields = validate_handler(req)?;
+    let row = sqlx::query(&format!(
+        "UPDATE event_handlers SET name = $2, address = $3, event_signature = $4, topic0 = $5, \
+         script = $6, enabled = $7, last_error = NULL, failed_at_block = NULL \
+         WHERE id = $1 RETURNING {}",
+        HANDLER_COLUMNS
+    ))
+    .bind(id)
+    .bind(fields.name)
+    .bind(fields.address)
+    .bind(fields.event_signature)
+    .bind(fields.topic0)
+    .bind(fields.script)
+    .bind(fields.enabled)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("Event handler {} not found.", id)))?;
+    Ok(Json(row_to_handler(&row)?))
+}
+
+/// Delete Event Handler
+///
+/// Also deletes every entity row the handler wrote.
+#[utoipa::path(
+    delete,
+    path = "/handlers/{id}",
+    params(("id" = i64, Path, description = "Handler id")),
+    responses(
+        (status = 204, description = "Handler deleted"),
+        (status = 404, description = "Handler not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn delete_handler_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query("DELETE FROM event_handlers WHERE id = $1")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!(
+            "Event handler {} not found.",
+            id
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List Handler Entities
+///
+/// Current rows of one of a handler's entity tables, ordered by key. Pass the last key of a
+/// page as `after` to get the next one.
+#[utoipa::path(
+    get,
+    path = "/handlers/{id}/entities/{entity}",
+    params(
+        ("id" = i64, Path, description = "Handler id"),
+        ("entity" = String, Path, description = "Entity table name"),
+        GetHandlerEntitiesQuery
+    ),
+    responses(
+        (status = 200, description = "Current entity rows", body = Vec<HandlerEntity>),
+        (status = 400, description = "Invalid entity name", body = GenericErrorResponse)
+    )
+)]
+pub async fn list_entities_handler(
+    State(pool): State<PgPool>,
+    Path((id, entity)): Path<(i64, String)>,
+    Query(params): Query<GetHandlerEntitiesQuery>,
+) -> Result<Json<Vec<HandlerEntity>>, ApiError> {
+    validate_entity_name(&entity).map_err(ApiError::BadRequest)?;
+    let rows = sqlx::query(
+        "SELECT entity_key, block_number, data::TEXT AS data FROM ( \
+             SELECT DISTINCT ON (entity_key) entity_key, block_number, data \
+             FROM handler_entities \
+             WHERE handler_id = $1 AND entity = $2 AND ($3::TEXT IS NULL OR entity_key > $3) \
+             ORDER BY entity_key, block_number DESC \
+         ) latest WHERE data IS NOT NULL ORDER BY entity_key LIMIT $4",
+    )
+    .bind(id)
+    .bind(&entity)
+    .bind(params.after)
+    .bind(params.limit.clamp(1, MAX_PAGE_SIZE) as i64)
+    .fetch_all(&pool)
+    .await?;
+    let entities = rows.iter().map(row_to_entity).collect::<Result<_, _>>()?;
+    Ok(Json(entities))
+}
+
+/// Get Handler Entity
+#[utoipa::path(
+    get,
+    path = "/handlers/{id}/entities/{entity}/{key}",
+    params(
+        ("id" = i64, Path, description = "Handler id"),
+        ("entity" = String, Path, description = "Entity table name"),
+        ("key" = String, Path, description = "Entity key")
+    ),
+    responses(
+        (status = 200, description = "Current entity row", body = HandlerEntity),
+        (status = 404, description = "No such entity row", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_entity_handler(
+    State(pool): State<PgPool>,
+    Path((id, entity, key)): Path<(i64, String, String)>,
+) -> Result<Json<HandlerEntity>, ApiError> {
+    validate_entity_name(&entity).map_err(ApiError::BadRequest)?;
+    let row = sqlx::query(
+        "SELECT entity_key, block_number, data::TEXT AS data FROM handler_entities \
+         WHERE handler_id = $1 AND entity = $2 AND entity_key = $3 \
+         ORDER BY block_number DESC LIMIT 1",
+    )
+    .bind(id)
+    .bind(&entity)
+    .bind(&key)
+    .fetch_optional(&pool)
+    .await?
+    .filter(|row| matches!(row.try_get::<Option<String>, _>("data"), Ok(Some(_))))
+    .ok_or_else(|| ApiError::NotFound(format!("No {} row with key {}.", entity, key)))?;
+    Ok(Json(row_to_entity(&row)?))
+}
+
+/// The read-only routes. Registering, changing and deleting handlers runs code in the
+/// ingester, so those routes are in `admin_router`, mounted only with API keys on.
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/handlers", get(list_handlers_handler))
+        .route("/handlers/{id}", get(get_handler_handler))
+        .route(
+            "/handlers/{id}/entities/{entity}",
+            get(list_entities_handler),
+        )
+        .route(
+            "/handlers/{id}/entities/{entity}/{key}",
+            get(get_entity_handler),
+        )
+        .with_state(pool)
+}
+
+pub fn admin_router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/handlers", post(create_handler_handler))
+        .route(
+            "/handlers/{id}",
+            put(update_handler_handler).delete(delete_handler_handler),
+        )
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler(script: &str) -> LoadedHandler {
+        let mut engine = base_engine();
+        let store = Arc::new(Mutex::new(EntityStore {
+            handler_id: 1,
+            loader: None,
+            cache: HashMap::new(),
+            call_writes: HashMap::new(),
+            block_writes: HashMap::new(),
+        }));
+        register_entity_functions(&mut engine, store.clone());
+        let ast = compile_script(&engine, script).unwrap();
+        LoadedHandler {
+            id: 1,
+            name: "test".to_string(),
+            address: format!("{:#x}", ethers::types::Address::zero()),
+            topic0: format!("{:#x}", H256::zero()),
+            event: parse_event_signature("Ping()").unwrap(),
+            engine,
+            ast,
+            store,
+            failed: false,
+        }
+    }
+
+    fn ping_log(topic0: &str) -> MyLog {
+        MyLog {
+            log_index: Some(0.into()),
+            transaction_hash: H256::zero(),
+            transaction_index: Some(0),
+            block_number: 1,
+            block_hash: H256::zero(),
+            block_timestamp: None,
+            address: ethers::types::Address::zero(),
+            data: "0x".to_string(),
+            topics: vec![topic0.to_string()],
+            address_ens_name: None,
+        }
+    }
+
+    fn run(script: &str) -> Vec<(i64, String)> {
+        let mut ping = handler(script);
+        ping.topic0 = format!("{:#x}", ping.event.signature());
+        let log = ping_log(&ping.topic0);
+        run_scripts(vec![ping], 1, &[(log, 1)]).1
+    }
+
+    #[test]
+    fn collections_are_capped() {
+        assert!(run("fn handle(event) { let a = []; for i in 0..10 { a.push(i); } }").is_empty());
+        let failures = run("fn handle(event) { let a = []; for i in 0..70000 { a.push(i); } }");
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].1.contains("too large"), "{}", failures[0].1);
+        let failures =
+            run("fn handle(event) { let m = #{}; for i in 0..70000 { m[`k${i}`] = i; } [m] }");
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].1.contains("too large"), "{}", failures[0].1);
+    }
+
+    #[test]
+    fn load_outside_a_block_fails_the_script() {
+        let failures = run(r#"fn handle(event) { load("balances", "a"); }"#);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0]
+            .1
+            .contains("only run while a block is processed"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn loads_are_answered_from_a_current_thread_runtime() {
+        let mut ping = handler(
+            r#"fn handle(event) { let v = load("balances", "a"); upsert("balances", "a", #{ n: v.n + 1 }); }"#,
+        );
+        ping.topic0 = format!("{:#x}", ping.event.signature());
+        let store = ping.store.clone();
+        let (loader, mut requests) = mpsc::channel::<LoadRequest>(1);
+        store.lock().unwrap().loader = Some(loader);
+        let log = ping_log(&ping.topic0);
+        let mut scripts =
+            tokio::task::spawn_blocking(move || run_scripts(vec![ping], 1, &[(log, 1)]));
+        let (_, failures) = loop {
+            tokio::select! {
+                Some(request) = requests.recv() => {
+                    let _ = request.reply.send(Ok(Some(serde_json::json!({ "n": 41 }))));
+                }
+                joined = &mut scripts => break joined.unwrap(),
+            }
+        };
+        assert!(failures.is_empty(), "{:?}", failures);
+        let writes = std::mem::take(&mut store.lock().unwrap().block_writes);
+        let written = writes[&("balances".to_string(), "a".to_string())].clone();
+        assert_eq!(written, Some(serde_json::json!({ "n": 42 })));
+    }
+}
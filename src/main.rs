@@ -1,82 +1,177 @@
 // src/main.rs
 mod api;
 mod api_models;
+mod auth;
+mod bench;
+mod contracts;
+mod control;
 mod db;
+mod dex;
 mod docs;
+mod ens;
+mod events;
+mod export;
+mod gas;
+mod graphql;
+mod handlers;
 mod models;
+mod pipeline;
+mod pricing;
+mod rpc;
+mod search;
+mod sinks;
+mod stats;
+mod storage;
+mod stream;
+mod supervisor;
+mod webhooks;
 use dotenvy::dotenv;
-use ethers::{
-    providers::{Http, Middleware, Provider},
-    types::U64,
-};
+use ethers::providers::{Http, Middleware, Provider};
 use eyre::Result;
-use futures::stream::{self, StreamExt};
 use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
 use std::env;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{error, info, warn};
 
-use models::{MyBlock, MyLog, MyTransaction};
+use control::{Command, IngesterControl, Phase};
+use events::{ChainEvent, EventSender};
+use storage::Storage;
 
 // --- Constants for Ingester ---
 const POLL_INTERVAL_SECONDS: u64 = 10;
-const BLOCKS_PER_BATCH: u64 = 5;
+const BLOCKS_PER_BATCH: u64 = 50; // Default upper bound on blocks pushed through the pipeline per cycle
 const DEFAULT_START_BLOCK: u64 = 23900790; // Configurable via START_BLOCK env var
-const MAX_RECEIPT_CONCURRENT: usize = 10; // Max parallel receipt fetches per block
-const MAX_BLOCK_FETCH_RETRIES: u32 = 3;
-const BASE_BLOCK_FETCH_BACKOFF_SECONDS: u64 = 2;
+const DEFAULT_BACKFILL_COMMIT_BLOCKS: u64 = 1; // Configurable via BACKFILL_COMMIT_BLOCKS env var
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30); // Wait for the ingester's last commit
 
-// Fetches a receipt with retries. Returns None if the RPC returns Ok(None).
-async fn fetch_receipt_with_retry(
+async fn run_continuous_ingester(
     provider: Arc<Provider<Http>>,
-    tx_hash: ethers::types::H256,
-    block_num: u64,
-) -> Result<Option<ethers::types::TransactionReceipt>> {
-    for attempt in 1..=3u32 {
-        match provider.get_transaction_receipt(tx_hash).await {
-            Ok(r) => return Ok(r),
-            Err(e) => {
-                if attempt == 3 {
-                    return Err(eyre::eyre!(
-                        "Failed to fetch receipt for tx {:?} in block {} after 3 attempts: {:?}",
-                        tx_hash,
-                        block_num,
-                        e
-                    ));
-                }
-                let backoff = Duration::from_secs(2_u64.pow(attempt - 1));
-                warn!(
-                    "Receipt fetch attempt {}/3 for {:?} failed: {}. Retrying in {}s...",
-                    attempt,
-                    tx_hash,
-                    e,
-                    backoff.as_secs()
-                );
-                tokio::time::sleep(backoff).await;
-            }
-        }
-    }
-    unreachable!()
-}
+    storage: Arc<dyn Storage>,
+    events: EventSender,
+    options: pipeline::IngestOptions,
+    control: Arc<IngesterControl>,
+) -> Result<()> {
+    // During backfill, several blocks can share one DB transaction to amortize commit cost.
+    let backfill_commit_blocks = env::var("BACKFILL_COMMIT_BLOCKS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_BACKFILL_COMMIT_BLOCKS)
+        .max(1);
 
-async fn run_continuous_ingester(provider: Arc<Provider<Http>>, pool: PgPool) -> Result<()> {
     info!("--- Continuous Ingester Task Started ---");
     info!(
-        "Polling every {}s, batch size {}.",
-        POLL_INTERVAL_SECONDS, BLOCKS_PER_BATCH
+        "Polling every {}s, batch size {}, up to {} blocks per commit while backfilling.",
+        POLL_INTERVAL_SECONDS,
+        control.batch_size(),
+        backfill_commit_blocks
     );
+    if options.dex.is_some() {
+        info!("DEX decoding on: Uniswap V2/V3 pools, swaps and liquidity events.");
+    }
+    if let Some(pricing) = &options.pricing {
+        let config = pricing.config();
+        info!(
+            "Pricing on: token/ETH via WETH {:#x}, ETH/USD from {} pool(s), min pool liquidity {} ETH, one price per {}.",
+            config.weth,
+            config.usd_pools.len(),
+            config.min_liquidity_eth,
+            config.resolution.as_str()
+        );
+    }
+    if options.gas_rollups {
+        info!("Gas rollups on: per-block and hourly fee history, per-contract gas.");
+    }
+    if options.stats_rollups {
+        info!("Activity rollups on: hourly and daily transactions, senders, ETH volume and logs.");
+    }
+    if let Some(contracts) = &options.contracts {
+        if contracts.traces() {
+            info!(
+                "Contract indexing on: deployments from receipts and trace_block, proxy detection."
+            );
+        } else {
+            info!("Contract indexing on: deployments from receipts, proxy detection.");
+        }
+    }
+    if let Some(ens) = &options.ens {
+        info!(
+            "ENS indexing on: registry {:#x}, resolver records and .eth registrations.",
+            ens.registry()
+        );
+    }
+    if let Some(filter) = &options.filter {
+        info!(
+            "Selective indexing: {} contract(s), {} topic0 value(s). Only matching logs and their transactions are stored.",
+            filter.addresses.len(),
+            filter.topic0.len()
+        );
+    }
 
-    loop {
-        let last_synced_block_opt = match db::get_last_synced_block(&pool).await {
+    let poll_interval = Duration::from_secs(POLL_INTERVAL_SECONDS);
+    'cycle: loop {
+        if control.shutdown().is_requested() {
+            info!("INGESTER: Shutdown requested. Stopping at block boundary.");
+            control.set_phase(Phase::Stopping, None);
+            return Ok(());
+        }
+        // Queued rewinds and re-indexes run first, paused or not.
+        while !control.shutdown().is_requested() {
+            let Some(command) = control.next_command() else {
+                break;
+            };
+            // A fresh head when the node answers, else the last one polled. With neither the
+            // command waits, rather than run (and record) a chain head of 0.
+            let chain_head = match provider.get_block_number().await {
+                Ok(head) => {
+                    control.set_chain_head(head.as_u64());
+                    head.as_u64()
+                }
+                Err(e) => match control.chain_head() {
+                    Some(head) => head,
+                    None => {
+                        error!(
+                            "INGESTER ETH: Failed to get chain head for {:?}: {}. Retrying in {}s.",
+                            command, e, POLL_INTERVAL_SECONDS
+                        );
+                        control.requeue(command);
+                        control.set_phase(Phase::Retrying, None);
+                        control.set_error(Some(format!("get chain head: {}", e)));
+                        control.wait(poll_interval).await;
+                        continue 'cycle;
+                    }
+                },
+            };
+            let settings = pipeline::RangeSettings {
+                chain_head,
+                blocks_per_commit: backfill_commit_blocks,
+                blocks_in_flight: control.blocks_in_flight(),
+            };
+            if let Err(e) = run_command(
+                &provider, &storage, &events, &options, &control, command, settings,
+            )
+            .await
+            {
+                error!("INGESTER: {}", e);
+                control.set_error(Some(e.to_string()));
+            }
+        }
+        if control.is_paused() {
+            control.set_phase(Phase::Paused, None);
+            control.wait(poll_interval).await;
+            continue;
+        }
+
+        let last_synced_block_opt = match storage.get_last_synced_block().await {
             Ok(val) => val,
             Err(e) => {
                 error!(
                     "INGESTER DB: Failed to get last synced block: {}. Retrying in {}s.",
                     e, POLL_INTERVAL_SECONDS
                 );
-                tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS)).await;
+                control.set_phase(Phase::Retrying, None);
+                control.set_error(Some(format!("get last synced block: {}", e)));
+                control.wait(poll_interval).await;
                 continue;
             }
         };
@@ -100,241 +195,201 @@ async fn run_continuous_ingester(provider: Arc<Provider<Http>>, pool: PgPool) ->
                     "INGESTER ETH: Failed to get chain head: {}. Retrying in {}s.",
                     e, POLL_INTERVAL_SECONDS
                 );
-                tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS)).await;
+                control.set_phase(Phase::Retrying, None);
+                control.set_error(Some(format!("get chain head: {}", e)));
+                control.wait(poll_interval).await;
                 continue;
             }
         };
+        control.set_chain_head(current_chain_head);
 
         if start_block_to_fetch > current_chain_head {
-            tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS)).await;
+            control.set_phase(Phase::Idle, None);
+            control.set_error(None);
+            control.wait(poll_interval).await;
             continue;
         }
 
-        let end_block_to_fetch =
-            (start_block_to_fetch + BLOCKS_PER_BATCH - 1).min(current_chain_head);
+        // Near the head every block commits (and is published) on its own.
+        let blocks_per_commit =
+            if current_chain_head - start_block_to_fetch + 1 > backfill_commit_blocks {
+                backfill_commit_blocks
+            } else {
+                1
+            };
+        let batch_size = control.batch_size().max(blocks_per_commit);
+        let end_block_to_fetch = (start_block_to_fetch + batch_size - 1).min(current_chain_head);
 
         info!(
             "INGESTER Cycle: blocks {} → {} (chain head: {})",
             start_block_to_fetch, end_block_to_fetch, current_chain_head
         );
+        control.set_phase(
+            Phase::Syncing,
+            Some(start_block_to_fetch..=end_block_to_fetch),
+        );
 
-        let mut blocks_processed_this_cycle = 0u64;
-
-        'batch: for block_num_u64 in start_block_to_fetch..=end_block_to_fetch {
-            let block_num_for_rpc = U64::from(block_num_u64);
-
-            // --- Fetch block with retries ---
-            let mut ethers_block_opt = None;
-            for attempt in 1..=MAX_BLOCK_FETCH_RETRIES {
-                match provider.get_block_with_txs(block_num_for_rpc).await {
-                    Ok(Some(b)) => {
-                        ethers_block_opt = Some(b);
-                        break;
-                    }
-                    Ok(None) => {
-                        warn!(
-                            "Block #{} not found on attempt {}. Skipping.",
-                            block_num_u64, attempt
-                        );
-                        break;
-                    }
-                    Err(e) => {
-                        error!(
-                            "Attempt {}/{} failed to fetch block #{}: {:?}.",
-                            attempt, MAX_BLOCK_FETCH_RETRIES, block_num_u64, e
-                        );
-                        if attempt == MAX_BLOCK_FETCH_RETRIES {
-                            error!(
-                                "Giving up on block #{} after max retries. Stopping batch.",
-                                block_num_u64
-                            );
-                            break 'batch;
-                        }
-                        let backoff = Duration::from_secs(
-                            BASE_BLOCK_FETCH_BACKOFF_SECONDS * 2_u64.pow(attempt - 1),
-                        );
-                        tokio::time::sleep(backoff).await;
-                    }
-                }
-            }
-
-            let ethers_block = match ethers_block_opt {
-                Some(b) => b,
-                None => continue,
-            };
-
-            // --- Reorg detection: validate parent_hash ---
-            if block_num_u64 > 0 {
-                let stored_hash =
-                    db::get_canonical_block_hash_at_height(&pool, block_num_u64 - 1).await;
-                if let Ok(Some(stored)) = stored_hash {
-                    let parent = format!("{:#x}", ethers_block.parent_hash);
-                    if parent != stored {
-                        warn!(
-                            "REORG DETECTED at height {}! Expected parent {}, got {}. Rolling back from height {}.",
-                            block_num_u64, stored, parent, block_num_u64 - 1
-                        );
-                        if let Err(e) = db::rollback_from_height(&pool, block_num_u64 - 1).await {
-                            error!("Rollback failed: {}. Stopping ingester cycle.", e);
-                            break 'batch;
-                        }
-                        info!(
-                            "Rollback complete. Re-ingesting from height {}.",
-                            block_num_u64 - 1
-                        );
-                        // Skip this block; next cycle will re-fetch from correct height
-                        break 'batch;
-                    }
-                }
-            }
-
-            let my_block = MyBlock {
-                block_number: ethers_block.number.unwrap_or_default(),
-                block_hash: ethers_block.hash.unwrap_or_default(),
-                parent_hash: ethers_block.parent_hash,
-                timestamp: ethers_block.timestamp,
-                gas_used: ethers_block.gas_used,
-                gas_limit: ethers_block.gas_limit,
-                base_fee_per_gas: ethers_block.base_fee_per_gas,
-            };
-
-            let transactions = ethers_block.transactions;
-            let total_txs = transactions.len();
-
-            // --- Phase 1: Parallel receipt fetching (RPC I/O only) ---
+        let blocks_processed_this_cycle = pipeline::ingest_range(
+            provider.clone(),
+            storage.clone(),
+            &events,
+            start_block_to_fetch..=end_block_to_fetch,
+            pipeline::RangeSettings {
+                chain_head: current_chain_head,
+                blocks_per_commit,
+                blocks_in_flight: control.blocks_in_flight(),
+            },
+            &options,
+            control.shutdown(),
+        )
+        .await;
+
+        // Keep going without pausing while backfilling; poll once caught up or stalled.
+        let caught_up = start_block_to_fetch + blocks_processed_this_cycle > current_chain_head;
+        if blocks_processed_this_cycle == 0 {
             info!(
-                "Block #{}: fetching {} receipts in parallel (concurrency={})...",
-                block_num_u64, total_txs, MAX_RECEIPT_CONCURRENT
+                "INGESTER: Cycle done. No blocks committed. Retrying in {}s...",
+                POLL_INTERVAL_SECONDS
             );
+            control.set_phase(Phase::Retrying, None);
+            control.wait(poll_interval).await;
+        } else if caught_up {
+            info!(
+                "INGESTER: Cycle done. {} blocks committed. Waiting {}s...",
+                blocks_processed_this_cycle, POLL_INTERVAL_SECONDS
+            );
+            control.set_phase(Phase::Idle, None);
+            control.set_error(None);
+            control.wait(poll_interval).await;
+        } else {
+            info!(
+                "INGESTER: Cycle done. {} blocks committed. Continuing backfill.",
+                blocks_processed_this_cycle
+            );
+            control.set_error(None);
+        }
+    }
+}
 
-            let provider_arc = provider.clone();
-            let tx_receipts: Vec<(
-                ethers::types::Transaction,
-                Option<ethers::types::TransactionReceipt>,
-            )> = stream::iter(transactions)
-                .map(|tx| {
-                    let p = provider_arc.clone();
-                    async move {
-                        let hash = tx.hash;
-                        let receipt = fetch_receipt_with_retry(p, hash, block_num_u64)
-                            .await
-                            .unwrap_or(None);
-                        (tx, receipt)
-                    }
-                })
-                .buffer_unordered(MAX_RECEIPT_CONCURRENT)
-                .collect()
-                .await;
-
-            // --- Phase 2: Sequential DB writes inside a single atomic transaction ---
-            let block_processing_result: Result<()> = async {
-                let mut db_tx = pool
-                    .begin()
-                    .await
-                    .map_err(|e| eyre::eyre!("DB: begin tx for block #{}: {}", block_num_u64, e))?;
-
-                db::insert_block_data(&mut db_tx, &my_block)
-                    .await
-                    .map_err(|e| eyre::eyre!("DB: insert block #{}: {}", block_num_u64, e))?;
-
-                for (idx, (ethers_tx, receipt_opt)) in tx_receipts.into_iter().enumerate() {
-                    if idx % 50 == 0 || idx == total_txs - 1 {
-                        info!(
-                            "  Block #{}: writing tx {}/{}...",
-                            block_num_u64,
-                            idx + 1,
-                            total_txs
-                        );
-                    }
-
-                    let status = receipt_opt
-                        .as_ref()
-                        .and_then(|r| r.status)
-                        .map(|s| s.as_u64());
-                    let my_tx = MyTransaction {
-                        tx_hash: ethers_tx.hash,
-                        block_number: ethers_tx.block_number.unwrap_or_default(),
-                        block_hash: ethers_tx.block_hash.unwrap_or_default(),
-                        transaction_index: ethers_tx.transaction_index,
-                        from_address: ethers_tx.from,
-                        to_address: ethers_tx.to,
-                        value: ethers_tx.value,
-                        gas_price: ethers_tx.gas_price,
-                        max_fee_per_gas: ethers_tx.max_fee_per_gas,
-                        max_priority_fee_per_gas: ethers_tx.max_priority_fee_per_gas,
-                        gas: ethers_tx.gas,
-                        input_data: ethers_tx.input.to_string(),
-                        status,
-                    };
-
-                    db::insert_transaction_data(&mut db_tx, &my_tx)
-                        .await
-                        .map_err(|e| eyre::eyre!("DB: insert tx {:?}: {}", my_tx.tx_hash, e))?;
-
-                    if let Some(ref receipt) = receipt_opt {
-                        for ethers_log in &receipt.logs {
-                            let my_log = MyLog {
-                                log_index: ethers_log.log_index,
-                                transaction_hash: ethers_log.transaction_hash.unwrap_or_default(),
-                                transaction_index: ethers_log.transaction_index.map(|i| i.as_u64()),
-                                block_number: ethers_log.block_number.map_or(0, |bn| bn.as_u64()),
-                                block_hash: ethers_log.block_hash.unwrap_or_default(),
-                                address: ethers_log.address,
-                                data: ethers_log.data.to_string(),
-                                topics: ethers_log
-                                    .topics
-                                    .iter()
-                                    .map(|h| format!("{:#x}", h))
-                                    .collect(),
-                            };
-                            db::insert_log_data(&mut db_tx, &my_log)
-                                .await
-                                .map_err(|e| {
-                                    eyre::eyre!("DB: insert log for tx {:?}: {}", my_tx.tx_hash, e)
-                                })?;
-                        }
-                    }
-                }
+/// Runs one queued admin command. See control.rs.
+async fn run_command(
+    provider: &Arc<Provider<Http>>,
+    storage: &Arc<dyn Storage>,
+    events: &EventSender,
+    options: &pipeline::IngestOptions,
+    control: &IngesterControl,
+    command: Command,
+    settings: pipeline::RangeSettings,
+) -> Result<()> {
+    match command {
+        Command::Rewind { height } => {
+            info!("INGESTER: Rewinding to block {}.", height);
+            control.set_phase(Phase::Rewinding, Some(height..=height));
+            storage
+                .rollback_from_height(height)
+                .await
+                .map_err(|e| eyre::eyre!("rewind to block {} failed: {}", height, e))?;
+            events::publish(
+                events,
+                ChainEvent::Reorg {
+                    fork_height: height,
+                },
+            );
+            info!("INGESTER: Rewind done. Re-syncing from block {}.", height);
+        }
+        Command::Reindex { from, to } => {
+            reindex(storage, events, control, from, to, |range| {
+                pipeline::ingest_range(
+                    provider.clone(),
+                    storage.clone(),
+                    events,
+                    range,
+                    settings,
+                    options,
+                    control.shutdown(),
+                )
+            })
+            .await?;
+        }
+    }
+    Ok(())
+}
 
-                db::set_last_synced_block(&mut db_tx, block_num_u64, current_chain_head)
-                    .await
-                    .map_err(|e| {
-                        eyre::eyre!("DB: set_last_synced_block #{}: {}", block_num_u64, e)
-                    })?;
-                db_tx
-                    .commit()
-                    .await
-                    .map_err(|e| eyre::eyre!("DB: commit block #{}: {}", block_num_u64, e))?;
+/// Deletes `from..=to` and ingests it again in batches through `ingest`, which returns how
+/// many blocks of a batch it committed.
+async fn reindex<F, Fut>(
+    storage: &Arc<dyn Storage>,
+    events: &EventSender,
+    control: &IngesterControl,
+    from: u64,
+    to: u64,
+    mut ingest: F,
+) -> Result<()>
+where
+    F: FnMut(std::ops::RangeInclusive<u64>) -> Fut,
+    Fut: std::future::Future<Output = u64>,
+{
+    info!("INGESTER: Re-indexing blocks {} → {}.", from, to);
+    control.set_phase(Phase::Reindexing, Some(from..=to));
+    // The hash at the top of the range tells afterwards whether the blocks above it
+    // still build on the re-indexed chain.
+    let old_top = storage.get_canonical_block_hash_at_height(to).await?;
+    storage
+        .delete_block_range(from, to)
+        .await
+        .map_err(|e| eyre::eyre!("delete blocks {}..{} failed: {}", from, to, e))?;
+
+    // Consumers drop what they hold for the range before it arrives again.
+    events::publish(
+        events,
+        ChainEvent::Retract {
+            from_block: from,
+            to_block: to,
+        },
+    );
 
-                Ok(())
-            }
-            .await;
-
-            match block_processing_result {
-                Ok(()) => {
-                    info!(
-                        "✓ Block #{} committed (lag: {} blocks behind chain head).",
-                        block_num_u64,
-                        current_chain_head.saturating_sub(block_num_u64)
-                    );
-                    blocks_processed_this_cycle += 1;
-                }
-                Err(e) => {
-                    error!(
-                        "INGESTER: block #{} failed: {}. Rolled back. Retrying next cycle.",
-                        block_num_u64, e
-                    );
-                    break 'batch;
-                }
+    let mut next = from;
+    while next <= to {
+        let end = next.saturating_add(control.batch_size().max(1) - 1).min(to);
+        let committed = ingest(next..=end).await;
+        next += committed;
+        if next <= end {
+            // Leave no gap below the sync point: drop everything from the first
+            // uncommitted block on and let the sync loop fetch it.
+            if let Err(e) = storage.rollback_from_height(next).await {
+                // Until the rollback goes through, the range has a gap; retry it first.
+                control.enqueue_first(Command::Rewind { height: next });
+                eyre::bail!(
+                    "re-index stopped at block {} and the rollback failed: {}; retrying",
+                    next,
+                    e
+                );
             }
+            events::publish(events, ChainEvent::Reorg { fork_height: next });
+            eyre::bail!(
+                "re-index stopped at block {}; rolled back and re-syncing from there",
+                next
+            );
         }
+    }
 
-        info!(
-            "INGESTER: Cycle done. {} blocks committed. Waiting {}s...",
-            blocks_processed_this_cycle, POLL_INTERVAL_SECONDS
+    let new_top = storage.get_canonical_block_hash_at_height(to).await?;
+    if old_top.is_some() && new_top != old_top {
+        warn!(
+            "INGESTER: Block {} changed while re-indexing. Rolling back from block {}.",
+            to,
+            to + 1
+        );
+        storage.rollback_from_height(to + 1).await?;
+        events::publish(
+            events,
+            ChainEvent::Reorg {
+                fork_height: to + 1,
+            },
         );
-        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS)).await;
     }
+    info!("INGESTER: Re-indexed blocks {} → {}.", from, to);
+    Ok(())
 }
 
 #[tokio::main]
@@ -342,36 +397,325 @@ async fn main() -> Result<()> {
     dotenv().ok();
     tracing_subscriber::fmt::init();
 
+    // `evm_indexer export ...` and `bench-writes ...` are one-shot commands that only need
+    // the database; `admin ...` talks to a running server.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("admin") {
+        return control::run_cli(&args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("export") {
+        let database_url = env::var("DATABASE_URL")?;
+        if database_url.starts_with("sqlite:") {
+            eyre::bail!("The export command requires a Postgres DATABASE_URL.");
+        }
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect(&database_url)
+            .await?;
+        return export::run_cli(pool, &args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("bench-writes") {
+        let storage = storage::connect(&env::var("DATABASE_URL")?).await?;
+        return bench::run_cli(storage.as_ref(), &args[1..]).await;
+    }
+
+    // Fail fast on a malformed filter or auth setup rather than inside the spawned tasks.
+    let index_filter = pipeline::IndexFilter::from_env()?.map(Arc::new);
+    let database_url = env::var("DATABASE_URL")?;
+    let auth_config = auth::AuthConfig::from_env(!database_url.starts_with("sqlite:"))?;
+
     info!("MAIN: Connecting to Ethereum node...");
     let rpc_url = env::var("ETH_RPC_URL")?;
     let provider = Arc::new(Provider::<Http>::try_from(rpc_url.as_str())?);
-    info!("MAIN: Connected to Ethereum provider.");
+    let chain_id = provider.get_chainid().await?.as_u64();
+    info!(
+        "MAIN: Connected to Ethereum provider (chain id {}).",
+        chain_id
+    );
 
     info!("MAIN: Connecting to database...");
-    let database_url = env::var("DATABASE_URL")?;
-    let pool: PgPool = PgPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await?;
+    let storage = storage::connect(&database_url).await?;
     info!("MAIN: Connected to database.");
+    if auth_config.is_some() && storage.postgres_pool().is_none() {
+        eyre::bail!("API_AUTH=true requires PostgreSQL storage.");
+    }
 
-    let provider_for_ingester = provider.clone();
-    let pool_for_ingester = pool.clone();
+    // Event handlers, DEX decoding, pricing and contract indexing keep their state in
+    // Postgres. DEX decoding is on by default and can be switched off with DEX_DECODING=false;
+    // pricing builds on it. Contract indexing, gas rollups and activity rollups are switched
+    // off with CONTRACT_INDEXING=false, GAS_ROLLUPS=false and STATS_ROLLUPS=false. ENS
+    // indexing runs on chains with a known registry (or ENS_REGISTRY) unless ENS_INDEXING=false.
+    let dex_decoding = env::var("DEX_DECODING").map_or(true, |v| v != "false" && v != "0");
+    let price_config = pricing::PriceConfig::from_env(chain_id)?;
+    let ingest_options = pipeline::IngestOptions {
+        filter: index_filter,
+        handlers: storage
+            .postgres_pool()
+            .map(|pool| Arc::new(handlers::HandlerRuntime::new(pool))),
+        dex: storage
+            .postgres_pool()
+            .filter(|_| dex_decoding)
+            .map(|pool| Arc::new(dex::DexDecoder::new(pool))),
+        pricing: storage
+            .postgres_pool()
+            .filter(|_| dex_decoding)
+            .zip(price_config)
+            .map(|(pool, config)| Arc::new(pricing::PriceOracle::new(pool, config))),
+        contracts: storage
+            .postgres_pool()
+            .and(contracts::ContractIndexer::from_env())
+            .map(Arc::new),
+        ens: storage
+            .postgres_pool()
+            .and(ens::EnsIndexer::from_env(chain_id)?)
+            .map(Arc::new),
+        gas_rollups: storage.postgres_pool().is_some()
+            && env::var("GAS_ROLLUPS").map_or(true, |v| v != "false" && v != "0"),
+        stats_rollups: storage.postgres_pool().is_some()
+            && env::var("STATS_ROLLUPS").map_or(true, |v| v != "false" && v != "0"),
+    };
+
+    // SIGINT/SIGTERM: stop serving, let the ingester commit the block it is writing, exit.
+    let (shutdown_trigger, shutdown) = supervisor::shutdown_channel();
+    let shutdown_trigger = Arc::new(shutdown_trigger);
+    let signal_trigger = shutdown_trigger.clone();
+    tokio::spawn(async move { signal_trigger.on_signal().await });
+
+    let events = events::channel();
+    let control = IngesterControl::new(
+        BLOCKS_PER_BATCH,
+        pipeline::DEFAULT_BLOCKS_IN_FLIGHT,
+        shutdown.clone(),
+    );
 
-    tokio::spawn(async move {
-        if let Err(e) = run_continuous_ingester(provider_for_ingester, pool_for_ingester).await {
-            error!("CRITICAL: Ingester task exited with error: {}", e);
-        } else {
-            error!("Ingester task completed (should loop forever).");
-        }
-    });
+    let ingester = {
+        let provider = provider.clone();
+        let storage = storage.clone();
+        let events = events.clone();
+        let control = control.clone();
+        supervisor::supervise("ingester", shutdown.clone(), move || {
+            let running = control.running();
+            let ingester = run_continuous_ingester(
+                provider.clone(),
+                storage.clone(),
+                events.clone(),
+                ingest_options.clone(),
+                control.clone(),
+            );
+            async move {
+                let _running = running;
+                ingester.await
+            }
+        })
+    };
     info!("MAIN: Ingester task spawned.");
 
+    // Webhooks and sinks keep their state in Postgres tables.
+    if let Some(pool) = storage.postgres_pool() {
+        let pool_for_webhooks = pool.clone();
+        let events_for_webhooks = events.clone();
+        // Not waited for on shutdown: interrupted deliveries resume on the next start.
+        supervisor::supervise("webhook dispatcher", shutdown.clone(), move || {
+            webhooks::run_dispatcher(pool_for_webhooks.clone(), events_for_webhooks.clone())
+        });
+        info!("MAIN: Webhook dispatcher spawned.");
+
+        let sinks = sinks::configured_sinks(&pool)?;
+        if !sinks.is_empty() {
+            info!("MAIN: Starting {} output sink(s).", sinks.len());
+            sinks::spawn_sinks(pool, events.clone(), sinks);
+        }
+    } else {
+        info!("MAIN: Using embedded SQLite storage; webhooks and output sinks are disabled.");
+    }
+
     info!("MAIN: Starting API server...");
-    if let Err(e) = api::run_api_server(pool).await {
+    let served = api::run_api_server(
+        storage,
+        provider,
+        chain_id,
+        events,
+        auth_config,
+        control,
+        shutdown,
+    )
+    .await;
+    if let Err(e) = &served {
         error!("CRITICAL: API server failed: {}", e);
-        return Err(e);
     }
 
-    Ok(())
+    // The API has stopped, on a signal or an error; either way the ingester finishes up too.
+    shutdown_trigger.trigger();
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, ingester).await {
+        Ok(_) => info!("MAIN: Ingester stopped. Bye."),
+        Err(_) => error!(
+            "MAIN: Ingester still busy after {}s; exiting anyway.",
+            SHUTDOWN_TIMEOUT.as_secs()
+        ),
+    }
+    served
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::RangeInclusive;
+    use storage::test_support::{block, hash, write_blocks, TestDb};
+
+    /// A database holding blocks 100-110 of fork 0.
+    async fn test_db(name: &str) -> TestDb {
+        let db = TestDb::new(&format!("reindex_{}", name)).await;
+        write_fork(&db.storage, 100..=110, 0).await;
+        db
+    }
+
+    /// Commits `blocks` of `fork` one block at a time, like the writer near the head.
+    async fn write_fork(storage: &Arc<dyn Storage>, blocks: RangeInclusive<u64>, fork: u64) {
+        write_blocks(
+            storage.as_ref(),
+            blocks.map(|number| block(number, fork, fork)),
+        )
+        .await;
+    }
+
+    /// Re-indexes `from..=to` in batches of two, writing blocks of `fork` and committing at
+    /// most `limit` blocks in all. `before_shortfall` runs when a batch comes up short.
+    async fn run_reindex(
+        db: &TestDb,
+        control: &IngesterControl,
+        (from, to): (u64, u64),
+        fork: u64,
+        limit: u64,
+        before_shortfall: impl Fn() -> std::pin::Pin<Box<dyn std::future::Future<Output = ()>>>,
+    ) -> (Result<()>, Vec<Arc<ChainEvent>>) {
+        let events = events::channel();
+        let mut subscriber = events.subscribe();
+        let mut written = 0;
+        let result = reindex(&db.storage, &events, control, from, to, |range| {
+            let storage = db.storage.clone();
+            let end = range
+                .start()
+                .saturating_add(limit.saturating_sub(written))
+                .min(*range.end() + 1);
+            let committed = end - range.start();
+            written += committed;
+            let shortfall = end <= *range.end();
+            let hook = shortfall.then(&before_shortfall);
+            async move {
+                if committed > 0 {
+                    write_fork(&storage, *range.start()..=end - 1, fork).await;
+                }
+                if let Some(hook) = hook {
+                    hook.await;
+                }
+                committed
+            }
+        })
+        .await;
+        let mut published = Vec::new();
+        while let Ok(event) = subscriber.try_recv() {
+            published.push(event);
+        }
+        (result, published)
+    }
+
+    fn no_hook() -> std::pin::Pin<Box<dyn std::future::Future<Output = ()>>> {
+        Box::pin(async {})
+    }
+
+    /// Re-indexes in batches of two. Keep the trigger alive: once it is dropped, shutdown
+    /// reads as requested.
+    fn control() -> (supervisor::ShutdownTrigger, Arc<IngesterControl>) {
+        let (trigger, shutdown) = supervisor::shutdown_channel();
+        (trigger, IngesterControl::new(2, 4, shutdown))
+    }
+
+    fn kinds(events: &[Arc<ChainEvent>]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event.as_ref() {
+                ChainEvent::BlockCommitted { block_number, .. } => {
+                    format!("committed {}", block_number)
+                }
+                ChainEvent::Reorg { fork_height } => format!("reorg {}", fork_height),
+                ChainEvent::Retract {
+                    from_block,
+                    to_block,
+                } => {
+                    format!("retract {}..{}", from_block, to_block)
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reindex_keeps_the_blocks_above_an_unchanged_top() {
+        let db = test_db("unchanged").await;
+        let (_trigger, control) = control();
+        let (result, events) = run_reindex(&db, &control, (103, 106), 0, u64::MAX, no_hook).await;
+        result.unwrap();
+        assert_eq!(kinds(&events), vec!["retract 103..106"]);
+        assert_eq!(db.hash_at(110).await, Some(format!("{:#x}", hash(110, 0))));
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(110));
+    }
+
+    #[tokio::test]
+    async fn reindex_rolls_back_above_a_changed_top() {
+        let db = test_db("changed").await;
+        let (_trigger, control) = control();
+        let (result, events) = run_reindex(&db, &control, (103, 106), 1, u64::MAX, no_hook).await;
+        result.unwrap();
+        assert_eq!(kinds(&events), vec!["retract 103..106", "reorg 107"]);
+        assert_eq!(db.hash_at(106).await, Some(format!("{:#x}", hash(106, 1))));
+        assert_eq!(db.hash_at(107).await, None);
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(106));
+    }
+
+    #[tokio::test]
+    async fn reindex_shortfall_rolls_back_from_the_first_missing_block() {
+        let db = test_db("shortfall").await;
+        let (_trigger, control) = control();
+        // The second batch, 105-106, commits only 105.
+        let (result, events) = run_reindex(&db, &control, (103, 106), 0, 3, no_hook).await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("stopped at block 106"), "{}", error);
+        assert_eq!(kinds(&events), vec!["retract 103..106", "reorg 106"]);
+        assert_eq!(db.hash_at(105).await, Some(format!("{:#x}", hash(105, 0))));
+        assert_eq!(db.hash_at(106).await, None);
+        assert_eq!(db.hash_at(110).await, None);
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(105));
+        assert_eq!(control.next_command(), None);
+    }
+
+    #[tokio::test]
+    async fn reindex_queues_a_rewind_when_the_shortfall_rollback_fails() {
+        let db = test_db("rollback").await;
+        let (_trigger, control) = control();
+        control.requeue(Command::Reindex { from: 1, to: 2 });
+        // Breaks the database just before the rollback runs.
+        let url = db.url.clone();
+        let break_db = move || -> std::pin::Pin<Box<dyn std::future::Future<Output = ()>>> {
+            let url = url.clone();
+            Box::pin(async move {
+                let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+                sqlx::query("DROP TABLE stats_counters")
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                pool.close().await;
+            })
+        };
+        let (result, events) = run_reindex(&db, &control, (103, 106), 0, 1, break_db).await;
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("rollback failed"), "{}", error);
+        assert_eq!(kinds(&events), vec!["retract 103..106"]);
+        assert_eq!(
+            control.next_command(),
+            Some(Command::Rewind { height: 104 })
+        );
+        assert_eq!(
+            control.next_command(),
+            Some(Command::Reindex { from: 1, to: 2 })
+        );
+    }
 }
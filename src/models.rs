@@ -16,12 +16,18 @@ pub struct MyLog {
     pub block_number: u64,
     #[schema(value_type = String, example = "0x...")]
     pub block_hash: H256,
+    /// Unix timestamp (seconds) of the block.
+    #[schema(example = 1694035835)]
+    pub block_timestamp: Option<u64>,
     #[schema(value_type = String, example = "0x...")]
     pub address: Address,
     #[schema(example = "0x0000...")]
     pub data: String,
     #[schema(example = json!(["0x...", "0x..."]))]
     pub topics: Vec<String>,
+    /// Primary ENS name of `address`; only set when ENS names are requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_ens_name: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
@@ -52,6 +58,9 @@ pub struct MyTransaction {
     pub block_number: U64,
     #[schema(value_type = String, example = "0x...")]
     pub block_hash: H256,
+    /// Unix timestamp (seconds) of the block.
+    #[schema(example = 1694035835)]
+    pub block_timestamp: Option<u64>,
     #[schema(value_type = Option<u64>, example = 100)]
     pub transaction_index: Option<U64>,
     #[schema(value_type = String, example = "0x...")]
@@ -68,7 +77,32 @@ pub struct MyTransaction {
     pub max_priority_fee_per_gas: Option<U256>,
     #[schema(value_type = String, example = "21000")]
     pub gas: U256,
+    /// Sender nonce; `None` for rows indexed before it was stored.
+    #[schema(value_type = Option<String>, example = "42")]
+    pub nonce: Option<U256>,
     #[schema(example = "0x...")]
     pub input_data: String,
     pub status: Option<u64>,
+    /// Gas used by the transaction, from its receipt.
+    #[schema(value_type = Option<String>, example = "21000")]
+    pub gas_used: Option<U256>,
+    /// Price per gas actually paid, from the receipt: base fee plus priority fee for
+    /// EIP-1559 transactions, `gasPrice` otherwise.
+    #[schema(value_type = Option<String>, example = "25000000000")]
+    pub effective_gas_price: Option<U256>,
+    /// EIP-2718 type: 0 legacy, 1 access list, 2 EIP-1559, 3 blob, 4 set-code. `None` for rows
+    /// indexed before it was stored.
+    #[schema(example = 2)]
+    pub transaction_type: Option<u64>,
+    /// Contract deployed by a creation transaction, from its receipt.
+    #[schema(value_type = Option<String>, example = "0x...")]
+    pub contract_address: Option<Address>,
+    /// Only set when USD values are requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_usd: Option<f64>,
+    /// Primary ENS names of the sender and recipient; only set when ENS names are requested.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_ens_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_ens_name: Option<String>,
 }
@@ -0,0 +1,1155 @@
+// src/pipeline.rs
+//
+// Staged ingestion for one block range. Four tasks connected by bounded channels:
+//
+//   headers ──▶ receipts ──▶ transform ──▶ writer
+//
+// The RPC stages keep several blocks in flight (`buffered` preserves order), while the writer
+// commits strictly in block order and validates every parent hash. Channels are small, so
+// once the writer falls behind the upstream stages block on `send` and stop fetching: the
+// database sets the pace. Each stage reports its throughput and how long it spent blocked.
+//
+// With an `IndexFilter` configured (selective indexing), the header stage fetches headers
+// only and the receipt stage asks `eth_getLogs` for the matching logs of each block by hash,
+// then fetches just the transactions (and receipts) those logs belong to.
+//
+// With DEX decoding on, the receipt stage also resolves the pools a block's logs touch and the
+// transform stage decodes their swaps and liquidity events (see dex.rs). Pricing hooks into
+// the same two stages: token decimals and symbols, then prices from the decoded events (see pricing.rs).
+// Contract indexing inspects the contracts a block created in the receipt stage and picks up
+// proxy upgrade events in the transform stage (see contracts.rs). Gas rollups are computed in
+// the transform stage from the block's transactions and receipts (see gas.rs), as is every
+// block's summary for the activity counters and rollups (see stats.rs). ENS indexing decodes
+// registry and resolver events in the transform stage (see ens.rs).
+
+use crate::contracts::{ContractBlock, ContractIndexer, Deployment};
+use crate::dex::{DexBlock, DexDecoder, PoolInfo};
+use crate::ens::{EnsBlock, EnsIndexer};
+use crate::events::{self, ChainEvent, EventSender, IndexedLog};
+use crate::gas::{self, GasBlock};
+use crate::handlers::{HandlerRuntime, HandlerSession};
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::pricing::{BlockPrices, PriceOracle, PricedPools};
+use crate::stats::{self, BlockStats};
+use crate::storage::{Storage, StorageTransaction};
+use crate::supervisor::Shutdown;
+use ethers::providers::ProviderError;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{
+    Address, Block, Filter, Log, Transaction, TransactionReceipt, ValueOrArray, H256, U64,
+};
+use eyre::Result;
+use futures::stream::StreamExt;
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// Blocks fetched ahead of the receipt stage, and blocks whose receipts are fetched at once.
+/// Adjustable at runtime through `PUT /admin/ingester/config`.
+pub const DEFAULT_BLOCKS_IN_FLIGHT: usize = 4;
+const MAX_RECEIPT_CONCURRENT: usize = 10; // Max parallel receipt fetches per block
+const STAGE_CHANNEL_CAPACITY: usize = 8;
+const MAX_BLOCK_FETCH_RETRIES: u32 = 3;
+const BASE_BLOCK_FETCH_BACKOFF_SECONDS: u64 = 2;
+
+/// Selective indexing: only logs emitted by `addresses` (and, when non-empty, whose topic0 is
+/// in `topic0`) are stored, together with their transactions. Block headers are still stored
+/// for every height so parent-hash reorg detection keeps working.
+#[derive(Clone, Debug)]
+pub struct IndexFilter {
+    pub addresses: Vec<Address>,
+    pub topic0: Vec<H256>,
+}
+
+impl IndexFilter {
+    /// Reads `INDEX_ADDRESSES` and `INDEX_TOPIC0` (comma-separated). Returns `None` when no
+    /// addresses are configured, i.e. full indexing.
+    pub fn from_env() -> Result<Option<Self>> {
+        fn parse_list<T: std::str::FromStr>(var: &str) -> Result<Vec<T>>
+        where
+            T::Err: std::fmt::Display,
+        {
+            let raw = env::var(var).unwrap_or_default();
+            raw.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|item| !item.is_empty())
+                .map(|item| {
+                    item.parse::<T>()
+                        .map_err(|e| eyre::eyre!("{}: invalid entry '{}': {}", var, item, e))
+                })
+                .collect()
+        }
+
+        let addresses: Vec<Address> = parse_list("INDEX_ADDRESSES")?;
+        let topic0: Vec<H256> = parse_list("INDEX_TOPIC0")?;
+        if addresses.is_empty() {
+            if !topic0.is_empty() {
+                eyre::bail!("INDEX_TOPIC0 requires INDEX_ADDRESSES to be set");
+            }
+            return Ok(None);
+        }
+        Ok(Some(IndexFilter { addresses, topic0 }))
+    }
+
+    fn logs_filter(&self, block_hash: H256) -> Filter {
+        let filter = Filter::new()
+            .at_block_hash(block_hash)
+            .address(ValueOrArray::Array(self.addresses.clone()));
+        if self.topic0.is_empty() {
+            filter
+        } else {
+            filter.topic0(ValueOrArray::Array(
+                self.topic0.iter().copied().map(Some).collect(),
+            ))
+        }
+    }
+}
+
+/// Optional ingestion features, fixed for the lifetime of the ingester.
+#[derive(Clone, Default)]
+pub struct IngestOptions {
+    pub filter: Option<Arc<IndexFilter>>,
+    /// User-defined event handlers, run by the writer inside each block's transaction.
+    pub handlers: Option<Arc<HandlerRuntime>>,
+    /// Built-in Uniswap V2/V3 decoding into the `dex_*` tables.
+    pub dex: Option<Arc<DexDecoder>>,
+    /// On-chain token prices; only set together with `dex`.
+    pub pricing: Option<Arc<PriceOracle>>,
+    /// Contract deployments and proxies into the `contracts` tables.
+    pub contracts: Option<Arc<ContractIndexer>>,
+    /// ENS registry and resolver events into `ens_names`.
+    pub ens: Option<Arc<EnsIndexer>>,
+    /// Per-block and hourly fee-market rollups into the `gas_*` tables.
+    pub gas_rollups: bool,
+    /// Hourly and daily activity rollups into the `stats_*` tables. The totals behind
+    /// `/stats` are kept either way.
+    pub stats_rollups: bool,
+}
+
+/// Output of the header stage. `transactions` is empty in selective mode.
+struct Header {
+    block: MyBlock,
+    transactions: Vec<Transaction>,
+}
+
+struct BlockWithReceipts {
+    block: MyBlock,
+    receipts: Vec<(Transaction, Option<TransactionReceipt>)>,
+    logs: Vec<Log>,
+    /// Pools touched by `logs`; empty unless DEX decoding is on.
+    dex_pools: HashMap<Address, PoolInfo>,
+    /// The WETH pools that may set prices and their tokens; empty unless pricing is on.
+    priced: PricedPools,
+    /// Contracts created by the block's transactions; empty unless contract indexing is on.
+    deployments: Vec<Deployment>,
+}
+
+struct BlockData {
+    block: MyBlock,
+    transactions: Vec<MyTransaction>,
+    logs: Vec<MyLog>,
+    dex: Option<DexBlock>,
+    prices: Option<BlockPrices>,
+    contracts: Option<ContractBlock>,
+    ens: Option<EnsBlock>,
+    gas: Option<GasBlock>,
+    stats: BlockStats,
+}
+
+/// Per-stage counters, logged when the range finishes.
+struct StageReport {
+    stage: &'static str,
+    blocks: u64,
+    items: u64,
+    item_label: &'static str,
+    /// Time spent waiting on a neighbour: a full output channel for the fetch stages
+    /// (backpressure), an empty input channel for the writer (starvation).
+    blocked: Duration,
+}
+
+impl StageReport {
+    fn new(stage: &'static str, item_label: &'static str) -> Self {
+        StageReport {
+            stage,
+            blocks: 0,
+            items: 0,
+            item_label,
+            blocked: Duration::ZERO,
+        }
+    }
+
+    fn log(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        info!(
+            "PIPELINE {:<9} {} blocks ({:.1}/s), {} {} ({:.1}/s), blocked {:.1}s",
+            self.stage,
+            self.blocks,
+            self.blocks as f64 / secs,
+            self.items,
+            self.item_label,
+            self.items as f64 / secs,
+            self.blocked.as_secs_f64()
+        );
+    }
+}
+
+/// Sends downstream, recording how long the stage was held up by a full channel.
+/// Returns false once the receiver is gone (the writer stopped early).
+async fn send_timed<T>(tx: &mpsc::Sender<T>, value: T, report: &mut StageReport) -> bool {
+    let started = Instant::now();
+    let sent = tx.send(value).await.is_ok();
+    report.blocked += started.elapsed();
+    sent
+}
+
+// Fetches a receipt with retries. Returns None if the RPC returns Ok(None).
+async fn fetch_receipt_with_retry(
+    provider: Arc<Provider<Http>>,
+    tx_hash: H256,
+    block_num: u64,
+) -> Result<Option<TransactionReceipt>> {
+    for attempt in 1..=3u32 {
+        match provider.get_transaction_receipt(tx_hash).await {
+            Ok(r) => return Ok(r),
+            Err(e) => {
+                if attempt == 3 {
+                    return Err(eyre::eyre!(
+                        "Failed to fetch receipt for tx {:?} in block {} after 3 attempts: {:?}",
+                        tx_hash,
+                        block_num,
+                        e
+                    ));
+                }
+                let backoff = Duration::from_secs(2_u64.pow(attempt - 1));
+                warn!(
+                    "Receipt fetch attempt {}/3 for {:?} failed: {}. Retrying in {}s...",
+                    attempt,
+                    tx_hash,
+                    e,
+                    backoff.as_secs()
+                );
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+    unreachable!()
+}
+
+fn to_my_block<T>(block: &Block<T>) -> MyBlock {
+    MyBlock {
+        block_number: block.number.unwrap_or_default(),
+        block_hash: block.hash.unwrap_or_default(),
+        parent_hash: block.parent_hash,
+        timestamp: block.timestamp,
+        gas_used: block.gas_used,
+        gas_limit: block.gas_limit,
+        base_fee_per_gas: block.base_fee_per_gas,
+    }
+}
+
+async fn fetch_header(
+    provider: &Provider<Http>,
+    block_num: u64,
+    with_transactions: bool,
+) -> Result<Option<Header>, ProviderError> {
+    let number = U64::from(block_num);
+    if with_transactions {
+        Ok(provider.get_block_with_txs(number).await?.map(|b| Header {
+            block: to_my_block(&b),
+            transactions: b.transactions,
+        }))
+    } else {
+        Ok(provider.get_block(number).await?.map(|b| Header {
+            block: to_my_block(&b),
+            transactions: Vec::new(),
+        }))
+    }
+}
+
+/// Fetches a block header, with full transactions unless `with_transactions` is false.
+/// `Ok(None)` means the node doesn't have it yet.
+async fn fetch_block_with_retry(
+    provider: Arc<Provider<Http>>,
+    block_num: u64,
+    with_transactions: bool,
+) -> Result<Option<Header>> {
+    for attempt in 1..=MAX_BLOCK_FETCH_RETRIES {
+        match fetch_header(&provider, block_num, with_transactions).await {
+            Ok(block) => return Ok(block),
+            Err(e) => {
+                error!(
+                    "Attempt {}/{} failed to fetch block #{}: {:?}.",
+                    attempt, MAX_BLOCK_FETCH_RETRIES, block_num, e
+                );
+                if attempt == MAX_BLOCK_FETCH_RETRIES {
+                    return Err(eyre::eyre!(
+                        "giving up on block #{} after {} attempts",
+                        block_num,
+                        MAX_BLOCK_FETCH_RETRIES
+                    ));
+                }
+                let backoff =
+                    Duration::from_secs(BASE_BLOCK_FETCH_BACKOFF_SECONDS * 2_u64.pow(attempt - 1));
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Retries a single RPC call with the block-fetch backoff schedule.
+pub(crate) async fn retry_rpc<T, Fut>(what: &str, mut call: impl FnMut() -> Fut) -> Result<T>
+where
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    for attempt in 1..=MAX_BLOCK_FETCH_RETRIES {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt == MAX_BLOCK_FETCH_RETRIES => {
+                return Err(eyre::eyre!(
+                    "giving up on {} after {} attempts: {}",
+                    what,
+                    MAX_BLOCK_FETCH_RETRIES,
+                    e
+                ));
+            }
+            Err(e) => {
+                warn!(
+                    "Attempt {}/{} for {} failed: {}.",
+                    attempt, MAX_BLOCK_FETCH_RETRIES, what, e
+                );
+                let backoff =
+                    Duration::from_secs(BASE_BLOCK_FETCH_BACKOFF_SECONDS * 2_u64.pow(attempt - 1));
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Receipts for every transaction in the block. A receipt that can't be fetched is stored as
+/// missing (no status, no logs), as the ingester always has.
+async fn fetch_all_receipts(provider: Arc<Provider<Http>>, header: Header) -> BlockWithReceipts {
+    let block_num = header.block.block_number.as_u64();
+    let receipts: Vec<(Transaction, Option<TransactionReceipt>)> =
+        futures::stream::iter(header.transactions)
+            .map(|tx| {
+                let provider = provider.clone();
+                async move {
+                    let receipt = fetch_receipt_with_retry(provider, tx.hash, block_num)
+                        .await
+                        .unwrap_or(None);
+                    (tx, receipt)
+                }
+            })
+            .buffer_unordered(MAX_RECEIPT_CONCURRENT)
+            .collect()
+            .await;
+    let logs = receipts
+        .iter()
+        .filter_map(|(_, receipt)| receipt.as_ref())
+        .flat_map(|receipt| receipt.logs.iter().cloned())
+        .collect();
+    BlockWithReceipts {
+        block: header.block,
+        receipts,
+        logs,
+        dex_pools: HashMap::new(),
+        priced: PricedPools::default(),
+        deployments: Vec::new(),
+    }
+}
+
+/// Selective mode: the block's matching logs via `eth_getLogs` (pinned to the block hash, so
+/// they can't come from a different fork than the header), then only the transactions and
+/// receipts those logs belong to. Unlike full mode, any failure here fails the block: a
+/// silently missing log would never be backfilled.
+async fn fetch_matching(
+    provider: Arc<Provider<Http>>,
+    filter: &IndexFilter,
+    header: Header,
+) -> Result<BlockWithReceipts> {
+    let block_num = header.block.block_number.as_u64();
+    let block_hash = header.block.block_hash;
+    let logs_filter = filter.logs_filter(block_hash);
+    let logs = retry_rpc(&format!("logs for block #{}", block_num), || {
+        provider.get_logs(&logs_filter)
+    })
+    .await?;
+
+    let mut tx_hashes: Vec<H256> = logs.iter().filter_map(|l| l.transaction_hash).collect();
+    tx_hashes.sort();
+    tx_hashes.dedup();
+
+    let receipts = futures::stream::iter(tx_hashes)
+        .map(|tx_hash| {
+            let provider = provider.clone();
+            async move {
+                let tx = retry_rpc(&format!("tx {:?}", tx_hash), || {
+                    provider.get_transaction(tx_hash)
+                })
+                .await?
+                .filter(|tx| tx.block_hash == Some(block_hash))
+                .ok_or_else(|| {
+                    eyre::eyre!("tx {:?} is no longer in block #{}", tx_hash, block_num)
+                })?;
+                let receipt = fetch_receipt_with_retry(provider, tx_hash, block_num)
+                    .await?
+                    .ok_or_else(|| {
+                        eyre::eyre!("no receipt for tx {:?} in block #{}", tx_hash, block_num)
+                    })?;
+                Ok::<_, eyre::Report>((tx, Some(receipt)))
+            }
+        })
+        .buffer_unordered(MAX_RECEIPT_CONCURRENT)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(BlockWithReceipts {
+        block: header.block,
+        receipts,
+        logs,
+        dex_pools: HashMap::new(),
+        priced: PricedPools::default(),
+        deployments: Vec::new(),
+    })
+}
+
+/// Stage 1: block headers (with full transactions unless filtering), in order, several
+/// requests ahead. Stops at the first block that can't be fetched so the range never has gaps.
+async fn header_stage(
+    provider: Arc<Provider<Http>>,
+    blocks: RangeInclusive<u64>,
+    with_transactions: bool,
+    blocks_in_flight: usize,
+    out: mpsc::Sender<Header>,
+) -> StageReport {
+    let mut report = StageReport::new("headers", "txs");
+    let mut blocks = futures::stream::iter(blocks)
+        .map(|n| {
+            let provider = provider.clone();
+            async move {
+                (
+                    n,
+                    fetch_block_with_retry(provider, n, with_transactions).await,
+                )
+            }
+        })
+        .buffered(blocks_in_flight);
+
+    while let Some((block_num, result)) = blocks.next().await {
+        let block = match result {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                warn!("Block #{} not found. Ending range early.", block_num);
+                break;
+            }
+            Err(e) => {
+                error!("{}. Ending range early.", e);
+                break;
+            }
+        };
+        report.blocks += 1;
+        report.items += block.transactions.len() as u64;
+        if !send_timed(&out, block, &mut report).await {
+            break;
+        }
+    }
+    report
+}
+
+/// Stage 2: receipts (or, when filtering, matching logs and their transactions),
+/// `blocks_in_flight` blocks at a time, plus the DEX pools the logs touch, the
+/// decimals and symbols of the tokens they trade and the contracts the block created.
+async fn receipt_stage(
+    provider: Arc<Provider<Http>>,
+    options: IngestOptions,
+    blocks_in_flight: usize,
+    input: mpsc::Receiver<Header>,
+    out: mpsc::Sender<BlockWithReceipts>,
+) -> StageReport {
+    let mut report = StageReport::new("receipts", "receipts");
+    let mut blocks = Box::pin(
+        receiver_stream(input)
+            .map(|header| {
+                let provider = provider.clone();
+                let filter = options.filter.clone();
+                let dex = options.dex.clone();
+                let pricing = options.pricing.clone();
+                let contracts = options.contracts.clone();
+                async move {
+                    let block_num = header.block.block_number.as_u64();
+                    let fetched = async {
+                        let mut fetched = match filter {
+                            Some(filter) => {
+                                fetch_matching(provider.clone(), &filter, header).await?
+                            }
+                            None => fetch_all_receipts(provider.clone(), header).await,
+                        };
+                        if let Some(dex) = dex {
+                            fetched.dex_pools = dex.resolve_pools(&provider, &fetched.logs).await?;
+                        }
+                        if let Some(pricing) = pricing {
+                            fetched.priced =
+                                pricing.resolve_pools(&provider, &fetched.dex_pools).await?;
+                        }
+                        if let Some(contracts) = contracts {
+                            fetched.deployments = contracts
+                                .collect_deployments(&provider, &fetched.block, &fetched.receipts)
+                                .await?;
+                        }
+                        Ok::<_, eyre::Report>(fetched)
+                    }
+                    .await;
+                    (block_num, fetched)
+                }
+            })
+            .buffered(blocks_in_flight),
+    );
+
+    while let Some((block_num, result)) = blocks.next().await {
+        let fetched = match result {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                error!("Block #{}: {}. Ending range early.", block_num, e);
+                break;
+            }
+        };
+        report.blocks += 1;
+        report.items += fetched.receipts.iter().filter(|(_, r)| r.is_some()).count() as u64;
+        if !send_timed(&out, fetched, &mut report).await {
+            break;
+        }
+    }
+    report
+}
+
+/// Adapts an mpsc receiver into a `Stream`.
+fn receiver_stream<T: Send + 'static>(
+    receiver: mpsc::Receiver<T>,
+) -> impl futures::Stream<Item = T> {
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    })
+}
+
+/// Stage 3: ethers types into rows, in block order. `with_transactions` is false in selective
+/// mode, where only some of a block's transactions are fetched.
+async fn transform_stage(
+    options: IngestOptions,
+    with_transactions: bool,
+    mut input: mpsc::Receiver<BlockWithReceipts>,
+    out: mpsc::Sender<BlockData>,
+) -> StageReport {
+    let mut report = StageReport::new("transform", "logs");
+    let gas_rollups = options.gas_rollups.then_some(with_transactions);
+    while let Some(fetched) = input.recv().await {
+        let data = to_block_data(
+            fetched,
+            options.dex.as_deref(),
+            options.pricing.as_deref(),
+            options.contracts.as_deref(),
+            options.ens.as_deref(),
+            gas_rollups,
+            options.stats_rollups,
+        );
+        report.blocks += 1;
+        report.items += data.logs.len() as u64;
+        if !send_timed(&out, data, &mut report).await {
+            break;
+        }
+    }
+    report
+}
+
+/// `gas_rollups` is `Some(all_transactions)` when rollups are on.
+fn to_block_data(
+    fetched: BlockWithReceipts,
+    dex: Option<&DexDecoder>,
+    pricing: Option<&PriceOracle>,
+    contracts: Option<&ContractIndexer>,
+    ens: Option<&EnsIndexer>,
+    gas_rollups: Option<bool>,
+    stats_rollups: bool,
+) -> BlockData {
+    // Receipts arrive out of order from buffer_unordered; write in block order.
+    let mut ethers_logs = fetched.logs;
+    ethers_logs.sort_by_key(|log| (log.transaction_index, log.log_index));
+    let dex = dex.and_then(|decoder| {
+        let senders: HashMap<H256, Address> = fetched
+            .receipts
+            .iter()
+            .map(|(tx, _)| (tx.hash, tx.from))
+            .collect();
+        decoder.decode_block(&fetched.block, &ethers_logs, &senders, &fetched.dex_pools)
+    });
+    let prices = pricing
+        .zip(dex.as_ref())
+        .and_then(|(oracle, dex)| oracle.price_block(dex, &fetched.priced));
+    let contracts = contracts.and_then(|indexer| {
+        indexer.decode_block(&fetched.block, fetched.deployments, &ethers_logs)
+    });
+    let ens = ens.and_then(|indexer| indexer.decode_block(&fetched.block, &ethers_logs));
+
+    let block_timestamp = Some(fetched.block.timestamp.as_u64());
+    let mut transactions = Vec::with_capacity(fetched.receipts.len());
+    for (ethers_tx, receipt_opt) in fetched.receipts {
+        let status = receipt_opt
+            .as_ref()
+            .and_then(|r| r.status)
+            .map(|s| s.as_u64());
+        transactions.push(MyTransaction {
+            tx_hash: ethers_tx.hash,
+            block_number: ethers_tx.block_number.unwrap_or_default(),
+            block_hash: ethers_tx.block_hash.unwrap_or_default(),
+            block_timestamp,
+            transaction_index: ethers_tx.transaction_index,
+            from_address: ethers_tx.from,
+            to_address: ethers_tx.to,
+            value: ethers_tx.value,
+            gas_price: ethers_tx.gas_price,
+            max_fee_per_gas: ethers_tx.max_fee_per_gas,
+            max_priority_fee_per_gas: ethers_tx.max_priority_fee_per_gas,
+            gas: ethers_tx.gas,
+            nonce: Some(ethers_tx.nonce),
+            input_data: ethers_tx.input.to_string(),
+            status,
+            gas_used: receipt_opt.as_ref().and_then(|r| r.gas_used),
+            effective_gas_price: receipt_opt.as_ref().and_then(|r| r.effective_gas_price),
+            // Nodes report "0x0" for legacy transactions too.
+            transaction_type: ethers_tx.transaction_type.map(|t| t.as_u64()),
+            contract_address: receipt_opt.as_ref().and_then(|r| r.contract_address),
+            value_usd: None,
+            from_ens_name: None,
+            to_ens_name: None,
+        });
+    }
+    let logs: Vec<MyLog> = ethers_logs
+        .iter()
+        .map(|ethers_log| MyLog {
+            log_index: ethers_log.log_index,
+            transaction_hash: ethers_log.transaction_hash.unwrap_or_default(),
+            transaction_index: ethers_log.transaction_index.map(|i| i.as_u64()),
+            block_number: ethers_log.block_number.map_or(0, |bn| bn.as_u64()),
+            block_hash: ethers_log.block_hash.unwrap_or_default(),
+            block_timestamp,
+            address: ethers_log.address,
+            data: ethers_log.data.to_string(),
+            topics: ethers_log
+                .topics
+                .iter()
+                .map(|h| format!("{:#x}", h))
+                .collect(),
+            address_ens_name: None,
+        })
+        .collect();
+    transactions.sort_by_key(|tx| tx.transaction_index);
+    let gas = gas_rollups
+        .map(|all_transactions| gas::rollup_block(&fetched.block, &transactions, all_transactions));
+    let stats = stats::summarize_block(&fetched.block, &transactions, &logs, stats_rollups);
+
+    BlockData {
+        block: fetched.block,
+        transactions,
+        logs,
+        dex,
+        prices,
+        contracts,
+        ens,
+        gas,
+        stats,
+    }
+}
+
+/// Commits the open DB transaction, then publishes the events of the blocks it contained.
+/// Returns the number of blocks committed.
+async fn commit_pending(
+    db_tx: Option<Box<dyn StorageTransaction>>,
+    pending_events: &mut Vec<ChainEvent>,
+    events: &EventSender,
+    chain_head: u64,
+) -> Result<u64> {
+    let Some(db_tx) = db_tx else {
+        pending_events.clear();
+        return Ok(0);
+    };
+    let committed: Vec<ChainEvent> = std::mem::take(pending_events);
+    let block_number = |event: &ChainEvent| match event {
+        ChainEvent::BlockCommitted { block_number, .. } => *block_number,
+        ChainEvent::Reorg { fork_height } => *fork_height,
+        ChainEvent::Retract { from_block, .. } => *from_block,
+    };
+    let (first, last) = match (committed.first(), committed.last()) {
+        (Some(first), Some(last)) => (block_number(first), block_number(last)),
+        _ => return Ok(0),
+    };
+    db_tx
+        .commit()
+        .await
+        .map_err(|e| eyre::eyre!("DB: commit blocks #{}..#{}: {}", first, last, e))?;
+
+    if first == last {
+        info!(
+            "✓ Block #{} committed (lag: {} blocks behind chain head).",
+            last,
+            chain_head.saturating_sub(last)
+        );
+    } else {
+        info!(
+            "✓ Blocks #{}..#{} committed in one transaction (lag: {} blocks behind chain head).",
+            first,
+            last,
+            chain_head.saturating_sub(last)
+        );
+    }
+    let count = committed.len() as u64;
+    for event in committed {
+        events::publish(events, event);
+    }
+    Ok(count)
+}
+
+async fn write_block(
+    db_tx: &mut dyn StorageTransaction,
+    data: &BlockData,
+    chain_head: u64,
+    handlers: Option<&mut HandlerSession>,
+) -> Result<Vec<i64>> {
+    let block_num = data.block.block_number.as_u64();
+    info!(
+        "  Block #{}: writing {} txs and {} logs...",
+        block_num,
+        data.transactions.len(),
+        data.logs.len()
+    );
+    db_tx
+        .insert_block_data(&data.block)
+        .await
+        .map_err(|e| eyre::eyre!("DB: insert block #{}: {}", block_num, e))?;
+    db_tx
+        .insert_transactions_batch(&data.transactions)
+        .await
+        .map_err(|e| eyre::eyre!("DB: insert txs for block #{}: {}", block_num, e))?;
+    let log_ids = db_tx
+        .insert_logs_batch(&data.logs)
+        .await
+        .map_err(|e| eyre::eyre!("DB: insert logs for block #{}: {}", block_num, e))?;
+    db_tx
+        .insert_block_stats(&data.stats)
+        .await
+        .map_err(|e| eyre::eyre!("DB: update stats for block #{}: {}", block_num, e))?;
+    if let Some(dex) = &data.dex {
+        db_tx
+            .insert_dex_block(dex, &log_ids)
+            .await
+            .map_err(|e| eyre::eyre!("DB: insert DEX rows for block #{}: {}", block_num, e))?;
+    }
+    if let Some(prices) = &data.prices {
+        db_tx
+            .insert_prices(prices)
+            .await
+            .map_err(|e| eyre::eyre!("DB: insert prices for block #{}: {}", block_num, e))?;
+    }
+    if let Some(gas) = &data.gas {
+        db_tx
+            .insert_gas_block(gas)
+            .await
+            .map_err(|e| eyre::eyre!("DB: insert gas rollup for block #{}: {}", block_num, e))?;
+    }
+    if let Some(contracts) = &data.contracts {
+        db_tx
+            .insert_contracts(contracts, &log_ids)
+            .await
+            .map_err(|e| eyre::eyre!("DB: insert contracts for block #{}: {}", block_num, e))?;
+    }
+    if let Some(ens) = &data.ens {
+        db_tx
+            .insert_ens(ens, &log_ids)
+            .await
+            .map_err(|e| eyre::eyre!("DB: insert ENS records for block #{}: {}", block_num, e))?;
+    }
+    if let Some(handlers) = handlers {
+        handlers
+            .run_block(db_tx, block_num, &data.logs, &log_ids)
+            .await?;
+    }
+    db_tx
+        .set_last_synced_block(block_num, chain_head)
+        .await
+        .map_err(|e| eyre::eyre!("DB: set_last_synced_block #{}: {}", block_num, e))?;
+    Ok(log_ids)
+}
+
+/// Stage 4: parent-hash validation and in-order writes, `blocks_per_commit` blocks per DB
+/// transaction. Returning drops the input channel, which winds down the upstream stages.
+/// Stops at a block boundary once `shutdown` is requested. Its report counts committed blocks
+/// and rows only.
+async fn writer_stage(
+    storage: &dyn Storage,
+    events: &EventSender,
+    mut input: mpsc::Receiver<BlockData>,
+    chain_head: u64,
+    blocks_per_commit: u64,
+    handler_runtime: Option<&HandlerRuntime>,
+    shutdown: &Shutdown,
+) -> StageReport {
+    let mut report = StageReport::new("writer", "rows");
+    let mut handlers = match handler_runtime {
+        Some(runtime) => match runtime.session().await {
+            Ok(session) => session,
+            Err(e) => {
+                error!(
+                    "INGESTER: loading event handlers failed: {}. Retrying next cycle.",
+                    e
+                );
+                return report;
+            }
+        },
+        None => None,
+    };
+    // Open DB transaction and the events it will publish once committed.
+    let mut pending: Option<Box<dyn StorageTransaction>> = None;
+    let mut pending_events: Vec<ChainEvent> = Vec::new();
+    // Rows written into `pending`, counted once it commits.
+    let mut pending_rows: u64 = 0;
+    // Hash of the last block written, which may not be committed yet.
+    let mut previous_hash: Option<H256> = None;
+
+    loop {
+        let waiting = Instant::now();
+        // On shutdown, stop taking blocks; the group written so far is still committed below.
+        let data = tokio::select! {
+            biased;
+            _ = shutdown.requested() => None,
+            data = input.recv() => data,
+        };
+        let Some(data) = data else {
+            break;
+        };
+        report.blocked += waiting.elapsed();
+        let block_num = data.block.block_number.as_u64();
+
+        // --- Reorg detection: validate parent_hash ---
+        if let Some(previous) = previous_hash {
+            if data.block.parent_hash != previous {
+                // The chain moved under this range. Drop the uncommitted blocks; the next
+                // cycle checks against the database and rolls back as usual.
+                warn!(
+                    "REORG DETECTED at height {} mid-cycle. Discarding uncommitted blocks.",
+                    block_num
+                );
+                pending = None;
+                pending_events.clear();
+                break;
+            }
+        } else if block_num > 0 {
+            let stored_hash = storage
+                .get_canonical_block_hash_at_height(block_num - 1)
+                .await;
+            if let Ok(Some(stored)) = stored_hash {
+                let parent = format!("{:#x}", data.block.parent_hash);
+                if parent != stored {
+                    warn!(
+                        "REORG DETECTED at height {}! Expected parent {}, got {}. Rolling back from height {}.",
+                        block_num, stored, parent, block_num - 1
+                    );
+                    if let Err(e) = storage.rollback_from_height(block_num - 1).await {
+                        error!("Rollback failed: {}. Stopping ingester cycle.", e);
+                        break;
+                    }
+                    events::publish(
+                        events,
+                        ChainEvent::Reorg {
+                            fork_height: block_num - 1,
+                        },
+                    );
+                    info!(
+                        "Rollback complete. Re-ingesting from height {}.",
+                        block_num - 1
+                    );
+                    // Skip this block; next cycle will re-fetch from correct height
+                    break;
+                }
+            }
+        }
+
+        // --- Batched DB writes into the open (possibly multi-block) transaction ---
+        let mut db_tx = match pending.take() {
+            Some(db_tx) => db_tx,
+            None => match storage.begin().await {
+                Ok(db_tx) => db_tx,
+                Err(e) => {
+                    error!(
+                        "INGESTER: DB: begin tx for block #{}: {}. Retrying next cycle.",
+                        block_num, e
+                    );
+                    pending_events.clear();
+                    break;
+                }
+            },
+        };
+        match write_block(db_tx.as_mut(), &data, chain_head, handlers.as_mut()).await {
+            Ok(log_ids) => {
+                pending_rows += 1 + data.transactions.len() as u64 + data.logs.len() as u64;
+                pending = Some(db_tx);
+                previous_hash = Some(data.block.block_hash);
+                pending_events.push(ChainEvent::BlockCommitted {
+                    block_number: block_num,
+                    block_hash: data.block.block_hash,
+                    transactions: data.transactions,
+                    logs: log_ids
+                        .into_iter()
+                        .zip(data.logs)
+                        .map(|(id, log)| IndexedLog { id, log })
+                        .collect(),
+                });
+            }
+            Err(e) => {
+                error!(
+                    "INGESTER: block #{} failed: {}. Rolled back. Retrying next cycle.",
+                    block_num, e
+                );
+                pending_events.clear();
+                break;
+            }
+        }
+
+        if pending_events.len() as u64 >= blocks_per_commit {
+            match commit_pending(pending.take(), &mut pending_events, events, chain_head).await {
+                Ok(committed) => {
+                    report.blocks += committed;
+                    report.items += std::mem::take(&mut pending_rows);
+                }
+                Err(e) => {
+                    error!("INGESTER: {}. Rolled back. Retrying next cycle.", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Flush a partial group left by the end of the range or an early stop. Blocks written
+    // into a group that was discarded above are dropped here and not counted.
+    match commit_pending(pending.take(), &mut pending_events, events, chain_head).await {
+        Ok(committed) if committed > 0 => {
+            report.blocks += committed;
+            report.items += pending_rows;
+        }
+        Ok(_) => {}
+        Err(e) => error!("INGESTER: {}. Rolled back. Retrying next cycle.", e),
+    }
+    report
+}
+
+/// Per-range pipeline settings.
+#[derive(Clone, Copy, Debug)]
+pub struct RangeSettings {
+    /// Chain head when the range was planned; only used to report the lag.
+    pub chain_head: u64,
+    /// Blocks sharing one DB transaction.
+    pub blocks_per_commit: u64,
+    /// See `DEFAULT_BLOCKS_IN_FLIGHT`.
+    pub blocks_in_flight: usize,
+}
+
+/// Ingests `blocks` through the staged pipeline and returns the number of blocks written
+/// by the writer. Blocks are only counted as ingested once their transaction commits, so after
+/// an early stop the next cycle resumes from the last synced block.
+pub async fn ingest_range(
+    provider: Arc<Provider<Http>>,
+    storage: Arc<dyn Storage>,
+    events: &EventSender,
+    blocks: RangeInclusive<u64>,
+    settings: RangeSettings,
+    options: &IngestOptions,
+    shutdown: &Shutdown,
+) -> u64 {
+    let started = Instant::now();
+    let (header_tx, header_rx) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+    let (receipt_tx, receipt_rx) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+    let (data_tx, data_rx) = mpsc::channel(STAGE_CHANNEL_CAPACITY);
+
+    let with_transactions = options.filter.is_none();
+    let blocks_in_flight = settings.blocks_in_flight.max(1);
+    let headers = tokio::spawn(header_stage(
+        provider.clone(),
+        blocks,
+        with_transactions,
+        blocks_in_flight,
+        header_tx,
+    ));
+    let receipts = tokio::spawn(receipt_stage(
+        provider,
+        options.clone(),
+        blocks_in_flight,
+        header_rx,
+        receipt_tx,
+    ));
+    let transform = tokio::spawn(transform_stage(
+        options.clone(),
+        with_transactions,
+        receipt_rx,
+        data_tx,
+    ));
+    let writer = writer_stage(
+        storage.as_ref(),
+        events,
+        data_rx,
+        settings.chain_head,
+        settings.blocks_per_commit,
+        options.handlers.as_deref(),
+        shutdown,
+    )
+    .await;
+
+    let elapsed = started.elapsed();
+    for stage in [headers, receipts, transform] {
+        // Fetches still in flight (possibly sleeping in a retry) are not worth waiting for.
+        if shutdown.is_requested() {
+            stage.abort();
+        }
+        match stage.await {
+            Ok(report) => report.log(elapsed),
+            Err(e) if e.is_cancelled() => {}
+            Err(e) => error!("PIPELINE: stage task failed: {}", e),
+        }
+    }
+    writer.log(elapsed);
+    writer.blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_support::{self, hash, TestDb};
+    use crate::supervisor::shutdown_channel;
+
+    /// Block `number` of `fork`, extending block `number - 1` of `parent_fork`.
+    fn block(number: u64, fork: u64, parent_fork: u64) -> BlockData {
+        let block = test_support::block(number, fork, parent_fork);
+        let stats = stats::summarize_block(&block, &[], &[], false);
+        BlockData {
+            block,
+            transactions: Vec::new(),
+            logs: Vec::new(),
+            dex: None,
+            prices: None,
+            contracts: None,
+            ens: None,
+            gas: None,
+            stats,
+        }
+    }
+
+    /// Runs the writer over `blocks`, returning its report and the events it published.
+    async fn write(
+        db: &TestDb,
+        blocks: Vec<BlockData>,
+        blocks_per_commit: u64,
+    ) -> (StageReport, Vec<Arc<ChainEvent>>) {
+        let (tx, rx) = mpsc::channel(blocks.len().max(1));
+        for data in blocks {
+            tx.send(data).await.unwrap();
+        }
+        drop(tx);
+        let events = events::channel();
+        let mut subscriber = events.subscribe();
+        let (_trigger, shutdown) = shutdown_channel();
+        let report = writer_stage(
+            db.storage.as_ref(),
+            &events,
+            rx,
+            200,
+            blocks_per_commit,
+            None,
+            &shutdown,
+        )
+        .await;
+        let mut published = Vec::new();
+        while let Ok(event) = subscriber.try_recv() {
+            published.push(event);
+        }
+        (report, published)
+    }
+
+    fn committed_numbers(events: &[Arc<ChainEvent>]) -> Vec<u64> {
+        events
+            .iter()
+            .filter_map(|event| match event.as_ref() {
+                ChainEvent::BlockCommitted { block_number, .. } => Some(*block_number),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn commits_the_blocks_before_a_parent_hash_break() {
+        let db = TestDb::new("writer_break").await;
+        // Block 103 extends a different 102 than the one written.
+        let blocks = vec![
+            block(100, 0, 0),
+            block(101, 0, 0),
+            block(102, 0, 0),
+            block(103, 1, 1),
+            block(104, 1, 1),
+        ];
+        let (report, events) = write(&db, blocks, 1).await;
+        assert_eq!(report.blocks, 3);
+        assert_eq!(report.items, 3);
+        assert_eq!(committed_numbers(&events), vec![100, 101, 102]);
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(102));
+        assert_eq!(db.hash_at(102).await, Some(format!("{:#x}", hash(102, 0))));
+        assert_eq!(db.hash_at(103).await, None);
+    }
+
+    #[tokio::test]
+    async fn discards_the_open_group_at_a_break() {
+        let db = TestDb::new("writer_group").await;
+        // 100-101 commit together; 102 is in the open group when 103 breaks the chain.
+        let blocks = vec![
+            block(100, 0, 0),
+            block(101, 0, 0),
+            block(102, 0, 0),
+            block(103, 1, 1),
+        ];
+        let (report, events) = write(&db, blocks, 2).await;
+        assert_eq!(report.blocks, 2);
+        assert_eq!(committed_numbers(&events), vec![100, 101]);
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(101));
+        assert_eq!(db.hash_at(102).await, None);
+    }
+
+    #[tokio::test]
+    async fn commits_a_partial_group_at_the_end_of_the_range() {
+        let db = TestDb::new("writer_partial").await;
+        let blocks = (100..=104).map(|number| block(number, 0, 0)).collect();
+        let (report, events) = write(&db, blocks, 3).await;
+        assert_eq!(report.blocks, 5);
+        assert_eq!(committed_numbers(&events), vec![100, 101, 102, 103, 104]);
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(104));
+    }
+
+    #[tokio::test]
+    async fn rolls_back_when_the_first_block_does_not_extend_the_stored_chain() {
+        let db = TestDb::new("writer_stored").await;
+        let (report, _) = write(&db, vec![block(100, 0, 0), block(101, 0, 0)], 1).await;
+        assert_eq!(report.blocks, 2);
+
+        // The next range starts on another fork of 101.
+        let (report, events) = write(&db, vec![block(102, 1, 1), block(103, 1, 1)], 1).await;
+        assert_eq!(report.blocks, 0);
+        assert!(matches!(
+            events.as_slice(),
+            [event] if matches!(event.as_ref(), ChainEvent::Reorg { fork_height: 101 })
+        ));
+        assert_eq!(db.storage.get_last_synced_block().await.unwrap(), Some(100));
+        assert_eq!(db.hash_at(101).await, None);
+        assert_eq!(db.hash_at(100).await, Some(format!("{:#x}", hash(100, 0))));
+    }
+}
@@ -0,0 +1,1233 @@
+// src/pricing.rs
+//
+// Token prices derived from the indexed DEX events, with no outside feed. After the transform
+// stage decodes a block's pool events, each pool that pairs a token with WETH is priced at its
+// state at the end of the block: V2 pools from their last Sync reserves, V3 pools from the
+// sqrtPriceX96 of their last swap. When several pools quote the same token, the one with the
+// most WETH behind it wins, and pools below PRICE_MIN_LIQUIDITY_ETH are ignored so a dust
+// pool can't set a price. Only pools of a factory in PRICE_FACTORIES quote at all, and only
+// once that factory returns the pool from `getPair`/`getPool`: a contract of its own making
+// can emit any Sync or Swap and claim any factory, but can't get into the factory's registry.
+// ETH/USD comes from the configured WETH/stablecoin pools, counting
+// the stablecoin as $1; WETH itself is recorded at 1 ETH alongside it.
+//
+// Prices are written in the block's DB transaction and rolled back with it. A USD value at
+// block N is the token's latest ETH price at or before N times the latest ETH/USD at or
+// before N. Token decimals, needed to compare raw amounts, are read once per token with
+// `decimals()` in the receipt stage and kept in the `tokens` table, along with the token's
+// `symbol()` for search.
+//
+// The account endpoints value ERC-20 Transfer logs. Balances are the net of indexed
+// transfers, so they are only complete when indexing started before the account's first
+// transfer (or in selective mode, when Transfer logs of the tokens are indexed).
+
+use crate::api::{ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{
+    AccountBalancesResponse, EthUsdPrice, GenericErrorResponse, GetBalancesQuery,
+    GetPriceHistoryQuery, GetPriceQuery, GetTransfersQuery, TokenBalance, TokenPrice,
+    TokenPriceHistoryResponse, TokenTransfer, TokenTransfersResponse,
+};
+use crate::dex::{call_output, call_word, factory_confirms, DexBlock, DexProtocol, PoolInfo};
+use crate::ens::resolve_address;
+use crate::models::MyTransaction;
+use crate::storage::PostgresStorage;
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::providers::{Http, Provider};
+use ethers::types::{Address, U256};
+use futures::stream::StreamExt;
+use sqlx::{postgres::PgRow, PgPool, QueryBuilder, Row as SqlxRow};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::sync::Mutex;
+use tracing::debug;
+
+/// Mainnet defaults, used when the chain id is 1 and the variables are unset.
+const MAINNET_WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+const MAINNET_USD_POOLS: [&str; 2] = [
+    "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc", // Uniswap V2 USDC/WETH
+    "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640", // Uniswap V3 USDC/WETH 0.05%
+];
+const MAINNET_FACTORIES: [&str; 2] = [
+    "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f", // Uniswap V2
+    "0x1f98431c8ad98523631ae4a59f267346ea31f984", // Uniswap V3
+];
+const DEFAULT_MIN_LIQUIDITY_ETH: f64 = 10.0;
+/// Tokens whose metadata is remembered before the cache starts over.
+const MAX_TOKEN_CACHE_ENTRIES: usize = 100_000;
+/// Pools whose factory check is remembered before the cache starts over.
+const MAX_POOL_CACHE_ENTRIES: usize = 100_000;
+const MAX_TOKEN_LOOKUPS_CONCURRENT: usize = 8;
+/// Longer `symbol()` results are not plausible tickers and are not stored.
+const MAX_SYMBOL_LEN: usize = 32;
+
+const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+/// The uint256 amount of a Transfer log; `logs.data` holds 0x-prefixed hex text.
+const TRANSFER_AMOUNT: &str = "hex_to_numeric(substr(convert_from(l.data, 'UTF8'), 3))";
+
+const TOKEN_PRICE_COLUMNS: &str = "t.token_address, t.price_eth, t.block_number, \
+     t.block_timestamp, t.pool_address, t.liquidity_eth";
+
+/// How finely prices are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceResolution {
+    Block,
+    /// One row per token and minute, holding the minute's last observation.
+    Minute,
+}
+
+impl PriceResolution {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PriceResolution::Block => "block",
+            PriceResolution::Minute => "minute",
+        }
+    }
+
+    fn bucket(self, block_number: u64, block_timestamp: u64) -> i64 {
+        match self {
+            PriceResolution::Block => block_number as i64,
+            PriceResolution::Minute => (block_timestamp - block_timestamp % 60) as i64,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PriceConfig {
+    pub weth: Address,
+    /// WETH/stablecoin pools that set ETH/USD.
+    pub usd_pools: HashSet<Address>,
+    /// Factories whose pools may set prices.
+    pub factories: HashSet<Address>,
+    pub min_liquidity_eth: f64,
+    pub resolution: PriceResolution,
+}
+
+impl PriceConfig {
+    /// Reads `PRICE_WETH`, `PRICE_USD_POOLS` and `PRICE_FACTORIES` (comma-separated),
+    /// `PRICE_MIN_LIQUIDITY_ETH` and `PRICE_RESOLUTION`. WETH, the pools and the factories
+    /// default to mainnet's Uniswap ones on chain 1; elsewhere pricing is off until
+    /// `PRICE_WETH` is set, and then needs `PRICE_FACTORIES`. `PRICING=false` turns it off
+    /// everywhere.
+    pub fn from_env(chain_id: u64) -> eyre::Result<Option<Self>> {
+        if env::var("PRICING").is_ok_and(|v| v == "false" || v == "0") {
+            return Ok(None);
+        }
+        let mainnet = chain_id == 1;
+        let weth = match env::var("PRICE_WETH") {
+            Ok(value) => value
+                .trim()
+                .parse::<Address>()
+                .map_err(|e| eyre::eyre!("PRICE_WETH: invalid address '{}': {}", value, e))?,
+            Err(_) if mainnet => MAINNET_WETH.parse()?,
+            Err(_) => return Ok(None),
+        };
+        let usd_pools = address_list("PRICE_USD_POOLS", mainnet.then_some(&MAINNET_USD_POOLS))?;
+        let factories = address_list("PRICE_FACTORIES", mainnet.then_some(&MAINNET_FACTORIES))?;
+        if factories.is_empty() {
+            eyre::bail!("PRICE_FACTORIES: set the DEX factories whose pools may set prices");
+        }
+        let min_liquidity_eth = match env::var("PRICE_MIN_LIQUIDITY_ETH") {
+            Ok(value) => value
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|min| *min >= 0.0)
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "PRICE_MIN_LIQUIDITY_ETH: expected a number, got '{}'",
+                        value
+                    )
+                })?,
+            Err(_) => DEFAULT_MIN_LIQUIDITY_ETH,
+        };
+        let resolution = match env::var("PRICE_RESOLUTION").as_deref() {
+            Err(_) | Ok("block") => PriceResolution::Block,
+            Ok("minute") => PriceResolution::Minute,
+            Ok(other) => eyre::bail!(
+                "PRICE_RESOLUTION: expected 'block' or 'minute', got '{}'",
+                other
+            ),
+        };
+        Ok(Some(PriceConfig {
+            weth,
+            usd_pools,
+            factories,
+            min_liquidity_eth,
+            resolution,
+        }))
+    }
+}
+
+/// A comma- or space-separated address list from `var`, or `default` when it is unset.
+fn address_list(var: &str, default: Option<&[&str; 2]>) -> eyre::Result<HashSet<Address>> {
+    match env::var(var) {
+        Ok(raw) => raw
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                item.parse::<Address>()
+                    .map_err(|e| eyre::eyre!("{}: invalid entry '{}': {}", var, item, e))
+            })
+            .collect(),
+        Err(_) => Ok(default
+            .into_iter()
+            .flatten()
+            .map(|address| address.parse())
+            .collect::<Result<_, _>>()?),
+    }
+}
+
+/// A token's ETH price according to one pool.
+#[derive(Clone, Debug)]
+pub struct TokenQuote {
+    pub token: Address,
+    pub price_eth: f64,
+    pub pool: Address,
+    /// WETH held by (V2) or active in (V3) the pool, in ETH.
+    pub liquidity_eth: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct EthUsdQuote {
+    pub price_usd: f64,
+    pub pool: Address,
+    pub liquidity_eth: f64,
+}
+
+/// What a token reports about itself; each field is `None` when its getter reverted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenMeta {
+    pub decimals: Option<u8>,
+    pub symbol: Option<String>,
+}
+
+/// What the receipt stage resolves for pricing one block.
+#[derive(Default)]
+pub struct PricedPools {
+    /// The block's WETH pools that may set prices: from an allowed factory that confirmed them.
+    pub pools: HashSet<Address>,
+    /// Decimals and symbols of both tokens of those pools.
+    pub tokens_meta: HashMap<Address, TokenMeta>,
+}
+
+/// What the writer stores for one block.
+pub struct BlockPrices {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub bucket: i64,
+    /// Metadata of every token in the block's WETH pools.
+    pub tokens_meta: Vec<(Address, TokenMeta)>,
+    pub tokens: Vec<TokenQuote>,
+    pub eth_usd: Option<EthUsdQuote>,
+}
+
+/// A pool's state after its last event in the block.
+enum PoolState {
+    Reserves(U256, U256),
+    /// sqrtPriceX96 and in-range liquidity.
+    SqrtPrice(U256, U256),
+}
+
+/// Lossy; prices only need about 15 significant digits.
+pub(crate) fn to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |acc, limb| {
+        acc * 18_446_744_073_709_551_616.0 + *limb as f64
+    })
+}
+
+/// Shared by the receipt and transform stages for the lifetime of the ingester.
+pub struct PriceOracle {
+    pool: PgPool,
+    config: PriceConfig,
+    tokens: Mutex<HashMap<Address, TokenMeta>>,
+    /// Whether each allowed-factory pool seen so far was confirmed by its factory.
+    confirmed: Mutex<HashMap<Address, bool>>,
+}
+
+impl PriceOracle {
+    pub fn new(pool: PgPool, config: PriceConfig) -> Self {
+        PriceOracle {
+            pool,
+            config,
+            tokens: Mutex::new(HashMap::new()),
+            confirmed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &PriceConfig {
+        &self.config
+    }
+
+    fn remember(&self, token: Address, meta: TokenMeta) {
+        let mut cache = self.tokens.lock().unwrap();
+        if cache.len() >= MAX_TOKEN_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(token, meta);
+    }
+
+    /// The WETH pools in `pools` that may set prices, and the decimals and symbols of their
+    /// tokens. A pool qualifies when its factory is in `PRICE_FACTORIES` and returns the pool
+    /// from `getPair`/`getPool`, so a look-alike pool can't quote whatever price it likes.
+    /// Checks are cached; metadata comes from the cache, the `tokens` table or
+    /// `decimals()`/`symbol()` calls. Like pool resolution, only unreachable backends fail.
+    pub async fn resolve_pools(
+        &self,
+        provider: &Provider<Http>,
+        pools: &HashMap<Address, PoolInfo>,
+    ) -> eyre::Result<PricedPools> {
+        let weth = self.config.weth;
+        let candidates: Vec<&PoolInfo> = pools
+            .values()
+            .filter(|pool| pool.token0 == weth || pool.token1 == weth)
+            .filter(|pool| {
+                pool.factory
+                    .is_some_and(|factory| self.config.factories.contains(&factory))
+            })
+            .collect();
+        let mut confirmed = HashSet::new();
+        let mut unchecked = Vec::new();
+        {
+            let cache = self.confirmed.lock().unwrap();
+            for pool in candidates {
+                match cache.get(&pool.address) {
+                    Some(true) => {
+                        confirmed.insert(pool.address);
+                    }
+                    Some(false) => {}
+                    None => unchecked.push(pool.clone()),
+                }
+            }
+        }
+        let checked = futures::stream::iter(unchecked)
+            .map(|pool| async move {
+                Ok::<_, eyre::Report>((pool.address, factory_confirms(provider, &pool).await?))
+            })
+            .buffer_unordered(MAX_TOKEN_LOOKUPS_CONCURRENT)
+            .collect::<Vec<_>>()
+            .await;
+        for result in checked {
+            let (address, ok) = result?;
+            if !ok {
+                debug!("PRICING: factory does not confirm pool {:?}", address);
+            }
+            let mut cache = self.confirmed.lock().unwrap();
+            if cache.len() >= MAX_POOL_CACHE_ENTRIES {
+                cache.clear();
+            }
+            cache.insert(address, ok);
+            if ok {
+                confirmed.insert(address);
+            }
+        }
+
+        let mut tokens: Vec<Address> = confirmed
+            .iter()
+            .flat_map(|address| [pools[address].token0, pools[address].token1])
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+
+        let mut resolved = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let cache = self.tokens.lock().unwrap();
+            for token in tokens {
+                match cache.get(&token) {
+                    Some(meta) => {
+                        resolved.insert(token, meta.clone());
+                    }
+                    None => missing.push(token),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(PricedPools {
+                pools: confirmed,
+                tokens_meta: resolved,
+            });
+        }
+
+        let rows: Vec<(String, Option<i16>, Option<String>)> = sqlx::query_as(
+            "SELECT token_address, decimals, symbol FROM tokens WHERE token_address = ANY($1)",
+        )
+        .bind(
+            missing
+                .iter()
+                .map(|token| format!("{:#x}", token))
+                .collect::<Vec<_>>(),
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| eyre::eyre!("DB: load token metadata: {}", e))?;
+        for (address, decimals, symbol) in rows {
+            let Ok(token) = address.parse::<Address>() else {
+                continue;
+            };
+            let meta = TokenMeta {
+                decimals: decimals.and_then(|d| u8::try_from(d).ok()),
+                symbol,
+            };
+            self.remember(token, meta.clone());
+            resolved.insert(token, meta);
+        }
+        missing.retain(|token| !resolved.contains_key(token));
+
+        let fetched = futures::stream::iter(missing)
+            .map(|token| async move {
+                let word = call_word(provider, token, "decimals()").await?;
+                let decimals = word
+                    .map(|word| U256::from_big_endian(&word))
+                    .filter(|value| *value <= U256::from(u8::MAX))
+                    .map(|value| value.as_u32() as u8);
+                let symbol = call_output(provider, token, "symbol()")
+                    .await?
+                    .and_then(|output| decode_symbol(&output));
+                Ok::<_, eyre::Report>((token, TokenMeta { decimals, symbol }))
+            })
+            .buffer_unordered(MAX_TOKEN_LOOKUPS_CONCURRENT)
+            .collect::<Vec<_>>()
+            .await;
+        for result in fetched {
+            let (token, meta) = result?;
+            self.remember(token, meta.clone());
+            resolved.insert(token, meta);
+        }
+        Ok(PricedPools {
+            pools: confirmed,
+            tokens_meta: resolved,
+        })
+    }
+
+    /// Prices the block from its decoded DEX events. `None` when the block touched no pool in
+    /// `priced.pools`.
+    pub fn price_block(&self, dex: &DexBlock, priced: &PricedPools) -> Option<BlockPrices> {
+        if priced.pools.is_empty() {
+            return None;
+        }
+        let tokens_meta = &priced.tokens_meta;
+        let mut states: BTreeMap<Address, (usize, PoolState)> = BTreeMap::new();
+        let mut record = |pool: Address, position: usize, state: PoolState| {
+            if states.get(&pool).is_none_or(|(last, _)| *last < position) {
+                states.insert(pool, (position, state));
+            }
+        };
+        for swap in &dex.swaps {
+            if let (Some(sqrt_price), Some(liquidity)) = (swap.sqrt_price_x96, swap.liquidity) {
+                record(
+                    swap.pool,
+                    swap.log_position,
+                    PoolState::SqrtPrice(sqrt_price, liquidity),
+                );
+            }
+        }
+        for event in dex.liquidity.iter().filter(|event| event.kind == "sync") {
+            record(
+                event.pool,
+                event.log_position,
+                PoolState::Reserves(event.amount0, event.amount1),
+            );
+        }
+
+        let pools: HashMap<Address, &PoolInfo> =
+            dex.pools.iter().map(|pool| (pool.address, pool)).collect();
+        let mut best: BTreeMap<Address, TokenQuote> = BTreeMap::new();
+        let mut eth_usd: Option<TokenQuote> = None;
+        for (address, (_, state)) in &states {
+            if !priced.pools.contains(address) {
+                continue;
+            }
+            let Some(quote) = pools
+                .get(address)
+                .and_then(|pool| self.quote(pool, state, tokens_meta))
+            else {
+                continue;
+            };
+            if quote.liquidity_eth < self.config.min_liquidity_eth {
+                continue;
+            }
+            if self.config.usd_pools.contains(address)
+                && eth_usd
+                    .as_ref()
+                    .is_none_or(|current| quote.liquidity_eth > current.liquidity_eth)
+            {
+                eth_usd = Some(quote.clone());
+            }
+            if best
+                .get(&quote.token)
+                .is_none_or(|current| quote.liquidity_eth > current.liquidity_eth)
+            {
+                best.insert(quote.token, quote);
+            }
+        }
+
+        let eth_usd = eth_usd.map(|stable| EthUsdQuote {
+            price_usd: 1.0 / stable.price_eth,
+            pool: stable.pool,
+            liquidity_eth: stable.liquidity_eth,
+        });
+        let mut tokens: Vec<TokenQuote> = best.into_values().collect();
+        if let Some(eth_usd) = &eth_usd {
+            tokens.push(TokenQuote {
+                token: self.config.weth,
+                price_eth: 1.0,
+                pool: eth_usd.pool,
+                liquidity_eth: eth_usd.liquidity_eth,
+            });
+        }
+        let mut tokens_meta: Vec<(Address, TokenMeta)> = tokens_meta
+            .iter()
+            .map(|(token, meta)| (*token, meta.clone()))
+            .collect();
+        tokens_meta.sort();
+        Some(BlockPrices {
+            block_number: dex.block_number,
+            block_timestamp: dex.block_timestamp,
+            bucket: self
+                .config
+                .resolution
+                .bucket(dex.block_number, dex.block_timestamp),
+            tokens_meta,
+            tokens,
+            eth_usd,
+        })
+    }
+
+    /// The ETH price of the non-WETH token of `pool`.
+    fn quote(
+        &self,
+        pool: &PoolInfo,
+        state: &PoolState,
+        tokens_meta: &HashMap<Address, TokenMeta>,
+    ) -> Option<TokenQuote> {
+        let weth = self.config.weth;
+        if pool.token0 != weth && pool.token1 != weth {
+            return None;
+        }
+        let decimals0 = tokens_meta.get(&pool.token0)?.decimals? as i32;
+        let decimals1 = tokens_meta.get(&pool.token1)?.decimals? as i32;
+        // Raw token1 per raw token0, and the raw amount of each token behind that price. For
+        // V3 these are the virtual reserves of the active range: L / sqrtP and L * sqrtP.
+        let (raw_price, depth0, depth1) = match (pool.protocol, state) {
+            (DexProtocol::UniswapV2, PoolState::Reserves(reserve0, reserve1)) => {
+                let (reserve0, reserve1) = (to_f64(*reserve0), to_f64(*reserve1));
+                (reserve1 / reserve0, reserve0, reserve1)
+            }
+            (DexProtocol::UniswapV3, PoolState::SqrtPrice(sqrt_price_x96, liquidity)) => {
+                let sqrt_price = to_f64(*sqrt_price_x96) / 2f64.powi(96);
+                let liquidity = to_f64(*liquidity);
+                (
+                    sqrt_price * sqrt_price,
+                    liquidity / sqrt_price,
+                    liquidity * sqrt_price,
+                )
+            }
+            _ => return None,
+        };
+        // Whole token1 per whole token0.
+        let price = raw_price * 10f64.powi(decimals0 - decimals1);
+        let (token, price_eth, weth_depth, weth_decimals) = if pool.token1 == weth {
+            (pool.token0, price, depth1, decimals1)
+        } else {
+            (pool.token1, 1.0 / price, depth0, decimals0)
+        };
+        (price_eth.is_finite() && price_eth > 0.0).then(|| TokenQuote {
+            token,
+            price_eth,
+            pool: pool.address,
+            liquidity_eth: weth_depth / 10f64.powi(weth_decimals),
+        })
+    }
+}
+
+/// Decodes a `symbol()` result: an ABI string, or a NUL-padded `bytes32` for older tokens
+/// such as MKR. Empty, oversized or non-UTF-8 symbols are dropped.
+fn decode_symbol(output: &[u8]) -> Option<String> {
+    let raw = if output.len() >= 64 {
+        let offset = U256::from_big_endian(&output[..32]);
+        let start = usize::try_from(offset).ok()?.checked_add(32)?;
+        let len = U256::from_big_endian(output.get(start - 32..start)?);
+        output.get(start..start.checked_add(usize::try_from(len).ok()?)?)?
+    } else {
+        let word = output.get(..32)?;
+        &word[..word.iter().position(|b| *b == 0).unwrap_or(32)]
+    };
+    let symbol = std::str::from_utf8(raw).ok()?.trim();
+    (!symbol.is_empty() && symbol.len() <= MAX_SYMBOL_LEN && !symbol.contains('\0'))
+        .then(|| symbol.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// REST API
+// ---------------------------------------------------------------------------
+
+/// ETH/USD as of the block expression `block`.
+fn eth_usd_at(block: &str) -> String {
+    format!(
+        "(SELECT e.price_usd FROM eth_usd_prices e WHERE e.block_number <= {} \
+         ORDER BY e.block_number DESC LIMIT 1)",
+        block
+    )
+}
+
+/// Joins a `price` relation holding `price_usd` of `token` as of `block` (SQL expressions).
+fn usd_price_join(token: &str, block: &str) -> String {
+    format!(
+        " LEFT JOIN LATERAL (SELECT tp.price_eth * {} AS price_usd FROM token_prices tp \
+         WHERE tp.token_address = {} AND tp.block_number <= {} \
+         ORDER BY tp.block_number DESC LIMIT 1) price ON TRUE",
+        eth_usd_at(block),
+        token,
+        block
+    )
+}
+
+/// A Transfer topic for `address` (already normalized).
+fn address_topic(address: &str) -> String {
+    format!("0x{:0>64}", address.trim_start_matches("0x"))
+}
+
+/// The block a point-in-time query refers to: `block`, the last block at or before `time`,
+/// or the latest indexed block.
+async fn as_of_block(
+    pool: &PgPool,
+    block: Option<i64>,
+    time: Option<i64>,
+) -> Result<i64, ApiError> {
+    match (block, time) {
+        (Some(_), Some(_)) => Err(ApiError::BadRequest(
+            "Pass either block or time, not both.".to_string(),
+        )),
+        (Some(block), None) => Ok(block),
+        (None, Some(time)) => sqlx::query_scalar::<_, i64>(
+            "SELECT block_number FROM blocks WHERE timestamp <= $1 \
+             ORDER BY block_number DESC LIMIT 1",
+        )
+        .bind(time)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No indexed block at or before {}.", time))),
+        (None, None) => Ok(sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT MAX(block_number) FROM blocks",
+        )
+        .fetch_one(pool)
+        .await?
+        .unwrap_or(0)),
+    }
+}
+
+/// Sets `value_usd` on each transaction from ETH/USD in its block. Transactions in blocks
+/// before the first ETH/USD observation are left without one.
+pub async fn fill_value_usd(
+    pool: &PgPool,
+    transactions: &mut [MyTransaction],
+) -> Result<(), ApiError> {
+    let mut blocks: Vec<i64> = transactions
+        .iter()
+        .map(|tx| tx.block_number.as_u64() as i64)
+        .collect();
+    blocks.sort_unstable();
+    blocks.dedup();
+    let rows: Vec<(i64, Option<f64>)> = sqlx::query_as(&format!(
+        "SELECT b, {} FROM UNNEST($1::BIGINT[]) AS b",
+        eth_usd_at("b")
+    ))
+    .bind(blocks)
+    .fetch_all(pool)
+    .await?;
+    let eth_usd: HashMap<i64, f64> = rows
+        .into_iter()
+        .filter_map(|(block, price)| Some((block, price?)))
+        .collect();
+    for tx in transactions {
+        tx.value_usd = eth_usd
+            .get(&(tx.block_number.as_u64() as i64))
+            .map(|price| to_f64(tx.value) / 1e18 * price);
+    }
+    Ok(())
+}
+
+fn row_to_token_price(row: &PgRow) -> Result<TokenPrice, ApiError> {
+    Ok(TokenPrice {
+        token_address: row.try_get("token_address")?,
+        price_eth: row.try_get("price_eth")?,
+        price_usd: row.try_get("price_usd")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        pool_address: row.try_get("pool_address")?,
+        liquidity_eth: row.try_get("liquidity_eth")?,
+    })
+}
+
+/// Get ETH Price
+///
+/// ETH/USD as of a block or time, from the configured stablecoin pools.
+#[utoipa::path(
+    get,
+    path = "/prices/eth",
+    params(GetPriceQuery),
+    responses(
+        (status = 200, description = "ETH/USD price", body = EthUsdPrice),
+        (status = 400, description = "Both block and time given", body = GenericErrorResponse),
+        (status = 404, description = "No price at or before that point", body = GenericErrorResponse)
+    )
+)]
+pub async fn eth_price_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetPriceQuery>,
+) -> Result<Json<EthUsdPrice>, ApiError> {
+    let block = as_of_block(&pool, params.block, params.time).await?;
+    let row = sqlx::query(
+        "SELECT price_usd, block_number, block_timestamp, pool_address, liquidity_eth \
+         FROM eth_usd_prices WHERE block_number <= $1 ORDER BY block_number DESC LIMIT 1",
+    )
+    .bind(block)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("No ETH/USD price at or before block {}.", block)))?;
+    Ok(Json(EthUsdPrice {
+        price_usd: row.try_get("price_usd")?,
+        block_number: row.try_get("block_number")?,
+        block_timestamp: row.try_get("block_timestamp")?,
+        pool_address: row.try_get("pool_address")?,
+        liquidity_eth: row.try_get("liquidity_eth")?,
+    }))
+}
+
+/// Get Token Price
+///
+/// The token's ETH price as of a block or time, and its USD price at the same point.
+#[utoipa::path(
+    get,
+    path = "/prices/tokens/{address}",
+    params(("address" = String, Path, description = "Token address"), GetPriceQuery),
+    responses(
+        (status = 200, description = "Token price", body = TokenPrice),
+        (status = 400, description = "Invalid address, or both block and time given", body = GenericErrorResponse),
+        (status = 404, description = "No price at or before that point", body = GenericErrorResponse)
+    )
+)]
+pub async fn token_price_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetPriceQuery>,
+) -> Result<Json<TokenPrice>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    let block = as_of_block(&pool, params.block, params.time).await?;
+    let row = sqlx::query(&format!(
+        "SELECT {}, t.price_eth * {} AS price_usd FROM token_prices t \
+         WHERE t.token_address = $1 AND t.block_number <= $2 \
+         ORDER BY t.block_number DESC LIMIT 1",
+        TOKEN_PRICE_COLUMNS,
+        eth_usd_at("$2")
+    ))
+    .bind(&address)
+    .bind(block)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "No price for {} at or before block {}.",
+            address, block
+        ))
+    })?;
+    Ok(Json(row_to_token_price(&row)?))
+}
+
+/// Get Token Price History
+///
+/// Every stored price of the token (one per block or minute it traded), oldest first.
+#[utoipa::path(
+    get,
+    path = "/prices/tokens/{address}/history",
+    params(("address" = String, Path, description = "Token address"), GetPriceHistoryQuery),
+    responses(
+        (status = 200, description = "Prices", body = TokenPriceHistoryResponse),
+        (status = 400, description = "Invalid address", body = GenericErrorResponse)
+    )
+)]
+pub async fn token_price_history_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetPriceHistoryQuery>,
+) -> Result<Json<TokenPriceHistoryResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    let mut query_builder = QueryBuilder::new(format!(
+        "SELECT {}, t.price_eth * {} AS price_usd FROM token_prices t WHERE t.token_address = ",
+        TOKEN_PRICE_COLUMNS,
+        eth_usd_at("t.block_number")
+    ));
+    query_builder.push_bind(address);
+    if let Some(from_block) = params.from_block {
+        query_builder.push(" AND t.block_number >= ");
+        query_builder.push_bind(from_block);
+    }
+    if let Some(to_block) = params.to_block {
+        query_builder.push(" AND t.block_number <= ");
+        query_builder.push_bind(to_block);
+    }
+    if let Some(from_time) = params.from_time {
+        query_builder.push(" AND t.block_timestamp >= ");
+        query_builder.push_bind(from_time);
+    }
+    if let Some(to_time) = params.to_time {
+        query_builder.push(" AND t.block_timestamp <= ");
+        query_builder.push_bind(to_time);
+    }
+    if let Some(cursor_block) = params.cursor_block {
+        query_builder.push(" AND t.block_number > ");
+        query_builder.push_bind(cursor_block);
+    }
+    let limit = params.limit.clamp(1, MAX_PAGE_SIZE) as i64;
+    query_builder.push(" ORDER BY t.block_number LIMIT ");
+    query_builder.push_bind(limit);
+    let rows = query_builder.build().fetch_all(&pool).await?;
+    let prices: Vec<TokenPrice> = rows
+        .iter()
+        .map(row_to_token_price)
+        .collect::<Result<_, _>>()?;
+    let next_cursor_block = prices
+        .last()
+        .filter(|_| prices.len() as i64 == limit)
+        .map(|price| price.block_number);
+    Ok(Json(TokenPriceHistoryResponse {
+        prices,
+        next_cursor_block,
+    }))
+}
+
+/// Get Account Token Transfers
+///
+/// ERC-20 Transfer events to or from the account, oldest first. With `usd=true` each amount
+/// is valued at the token's price in the transfer's block.
+#[utoipa::path(
+    get,
+    path = "/accounts/{address}/transfers",
+    params(("address" = String, Path, description = "Account address or ENS name"), GetTransfersQuery),
+    responses(
+        (status = 200, description = "Transfers", body = TokenTransfersResponse),
+        (status = 400, description = "Invalid address or cursor", body = GenericErrorResponse)
+    )
+)]
+pub async fn account_transfers_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetTransfersQuery>,
+) -> Result<Json<TokenTransfersResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    if params.cursor_block.is_some() != params.cursor_log_id.is_some() {
+        return Err(ApiError::BadRequest(
+            "cursorBlock and cursorLogId must be passed together.".to_string(),
+        ));
+    }
+    let topic = address_topic(&address);
+    let (amount_usd, price_join) = if params.usd {
+        (
+            format!(
+                "({} / 10::NUMERIC ^ tk.decimals)::FLOAT8 * price.price_usd",
+                TRANSFER_AMOUNT
+            ),
+            usd_price_join("l.contract_address", "l.block_number"),
+        )
+    } else {
+        ("NULL::FLOAT8".to_string(), String::new())
+    };
+    let mut query_builder = QueryBuilder::new(format!(
+        "SELECT l.id AS log_id, l.block_number, \
+         (SELECT b.timestamp FROM blocks b WHERE b.block_number = l.block_number LIMIT 1) \
+         AS block_timestamp, l.transaction_hash, l.log_index_in_tx, l.contract_address, \
+         '0x' || substr(l.topic1, 27) AS from_address, '0x' || substr(l.topic2, 27) AS to_address, \
+         {amount}::TEXT AS amount, tk.decimals::INT AS decimals, {amount_usd} AS amount_usd \
+         FROM logs l LEFT JOIN tokens tk ON tk.token_address = l.contract_address{price_join} \
+         WHERE l.topic0 = '{TRANSFER_TOPIC}' AND l.topic3 IS NULL AND length(l.data) = 66 \
+         AND (l.topic1 = ",
+        amount = TRANSFER_AMOUNT,
+    ));
+    query_builder.push_bind(topic.clone());
+    query_builder.push(" OR l.topic2 = ");
+    query_builder.push_bind(topic);
+    query_builder.push(")");
+    if let Some(token) = &params.token {
+        query_builder.push(" AND l.contract_address = ");
+        query_builder.push_bind(resolve_address(&pool, token, "token").await?);
+    }
+    if let Some(from_block) = params.from_block {
+        query_builder.push(" AND l.block_number >= ");
+        query_builder.push_bind(from_block);
+    }
+    if let Some(to_block) = params.to_block {
+        query_builder.push(" AND l.block_number <= ");
+        query_builder.push_bind(to_block);
+    }
+    let (from_ts_block, to_ts_block) = crate::storage::resolve_time_range(
+        &PostgresStorage::new(pool.clone()),
+        params.from_timestamp,
+        params.to_timestamp,
+    )
+    .await?;
+    if let Some(from_block) = from_ts_block {
+        query_builder.push(" AND l.block_number >= ");
+        query_builder.push_bind(from_block);
+    }
+    if let Some(to_block) = to_ts_block {
+        query_builder.push(" AND l.block_number <= ");
+        query_builder.push_bind(to_block);
+    }
+    if let (Some(block), Some(log_id)) = (params.cursor_block, params.cursor_log_id) {
+        query_builder.push(" AND (l.block_number, l.id) > (");
+        query_builder.push_bind(block);
+        query_builder.push(", ");
+        query_builder.push_bind(log_id);
+        query_builder.push(")");
+    }
+    let limit = params.limit.clamp(1, MAX_PAGE_SIZE) as i64;
+    query_builder.push(" ORDER BY l.block_number, l.id LIMIT ");
+    query_builder.push_bind(limit);
+
+    let rows = query_builder.build().fetch_all(&pool).await?;
+    let transfers = rows
+        .iter()
+        .map(|row| {
+            let from: String = row.try_get("from_address")?;
+            let to: String = row.try_get("to_address")?;
+            let direction = if from == to {
+                "self"
+            } else if to == address {
+                "in"
+            } else {
+                "out"
+            };
+            Ok(TokenTransfer {
+                log_id: row.try_get("log_id")?,
+                block_number: row.try_get("block_number")?,
+                block_timestamp: row.try_get("block_timestamp")?,
+                tx_hash: row.try_get("transaction_hash")?,
+                log_index: row.try_get("log_index_in_tx")?,
+                token_address: row.try_get("contract_address")?,
+                from,
+                to,
+                direction: direction.to_string(),
+                amount: row.try_get("amount")?,
+                decimals: row.try_get("decimals")?,
+                amount_usd: row.try_get("amount_usd")?,
+            })
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+    let last = transfers.last().filter(|_| transfers.len() as i64 == limit);
+    Ok(Json(TokenTransfersResponse {
+        next_cursor_block: last.map(|transfer| transfer.block_number),
+        next_cursor_log_id: last.map(|transfer| transfer.log_id),
+        transfers,
+    }))
+}
+
+/// Get Account Token Balances
+///
+/// Net ERC-20 balances from the account's indexed Transfer events as of a block or time,
+/// largest USD value first when `usd=true`. Transfers before the first indexed block are not
+/// counted, so balances of older accounts can be short or even negative.
+#[utoipa::path(
+    get,
+    path = "/accounts/{address}/balances",
+    params(("address" = String, Path, description = "Account address or ENS name"), GetBalancesQuery),
+    responses(
+        (status = 200, description = "Balances", body = AccountBalancesResponse),
+        (status = 400, description = "Invalid address, or both block and time given", body = GenericErrorResponse),
+        (status = 404, description = "No indexed block at or before time", body = GenericErrorResponse)
+    )
+)]
+pub async fn account_balances_handler(
+    State(pool): State<PgPool>,
+    Path(address): Path<String>,
+    Query(params): Query<GetBalancesQuery>,
+) -> Result<Json<AccountBalancesResponse>, ApiError> {
+    let address = resolve_address(&pool, &address, "address").await?;
+    let block = as_of_block(&pool, params.block, params.time).await?;
+    let (price_usd, value_usd, price_join) = if params.usd {
+        (
+            "price.price_usd",
+            "(x.balance / 10::NUMERIC ^ tk.decimals)::FLOAT8 * price.price_usd",
+            usd_price_join("x.token_address", "$2"),
+        )
+    } else {
+        ("NULL::FLOAT8", "NULL::FLOAT8", String::new())
+    };
+    let rows = sqlx::query(&format!(
+        "SELECT x.token_address, x.balance::TEXT AS balance, tk.decimals::INT AS decimals, \
+         {price_usd} AS price_usd, {value_usd} AS value_usd FROM ( \
+             SELECT l.contract_address AS token_address, \
+             SUM(CASE WHEN l.topic2 = $1 THEN {amount} ELSE 0 END) \
+             - SUM(CASE WHEN l.topic1 = $1 THEN {amount} ELSE 0 END) AS balance \
+             FROM logs l WHERE l.topic0 = '{TRANSFER_TOPIC}' AND l.topic3 IS NULL \
+             AND length(l.data) = 66 AND (l.topic1 = $1 OR l.topic2 = $1) \
+             AND l.block_number <= $2 GROUP BY l.contract_address \
+         ) x LEFT JOIN tokens tk ON tk.token_address = x.token_address{price_join} \
+         WHERE x.balance <> 0 ORDER BY value_usd DESC NULLS LAST, x.token_address",
+        amount = TRANSFER_AMOUNT,
+    ))
+    .bind(address_topic(&address))
+    .bind(block)
+    .fetch_all(&pool)
+    .await?;
+    let balances = rows
+        .iter()
+        .map(|row| {
+            Ok(TokenBalance {
+                token_address: row.try_get("token_address")?,
+                balance: row.try_get("balance")?,
+                decimals: row.try_get("decimals")?,
+                price_usd: row.try_get("price_usd")?,
+                value_usd: row.try_get("value_usd")?,
+            })
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+    let total_usd = params.usd.then(|| {
+        balances
+            .iter()
+            .filter_map(|balance| balance.value_usd)
+            .sum::<f64>()
+    });
+    Ok(Json(AccountBalancesResponse {
+        address,
+        block_number: block,
+        total_usd,
+        balances,
+    }))
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/prices/eth", get(eth_price_handler))
+        .route("/prices/tokens/{address}", get(token_price_handler))
+        .route(
+            "/prices/tokens/{address}/history",
+            get(token_price_history_handler),
+        )
+        .route(
+            "/accounts/{address}/transfers",
+            get(account_transfers_handler),
+        )
+        .route(
+            "/accounts/{address}/balances",
+            get(account_balances_handler),
+        )
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WETH: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+    /// 6 decimals, sorts before WETH.
+    const USDC: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    /// 6 decimals, sorts after WETH.
+    const USDT: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+    /// 18 decimals, sorts before WETH.
+    const DAI: &str = "0x6b175474e89094c44da98b954eedeac495271d0f";
+    /// 18 decimals, sorts after WETH.
+    const CRV: &str = "0xd533a949740bb3306d119cc777fa900ba034cd52";
+
+    fn address(value: &str) -> Address {
+        value.parse().unwrap()
+    }
+
+    fn oracle() -> PriceOracle {
+        PriceOracle::new(
+            PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+            PriceConfig {
+                weth: address(WETH),
+                usd_pools: HashSet::new(),
+                factories: HashSet::new(),
+                min_liquidity_eth: DEFAULT_MIN_LIQUIDITY_ETH,
+                resolution: PriceResolution::Block,
+            },
+        )
+    }
+
+    fn pool(protocol: DexProtocol, token0: &str, token1: &str) -> PoolInfo {
+        PoolInfo {
+            address: address("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"),
+            protocol,
+            factory: None,
+            token0: address(token0),
+            token1: address(token1),
+            fee: None,
+            tick_spacing: None,
+            created_block: None,
+        }
+    }
+
+    fn meta() -> HashMap<Address, TokenMeta> {
+        [(WETH, 18), (USDC, 6), (USDT, 6), (DAI, 18), (CRV, 18)]
+            .into_iter()
+            .map(|(token, decimals)| {
+                let meta = TokenMeta {
+                    decimals: Some(decimals),
+                    symbol: None,
+                };
+                (address(token), meta)
+            })
+            .collect()
+    }
+
+    fn reserves(reserve0: U256, reserve1: U256) -> PoolState {
+        PoolState::Reserves(reserve0, reserve1)
+    }
+
+    fn sqrt_price(sqrt_price_x96: &str, liquidity: U256) -> PoolState {
+        PoolState::SqrtPrice(U256::from_dec_str(sqrt_price_x96).unwrap(), liquidity)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            ((actual - expected) / expected).abs() < 1e-9,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn quotes_v2_reserves_in_both_token_orders() {
+        let oracle = oracle();
+        let eth = |amount: u64| U256::exp10(18) * amount;
+        // 2,000 of the token per WETH in every pool; 15,000 WETH deep.
+        for (token0, token1, reserve0, reserve1) in [
+            (
+                USDC,
+                WETH,
+                U256::from(30_000_000u64) * U256::exp10(6),
+                eth(15_000),
+            ),
+            (
+                WETH,
+                USDT,
+                eth(15_000),
+                U256::from(30_000_000u64) * U256::exp10(6),
+            ),
+            (DAI, WETH, eth(30_000_000), eth(15_000)),
+            (WETH, CRV, eth(15_000), eth(30_000_000)),
+        ] {
+            let quote = oracle
+                .quote(
+                    &pool(DexProtocol::UniswapV2, token0, token1),
+                    &reserves(reserve0, reserve1),
+                    &meta(),
+                )
+                .unwrap();
+            let token = if token0 == WETH { token1 } else { token0 };
+            assert_eq!(quote.token, address(token));
+            assert_close(quote.price_eth, 0.0005);
+            assert_close(quote.liquidity_eth, 15_000.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn quotes_v3_sqrt_price_in_both_token_orders() {
+        let oracle = oracle();
+        // 2,000 USDC per WETH: raw WETH per raw USDC is 1e18 / 2000e6, and sqrtPriceX96 is
+        // its square root times 2^96. In-range WETH is L * sqrtP.
+        let quote = oracle
+            .quote(
+                &pool(DexProtocol::UniswapV3, USDC, WETH),
+                &sqrt_price("1771595571142957102961017161607260", U256::exp10(18) * 12),
+                &meta(),
+            )
+            .unwrap();
+        assert_eq!(quote.token, address(USDC));
+        assert_close(quote.price_eth, 0.0005);
+        assert_close(quote.liquidity_eth, 12.0 * (1e18f64 / 2000e6).sqrt());
+
+        // 2,000 USDT per WETH with WETH as token0: raw USDT per raw WETH is 2000e6 / 1e18.
+        // In-range WETH is L / sqrtP.
+        let quote = oracle
+            .quote(
+                &pool(DexProtocol::UniswapV3, WETH, USDT),
+                &sqrt_price("3543191142285914205922034", U256::exp10(14)),
+                &meta(),
+            )
+            .unwrap();
+        assert_eq!(quote.token, address(USDT));
+        assert_close(quote.price_eth, 0.0005);
+        assert_close(quote.liquidity_eth, 1e14 / (2000e6f64 / 1e18).sqrt() / 1e18);
+
+        // 18 decimals on both sides: 2,000 DAI per WETH.
+        let quote = oracle
+            .quote(
+                &pool(DexProtocol::UniswapV3, DAI, WETH),
+                &sqrt_price("1771595571142957102961017161", U256::exp10(18)),
+                &meta(),
+            )
+            .unwrap();
+        assert_eq!(quote.token, address(DAI));
+        assert_close(quote.price_eth, 0.0005);
+    }
+
+    #[tokio::test]
+    async fn quote_needs_weth_decimals_and_a_matching_state() {
+        let oracle = oracle();
+        let state = reserves(U256::exp10(18), U256::exp10(18));
+        assert!(oracle
+            .quote(&pool(DexProtocol::UniswapV2, USDC, USDT), &state, &meta())
+            .is_none());
+        let mut unknown = meta();
+        unknown.get_mut(&address(DAI)).unwrap().decimals = None;
+        assert!(oracle
+            .quote(&pool(DexProtocol::UniswapV2, DAI, WETH), &state, &unknown)
+            .is_none());
+        assert!(oracle
+            .quote(&pool(DexProtocol::UniswapV3, DAI, WETH), &state, &meta())
+            .is_none());
+        assert!(oracle
+            .quote(
+                &pool(DexProtocol::UniswapV2, DAI, WETH),
+                &reserves(U256::zero(), U256::exp10(18)),
+                &meta()
+            )
+            .is_none());
+    }
+
+    /// The ABI encoding of a `string` return value.
+    fn abi_string(value: &[u8]) -> Vec<u8> {
+        ethers::abi::encode(&[ethers::abi::Token::Bytes(value.to_vec())])
+    }
+
+    fn bytes32(value: &[u8]) -> Vec<u8> {
+        let mut word = value.to_vec();
+        word.resize(32, 0);
+        word
+    }
+
+    #[test]
+    fn decodes_string_and_bytes32_symbols() {
+        assert_eq!(abi_string(b"UNI").len(), 96);
+        assert_eq!(decode_symbol(&abi_string(b"UNI")).as_deref(), Some("UNI"));
+        assert_eq!(
+            decode_symbol(&abi_string(b" USDC ")).as_deref(),
+            Some("USDC")
+        );
+        // MKR and SAI return bytes32.
+        assert_eq!(decode_symbol(&bytes32(b"MKR")).as_deref(), Some("MKR"));
+        assert_eq!(decode_symbol(&bytes32(b"SAI")).as_deref(), Some("SAI"));
+    }
+
+    #[test]
+    fn rejects_empty_oversized_and_malformed_symbols() {
+        assert_eq!(decode_symbol(&abi_string(b"")), None);
+        assert_eq!(decode_symbol(&bytes32(b"")), None);
+        assert_eq!(decode_symbol(&abi_string(&[b'A'; 33])), None);
+        assert_eq!(decode_symbol(&abi_string(&[0xff, 0xfe])), None);
+        assert_eq!(decode_symbol(&abi_string(b"A\0B")), None);
+        assert_eq!(decode_symbol(&[0x4d, 0x4b, 0x52]), None);
+        // A string whose length runs past the output.
+        let mut truncated = abi_string(b"UNI");
+        truncated[63] = 200;
+        assert_eq!(decode_symbol(&truncated), None);
+    }
+}
@@ -0,0 +1,623 @@
+// src/rpc.rs
+//
+// Read-only Ethereum JSON-RPC facade served straight from the indexed Postgres tables.
+// Responses are built from the `ethers` RPC types so clients see the standard shapes, then
+// cut down to the fields the indexer stores (`BLOCK_FIELDS`, `TRANSACTION_FIELDS`,
+// `RECEIPT_FIELDS`). The rest (state and receipt roots, `logsBloom`, `miner`, signatures,
+// `cumulativeGasUsed`, ...) are left out rather than returned as zeros. Rows indexed before
+// `nonce`, `type` and `contractAddress` were stored come back without them, or with a null
+// `contractAddress`.
+
+use crate::api::{
+    push_log_filters, resolve_block_tag, row_to_block, row_to_log, row_to_transaction, ApiError,
+    BLOCK_COLUMNS, LOG_COLUMNS, TRANSACTION_COLUMNS,
+};
+use crate::api_models::{BlockTag, GetLogsFilter};
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json},
+    routing::post,
+    Router,
+};
+use ethers::core::types::{Block, Bytes, Log, Transaction, TransactionReceipt, H256, U256, U64};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgPool, QueryBuilder};
+use std::str::FromStr;
+
+const MAX_RPC_BATCH_SIZE: usize = 100;
+const MAX_RPC_LOG_RESULTS: i64 = 10_000; // Same order of magnitude as hosted providers
+
+// Standard JSON-RPC 2.0 error codes, plus the common `eth_getLogs` limit code.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+const LIMIT_EXCEEDED: i64 = -32005;
+
+const BLOCK_FIELDS: &[&str] = &[
+    "hash",
+    "parentHash",
+    "number",
+    "timestamp",
+    "gasUsed",
+    "gasLimit",
+    "baseFeePerGas",
+    "transactions",
+];
+const TRANSACTION_FIELDS: &[&str] = &[
+    "hash",
+    "nonce",
+    "blockHash",
+    "blockNumber",
+    "transactionIndex",
+    "from",
+    "to",
+    "value",
+    "gasPrice",
+    "gas",
+    "input",
+    "type",
+    "maxFeePerGas",
+    "maxPriorityFeePerGas",
+    "chainId",
+];
+const RECEIPT_FIELDS: &[&str] = &[
+    "transactionHash",
+    "transactionIndex",
+    "blockHash",
+    "blockNumber",
+    "from",
+    "to",
+    "gasUsed",
+    "effectiveGasPrice",
+    "contractAddress",
+    "logs",
+    "status",
+    "type",
+];
+
+#[derive(Clone)]
+pub struct RpcState {
+    pub pool: PgPool,
+    pub chain_id: u64,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: Option<String>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    id: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ApiError> for RpcError {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::BadRequest(msg) => RpcError::new(INVALID_PARAMS, msg),
+            ApiError::NotFound(msg) => RpcError::new(INTERNAL_ERROR, msg),
+            ApiError::InternalServerError(msg) => RpcError::new(INTERNAL_ERROR, msg),
+            ApiError::DatabaseError(db_err) => {
+                tracing::error!("RPC database error: {:?}", db_err);
+                RpcError::new(INTERNAL_ERROR, "A database error occurred")
+            }
+        }
+    }
+}
+
+impl From<sqlx::Error> for RpcError {
+    fn from(err: sqlx::Error) -> Self {
+        ApiError::from(err).into()
+    }
+}
+
+impl RpcResponse {
+    fn success(id: Value, result: Value) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failure(id: Value, error: RpcError) -> Self {
+        RpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Ethereum JSON-RPC
+///
+/// Serves a read-only subset of the Ethereum JSON-RPC API from the indexed database:
+/// `eth_getLogs`, `eth_getBlockByNumber`, `eth_getBlockByHash`, `eth_getTransactionByHash`,
+/// `eth_getTransactionReceipt`, `eth_blockNumber` and `eth_chainId`. Batch requests are supported.
+#[utoipa::path(
+    post,
+    path = "/rpc",
+    request_body(content = Object, description = "A JSON-RPC 2.0 request object or batch array"),
+    responses(
+        (status = 200, description = "JSON-RPC response object or batch array", body = Object),
+        (status = 204, description = "Only notifications were sent; nothing to return")
+    )
+)]
+pub async fn rpc_handler(State(state): State<RpcState>, body: String) -> axum::response::Response {
+    let payload: Value = match serde_json::from_str(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            let resp = RpcResponse::failure(
+                Value::Null,
+                RpcError::new(PARSE_ERROR, format!("Parse error: {}", e)),
+            );
+            return Json(resp).into_response();
+        }
+    };
+
+    match payload {
+        Value::Array(requests) => {
+            if requests.is_empty() || requests.len() > MAX_RPC_BATCH_SIZE {
+                let resp = RpcResponse::failure(
+                    Value::Null,
+                    RpcError::new(
+                        INVALID_REQUEST,
+                        format!("Batch must contain 1 to {} requests", MAX_RPC_BATCH_SIZE),
+                    ),
+                );
+                return Json(resp).into_response();
+            }
+            let responses: Vec<RpcResponse> =
+                join_all(requests.into_iter().map(|r| handle_request(&state, r)))
+                    .await
+                    .into_iter()
+                    .flatten()
+                    .collect();
+            if responses.is_empty() {
+                StatusCode::NO_CONTENT.into_response()
+            } else {
+                Json(responses).into_response()
+            }
+        }
+        single => match handle_request(&state, single).await {
+            Some(resp) => Json(resp).into_response(),
+            None => StatusCode::NO_CONTENT.into_response(),
+        },
+    }
+}
+
+/// Handles one request object. Returns `None` for notifications (requests without an id).
+async fn handle_request(state: &RpcState, raw: Value) -> Option<RpcResponse> {
+    let request: RpcRequest = match serde_json::from_value(raw) {
+        Ok(r) => r,
+        Err(e) => {
+            return Some(RpcResponse::failure(
+                Value::Null,
+                RpcError::new(INVALID_REQUEST, format!("Invalid request: {}", e)),
+            ))
+        }
+    };
+    let id = request.id.clone();
+
+    let outcome = if request.jsonrpc.as_deref() != Some("2.0") {
+        Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""))
+    } else {
+        dispatch(state, &request.method, request.params).await
+    };
+
+    let id = id?;
+    Some(match outcome {
+        Ok(result) => RpcResponse::success(id, result),
+        Err(error) => RpcResponse::failure(id, error),
+    })
+}
+
+async fn dispatch(state: &RpcState, method: &str, params: Value) -> Result<Value, RpcError> {
+    let pool = &state.pool;
+    match method {
+        "eth_chainId" => Ok(quantity(state.chain_id)),
+        "eth_blockNumber" => {
+            let latest = crate::db::get_last_synced_block(pool).await?.unwrap_or(0);
+            Ok(quantity(latest))
+        }
+        "eth_getLogs" => {
+            let filter: GetLogsFilter = param(&params, 0)?;
+            get_logs(pool, &filter).await
+        }
+        "eth_getBlockByNumber" => {
+            let tag: BlockTag = param(&params, 0)?;
+            let full: bool = optional_param(&params, 1)?.unwrap_or(false);
+            let number = resolve_block_tag(pool, tag).await?;
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM blocks WHERE block_number = $1 LIMIT 1",
+                BLOCK_COLUMNS
+            ))
+            .bind(number)
+            .fetch_optional(pool)
+            .await?;
+            match row {
+                Some(row) => block_result(pool, state.chain_id, row_to_block(&row)?, full).await,
+                None => Ok(Value::Null),
+            }
+        }
+        "eth_getBlockByHash" => {
+            let hash: H256 = param(&params, 0)?;
+            let full: bool = optional_param(&params, 1)?.unwrap_or(false);
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM blocks WHERE block_hash = $1",
+                BLOCK_COLUMNS
+            ))
+            .bind(format!("{:#x}", hash))
+            .fetch_optional(pool)
+            .await?;
+            match row {
+                Some(row) => block_result(pool, state.chain_id, row_to_block(&row)?, full).await,
+                None => Ok(Value::Null),
+            }
+        }
+        "eth_getTransactionByHash" => {
+            let hash: H256 = param(&params, 0)?;
+            match fetch_transaction(pool, hash).await? {
+                Some(tx) => rpc_transaction(&tx, state.chain_id),
+                None => Ok(Value::Null),
+            }
+        }
+        "eth_getTransactionReceipt" => {
+            let hash: H256 = param(&params, 0)?;
+            let tx = match fetch_transaction(pool, hash).await? {
+                Some(tx) => tx,
+                None => return Ok(Value::Null),
+            };
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM logs WHERE transaction_hash = $1 ORDER BY log_index_in_tx ASC, id ASC",
+                LOG_COLUMNS
+            ))
+            .bind(format!("{:#x}", hash))
+            .fetch_all(pool)
+            .await?;
+            let logs = rows
+                .iter()
+                .map(|row| row_to_log(row).and_then(|l| to_rpc_log(&l)))
+                .collect::<Result<Vec<Log>, ApiError>>()?;
+            let receipt = TransactionReceipt {
+                transaction_hash: tx.tx_hash,
+                transaction_index: tx.transaction_index.unwrap_or_default(),
+                block_hash: Some(tx.block_hash),
+                block_number: Some(tx.block_number),
+                from: tx.from_address,
+                to: tx.to_address,
+                contract_address: tx.contract_address,
+                logs,
+                status: tx.status.map(U64::from),
+                gas_used: tx.gas_used,
+                effective_gas_price: tx.effective_gas_price,
+                transaction_type: tx.transaction_type.map(U64::from),
+                ..Default::default()
+            };
+            stored_fields(receipt, RECEIPT_FIELDS)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method {} is not supported", method),
+        )),
+    }
+}
+
+async fn get_logs(pool: &PgPool, filter: &GetLogsFilter) -> Result<Value, RpcError> {
+    let mut query_builder: QueryBuilder<sqlx::Postgres> =
+        QueryBuilder::new(format!("SELECT {} FROM logs WHERE 1=1", LOG_COLUMNS));
+    push_log_filters(pool, &mut query_builder, filter).await?;
+    query_builder.push(" ORDER BY block_number ASC, id ASC LIMIT ");
+    // Fetch one extra row so an over-limit query is reported instead of silently truncated.
+    query_builder.push_bind(MAX_RPC_LOG_RESULTS + 1);
+
+    let rows = query_builder.build().fetch_all(pool).await?;
+    if rows.len() as i64 > MAX_RPC_LOG_RESULTS {
+        return Err(RpcError::new(
+            LIMIT_EXCEEDED,
+            format!(
+                "query returned more than {} results; narrow the block range",
+                MAX_RPC_LOG_RESULTS
+            ),
+        ));
+    }
+
+    let logs = rows
+        .iter()
+        .map(|row| row_to_log(row).and_then(|l| to_rpc_log(&l)))
+        .collect::<Result<Vec<Log>, ApiError>>()?;
+    to_value(logs)
+}
+
+async fn fetch_transaction(pool: &PgPool, hash: H256) -> Result<Option<MyTransaction>, RpcError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM transactions WHERE tx_hash = $1",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(format!("{:#x}", hash))
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.as_ref().map(row_to_transaction).transpose()?)
+}
+
+async fn block_result(
+    pool: &PgPool,
+    chain_id: u64,
+    block: MyBlock,
+    full: bool,
+) -> Result<Value, RpcError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM transactions WHERE block_hash = $1 ORDER BY transaction_index ASC",
+        TRANSACTION_COLUMNS
+    ))
+    .bind(format!("{:#x}", block.block_hash))
+    .fetch_all(pool)
+    .await?;
+    let txs = rows
+        .iter()
+        .map(row_to_transaction)
+        .collect::<Result<Vec<MyTransaction>, ApiError>>()?;
+
+    let header = Block::<H256> {
+        hash: Some(block.block_hash),
+        parent_hash: block.parent_hash,
+        number: Some(block.block_number),
+        timestamp: block.timestamp,
+        gas_used: block.gas_used,
+        gas_limit: block.gas_limit,
+        base_fee_per_gas: block.base_fee_per_gas,
+        ..Default::default()
+    };
+    let mut result = stored_fields(header, BLOCK_FIELDS)?;
+    result["transactions"] = if full {
+        Value::Array(
+            txs.iter()
+                .map(|tx| rpc_transaction(tx, chain_id))
+                .collect::<Result<Vec<Value>, RpcError>>()?,
+        )
+    } else {
+        to_value(txs.iter().map(|tx| tx.tx_hash).collect::<Vec<H256>>())?
+    };
+    Ok(result)
+}
+
+fn rpc_transaction(tx: &MyTransaction, chain_id: u64) -> Result<Value, RpcError> {
+    let input = Bytes::from_str(&tx.input_data)
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, format!("Invalid input data: {}", e)))?;
+    let transaction = Transaction {
+        hash: tx.tx_hash,
+        nonce: tx.nonce.unwrap_or_default(),
+        block_hash: Some(tx.block_hash),
+        block_number: Some(tx.block_number),
+        transaction_index: tx.transaction_index,
+        from: tx.from_address,
+        to: tx.to_address,
+        value: tx.value,
+        gas_price: tx.gas_price,
+        gas: tx.gas,
+        input,
+        transaction_type: tx.transaction_type.map(U64::from),
+        max_fee_per_gas: tx.max_fee_per_gas,
+        max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+        chain_id: tx
+            .transaction_type
+            .is_some_and(|t| t > 0)
+            .then(|| U256::from(chain_id)),
+        ..Default::default()
+    };
+    let mut value = stored_fields(transaction, TRANSACTION_FIELDS)?;
+    if tx.nonce.is_none() {
+        if let Value::Object(fields) = &mut value {
+            fields.remove("nonce");
+        }
+    }
+    Ok(value)
+}
+
+/// Serializes `value` and keeps only `fields`, the ones backed by stored columns.
+fn stored_fields<T: Serialize>(value: T, fields: &[&str]) -> Result<Value, RpcError> {
+    let mut value = to_value(value)?;
+    if let Value::Object(map) = &mut value {
+        map.retain(|key, _| fields.contains(&key.as_str()));
+    }
+    Ok(value)
+}
+
+fn to_rpc_log(log: &MyLog) -> Result<Log, ApiError> {
+    Ok(Log {
+        address: log.address,
+        topics: log
+            .topics
+            .iter()
+            .map(|t| H256::from_str(t))
+            .collect::<Result<Vec<H256>, _>>()
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid topic: {}", e)))?,
+        data: Bytes::from_str(&log.data)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid log data: {}", e)))?,
+        block_hash: Some(log.block_hash),
+        block_number: Some(U64::from(log.block_number)),
+        transaction_hash: Some(log.transaction_hash),
+        transaction_index: log.transaction_index.map(U64::from),
+        log_index: log.log_index,
+        removed: Some(false),
+        ..Default::default()
+    })
+}
+
+fn quantity(n: u64) -> Value {
+    Value::String(format!("{:#x}", n))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn optional_param<T: serde::de::DeserializeOwned>(
+    params: &Value,
+    index: usize,
+) -> Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => serde_json::from_value(v.clone())
+            .map(Some)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid param {}: {}", index, e))),
+    }
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &Value, index: usize) -> Result<T, RpcError> {
+    optional_param(params, index)?
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Missing param {}", index)))
+}
+
+pub fn router(pool: PgPool, chain_id: u64) -> Router {
+    Router::new()
+        .route("/rpc", post(rpc_handler))
+        .with_state(RpcState { pool, chain_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+
+    fn transaction(nonce: Option<u64>, transaction_type: Option<u64>) -> MyTransaction {
+        MyTransaction {
+            tx_hash: H256::from_low_u64_be(1),
+            block_number: U64::from(100),
+            block_hash: H256::from_low_u64_be(2),
+            block_timestamp: None,
+            transaction_index: Some(U64::zero()),
+            from_address: Address::from_low_u64_be(3),
+            to_address: None,
+            value: U256::zero(),
+            gas_price: Some(U256::from(10)),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            gas: U256::from(21_000),
+            nonce: nonce.map(U256::from),
+            input_data: "0x".to_string(),
+            status: Some(1),
+            gas_used: None,
+            effective_gas_price: None,
+            transaction_type,
+            contract_address: Some(Address::from_low_u64_be(4)),
+            value_usd: None,
+            from_ens_name: None,
+            to_ens_name: None,
+        }
+    }
+
+    fn keys(value: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    fn sorted(fields: &[&'static str]) -> Vec<&'static str> {
+        let mut fields = fields.to_vec();
+        fields.sort_unstable();
+        fields
+    }
+
+    #[test]
+    fn transactions_carry_only_stored_fields() {
+        let access_list = rpc_transaction(&transaction(Some(7), Some(1)), 1).unwrap();
+        assert_eq!(access_list["type"], "0x1");
+        assert_eq!(access_list["nonce"], "0x7");
+        assert_eq!(access_list["chainId"], "0x1");
+        assert!(access_list.get("v").is_none() && access_list.get("r").is_none());
+        assert_eq!(
+            rpc_transaction(&transaction(Some(0), Some(3)), 1).unwrap()["type"],
+            "0x3"
+        );
+
+        let legacy = rpc_transaction(&transaction(Some(0), Some(0)), 1).unwrap();
+        assert_eq!(legacy["type"], "0x0");
+        assert!(legacy.get("chainId").is_none());
+
+        // Indexed before nonce and type were stored.
+        let old = rpc_transaction(&transaction(None, None), 1).unwrap();
+        assert!(old.get("nonce").is_none() && old.get("type").is_none());
+    }
+
+    #[test]
+    fn field_lists_match_the_ethers_names() {
+        let block = Block::<H256> {
+            hash: Some(H256::zero()),
+            number: Some(U64::zero()),
+            base_fee_per_gas: Some(U256::zero()),
+            ..Default::default()
+        };
+        assert_eq!(
+            keys(&stored_fields(block, BLOCK_FIELDS).unwrap()),
+            sorted(BLOCK_FIELDS)
+        );
+
+        let full = transaction(Some(1), Some(2));
+        let transaction = Transaction {
+            max_fee_per_gas: Some(U256::zero()),
+            max_priority_fee_per_gas: Some(U256::zero()),
+            gas_price: Some(U256::zero()),
+            chain_id: Some(U256::one()),
+            transaction_type: Some(U64::from(2)),
+            ..Default::default()
+        };
+        assert_eq!(
+            keys(&stored_fields(transaction, TRANSACTION_FIELDS).unwrap()),
+            sorted(TRANSACTION_FIELDS)
+        );
+
+        let receipt = TransactionReceipt {
+            contract_address: full.contract_address,
+            status: Some(U64::one()),
+            transaction_type: Some(U64::from(2)),
+            effective_gas_price: Some(U256::zero()),
+            ..Default::default()
+        };
+        let receipt = stored_fields(receipt, RECEIPT_FIELDS).unwrap();
+        assert_eq!(keys(&receipt), sorted(RECEIPT_FIELDS));
+        assert_eq!(
+            receipt["contractAddress"],
+            format!("{:#x}", Address::from_low_u64_be(4))
+        );
+        assert!(receipt.get("cumulativeGasUsed").is_none());
+        assert!(receipt.get("logsBloom").is_none());
+    }
+}
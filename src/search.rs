@@ -0,0 +1,305 @@
+// src/search.rs
+//
+// Universal search. `GET /search?q=` works out what the input is and looks it up everywhere it
+// could live: a decimal number is a block number, a 32-byte hash is checked against both blocks
+// and transactions, a 20-byte address against contracts, tokens and indexed activity, and any
+// other input is matched against token symbols and, when it looks like a name, resolved as an
+// ENS name: from the indexed ENS records (see ens.rs) first, then through the node. Each match
+// names its kind and links to the endpoint serving it; addresses carry their primary ENS name.
+//
+// Symbols are only known for tokens the pricing stage has seen, i.e. tokens in a WETH pool.
+
+use crate::api::{normalize_hex_values, ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{GenericErrorResponse, SearchMatch, SearchQuery, SearchResponse};
+use crate::ens;
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::providers::{Http, Middleware, Provider, ProviderError, RpcError};
+use ethers::types::Address;
+use sqlx::{PgPool, Row as SqlxRow};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Longest query accepted; ENS names are the longest legitimate input.
+const MAX_QUERY_LEN: usize = 255;
+
+#[derive(Clone)]
+pub struct SearchState {
+    pub pool: PgPool,
+    pub provider: Arc<Provider<Http>>,
+}
+
+/// What the shape of a query says it can be.
+#[derive(Debug, PartialEq, Eq)]
+enum QueryKind {
+    BlockNumber(i64),
+    Hash(String),
+    Address(String),
+    /// A token symbol, and an ENS name when `ens` is set.
+    Text {
+        ens: bool,
+    },
+}
+
+fn classify(q: &str) -> QueryKind {
+    if !q.is_empty() && q.chars().all(|c| c.is_ascii_digit()) {
+        if let Ok(number) = q.parse() {
+            return QueryKind::BlockNumber(number);
+        }
+    }
+    if let Ok(mut hash) = normalize_hex_values(&[q.to_string()], 32, "q") {
+        return QueryKind::Hash(hash.remove(0));
+    }
+    if let Ok(mut address) = normalize_hex_values(&[q.to_string()], 20, "q") {
+        return QueryKind::Address(address.remove(0));
+    }
+    QueryKind::Text {
+        ens: ens::is_ens_name(q),
+    }
+}
+
+fn search_match(kind: &str, value: String, label: Option<String>, link: String) -> SearchMatch {
+    SearchMatch {
+        kind: kind.to_string(),
+        value,
+        label,
+        block_number: None,
+        link,
+    }
+}
+
+/// Search
+///
+/// Finds blocks, transactions, contracts, tokens and accounts matching `q`: a block number, a
+/// block or transaction hash, an address, a token symbol or an ENS name. The input type is
+/// worked out from its shape; an input that matches nothing returns an empty list.
+#[utoipa::path(
+    get,
+    path = "/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matches, possibly none", body = SearchResponse),
+        (status = 400, description = "Empty or oversized query", body = GenericErrorResponse),
+        (status = 500, description = "Internal server error", body = GenericErrorResponse)
+    )
+)]
+pub async fn search_handler(
+    State(state): State<SearchState>,
+    Query(params): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, ApiError> {
+    let q = params.q.trim();
+    if q.is_empty() || q.len() > MAX_QUERY_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "q must be between 1 and {} characters.",
+            MAX_QUERY_LEN
+        )));
+    }
+
+    let matches = match classify(q) {
+        QueryKind::BlockNumber(number) => search_block_number(&state.pool, number).await?,
+        QueryKind::Hash(hash) => search_hash(&state.pool, &hash).await?,
+        QueryKind::Address(address) => search_address(&state.pool, &address).await?,
+        QueryKind::Text { ens } => {
+            let mut matches = search_symbol(&state.pool, q).await?;
+            if ens {
+                matches.extend(resolve_ens(&state, &q.to_lowercase()).await?);
+            }
+            matches
+        }
+    };
+
+    Ok(Json(SearchResponse {
+        query: q.to_string(),
+        matches,
+    }))
+}
+
+async fn search_block_number(pool: &PgPool, number: i64) -> Result<Vec<SearchMatch>, ApiError> {
+    let hash: Option<String> =
+        sqlx::query_scalar("SELECT block_hash FROM blocks WHERE block_number = $1")
+            .bind(number)
+            .fetch_optional(pool)
+            .await?;
+    Ok(hash
+        .map(|hash| SearchMatch {
+            block_number: Some(number),
+            ..search_match("block", hash, None, format!("/block/{}", number))
+        })
+        .into_iter()
+        .collect())
+}
+
+/// A hash is looked up as both a block and a transaction hash.
+async fn search_hash(pool: &PgPool, hash: &str) -> Result<Vec<SearchMatch>, ApiError> {
+    let mut matches = Vec::new();
+    let block: Option<i64> =
+        sqlx::query_scalar("SELECT block_number FROM blocks WHERE block_hash = $1")
+            .bind(hash)
+            .fetch_optional(pool)
+            .await?;
+    if let Some(number) = block {
+        matches.push(SearchMatch {
+            block_number: Some(number),
+            ..search_match("block", hash.to_string(), None, format!("/block/{}", hash))
+        });
+    }
+    let transaction: Option<i64> =
+        sqlx::query_scalar("SELECT block_number FROM transactions WHERE tx_hash = $1")
+            .bind(hash)
+            .fetch_optional(pool)
+            .await?;
+    if let Some(number) = transaction {
+        matches.push(SearchMatch {
+            block_number: Some(number),
+            ..search_match(
+                "transaction",
+                hash.to_string(),
+                None,
+                format!("/transaction/{}", hash),
+            )
+        });
+    }
+    Ok(matches)
+}
+
+/// An address can be a deployed contract, a priced token and an account with indexed
+/// activity at once; each is a separate match.
+async fn search_address(pool: &PgPool, address: &str) -> Result<Vec<SearchMatch>, ApiError> {
+    let row = sqlx::query(
+        "SELECT \
+             (SELECT block_number FROM contracts WHERE address = $1) AS deployed_block, \
+             (SELECT symbol FROM tokens WHERE token_address = $1) AS symbol, \
+             EXISTS (SELECT 1 FROM tokens WHERE token_address = $1) AS is_token, \
+             EXISTS (SELECT 1 FROM transactions WHERE from_address = $1) \
+             OR EXISTS (SELECT 1 FROM transactions WHERE to_address = $1) \
+             OR EXISTS (SELECT 1 FROM logs WHERE contract_address = $1) AS has_activity",
+    )
+    .bind(address)
+    .fetch_one(pool)
+    .await?;
+
+    let primary_name = match address.parse::<Address>() {
+        Ok(parsed) => ens::primary_names(pool, &HashSet::from([parsed]))
+            .await?
+            .remove(&parsed),
+        Err(_) => None,
+    };
+    let mut matches = Vec::new();
+    if let Some(block) = row.try_get::<Option<i64>, _>("deployed_block")? {
+        matches.push(SearchMatch {
+            block_number: Some(block),
+            ..search_match(
+                "contract",
+                address.to_string(),
+                primary_name.clone(),
+                format!("/contract/{}", address),
+            )
+        });
+    }
+    if row.try_get("is_token")? {
+        matches.push(search_match(
+            "token",
+            address.to_string(),
+            row.try_get("symbol")?,
+            format!("/prices/tokens/{}", address),
+        ));
+    }
+    if row.try_get("has_activity")? {
+        matches.push(account_match(address.to_string(), primary_name));
+    }
+    Ok(matches)
+}
+
+fn account_match(address: String, label: Option<String>) -> SearchMatch {
+    let link = format!("/accounts/{}/transfers", address);
+    search_match("address", address, label, link)
+}
+
+/// Tokens whose symbol equals the query, ignoring case.
+async fn search_symbol(pool: &PgPool, q: &str) -> Result<Vec<SearchMatch>, ApiError> {
+    let rows = sqlx::query(
+        "SELECT token_address, symbol FROM tokens WHERE UPPER(symbol) = UPPER($1) \
+         ORDER BY token_address LIMIT $2",
+    )
+    .bind(q)
+    .bind(MAX_PAGE_SIZE as i64)
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            let address: String = row.try_get("token_address")?;
+            let link = format!("/prices/tokens/{}", address);
+            Ok(search_match("token", address, row.try_get("symbol")?, link))
+        })
+        .collect()
+}
+
+/// Resolves `name` from the indexed ENS records, falling back to the registry on the node for
+/// names set before the indexed range. Names without a resolver or address are not matches;
+/// an unreachable node is an error.
+async fn resolve_ens(state: &SearchState, name: &str) -> Result<Option<SearchMatch>, ApiError> {
+    let ens_match = |address: Address| SearchMatch {
+        kind: "ens".to_string(),
+        ..account_match(format!("{:#x}", address), Some(name.to_string()))
+    };
+    if let Some(address) = ens::resolve_name(&state.pool, name).await? {
+        return Ok(Some(ens_match(address)));
+    }
+    match state.provider.resolve_name(name).await {
+        Ok(address) => Ok(Some(ens_match(address))),
+        Err(ProviderError::EnsError(_) | ProviderError::EnsNotOwned(_)) => Ok(None),
+        Err(e) if e.as_error_response().is_some() => Ok(None),
+        Err(e) => Err(ApiError::InternalServerError(format!(
+            "ENS lookup failed: {}",
+            e
+        ))),
+    }
+}
+
+pub fn router(pool: PgPool, provider: Arc<Provider<Http>>) -> Router {
+    Router::new()
+        .route("/search", get(search_handler))
+        .with_state(SearchState { pool, provider })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_queries_by_shape() {
+        let hash = "0x88df016429689c079f3b2f6ad39fa052532c56795b733da78a91ebe6a713944b";
+        let address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045";
+        assert_eq!(classify("18000000"), QueryKind::BlockNumber(18_000_000));
+        assert_eq!(classify("0"), QueryKind::BlockNumber(0));
+        assert_eq!(classify(hash), QueryKind::Hash(hash.to_string()));
+        assert_eq!(
+            classify(&hash.to_uppercase().replace("0X", "0x")),
+            QueryKind::Hash(hash.to_string())
+        );
+        assert_eq!(
+            classify(address),
+            QueryKind::Address(address.to_lowercase())
+        );
+        assert_eq!(classify("vitalik.eth"), QueryKind::Text { ens: true });
+        assert_eq!(classify("USDC"), QueryKind::Text { ens: false });
+    }
+
+    #[test]
+    fn falls_back_to_text() {
+        for q in [
+            "",
+            "99999999999999999999",
+            "-1",
+            "0x1234",
+            "0xzz8da6bf26964af9d7eed9e03e53415d37aa9604",
+            "1.5",
+        ] {
+            assert_eq!(classify(q), QueryKind::Text { ens: false }, "{}", q);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+// src/sinks/mod.rs
+//
+// Output sinks fan committed chain data out to downstream systems. Each configured sink runs
+// in its own task fed by the ingester's event bus. A sink's progress is checkpointed in
+// `sink_checkpoints` only after it accepts an event, and failed writes are retried until
+// they succeed, so delivery is at-least-once: after a crash or restart the sink replays
+// from its checkpoint and may see some events twice.
+
+mod nats;
+mod ndjson;
+mod notify;
+
+use crate::events::{self, ChainEvent, EventSender};
+use async_trait::async_trait;
+use serde_json::json;
+use sqlx::PgPool;
+use std::env;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, info, warn};
+
+pub use nats::NatsSink;
+pub use ndjson::NdjsonSink;
+pub use notify::PgNotifySink;
+
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+// How far to rewind when a checkpointed block was replaced while the sink was down.
+// The exact fork height is unknown at that point, so replay a safe margin.
+const CHECKPOINT_REWIND_DEPTH: u64 = 64;
+
+/// A destination for committed blocks and reorg and re-index notifications.
+#[async_trait]
+pub trait Sink: Send {
+    /// Stable name used as the checkpoint key; renaming a sink restarts it from the head.
+    fn name(&self) -> &str;
+
+    /// Hands one event to the destination. Returning `Ok` means the event is durably
+    /// accepted and the checkpoint may move past it; errors are retried.
+    async fn write(&mut self, event: &ChainEvent) -> eyre::Result<()>;
+}
+
+/// JSON form of an event shared by the sinks: one object per committed block or reorg.
+pub fn event_json(event: &ChainEvent) -> serde_json::Value {
+    match event {
+        ChainEvent::BlockCommitted {
+            block_number,
+            block_hash,
+            transactions,
+            logs,
+        } => {
+            let logs: Vec<serde_json::Value> = logs
+                .iter()
+                .map(|indexed| {
+                    let mut log = serde_json::to_value(&indexed.log).unwrap_or_default();
+                    log["id"] = json!(indexed.id);
+                    log
+                })
+                .collect();
+            json!({
+                "type": "block",
+                "blockNumber": block_number,
+                "blockHash": format!("{:#x}", block_hash),
+                "transactions": transactions,
+                "logs": logs,
+            })
+        }
+        ChainEvent::Reorg { fork_height } => json!({
+            "type": "reorg",
+            "forkHeight": fork_height,
+        }),
+        ChainEvent::Retract {
+            from_block,
+            to_block,
+        } => json!({
+            "type": "retract",
+            "fromBlock": from_block,
+            "toBlock": to_block,
+        }),
+    }
+}
+
+/// Compact form for transports with small payload limits; consumers fetch details via the API.
+pub fn event_summary_json(event: &ChainEvent) -> serde_json::Value {
+    match event {
+        ChainEvent::BlockCommitted {
+            block_number,
+            block_hash,
+            transactions,
+            logs,
+        } => json!({
+            "type": "block",
+            "blockNumber": block_number,
+            "blockHash": format!("{:#x}", block_hash),
+            "transactionCount": transactions.len(),
+            "logCount": logs.len(),
+        }),
+        ChainEvent::Reorg { .. } | ChainEvent::Retract { .. } => event_json(event),
+    }
+}
+
+/// Builds the sinks enabled in the environment:
+/// `SINK_NDJSON_DIR`, `SINK_PG_NOTIFY_CHANNEL` and `SINK_NATS_URL`.
+pub fn configured_sinks(pool: &PgPool) -> eyre::Result<Vec<Box<dyn Sink>>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+
+    if let Ok(dir) = env::var("SINK_NDJSON_DIR") {
+        let max_bytes = match env::var("SINK_NDJSON_MAX_BYTES") {
+            Ok(v) => v.parse()?,
+            Err(_) => ndjson::DEFAULT_MAX_FILE_BYTES,
+        };
+        sinks.push(Box::new(NdjsonSink::new(dir, max_bytes)));
+    }
+    if let Ok(channel) = env::var("SINK_PG_NOTIFY_CHANNEL") {
+        sinks.push(Box::new(PgNotifySink::new(pool.clone(), channel)?));
+    }
+    if let Ok(url) = env::var("SINK_NATS_URL") {
+        let subject =
+            env::var("SINK_NATS_SUBJECT").unwrap_or_else(|_| nats::DEFAULT_SUBJECT.to_string());
+        sinks.push(Box::new(NatsSink::new(url, subject)));
+    }
+
+    Ok(sinks)
+}
+
+/// Spawns one task per sink. A sink that fails (e.g. on a database error while
+/// checkpointing) restarts from its last saved checkpoint.
+pub fn spawn_sinks(pool: PgPool, events: EventSender, sinks: Vec<Box<dyn Sink>>) {
+    for mut sink in sinks {
+        let pool = pool.clone();
+        let events = events.clone();
+        tokio::spawn(async move {
+            loop {
+                match run_sink(&pool, &events, sink.as_mut()).await {
+                    Ok(()) => return,
+                    Err(e) => {
+                        error!(
+                            "SINK '{}': failed: {}. Restarting from checkpoint in {:?}.",
+                            sink.name(),
+                            e,
+                            MAX_RETRY_BACKOFF
+                        );
+                        tokio::time::sleep(MAX_RETRY_BACKOFF).await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+async fn run_sink(pool: &PgPool, events: &EventSender, sink: &mut dyn Sink) -> eyre::Result<()> {
+    // Subscribe first so blocks committed while catching up are buffered, not lost.
+    let mut rx = events.subscribe();
+
+    let mut checkpoint = match load_checkpoint(pool, sink.name()).await? {
+        Some(checkpoint) => checkpoint,
+        None => {
+            // New sinks start at the current head rather than replaying all history.
+            let head = crate::db::get_last_synced_block(pool)
+                .await?
+                .map_or(-1, |b| b as i64);
+            save_checkpoint(pool, sink.name(), head).await?;
+            head
+        }
+    };
+    info!(
+        "SINK '{}': starting after block {}.",
+        sink.name(),
+        checkpoint
+    );
+    catch_up(pool, sink, &mut checkpoint).await?;
+    // Range of the last re-index: its blocks are written again without moving the checkpoint.
+    let mut reindexing = None;
+
+    loop {
+        match rx.recv().await {
+            Ok(event) if event.is_reindexed(reindexing) => {
+                write_with_retry(sink, &event).await;
+            }
+            Ok(event) => match &*event {
+                ChainEvent::BlockCommitted { block_number, .. } => {
+                    let block_number = *block_number as i64;
+                    if block_number <= checkpoint {
+                        continue;
+                    }
+                    if block_number > checkpoint + 1 {
+                        // A gap means events were missed; fill it from Postgres.
+                        catch_up(pool, sink, &mut checkpoint).await?;
+                        if block_number <= checkpoint {
+                            continue;
+                        }
+                    }
+                    write_with_retry(sink, &event).await;
+                    checkpoint = block_number;
+                    save_checkpoint(pool, sink.name(), checkpoint).await?;
+                }
+                ChainEvent::Reorg { fork_height } => {
+                    reindexing = None;
+                    rewind(pool, sink, &mut checkpoint, *fork_height).await?;
+                }
+                ChainEvent::Retract {
+                    from_block,
+                    to_block,
+                } => {
+                    if (*from_block as i64) <= checkpoint {
+                        write_with_retry(sink, &event).await;
+                        reindexing = Some((*from_block, *to_block));
+                    }
+                }
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "SINK '{}': lagged by {} events; catching up from the database.",
+                    sink.name(),
+                    skipped
+                );
+                catch_up(pool, sink, &mut checkpoint).await?;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+/// Writes every stored block after the checkpoint up to the indexed head.
+async fn catch_up(pool: &PgPool, sink: &mut dyn Sink, checkpoint: &mut i64) -> eyre::Result<()> {
+    // If the checkpointed block was rolled back while we were away, downstream holds
+    // orphaned data: retract a safe margin and replay it.
+    if *checkpoint >= 0 {
+        let stored =
+            crate::db::get_canonical_block_hash_at_height(pool, *checkpoint as u64).await?;
+        let saved = load_checkpoint_hash(pool, sink.name()).await?;
+        if saved.is_some() && stored != saved {
+            let fork_height = (*checkpoint as u64).saturating_sub(CHECKPOINT_REWIND_DEPTH - 1);
+            warn!(
+                "SINK '{}': checkpoint block {} is no longer canonical; replaying from {}.",
+                sink.name(),
+                *checkpoint,
+                fork_height
+            );
+            rewind(pool, sink, checkpoint, fork_height).await?;
+        }
+    }
+
+    let head = crate::db::get_last_synced_block(pool)
+        .await?
+        .map_or(-1, |b| b as i64);
+    while *checkpoint < head {
+        let block_number = *checkpoint + 1;
+        if let Some(event) = events::load_block_committed(pool, block_number as u64).await? {
+            write_with_retry(sink, &event).await;
+        }
+        *checkpoint = block_number;
+        save_checkpoint(pool, sink.name(), block_number).await?;
+    }
+    Ok(())
+}
+
+/// Tells the sink that data at or above `fork_height` is gone and moves the checkpoint back.
+async fn rewind(
+    pool: &PgPool,
+    sink: &mut dyn Sink,
+    checkpoint: &mut i64,
+    fork_height: u64,
+) -> eyre::Result<()> {
+    write_with_retry(sink, &ChainEvent::Reorg { fork_height }).await;
+    *checkpoint = (*checkpoint).min(fork_height as i64 - 1);
+    save_checkpoint(pool, sink.name(), *checkpoint).await?;
+    Ok(())
+}
+
+/// Retries with exponential backoff until the sink accepts the event. Skipping would
+/// break the at-least-once guarantee, so a broken sink stalls rather than drops data.
+async fn write_with_retry(sink: &mut dyn Sink, event: &ChainEvent) {
+    let mut backoff = INITIAL_RETRY_BACKOFF;
+    loop {
+        match sink.write(event).await {
+            Ok(()) => return,
+            Err(e) => {
+                warn!(
+                    "SINK '{}': write failed: {}. Retrying in {:?}.",
+                    sink.name(),
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+            }
+        }
+    }
+}
+
+/// The sink's last accepted block, if it has run before.
+async fn load_checkpoint(pool: &PgPool, name: &str) -> Result<Option<i64>, sqlx::Error> {
+    let row: Option<(i64,)> =
+        sqlx::query_as("SELECT last_block FROM sink_checkpoints WHERE sink_name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(|(block_number,)| block_number))
+}
+
+async fn load_checkpoint_hash(pool: &PgPool, name: &str) -> Result<Option<String>, sqlx::Error> {
+    let row: Option<(Option<String>,)> =
+        sqlx::query_as("SELECT last_block_hash FROM sink_checkpoints WHERE sink_name = $1")
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.and_then(|(hash,)| hash))
+}
+
+/// Records the checkpoint together with the stored hash of that block, so a later restart
+/// can tell whether it was replaced by a reorg.
+async fn save_checkpoint(pool: &PgPool, name: &str, block_number: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO sink_checkpoints (sink_name, last_block, last_block_hash, updated_at) \
+         VALUES ($1, $2, (SELECT block_hash FROM blocks WHERE block_number = $2), NOW()) \
+         ON CONFLICT (sink_name) DO UPDATE SET last_block = EXCLUDED.last_block, \
+         last_block_hash = EXCLUDED.last_block_hash, updated_at = NOW()",
+    )
+    .bind(name)
+    .bind(block_number)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,386 @@
+// src/sinks/nats.rs
+//
+// Message-queue producer speaking the NATS core text protocol over TCP. Blocks are published
+// to `<subject>.block`, reorgs to `<subject>.reorg` and re-index retractions to
+// `<subject>.retract`. Every publish is followed by a PING/PONG round trip, so a successful
+// write means the broker has processed the message. Any local NATS server (e.g. the `nats`
+// service in docker-compose.yml) works as a broker.
+//
+// A block whose JSON exceeds the server's `max_payload` is split into several messages on the
+// same subject, each with the block's number and hash, `part` (from 0) and `parts`, and a
+// share of its transactions and logs in order. The checkpoint only moves once every part is
+// published; a retry after a failure in between sends all parts again. A single transaction
+// or log too large for one message fails the write instead, which stalls the sink with an
+// error in the log rather than lose data.
+
+use super::{event_json, Sink};
+use crate::events::ChainEvent;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{error, info};
+
+pub const DEFAULT_SUBJECT: &str = "evm_indexer";
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+// Server default; replaced by the `max_payload` the server announces in INFO.
+const DEFAULT_MAX_PAYLOAD: usize = 1024 * 1024;
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    max_payload: usize,
+}
+
+pub struct NatsSink {
+    address: String,
+    subject: String,
+    connection: Option<Connection>,
+}
+
+impl NatsSink {
+    /// `url` is `host:port`, optionally prefixed with `nats://`.
+    pub fn new(url: String, subject: String) -> Self {
+        let address = url.trim_start_matches("nats://").to_string();
+        NatsSink {
+            address,
+            subject,
+            connection: None,
+        }
+    }
+
+    async fn connect(&self) -> eyre::Result<Connection> {
+        let stream = TcpStream::connect(&self.address).await?;
+        let mut stream = BufReader::new(stream);
+
+        let info = read_line(&mut stream).await?;
+        let info_json = info
+            .strip_prefix("INFO ")
+            .ok_or_else(|| eyre::eyre!("expected INFO from NATS server, got: {}", info))?;
+        let max_payload = serde_json::from_str::<serde_json::Value>(info_json)
+            .ok()
+            .and_then(|v| v["max_payload"].as_u64())
+            .map_or(DEFAULT_MAX_PAYLOAD, |v| v as usize);
+
+        stream
+            .get_mut()
+            .write_all(
+                b"CONNECT {\"verbose\":false,\"pedantic\":false,\"name\":\"evm_indexer\"}\r\n",
+            )
+            .await?;
+        info!("SINK 'nats': connected to {}.", self.address);
+        Ok(Connection {
+            stream,
+            max_payload,
+        })
+    }
+
+    async fn publish(
+        connection: &mut Connection,
+        subject: &str,
+        payload: &[u8],
+    ) -> eyre::Result<()> {
+        let stream = connection.stream.get_mut();
+        stream
+            .write_all(format!("PUB {} {}\r\n", subject, payload.len()).as_bytes())
+            .await?;
+        stream.write_all(payload).await?;
+        stream.write_all(b"\r\nPING\r\n").await?;
+
+        // Wait for the PONG to our PING; the server handles messages in order.
+        loop {
+            let line = read_line(&mut connection.stream).await?;
+            match line.as_str() {
+                "PONG" => return Ok(()),
+                "PING" => connection.stream.get_mut().write_all(b"PONG\r\n").await?,
+                "+OK" => {}
+                _ if line.starts_with("INFO ") => {}
+                _ if line.starts_with("-ERR") => eyre::bail!("NATS server error: {}", line),
+                _ => eyre::bail!("Unexpected NATS protocol line: {}", line),
+            }
+        }
+    }
+}
+
+async fn read_line(stream: &mut BufReader<TcpStream>) -> eyre::Result<String> {
+    let mut line = String::new();
+    let read = tokio::time::timeout(IO_TIMEOUT, stream.read_line(&mut line)).await??;
+    if read == 0 {
+        eyre::bail!("NATS connection closed");
+    }
+    Ok(line.trim_end().to_string())
+}
+
+#[async_trait]
+impl Sink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn write(&mut self, event: &ChainEvent) -> eyre::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect().await?);
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+
+        let kind = match event {
+            ChainEvent::BlockCommitted { .. } => "block",
+            ChainEvent::Reorg { .. } => "reorg",
+            ChainEvent::Retract { .. } => "retract",
+        };
+        let subject = format!("{}.{}", self.subject, kind);
+        let payloads =
+            split_payload(event_json(event), connection.max_payload).inspect_err(|e| {
+                error!("SINK 'nats': cannot publish {}: {}", kind, e);
+            })?;
+
+        for payload in &payloads {
+            let result =
+                tokio::time::timeout(IO_TIMEOUT, Self::publish(connection, &subject, payload))
+                    .await
+                    .map_err(eyre::Report::from)
+                    .and_then(|r| r);
+            if result.is_err() {
+                // Reconnect on the next attempt rather than reuse a connection in an unknown
+                // state.
+                self.connection = None;
+                return result;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `event` as one payload, or for a block over `max_payload` bytes, as parts that each fit.
+fn split_payload(mut event: Value, max_payload: usize) -> eyre::Result<Vec<Vec<u8>>> {
+    let whole = serde_json::to_vec(&event)?;
+    if whole.len() <= max_payload {
+        return Ok(vec![whole]);
+    }
+    let (Some(Value::Array(transactions)), Some(Value::Array(logs))) = (
+        event.get_mut("transactions").map(Value::take),
+        event.get_mut("logs").map(Value::take),
+    ) else {
+        eyre::bail!(
+            "{} byte message exceeds the server's max_payload of {}",
+            whole.len(),
+            max_payload
+        );
+    };
+
+    // Room left in a part once its envelope is written, with `part` and `parts` as wide as
+    // they can get. Each item also takes a separating comma.
+    let envelope = |part: usize, parts: usize, transactions: &[Value], logs: &[Value]| {
+        json!({
+            "type": event["type"],
+            "blockNumber": event["blockNumber"],
+            "blockHash": event["blockHash"],
+            "part": part,
+            "parts": parts,
+            "transactions": transactions,
+            "logs": logs,
+        })
+    };
+    let overhead = serde_json::to_vec(&envelope(usize::MAX, usize::MAX, &[], &[]))?.len();
+    let room = max_payload.saturating_sub(overhead);
+
+    let mut parts: Vec<(Vec<Value>, Vec<Value>)> = vec![Default::default()];
+    let mut used = 0;
+    let items = transactions
+        .into_iter()
+        .map(|item| (true, item))
+        .chain(logs.into_iter().map(|item| (false, item)));
+    for (is_transaction, item) in items {
+        let size = serde_json::to_vec(&item)?.len() + 1;
+        if size > room {
+            eyre::bail!(
+                "a single {} of {} bytes exceeds the server's max_payload of {}",
+                if is_transaction { "transaction" } else { "log" },
+                size - 1,
+                max_payload
+            );
+        }
+        if used + size > room {
+            parts.push(Default::default());
+            used = 0;
+        }
+        let (part_transactions, part_logs) = parts.last_mut().expect("never empty");
+        match is_transaction {
+            true => part_transactions.push(item),
+            false => part_logs.push(item),
+        }
+        used += size;
+    }
+
+    let count = parts.len();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(part, (transactions, logs))| {
+            Ok(serde_json::to_vec(&envelope(
+                part,
+                count,
+                transactions,
+                logs,
+            ))?)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::IndexedLog;
+    use crate::models::MyLog;
+    use ethers::types::{Address, H256, U256};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// A broker that accepts one client, announces `max_payload`, answers each PING with a
+    /// PONG (after a PING of its own, which the client must answer) and returns the messages
+    /// published until the client disconnects.
+    async fn broker(max_payload: usize) -> (String, JoinHandle<Vec<(String, Value)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let info = format!(
+                "INFO {{\"server_id\":\"test\",\"max_payload\":{}}}\r\n",
+                max_payload
+            );
+            stream.get_mut().write_all(info.as_bytes()).await.unwrap();
+
+            let mut published = Vec::new();
+            let mut line = String::new();
+            let mut awaiting_pong = false;
+            loop {
+                line.clear();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    return published;
+                }
+                let line = line.trim_end();
+                if line.starts_with("CONNECT ") {
+                    continue;
+                } else if let Some(args) = line.strip_prefix("PUB ") {
+                    let (subject, len) = args.split_once(' ').unwrap();
+                    let mut payload = vec![0; len.parse::<usize>().unwrap() + 2];
+                    stream.read_exact(&mut payload).await.unwrap();
+                    assert_eq!(&payload[payload.len() - 2..], b"\r\n");
+                    payload.truncate(payload.len() - 2);
+                    assert!(payload.len() <= max_payload, "payload over max_payload");
+                    let payload = serde_json::from_slice(&payload).unwrap();
+                    published.push((subject.to_string(), payload));
+                } else if line == "PING" {
+                    stream.get_mut().write_all(b"PING\r\n").await.unwrap();
+                    awaiting_pong = true;
+                } else if line == "PONG" && awaiting_pong {
+                    stream.get_mut().write_all(b"PONG\r\n").await.unwrap();
+                    awaiting_pong = false;
+                } else {
+                    panic!("unexpected line from client: {}", line);
+                }
+            }
+        });
+        (address, handle)
+    }
+
+    /// Block `number` with `logs` logs of `data_bytes` bytes of data each.
+    fn block(number: u64, logs: usize, data_bytes: usize) -> ChainEvent {
+        ChainEvent::BlockCommitted {
+            block_number: number,
+            block_hash: H256::from_low_u64_be(number),
+            transactions: Vec::new(),
+            logs: (0..logs)
+                .map(|i| IndexedLog {
+                    id: i as i64,
+                    log: MyLog {
+                        log_index: Some(U256::from(i)),
+                        transaction_hash: H256::from_low_u64_be(1_000 + i as u64),
+                        transaction_index: Some(i as u64),
+                        block_number: number,
+                        block_hash: H256::from_low_u64_be(number),
+                        block_timestamp: None,
+                        address: Address::from_low_u64_be(7),
+                        data: format!("0x{}", "ab".repeat(data_bytes)),
+                        topics: Vec::new(),
+                        address_ens_name: None,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    fn log_ids(message: &Value) -> Vec<i64> {
+        message["logs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|log| log["id"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn publishes_events_to_their_subjects() {
+        let (address, broker) = broker(DEFAULT_MAX_PAYLOAD).await;
+        let mut sink = NatsSink::new(format!("nats://{}", address), "test".to_string());
+        sink.write(&block(100, 2, 4)).await.unwrap();
+        sink.write(&ChainEvent::Reorg { fork_height: 99 })
+            .await
+            .unwrap();
+        sink.write(&ChainEvent::Retract {
+            from_block: 90,
+            to_block: 95,
+        })
+        .await
+        .unwrap();
+        drop(sink);
+
+        let published = broker.await.unwrap();
+        let subjects: Vec<&str> = published.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(subjects, ["test.block", "test.reorg", "test.retract"]);
+        assert_eq!(published[0].1, event_json(&block(100, 2, 4)));
+        assert_eq!(published[1].1, json!({"type": "reorg", "forkHeight": 99}));
+        assert_eq!(
+            published[2].1,
+            json!({"type": "retract", "fromBlock": 90, "toBlock": 95})
+        );
+    }
+
+    #[tokio::test]
+    async fn splits_a_block_over_max_payload_into_parts() {
+        let (address, broker) = broker(4_096).await;
+        let mut sink = NatsSink::new(address, "test".to_string());
+        // 40 logs of about 1 KB each.
+        sink.write(&block(100, 40, 500)).await.unwrap();
+        drop(sink);
+
+        let published = broker.await.unwrap();
+        assert!(published.len() > 1);
+        let parts = published.len();
+        let mut ids = Vec::new();
+        for (part, (subject, message)) in published.iter().enumerate() {
+            assert_eq!(subject, "test.block");
+            assert_eq!(message["blockNumber"], 100);
+            assert_eq!(
+                message["blockHash"],
+                format!("{:#x}", H256::from_low_u64_be(100))
+            );
+            assert_eq!(message["part"], part);
+            assert_eq!(message["parts"], parts);
+            assert!(message["transactions"].as_array().unwrap().is_empty());
+            ids.extend(log_ids(message));
+        }
+        assert_eq!(ids, (0..40).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_log_larger_than_max_payload() {
+        let (address, broker) = broker(4_096).await;
+        let mut sink = NatsSink::new(address, "test".to_string());
+        assert!(sink.write(&block(100, 2, 4_096)).await.is_err());
+        drop(sink);
+        assert!(broker.await.unwrap().is_empty());
+    }
+}
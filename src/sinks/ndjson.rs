@@ -0,0 +1,87 @@
+// src/sinks/ndjson.rs
+//
+// Appends one JSON line per event to files in a directory, starting a new file once the
+// current one would exceed the size limit. Each line is fsynced before the write returns.
+
+use super::{event_json, Sink};
+use crate::events::ChainEvent;
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+pub const DEFAULT_MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+pub struct NdjsonSink {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    current: Option<(File, u64)>,
+}
+
+impl NdjsonSink {
+    pub fn new(dir: impl Into<PathBuf>, max_file_bytes: u64) -> Self {
+        NdjsonSink {
+            dir: dir.into(),
+            max_file_bytes,
+            current: None,
+        }
+    }
+
+    /// Opens a fresh file named after the first block it will contain.
+    async fn rotate(&mut self, event: &ChainEvent) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+        let block = match event {
+            ChainEvent::BlockCommitted { block_number, .. } => *block_number,
+            ChainEvent::Reorg { fork_height } => *fork_height,
+            ChainEvent::Retract { from_block, .. } => *from_block,
+        };
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let path = self
+            .dir
+            .join(format!("events-{:012}-{}.ndjson", block, millis));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        self.current = Some((file, 0));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for NdjsonSink {
+    fn name(&self) -> &str {
+        "ndjson"
+    }
+
+    async fn write(&mut self, event: &ChainEvent) -> eyre::Result<()> {
+        let mut line = serde_json::to_vec(&event_json(event))?;
+        line.push(b'\n');
+
+        let needs_rotation = match &self.current {
+            None => true,
+            Some((_, written)) => *written > 0 && written + line.len() as u64 > self.max_file_bytes,
+        };
+        if needs_rotation {
+            self.rotate(event).await?;
+        }
+
+        let (file, written) = self.current.as_mut().expect("file opened above");
+        let result = async {
+            file.write_all(&line).await?;
+            file.sync_data().await
+        }
+        .await;
+        if let Err(e) = result {
+            // A partially written line may be on disk; continue in a fresh file.
+            self.current = None;
+            return Err(e.into());
+        }
+        *written += line.len() as u64;
+        Ok(())
+    }
+}
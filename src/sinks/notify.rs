@@ -0,0 +1,47 @@
+// src/sinks/notify.rs
+//
+// Publishes events with Postgres NOTIFY so services already connected to the database can
+// LISTEN instead of polling. NOTIFY payloads are limited to 8000 bytes, so blocks are sent
+// in their compact summary form.
+
+use super::{event_summary_json, Sink};
+use crate::events::ChainEvent;
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+pub struct PgNotifySink {
+    pool: PgPool,
+    channel: String,
+}
+
+impl PgNotifySink {
+    pub fn new(pool: PgPool, channel: String) -> eyre::Result<Self> {
+        let valid = !channel.is_empty()
+            && channel.len() <= 63
+            && !channel.starts_with(|c: char| c.is_ascii_digit())
+            && channel
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            eyre::bail!("Invalid NOTIFY channel name: {}", channel);
+        }
+        Ok(PgNotifySink { pool, channel })
+    }
+}
+
+#[async_trait]
+impl Sink for PgNotifySink {
+    fn name(&self) -> &str {
+        "pg_notify"
+    }
+
+    async fn write(&mut self, event: &ChainEvent) -> eyre::Result<()> {
+        let payload = serde_json::to_string(&event_summary_json(event))?;
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(&self.channel)
+            .bind(payload)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+// src/stats.rs
+//
+// Chain activity counters and time-series rollups. The transform stage summarizes each block
+// (transaction and log counts, and with rollups on its distinct senders, ETH volume and logs
+// per contract and topic0); the writer folds the summary into the block's transaction:
+//
+//   * `stats_counters`: running totals of blocks, transactions and logs, read by GET /stats
+//     instead of counting the tables.
+//   * `stats_rollups`, `stats_log_rollups`: hourly and daily buckets, read by
+//     GET /stats/timeseries. Unique senders are exact: `stats_senders` keeps each bucket's
+//     senders with the first block they appeared in, and a bucket's count only grows when a
+//     sender is new to it.
+//
+// Every number is added per block, so a reorg rollback subtracts the rolled-back blocks'
+// share, computed from their rows before they are deleted. `stats_blocks` records which blocks
+// were rolled up, so blocks ingested with rollups off are never subtracted. The senders of
+// rolled-back transactions are recounted from the transactions left in their buckets, so
+// unique senders stay exact when a range in the middle of a bucket is re-indexed.
+//
+// Counts cover what is indexed: in selective mode, only the matching logs and their
+// transactions.
+
+use crate::api::ApiError;
+use crate::api_models::{
+    GenericErrorResponse, GetStatsTimeseriesQuery, StatsTimeseriesEntry, StatsTimeseriesResponse,
+};
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use ethers::types::{Address, U256};
+use sqlx::{postgres::PgRow, PgPool, Row as SqlxRow};
+use std::collections::{BTreeSet, HashMap};
+
+/// Rollup resolutions and their bucket length in seconds.
+pub const RESOLUTIONS: [(&str, i64); 2] = [("hour", crate::gas::HOUR_SECONDS), ("day", 86_400)];
+const DEFAULT_BUCKETS: u64 = 100;
+const MAX_BUCKETS: u64 = 1024;
+
+/// One block's contribution to the counters and rollups, handed to the writer.
+#[derive(Clone, Debug)]
+pub struct BlockStats {
+    pub block_number: u64,
+    pub block_timestamp: u64,
+    pub tx_count: u64,
+    pub log_count: u64,
+    /// Detail for the hourly and daily rollups; `None` when rollups are off, in which case
+    /// only the counters are kept.
+    pub rollup: Option<StatsRollup>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StatsRollup {
+    /// Distinct senders of the block's transactions, as lowercase hex.
+    pub senders: Vec<String>,
+    /// Value moved by successful transactions, in wei.
+    pub eth_volume: U256,
+    pub logs: Vec<LogCount>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LogCount {
+    pub contract_address: String,
+    /// `None` for anonymous events.
+    pub topic0: Option<String>,
+    pub count: i64,
+}
+
+/// Summarizes a block's indexed transactions and logs.
+pub fn summarize_block(
+    block: &MyBlock,
+    transactions: &[MyTransaction],
+    logs: &[MyLog],
+    rollups: bool,
+) -> BlockStats {
+    let rollup = rollups.then(|| {
+        let senders: BTreeSet<String> = transactions
+            .iter()
+            .map(|tx| format!("{:#x}", tx.from_address))
+            .collect();
+        let eth_volume = transactions
+            .iter()
+            .filter(|tx| tx.status != Some(0))
+            .fold(U256::zero(), |sum, tx| sum.saturating_add(tx.value));
+        let mut counts: HashMap<(Address, Option<&String>), i64> = HashMap::new();
+        for log in logs {
+            *counts.entry((log.address, log.topics.first())).or_default() += 1;
+        }
+        StatsRollup {
+            senders: senders.into_iter().collect(),
+            eth_volume,
+            logs: counts
+                .into_iter()
+                .map(|((address, topic0), count)| LogCount {
+                    contract_address: format!("{:#x}", address),
+                    topic0: topic0.cloned(),
+                    count,
+                })
+                .collect(),
+        }
+    });
+    BlockStats {
+        block_number: block.block_number.as_u64(),
+        block_timestamp: block.timestamp.as_u64(),
+        tx_count: transactions.len() as u64,
+        log_count: logs.len() as u64,
+        rollup,
+    }
+}
+
+fn row_to_entry(row: &PgRow) -> Result<StatsTimeseriesEntry, ApiError> {
+    Ok(StatsTimeseriesEntry {
+        bucket: row.try_get("bucket")?,
+        blocks: row.try_get("blocks")?,
+        tx_count: row.try_get("tx_count")?,
+        unique_senders: row.try_get("unique_senders")?,
+        eth_volume: row.try_get("eth_volume")?,
+        log_count: row.try_get("log_count")?,
+    })
+}
+
+/// Activity Time Series
+///
+/// Blocks, transactions, unique senders, ETH volume and logs per hour or day, oldest first,
+/// ending at the bucket containing `toTime`. With `contract` and/or `topic0` only the matching
+/// log count is returned. Buckets with no indexed blocks are omitted.
+#[utoipa::path(
+    get,
+    path = "/stats/timeseries",
+    params(GetStatsTimeseriesQuery),
+    responses(
+        (status = 200, description = "Activity per bucket", body = StatsTimeseriesResponse),
+        (status = 400, description = "Invalid resolution, range or filter", body = GenericErrorResponse)
+    )
+)]
+pub async fn stats_timeseries_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetStatsTimeseriesQuery>,
+) -> Result<Json<StatsTimeseriesResponse>, ApiError> {
+    let resolution = params.resolution.as_deref().unwrap_or("hour");
+    let Some((_, seconds)) = RESOLUTIONS.iter().find(|(name, _)| *name == resolution) else {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid resolution '{}'; expected hour or day.",
+            resolution
+        )));
+    };
+    if let (Some(from), Some(to)) = (params.from_time, params.to_time) {
+        if from > to {
+            return Err(ApiError::BadRequest(
+                "fromTime must not be after toTime.".to_string(),
+            ));
+        }
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_BUCKETS)
+        .clamp(1, MAX_BUCKETS) as i64;
+    // Bucket keys are their start, so round the bounds down to whole buckets.
+    let from_bucket = params.from_time.map(|t| t.div_euclid(*seconds) * seconds);
+    let to_bucket = params.to_time.map(|t| t.div_euclid(*seconds) * seconds);
+
+    let rows = if params.contract.is_none() && params.topic0.is_none() {
+        sqlx::query(
+            "SELECT * FROM ( \
+                 SELECT bucket, blocks, tx_count, unique_senders, eth_volume::TEXT AS eth_volume, \
+                        log_count \
+                 FROM stats_rollups \
+                 WHERE resolution = $1 AND bucket >= COALESCE($2, bucket) \
+                   AND bucket <= COALESCE($3, bucket) \
+                 ORDER BY bucket DESC LIMIT $4 \
+             ) b ORDER BY bucket",
+        )
+        .bind(resolution)
+        .bind(from_bucket)
+        .bind(to_bucket)
+        .bind(limit)
+        .fetch_all(&pool)
+        .await?
+    } else {
+        let contract = params
+            .contract
+            .as_deref()
+            .map(|c| crate::dex::normalize_address(c, "contract"))
+            .transpose()?;
+        let topic0 = params
+            .topic0
+            .as_ref()
+            .map(|t| crate::api::normalize_hex_values(std::slice::from_ref(t), 32, "topic0"))
+            .transpose()?
+            .map(|mut values| values.remove(0));
+        sqlx::query(
+            "SELECT * FROM ( \
+                 SELECT bucket, NULL::BIGINT AS blocks, NULL::BIGINT AS tx_count, \
+                        NULL::BIGINT AS unique_senders, NULL::TEXT AS eth_volume, \
+                        SUM(log_count)::BIGINT AS log_count \
+                 FROM stats_log_rollups \
+                 WHERE resolution = $1 AND bucket >= COALESCE($2, bucket) \
+                   AND bucket <= COALESCE($3, bucket) \
+                   AND contract_address = COALESCE($4, contract_address) \
+                   AND topic0 IS NOT DISTINCT FROM COALESCE($5, topic0) \
+                 GROUP BY bucket ORDER BY bucket DESC LIMIT $6 \
+             ) b ORDER BY bucket",
+        )
+        .bind(resolution)
+        .bind(from_bucket)
+        .bind(to_bucket)
+        .bind(contract)
+        .bind(topic0)
+        .bind(limit)
+        .fetch_all(&pool)
+        .await?
+    };
+    Ok(Json(StatsTimeseriesResponse {
+        resolution: resolution.to_string(),
+        contract: params.contract.map(|c| c.to_lowercase()),
+        topic0: params.topic0.map(|t| t.to_lowercase()),
+        entries: rows.iter().map(row_to_entry).collect::<Result<_, _>>()?,
+    }))
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route("/stats/timeseries", get(stats_timeseries_handler))
+        .with_state(pool)
+}
@@ -0,0 +1,714 @@
+// src/storage/mod.rs
+//
+// Backend-neutral storage interface. The ingester writes and the core REST reads go through
+// `Storage`, so the indexer can run on Postgres (the default, and required for the RPC,
+// GraphQL, streaming, webhook, sink and export features) or on an embedded SQLite file.
+
+mod postgres;
+mod sqlite;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+use crate::api::ApiError;
+use crate::api_models::{
+    Closest, GetBlocksQuery, GetLogsFilter, GetTransactionsFilter, IndexerStats,
+};
+use crate::contracts::ContractBlock;
+use crate::dex::DexBlock;
+use crate::ens::EnsBlock;
+use crate::gas::GasBlock;
+use crate::handlers::EntityWrite;
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::pricing::BlockPrices;
+use crate::stats::BlockStats;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Writes for one commit group: a single block, or several consecutive blocks during backfill
+/// (`BACKFILL_COMMIT_BLOCKS`). Applied atomically on `commit`; dropping the writer without
+/// committing rolls everything back.
+#[async_trait]
+pub trait StorageTransaction: Send {
+    async fn insert_block_data(&mut self, block: &MyBlock) -> Result<(), sqlx::Error>;
+
+    async fn insert_transaction_data(&mut self, tx: &MyTransaction) -> Result<(), sqlx::Error>;
+
+    /// Returns the generated log id, which orders logs within a block for cursors.
+    async fn insert_log_data(&mut self, log: &MyLog) -> Result<i64, sqlx::Error>;
+
+    /// Bulk variant of `insert_transaction_data`. Backends override this when they can write
+    /// many rows per round trip.
+    async fn insert_transactions_batch(
+        &mut self,
+        txs: &[MyTransaction],
+    ) -> Result<(), sqlx::Error> {
+        for tx in txs {
+            self.insert_transaction_data(tx).await?;
+        }
+        Ok(())
+    }
+
+    /// Bulk variant of `insert_log_data`; ids are returned in input order.
+    async fn insert_logs_batch(&mut self, logs: &[MyLog]) -> Result<Vec<i64>, sqlx::Error> {
+        let mut ids = Vec::with_capacity(logs.len());
+        for log in logs {
+            ids.push(self.insert_log_data(log).await?);
+        }
+        Ok(ids)
+    }
+
+    async fn set_last_synced_block(
+        &mut self,
+        block_number: u64,
+        chain_head: u64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Adds a block to the running totals behind `get_stats`, and to the activity rollups
+    /// where the backend keeps them.
+    async fn insert_block_stats(&mut self, stats: &BlockStats) -> Result<(), sqlx::Error>;
+
+    /// Entity versions written by event handlers for one block. Handlers only run on Postgres.
+    async fn upsert_handler_entities(&mut self, _rows: &[EntityWrite]) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "event handlers require PostgreSQL storage".into(),
+        ))
+    }
+
+    /// Records that a handler's script failed at `block_number`, so the failure commits (or
+    /// rolls back) with the block.
+    async fn mark_handler_failed(
+        &mut self,
+        _handler_id: i64,
+        _block_number: u64,
+        _error: &str,
+    ) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "event handlers require PostgreSQL storage".into(),
+        ))
+    }
+
+    /// Decoded DEX pools, swaps and liquidity events for one block; `log_ids` are the ids
+    /// returned for the block's logs. DEX decoding only runs on Postgres.
+    async fn insert_dex_block(
+        &mut self,
+        _dex: &DexBlock,
+        _log_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "DEX decoding requires PostgreSQL storage".into(),
+        ))
+    }
+
+    /// Token decimals and the prices derived from one block's DEX events.
+    async fn insert_prices(&mut self, _prices: &BlockPrices) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "Pricing requires PostgreSQL storage".into(),
+        ))
+    }
+
+    /// One block's gas rollup, folded into the hourly rollups.
+    async fn insert_gas_block(&mut self, _gas: &GasBlock) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "Gas rollups require PostgreSQL storage".into(),
+        ))
+    }
+
+    /// Contracts deployed in one block and the proxy upgrades it emitted; `log_ids` as for
+    /// `insert_dex_block`.
+    async fn insert_contracts(
+        &mut self,
+        _contracts: &ContractBlock,
+        _log_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "Contract indexing requires PostgreSQL storage".into(),
+        ))
+    }
+
+    /// One block's ENS registry and resolver events; `log_ids` as for `insert_dex_block`.
+    async fn insert_ens(&mut self, _ens: &EnsBlock, _log_ids: &[i64]) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "ENS indexing requires PostgreSQL storage".into(),
+        ))
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), sqlx::Error>;
+}
+
+/// Pagination for the filtered list endpoints: either a keyset cursor or a 1-based page.
+#[derive(Debug, Clone, Copy)]
+pub struct Page {
+    pub limit: i64,
+    pub cursor: Option<(i64, i64)>,
+    pub offset: i64,
+}
+
+impl Page {
+    pub fn new(page: u64, page_size: u64, cursor: Option<(i64, i64)>) -> Self {
+        let limit = page_size.clamp(1, crate::api::MAX_PAGE_SIZE);
+        Page {
+            limit: limit as i64,
+            cursor,
+            offset: ((page.max(1) - 1) * limit) as i64,
+        }
+    }
+}
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    // --- Ingester ---
+
+    async fn get_last_synced_block(&self) -> Result<Option<u64>, sqlx::Error>;
+
+    async fn get_canonical_block_hash_at_height(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<String>, sqlx::Error>;
+
+    /// Deletes everything at or above `fork_height` and moves the sync point below it.
+    async fn rollback_from_height(&self, fork_height: u64) -> Result<(), sqlx::Error>;
+
+    /// Deletes the blocks in `from..=to` without moving the sync point, for re-indexing.
+    async fn delete_block_range(&self, from: u64, to: u64) -> Result<(), sqlx::Error>;
+
+    async fn begin(&self) -> Result<Box<dyn StorageTransaction>, sqlx::Error>;
+
+    // --- API reads ---
+
+    /// Last processed block and the chain head seen at that time.
+    async fn get_indexer_status(&self) -> Result<Option<(i64, i64)>, sqlx::Error>;
+
+    async fn get_stats(&self) -> Result<IndexerStats, ApiError>;
+
+    /// Looks up a block by number or 0x-prefixed hash.
+    async fn get_block(&self, identifier: &str) -> Result<MyBlock, ApiError>;
+
+    async fn get_latest_block(&self) -> Result<MyBlock, ApiError>;
+
+    /// Lowest and highest indexed block numbers, `None` when no block is indexed.
+    async fn get_block_number_range(&self) -> Result<Option<(i64, i64)>, ApiError>;
+
+    /// Number and timestamp of the first indexed block at or above `block_number`. The probe
+    /// behind the timestamp binary search; it tolerates gaps left by selective indexing.
+    async fn get_block_at_or_above(
+        &self,
+        block_number: i64,
+    ) -> Result<Option<(i64, i64)>, ApiError>;
+
+    /// Blocks newest-first; `cursor_block` continues below a previous page.
+    async fn get_blocks(
+        &self,
+        query: &GetBlocksQuery,
+        limit: u64,
+    ) -> Result<Vec<MyBlock>, ApiError>;
+
+    async fn get_block_transactions(&self, block: &MyBlock)
+        -> Result<Vec<MyTransaction>, ApiError>;
+
+    async fn get_block_logs(&self, block: &MyBlock) -> Result<Vec<MyLog>, ApiError>;
+
+    /// `tx_hash` must already be validated and lowercased.
+    async fn get_transaction(&self, tx_hash: &str) -> Result<MyTransaction, ApiError>;
+
+    async fn get_transaction_logs(&self, tx_hash: &str) -> Result<Vec<MyLog>, ApiError>;
+
+    /// Logs matching an `eth_getLogs`-style filter, with their ids, ordered by
+    /// `(block_number, id)`. The page cursor is `(block_number, id)`.
+    async fn get_logs(
+        &self,
+        filters: &GetLogsFilter,
+        page: Page,
+    ) -> Result<Vec<(i64, MyLog)>, ApiError>;
+
+    /// Transactions ordered by `(block_number, transaction_index)`, which is also the cursor.
+    async fn get_transactions(
+        &self,
+        filters: &GetTransactionsFilter,
+        page: Page,
+    ) -> Result<Vec<MyTransaction>, ApiError>;
+
+    /// The underlying Postgres pool, for the features that are only built on Postgres.
+    fn postgres_pool(&self) -> Option<PgPool> {
+        None
+    }
+}
+
+/// Finds the last indexed block at or before `timestamp` (`Closest::Before`) or the first at
+/// or after it (`Closest::After`). Block timestamps never decrease with height, so this is a
+/// binary search over block numbers that needs no index on `blocks.timestamp`.
+pub async fn find_block_by_timestamp(
+    storage: &dyn Storage,
+    timestamp: i64,
+    closest: Closest,
+) -> Result<Option<i64>, ApiError> {
+    let Some((mut low, mut high)) = storage.get_block_number_range().await? else {
+        return Ok(None);
+    };
+
+    let mut found = None;
+    while low <= high {
+        let mid = low + (high - low) / 2;
+        let Some((number, block_timestamp)) = storage.get_block_at_or_above(mid).await? else {
+            high = mid - 1;
+            continue;
+        };
+        match closest {
+            Closest::Before if block_timestamp <= timestamp => {
+                found = Some(number);
+                low = number + 1;
+            }
+            Closest::Before => high = mid - 1,
+            Closest::After if block_timestamp >= timestamp => {
+                found = Some(number);
+                high = mid - 1;
+            }
+            Closest::After => low = number + 1,
+        }
+    }
+    Ok(found)
+}
+
+/// Resolves a `fromTimestamp`/`toTimestamp` pair to inclusive block bounds. When no indexed
+/// block falls inside the window the bounds come back inverted, so range filters match nothing.
+pub async fn resolve_time_range(
+    storage: &dyn Storage,
+    from_timestamp: Option<i64>,
+    to_timestamp: Option<i64>,
+) -> Result<(Option<i64>, Option<i64>), ApiError> {
+    if let (Some(from), Some(to)) = (from_timestamp, to_timestamp) {
+        if from > to {
+            return Err(ApiError::BadRequest(
+                "fromTimestamp must not be after toTimestamp.".to_string(),
+            ));
+        }
+    }
+
+    let mut from_block = None;
+    if let Some(ts) = from_timestamp {
+        match find_block_by_timestamp(storage, ts, Closest::After).await? {
+            Some(number) => from_block = Some(number),
+            None => return Ok((Some(i64::MAX), Some(0))),
+        }
+    }
+    let mut to_block = None;
+    if let Some(ts) = to_timestamp {
+        match find_block_by_timestamp(storage, ts, Closest::Before).await? {
+            Some(number) => to_block = Some(number),
+            None => return Ok((Some(i64::MAX), Some(0))),
+        }
+    }
+    Ok((from_block, to_block))
+}
+
+/// Opens the backend selected by the `DATABASE_URL` scheme: `sqlite:` for an embedded
+/// database file (created with its schema on first use), anything else for Postgres.
+pub async fn connect(database_url: &str) -> eyre::Result<Arc<dyn Storage>> {
+    if database_url.starts_with("sqlite:") {
+        Ok(Arc::new(SqliteStorage::connect(database_url).await?))
+    } else {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .max_connections(10)
+            .connect(database_url)
+            .await?;
+        crate::db::init_stats_counters(&pool).await?;
+        Ok(Arc::new(PostgresStorage::new(pool)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{
+        block, hash, remove_db, temp_db_path, write_blocks, ScratchSchema, TestDb,
+    };
+    use super::*;
+    use ethers::types::{H256, U256, U64};
+    use std::str::FromStr;
+
+    /// Blocks 100-102 and 105-106, 12 seconds apart, with a gap like selective indexing
+    /// leaves.
+    const BLOCKS: [u64; 5] = [100, 101, 102, 105, 106];
+
+    /// A database holding `blocks` of fork 0.
+    async fn test_db(name: &str, blocks: &[u64]) -> TestDb {
+        let db = TestDb::new(name).await;
+        write_blocks(db.storage.as_ref(), blocks.iter().map(|&n| block(n, 0, 0))).await;
+        db
+    }
+
+    async fn find(storage: &dyn Storage, timestamp: i64) -> (Option<i64>, Option<i64>) {
+        (
+            find_block_by_timestamp(storage, timestamp, Closest::Before)
+                .await
+                .unwrap(),
+            find_block_by_timestamp(storage, timestamp, Closest::After)
+                .await
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn finds_blocks_around_a_timestamp() {
+        let db = test_db("find", &BLOCKS).await;
+        let storage = db.storage.as_ref();
+        // Before the first block, after the last, exactly on one, and in the gap between
+        // blocks 102 (1,024) and 105 (1,060).
+        assert_eq!(find(storage, 900).await, (None, Some(100)));
+        assert_eq!(find(storage, 999).await, (None, Some(100)));
+        assert_eq!(find(storage, 1_000).await, (Some(100), Some(100)));
+        assert_eq!(find(storage, 1_073).await, (Some(106), None));
+        assert_eq!(find(storage, 5_000).await, (Some(106), None));
+        assert_eq!(find(storage, 1_024).await, (Some(102), Some(102)));
+        assert_eq!(find(storage, 1_072).await, (Some(106), Some(106)));
+        assert_eq!(find(storage, 1_030).await, (Some(102), Some(105)));
+        assert_eq!(find(storage, 1_013).await, (Some(101), Some(102)));
+    }
+
+    #[tokio::test]
+    async fn adds_receipt_columns_to_an_older_database() {
+        let path = temp_db_path("upgrade");
+        let url = format!("sqlite:{}", path.display());
+        let options = sqlx::sqlite::SqliteConnectOptions::from_str(&url)
+            .unwrap()
+            .create_if_missing(true);
+        let old = sqlx::SqlitePool::connect_with(options).await.unwrap();
+        // The transactions table as it was before receipt gas fields were stored.
+        sqlx::raw_sql(
+            "CREATE TABLE transactions (
+              tx_hash TEXT PRIMARY KEY, block_number INTEGER NOT NULL, block_hash TEXT NOT NULL,
+              transaction_index INTEGER, from_address TEXT NOT NULL, to_address TEXT,
+              value TEXT NOT NULL, gas_price TEXT, max_fee_per_gas TEXT,
+              max_priority_fee_per_gas TEXT, gas_provided TEXT NOT NULL, input_data TEXT,
+              status INTEGER
+            )",
+        )
+        .execute(&old)
+        .await
+        .unwrap();
+
+        SqliteStorage::connect(&url).await.unwrap();
+        // Connecting again finds the columns present.
+        SqliteStorage::connect(&url).await.unwrap();
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('transactions')")
+                .fetch_all(&old)
+                .await
+                .unwrap();
+        old.close().await;
+        remove_db(&path);
+        for column in [
+            "gas_used",
+            "effective_gas_price",
+            "nonce",
+            "transaction_type",
+            "contract_address",
+        ] {
+            assert!(columns.iter().any(|name| name == column), "{}", column);
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_nothing_without_blocks() {
+        let db = test_db("empty", &[]).await;
+        assert_eq!(find(db.storage.as_ref(), 1_000).await, (None, None));
+    }
+
+    #[tokio::test]
+    async fn resolves_time_ranges_to_inclusive_blocks() {
+        let db = test_db("range", &BLOCKS).await;
+        let range = |from, to| resolve_time_range(db.storage.as_ref(), from, to);
+        assert_eq!(range(None, None).await.unwrap(), (None, None));
+        assert_eq!(
+            range(Some(900), Some(5_000)).await.unwrap(),
+            (Some(100), Some(106))
+        );
+        assert_eq!(
+            range(Some(1_024), Some(1_024)).await.unwrap(),
+            (Some(102), Some(102))
+        );
+        assert_eq!(
+            range(Some(1_013), Some(1_065)).await.unwrap(),
+            (Some(102), Some(105))
+        );
+        assert_eq!(range(Some(1_030), None).await.unwrap(), (Some(105), None));
+        assert_eq!(range(None, Some(1_030)).await.unwrap(), (None, Some(102)));
+        // Windows with no block in them come back inverted.
+        assert_eq!(
+            range(Some(1_025), Some(1_059)).await.unwrap(),
+            (Some(105), Some(102))
+        );
+        assert_eq!(
+            range(Some(2_000), Some(3_000)).await.unwrap(),
+            (Some(i64::MAX), Some(0))
+        );
+        assert_eq!(
+            range(Some(100), Some(200)).await.unwrap(),
+            (Some(i64::MAX), Some(0))
+        );
+        assert!(matches!(
+            range(Some(1_100), Some(1_000)).await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    /// Block `number` of fork `fork`: one to three transactions, the first two from two
+    /// alternating senders and the third from a sender seen nowhere else. The second one
+    /// failed. Each has a log from one of two contracts. Every third log is
+    /// anonymous. Blocks are five minutes apart, so 100-101 fall in one hour and the rest in
+    /// the next.
+    fn activity_block(number: u64, fork: u64) -> (MyBlock, Vec<MyTransaction>, Vec<MyLog>) {
+        let block = MyBlock {
+            block_number: U64::from(number),
+            block_hash: hash(number, fork),
+            parent_hash: hash(number - 1, fork),
+            timestamp: U256::from(3_000 + (number - 100) * 300),
+            gas_used: U256::zero(),
+            gas_limit: U256::zero(),
+            base_fee_per_gas: None,
+        };
+        let mut transactions = Vec::new();
+        let mut logs = Vec::new();
+        for i in 0..(number + fork) % 3 + 1 {
+            let tx_hash = H256::from_low_u64_be(number * 1_000 + fork * 100 + i);
+            transactions.push(MyTransaction {
+                tx_hash,
+                block_number: U64::from(number),
+                block_hash: block.block_hash,
+                block_timestamp: None,
+                transaction_index: Some(U64::from(i)),
+                from_address: ethers::types::Address::from_low_u64_be(if i == 2 {
+                    number * 10 + fork
+                } else {
+                    1 + (number + i) % 2
+                }),
+                to_address: Some(ethers::types::Address::from_low_u64_be(7 + i % 2)),
+                value: U256::from(number * 1_000 + fork * 10 + i),
+                gas_price: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                gas: U256::from(21_000),
+                nonce: None,
+                input_data: "0x".to_string(),
+                status: Some(if i == 1 { 0 } else { 1 }),
+                gas_used: None,
+                effective_gas_price: None,
+                transaction_type: None,
+                contract_address: None,
+                value_usd: None,
+                from_ens_name: None,
+                to_ens_name: None,
+            });
+            let anonymous = (number + i).is_multiple_of(3);
+            logs.push(MyLog {
+                log_index: Some(U256::from(i)),
+                transaction_hash: tx_hash,
+                transaction_index: Some(i),
+                block_number: number,
+                block_hash: block.block_hash,
+                block_timestamp: None,
+                address: ethers::types::Address::from_low_u64_be(7 + (number + i) % 2),
+                data: "0x".to_string(),
+                topics: if anonymous {
+                    Vec::new()
+                } else {
+                    vec![format!("{:#x}", H256::from_low_u64_be(0xe0 + i % 2))]
+                },
+                address_ens_name: None,
+            });
+        }
+        (block, transactions, logs)
+    }
+
+    /// Writes `numbers` of `fork` the way the ingester's writer does, one commit per block.
+    async fn ingest(storage: &dyn Storage, numbers: std::ops::RangeInclusive<u64>, fork: u64) {
+        for number in numbers {
+            let (block, transactions, logs) = activity_block(number, fork);
+            let stats = crate::stats::summarize_block(&block, &transactions, &logs, true);
+            let mut tx = storage.begin().await.unwrap();
+            tx.insert_block_data(&block).await.unwrap();
+            tx.insert_transactions_batch(&transactions).await.unwrap();
+            tx.insert_logs_batch(&logs).await.unwrap();
+            tx.insert_block_stats(&stats).await.unwrap();
+            tx.set_last_synced_block(number, number).await.unwrap();
+            tx.commit().await.unwrap();
+        }
+    }
+
+    async fn counters(storage: &dyn Storage) -> (i64, i64, i64) {
+        let stats = storage.get_stats().await.unwrap();
+        (
+            stats.total_blocks,
+            stats.total_transactions,
+            stats.total_logs,
+        )
+    }
+
+    const COUNT_TABLES: &str = "SELECT (SELECT COUNT(*) FROM blocks), \
+         (SELECT COUNT(*) FROM transactions), (SELECT COUNT(*) FROM logs)";
+
+    #[tokio::test]
+    async fn counters_follow_rollbacks_and_are_seeded_from_the_tables() {
+        let db = TestDb::new("counters").await;
+        let tables = sqlx::SqlitePool::connect(&db.url).await.unwrap();
+        let count = || async {
+            sqlx::query_as::<_, (i64, i64, i64)>(COUNT_TABLES)
+                .fetch_one(&tables)
+                .await
+                .unwrap()
+        };
+
+        ingest(db.storage.as_ref(), 100..=111, 0).await;
+        assert_eq!(counters(db.storage.as_ref()).await, count().await);
+        assert_eq!(count().await.0, 12);
+
+        db.storage.rollback_from_height(106).await.unwrap();
+        assert_eq!(counters(db.storage.as_ref()).await, count().await);
+        assert_eq!(count().await.0, 6);
+
+        // The replacement fork, then a re-indexed range in the middle.
+        ingest(db.storage.as_ref(), 106..=111, 1).await;
+        assert_eq!(counters(db.storage.as_ref()).await, count().await);
+        db.storage.delete_block_range(103, 104).await.unwrap();
+        assert_eq!(counters(db.storage.as_ref()).await, count().await);
+        ingest(db.storage.as_ref(), 103..=104, 0).await;
+        assert_eq!(counters(db.storage.as_ref()).await, count().await);
+
+        // A database from before the counters were kept is seeded on connect.
+        sqlx::query("DELETE FROM stats_counters")
+            .execute(&tables)
+            .await
+            .unwrap();
+        let reopened = SqliteStorage::connect(&db.url).await.unwrap();
+        assert_eq!(counters(&reopened).await, count().await);
+        tables.close().await;
+    }
+
+    /// Counters and rollups after a rollback must match counting what is left. The rollups
+    /// are Postgres-only, so this runs when `TEST_DATABASE_URL` points at a database it may
+    /// create a scratch schema in.
+    #[tokio::test]
+    async fn rollups_follow_rollbacks_on_postgres() {
+        let Some(schema) = ScratchSchema::new("rollups").await else {
+            return;
+        };
+        let pool = schema.pool.clone();
+        crate::db::init_stats_counters(&pool).await.unwrap();
+        let storage = PostgresStorage::new(pool.clone());
+
+        ingest(&storage, 100..=111, 0).await;
+        assert_rollups_match_tables(&storage, &pool).await;
+        // Rolls back part of the second hour.
+        storage.rollback_from_height(106).await.unwrap();
+        assert_rollups_match_tables(&storage, &pool).await;
+        ingest(&storage, 106..=111, 1).await;
+        assert_rollups_match_tables(&storage, &pool).await;
+        // Rolls back across the hour boundary, emptying the second hour.
+        storage.rollback_from_height(101).await.unwrap();
+        assert_rollups_match_tables(&storage, &pool).await;
+        ingest(&storage, 101..=105, 2).await;
+        assert_rollups_match_tables(&storage, &pool).await;
+        // Re-indexes the start of the second hour, whose senders also appear later in it, and
+        // writes blocks with other senders in its place.
+        ingest(&storage, 106..=111, 2).await;
+        storage.delete_block_range(102, 103).await.unwrap();
+        assert_rollups_match_tables(&storage, &pool).await;
+        ingest(&storage, 102..=103, 3).await;
+        assert_rollups_match_tables(&storage, &pool).await;
+
+        sqlx::query("DELETE FROM stats_counters")
+            .execute(&pool)
+            .await
+            .unwrap();
+        crate::db::init_stats_counters(&pool).await.unwrap();
+        assert_rollups_match_tables(&storage, &pool).await;
+
+        schema.remove().await;
+    }
+
+    async fn assert_rollups_match_tables(storage: &PostgresStorage, pool: &PgPool) {
+        let count: (i64, i64, i64) = sqlx::query_as(COUNT_TABLES).fetch_one(pool).await.unwrap();
+        assert_eq!(counters(storage).await, count);
+
+        for (resolution, seconds) in crate::stats::RESOLUTIONS {
+            type Rollup = (i64, i64, i64, i64, String, i64);
+            let kept: Vec<Rollup> = sqlx::query_as(
+                "SELECT bucket, blocks, tx_count, unique_senders, eth_volume::TEXT, log_count \
+                 FROM stats_rollups WHERE resolution = $1 ORDER BY bucket",
+            )
+            .bind(resolution)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            let counted: Vec<Rollup> = sqlx::query_as(
+                r#"
+                SELECT b.bucket, b.blocks, COALESCE(t.tx_count, 0), COALESCE(t.senders, 0),
+                       COALESCE(t.eth_volume, 0)::TEXT, COALESCE(l.log_count, 0)
+                FROM (SELECT timestamp / $1 * $1 AS bucket, COUNT(*) AS blocks
+                      FROM blocks GROUP BY 1) b
+                LEFT JOIN (
+                    SELECT bl.timestamp / $1 * $1 AS bucket, COUNT(*) AS tx_count,
+                           COUNT(DISTINCT t.from_address) AS senders,
+                           SUM(t.value::NUMERIC) FILTER (WHERE t.status IS DISTINCT FROM 0)
+                               AS eth_volume
+                    FROM transactions t JOIN blocks bl ON bl.block_hash = t.block_hash
+                    GROUP BY 1
+                ) t ON t.bucket = b.bucket
+                LEFT JOIN (
+                    SELECT bl.timestamp / $1 * $1 AS bucket, COUNT(*) AS log_count
+                    FROM logs l JOIN blocks bl ON bl.block_hash = l.block_hash
+                    GROUP BY 1
+                ) l ON l.bucket = b.bucket
+                ORDER BY 1
+                "#,
+            )
+            .bind(seconds)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            assert_eq!(kept, counted, "{} rollups", resolution);
+
+            type LogRollup = (i64, String, Option<String>, i64);
+            let kept: Vec<LogRollup> = sqlx::query_as(
+                "SELECT bucket, contract_address, topic0, log_count FROM stats_log_rollups \
+                 WHERE resolution = $1 ORDER BY 1, 2, 3",
+            )
+            .bind(resolution)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            let counted: Vec<LogRollup> = sqlx::query_as(
+                "SELECT bl.timestamp / $1 * $1, l.contract_address, l.topic0, COUNT(*) \
+                 FROM logs l JOIN blocks bl ON bl.block_hash = l.block_hash \
+                 GROUP BY 1, 2, 3 ORDER BY 1, 2, 3",
+            )
+            .bind(seconds)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            assert_eq!(kept, counted, "{} log rollups", resolution);
+
+            type Sender = (i64, String, i64);
+            let kept: Vec<Sender> = sqlx::query_as(
+                "SELECT bucket, address, first_block FROM stats_senders \
+                 WHERE resolution = $1 ORDER BY 1, 2",
+            )
+            .bind(resolution)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            let counted: Vec<Sender> = sqlx::query_as(
+                "SELECT bl.timestamp / $1 * $1, t.from_address, MIN(t.block_number) \
+                 FROM transactions t JOIN blocks bl ON bl.block_hash = t.block_hash \
+                 GROUP BY 1, 2 ORDER BY 1, 2",
+            )
+            .bind(seconds)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+            assert_eq!(kept, counted, "{} senders", resolution);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+// src/storage/postgres.rs
+//
+// The Postgres backend. Writes go through the functions in db.rs; reads share the column
+// lists and row mappers in api.rs with the RPC and GraphQL layers.
+
+use super::{Page, Storage, StorageTransaction};
+use crate::api::{
+    fetch_block_row, push_log_filters, push_transaction_filters, row_to_block, row_to_log,
+    row_to_transaction, ApiError, BLOCK_COLUMNS, LOG_COLUMNS, TRANSACTION_COLUMNS,
+};
+use crate::api_models::{GetBlocksQuery, GetLogsFilter, GetTransactionsFilter, IndexerStats};
+use crate::contracts::ContractBlock;
+use crate::db;
+use crate::dex::DexBlock;
+use crate::ens::EnsBlock;
+use crate::gas::GasBlock;
+use crate::handlers::EntityWrite;
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::pricing::BlockPrices;
+use crate::stats::BlockStats;
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, QueryBuilder, Row as SqlxRow, Transaction};
+
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: PgPool) -> Self {
+        PostgresStorage { pool }
+    }
+}
+
+struct PostgresTransaction(Transaction<'static, Postgres>);
+
+#[async_trait]
+impl StorageTransaction for PostgresTransaction {
+    async fn insert_block_data(&mut self, block: &MyBlock) -> Result<(), sqlx::Error> {
+        db::insert_block_data(&mut self.0, block).await
+    }
+
+    async fn insert_transaction_data(&mut self, tx: &MyTransaction) -> Result<(), sqlx::Error> {
+        db::insert_transaction_data(&mut self.0, tx).await
+    }
+
+    async fn insert_log_data(&mut self, log: &MyLog) -> Result<i64, sqlx::Error> {
+        db::insert_log_data(&mut self.0, log).await
+    }
+
+    async fn insert_transactions_batch(
+        &mut self,
+        txs: &[MyTransaction],
+    ) -> Result<(), sqlx::Error> {
+        db::insert_transactions_batch(&mut self.0, txs).await
+    }
+
+    async fn insert_logs_batch(&mut self, logs: &[MyLog]) -> Result<Vec<i64>, sqlx::Error> {
+        db::insert_logs_batch(&mut self.0, logs).await
+    }
+
+    async fn set_last_synced_block(
+        &mut self,
+        block_number: u64,
+        chain_head: u64,
+    ) -> Result<(), sqlx::Error> {
+        db::set_last_synced_block(&mut self.0, block_number, chain_head).await
+    }
+
+    async fn insert_block_stats(&mut self, stats: &BlockStats) -> Result<(), sqlx::Error> {
+        db::insert_block_stats(&mut self.0, stats).await
+    }
+
+    async fn upsert_handler_entities(&mut self, rows: &[EntityWrite]) -> Result<(), sqlx::Error> {
+        db::upsert_handler_entities(&mut self.0, rows).await
+    }
+
+    async fn mark_handler_failed(
+        &mut self,
+        handler_id: i64,
+        block_number: u64,
+        error: &str,
+    ) -> Result<(), sqlx::Error> {
+        db::mark_handler_failed(&mut self.0, handler_id, block_number, error).await
+    }
+
+    async fn insert_dex_block(
+        &mut self,
+        dex: &DexBlock,
+        log_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        db::insert_dex_block(&mut self.0, dex, log_ids).await
+    }
+
+    async fn insert_prices(&mut self, prices: &BlockPrices) -> Result<(), sqlx::Error> {
+        db::insert_prices(&mut self.0, prices).await
+    }
+
+    async fn insert_gas_block(&mut self, gas: &GasBlock) -> Result<(), sqlx::Error> {
+        db::insert_gas_block(&mut self.0, gas).await
+    }
+
+    async fn insert_contracts(
+        &mut self,
+        contracts: &ContractBlock,
+        log_ids: &[i64],
+    ) -> Result<(), sqlx::Error> {
+        db::insert_contracts(&mut self.0, contracts, log_ids).await
+    }
+
+    async fn insert_ens(&mut self, ens: &EnsBlock, log_ids: &[i64]) -> Result<(), sqlx::Error> {
+        db::insert_ens(&mut self.0, ens, log_ids).await
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), sqlx::Error> {
+        self.0.commit().await
+    }
+}
+
+/// Appends keyset or offset pagination; `columns` is the `block_number, …` cursor pair.
+/// Keyset: `(block_number, x) > (cursor)` gives deterministic ordering with no duplicate or
+/// skipped rows, and stays O(log n) on the composite indexes where OFFSET is O(n).
+fn push_page(query_builder: &mut QueryBuilder<'_, Postgres>, columns: &str, page: Page) {
+    if let Some((block, position)) = page.cursor {
+        query_builder.push(format!(" AND ({}) > (", columns));
+        query_builder.push_bind(block);
+        query_builder.push(", ");
+        query_builder.push_bind(position);
+        query_builder.push(")");
+    }
+    query_builder.push(format!(" ORDER BY {} LIMIT ", columns));
+    query_builder.push_bind(page.limit);
+    if page.cursor.is_none() {
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(page.offset);
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn get_last_synced_block(&self) -> Result<Option<u64>, sqlx::Error> {
+        db::get_last_synced_block(&self.pool).await
+    }
+
+    async fn get_canonical_block_hash_at_height(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        db::get_canonical_block_hash_at_height(&self.pool, block_number).await
+    }
+
+    async fn rollback_from_height(&self, fork_height: u64) -> Result<(), sqlx::Error> {
+        db::rollback_from_height(&self.pool, fork_height).await
+    }
+
+    async fn delete_block_range(&self, from: u64, to: u64) -> Result<(), sqlx::Error> {
+        db::delete_block_range(&self.pool, from, to).await
+    }
+
+    async fn begin(&self) -> Result<Box<dyn StorageTransaction>, sqlx::Error> {
+        Ok(Box::new(PostgresTransaction(self.pool.begin().await?)))
+    }
+
+    async fn get_indexer_status(&self) -> Result<Option<(i64, i64)>, sqlx::Error> {
+        db::get_indexer_status(&self.pool).await
+    }
+
+    async fn get_stats(&self) -> Result<IndexerStats, ApiError> {
+        // Maintained by the ingester (see stats.rs) rather than counted per request.
+        let (total_blocks, total_transactions, total_logs): (i64, i64, i64) =
+            sqlx::query_as("SELECT blocks, transactions, logs FROM stats_counters")
+                .fetch_optional(&self.pool)
+                .await?
+                .unwrap_or_default();
+
+        // Fetch last_processed_block and chain_head for lag calculation
+        let status = db::get_indexer_status(&self.pool).await?;
+        let (last_synced_block, ingestion_lag) = match status {
+            Some((last, head)) => (Some(last), Some(head - last)),
+            None => (None, None),
+        };
+
+        Ok(IndexerStats {
+            total_blocks,
+            total_transactions,
+            total_logs,
+            last_synced_block,
+            ingestion_lag,
+        })
+    }
+
+    async fn get_block(&self, identifier: &str) -> Result<MyBlock, ApiError> {
+        row_to_block(&fetch_block_row(&self.pool, identifier).await?)
+    }
+
+    async fn get_latest_block(&self) -> Result<MyBlock, ApiError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM blocks ORDER BY block_number DESC LIMIT 1",
+            BLOCK_COLUMNS
+        ))
+        .fetch_one(&self.pool)
+        .await?;
+        row_to_block(&row)
+    }
+
+    async fn get_block_number_range(&self) -> Result<Option<(i64, i64)>, ApiError> {
+        let (first, last): (Option<i64>, Option<i64>) =
+            sqlx::query_as("SELECT MIN(block_number), MAX(block_number) FROM blocks")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(first.zip(last))
+    }
+
+    async fn get_block_at_or_above(
+        &self,
+        block_number: i64,
+    ) -> Result<Option<(i64, i64)>, ApiError> {
+        Ok(sqlx::query_as(
+            "SELECT block_number, timestamp FROM blocks WHERE block_number >= $1 \
+             ORDER BY block_number LIMIT 1",
+        )
+        .bind(block_number)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn get_blocks(
+        &self,
+        query: &GetBlocksQuery,
+        limit: u64,
+    ) -> Result<Vec<MyBlock>, ApiError> {
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM blocks WHERE 1=1", BLOCK_COLUMNS));
+
+        if let Some(fb) = query.from_block {
+            query_builder.push(" AND block_number >= ");
+            query_builder.push_bind(fb as i64);
+        }
+        if let Some(tb) = query.to_block {
+            query_builder.push(" AND block_number <= ");
+            query_builder.push_bind(tb as i64);
+        }
+        if let Some(from_ts) = query.from_timestamp {
+            query_builder.push(" AND timestamp >= ");
+            query_builder.push_bind(from_ts);
+        }
+        if let Some(to_ts) = query.to_timestamp {
+            query_builder.push(" AND timestamp <= ");
+            query_builder.push_bind(to_ts);
+        }
+        if let Some(cb) = query.cursor_block {
+            query_builder.push(" AND block_number < ");
+            query_builder.push_bind(cb);
+        }
+
+        query_builder.push(" ORDER BY block_number DESC LIMIT ");
+        query_builder.push_bind(limit as i64);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_block).collect()
+    }
+
+    async fn get_block_transactions(
+        &self,
+        block: &MyBlock,
+    ) -> Result<Vec<MyTransaction>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM transactions WHERE block_hash = $1 ORDER BY transaction_index ASC",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(format!("{:#x}", block.block_hash))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_transaction).collect()
+    }
+
+    async fn get_block_logs(&self, block: &MyBlock) -> Result<Vec<MyLog>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM logs WHERE block_hash = $1 \
+             ORDER BY transaction_index_in_block ASC, log_index_in_tx ASC, id ASC",
+            LOG_COLUMNS
+        ))
+        .bind(format!("{:#x}", block.block_hash))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_log).collect()
+    }
+
+    async fn get_transaction(&self, tx_hash: &str) -> Result<MyTransaction, ApiError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM transactions WHERE tx_hash = $1",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(tx_hash)
+        .fetch_one(&self.pool)
+        .await?;
+        row_to_transaction(&row)
+    }
+
+    async fn get_transaction_logs(&self, tx_hash: &str) -> Result<Vec<MyLog>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM logs WHERE transaction_hash = $1 ORDER BY log_index_in_tx ASC, id ASC",
+            LOG_COLUMNS
+        ))
+        .bind(tx_hash)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_log).collect()
+    }
+
+    async fn get_logs(
+        &self,
+        filters: &GetLogsFilter,
+        page: Page,
+    ) -> Result<Vec<(i64, MyLog)>, ApiError> {
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM logs WHERE 1=1", LOG_COLUMNS));
+        push_log_filters(&self.pool, &mut query_builder, filters).await?;
+        push_page(&mut query_builder, "block_number, id", page);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| Ok((SqlxRow::try_get(row, "id")?, row_to_log(row)?)))
+            .collect()
+    }
+
+    async fn get_transactions(
+        &self,
+        filters: &GetTransactionsFilter,
+        page: Page,
+    ) -> Result<Vec<MyTransaction>, ApiError> {
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+            "SELECT {} FROM transactions WHERE 1=1",
+            TRANSACTION_COLUMNS
+        ));
+        push_transaction_filters(&mut query_builder, filters)?;
+        push_page(&mut query_builder, "block_number, transaction_index", page);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_transaction).collect()
+    }
+
+    fn postgres_pool(&self) -> Option<PgPool> {
+        Some(self.pool.clone())
+    }
+}
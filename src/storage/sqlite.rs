@@ -0,0 +1,701 @@
+// src/storage/sqlite.rs
+//
+// Embedded backend for small local deployments: a single SQLite file, no server. The schema
+// in sqlite_schema.sql is applied on connect. Differences from Postgres: hex payloads are TEXT
+// rather than BYTEA, `all_topics` is a JSON array, OR-lists use `IN (...)` instead of
+// `= ANY(...)`, and decimal values are compared by length first since there is no NUMERIC.
+
+use super::{Page, Storage, StorageTransaction};
+use crate::api::{
+    block_tag_height, normalize_address, normalize_hex_values, normalize_method_id,
+    transaction_status, validate_log_filter, ApiError, BLOCK_COLUMNS,
+};
+use crate::api_models::{GetBlocksQuery, GetLogsFilter, GetTransactionsFilter, IndexerStats};
+use crate::models::{MyBlock, MyLog, MyTransaction};
+use crate::stats::BlockStats;
+use async_trait::async_trait;
+use ethers::core::types::{Address, H256, U256, U64};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow};
+use sqlx::{QueryBuilder, Row as SqlxRow, Sqlite, SqlitePool, Transaction};
+use std::str::FromStr;
+use std::time::Duration;
+
+const INDEXER_NAME: &str = "evm_main_sync";
+const SCHEMA: &str = include_str!("sqlite_schema.sql");
+
+const TRANSACTION_COLUMNS: &str = "tx_hash, block_number, block_hash, transaction_index, \
+     from_address, to_address, value, gas_price, max_fee_per_gas, \
+     max_priority_fee_per_gas, gas_provided, input_data, status, gas_used, effective_gas_price, \
+     nonce, transaction_type, contract_address, \
+     (SELECT b.timestamp FROM blocks b WHERE b.block_hash = transactions.block_hash) \
+     AS block_timestamp";
+
+const LOG_COLUMNS: &str = "id, log_index_in_tx AS log_index, transaction_hash, \
+     transaction_index_in_block AS transaction_index, \
+     block_number, block_hash, contract_address AS address, data, all_topics AS topics, \
+     (SELECT b.timestamp FROM blocks b WHERE b.block_hash = logs.block_hash) AS block_timestamp";
+
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    /// Opens (creating if needed) the database file in WAL mode, so API reads don't block
+    /// on the ingester's write transactions.
+    pub async fn connect(database_url: &str) -> eyre::Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_secs(10));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        // SQLite has no ADD COLUMN IF NOT EXISTS, so transaction fields added after a
+        // database was created are checked for one by one.
+        let columns: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM pragma_table_info('transactions')")
+                .fetch_all(&pool)
+                .await?;
+        for (column, column_type) in [
+            ("gas_used", "TEXT"),
+            ("effective_gas_price", "TEXT"),
+            ("nonce", "TEXT"),
+            ("transaction_type", "INTEGER"),
+            ("contract_address", "TEXT"),
+        ] {
+            if !columns.iter().any(|name| name == column) {
+                sqlx::query(&format!(
+                    "ALTER TABLE transactions ADD COLUMN {} {}",
+                    column, column_type
+                ))
+                .execute(&pool)
+                .await?;
+            }
+        }
+        // Seeds the totals behind /stats on a database created before they were kept.
+        sqlx::query(
+            "INSERT INTO stats_counters (id, blocks, transactions, logs)
+            SELECT 1, (SELECT COUNT(*) FROM blocks), (SELECT COUNT(*) FROM transactions),
+                   (SELECT COUNT(*) FROM logs)
+            WHERE NOT EXISTS (SELECT 1 FROM stats_counters)",
+        )
+        .execute(&pool)
+        .await?;
+        Ok(SqliteStorage { pool })
+    }
+
+    /// Deletes `from..=to`, or everything from `from` on when `to` is `None`, in which case
+    /// the sync point moves back to `from - 1`.
+    async fn delete_blocks(&self, from: u64, to: Option<u64>) -> Result<(), sqlx::Error> {
+        let upper = to.map_or(i64::MAX, |to| to as i64);
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "UPDATE stats_counters SET
+                blocks = blocks - (SELECT COUNT(*) FROM blocks WHERE block_number BETWEEN ?1 AND ?2),
+                transactions = transactions
+                    - (SELECT COUNT(*) FROM transactions WHERE block_number BETWEEN ?1 AND ?2),
+                logs = logs - (SELECT COUNT(*) FROM logs WHERE block_number BETWEEN ?1 AND ?2)",
+        )
+        .bind(from as i64)
+        .bind(upper)
+        .execute(&mut *tx)
+        .await?;
+        for table in ["logs", "transactions", "blocks"] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE block_number BETWEEN ? AND ?",
+                table
+            ))
+            .bind(from as i64)
+            .bind(upper)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if to.is_none() {
+            sqlx::query(
+                "UPDATE indexer_status SET last_processed_block = ?1 - 1 \
+                 WHERE indexer_name = ?2 AND last_processed_block >= ?1",
+            )
+            .bind(from as i64)
+            .bind(INDEXER_NAME)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DELETE FROM indexer_status WHERE last_processed_block < 0")
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+}
+
+fn parse_hex<T: FromStr>(value: &str, field: &str) -> Result<T, ApiError>
+where
+    T::Err: std::fmt::Display,
+{
+    T::from_str(value)
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid {}: {}", field, e)))
+}
+
+fn parse_dec(value: &str, field: &str) -> Result<U256, ApiError> {
+    U256::from_dec_str(value)
+        .map_err(|e| ApiError::InternalServerError(format!("Invalid {}: {}", field, e)))
+}
+
+fn row_to_transaction(row: &SqliteRow) -> Result<MyTransaction, ApiError> {
+    let optional_dec = |column: &str| -> Result<Option<U256>, ApiError> {
+        Ok(SqlxRow::try_get::<Option<String>, _>(row, column)?
+            .and_then(|s| U256::from_dec_str(&s).ok()))
+    };
+    Ok(MyTransaction {
+        tx_hash: parse_hex(&row.try_get::<String, _>("tx_hash")?, "tx_hash")?,
+        block_number: U64::from(row.try_get::<i64, _>("block_number")?),
+        block_hash: parse_hex(&row.try_get::<String, _>("block_hash")?, "block_hash")?,
+        block_timestamp: row
+            .try_get::<Option<i64>, _>("block_timestamp")?
+            .map(|t| t as u64),
+        transaction_index: row
+            .try_get::<Option<i64>, _>("transaction_index")?
+            .map(U64::from),
+        from_address: parse_hex(&row.try_get::<String, _>("from_address")?, "from_address")?,
+        to_address: row
+            .try_get::<Option<String>, _>("to_address")?
+            .and_then(|s| Address::from_str(&s).ok()),
+        value: parse_dec(&row.try_get::<String, _>("value")?, "value")?,
+        gas_price: optional_dec("gas_price")?,
+        max_fee_per_gas: optional_dec("max_fee_per_gas")?,
+        max_priority_fee_per_gas: optional_dec("max_priority_fee_per_gas")?,
+        gas: parse_dec(&row.try_get::<String, _>("gas_provided")?, "gas")?,
+        nonce: optional_dec("nonce")?,
+        input_data: row
+            .try_get::<Option<String>, _>("input_data")?
+            .unwrap_or_default(),
+        status: row.try_get::<Option<i64>, _>("status")?.map(|s| s as u64),
+        gas_used: optional_dec("gas_used")?,
+        effective_gas_price: optional_dec("effective_gas_price")?,
+        transaction_type: row
+            .try_get::<Option<i64>, _>("transaction_type")?
+            .map(|t| t as u64),
+        contract_address: row
+            .try_get::<Option<String>, _>("contract_address")?
+            .and_then(|s| Address::from_str(&s).ok()),
+        value_usd: None,
+        from_ens_name: None,
+        to_ens_name: None,
+    })
+}
+
+fn row_to_log(row: &SqliteRow) -> Result<MyLog, ApiError> {
+    let topics: String = row.try_get("topics")?;
+    Ok(MyLog {
+        log_index: row
+            .try_get::<Option<i64>, _>("log_index")?
+            .map(|v| U256::from(v as u64)),
+        transaction_hash: parse_hex(
+            &row.try_get::<String, _>("transaction_hash")?,
+            "transaction_hash",
+        )?,
+        transaction_index: row
+            .try_get::<Option<i64>, _>("transaction_index")?
+            .map(|v| v as u64),
+        block_number: row.try_get::<i64, _>("block_number")? as u64,
+        block_hash: parse_hex(&row.try_get::<String, _>("block_hash")?, "block_hash")?,
+        block_timestamp: row
+            .try_get::<Option<i64>, _>("block_timestamp")?
+            .map(|t| t as u64),
+        address: parse_hex(&row.try_get::<String, _>("address")?, "address")?,
+        data: row
+            .try_get::<Option<String>, _>("data")?
+            .unwrap_or_default(),
+        topics: serde_json::from_str(&topics)
+            .map_err(|e| ApiError::InternalServerError(format!("Invalid topics: {}", e)))?,
+        address_ens_name: None,
+    })
+}
+
+fn row_to_block(row: &SqliteRow) -> Result<MyBlock, ApiError> {
+    Ok(MyBlock {
+        block_number: U64::from(row.try_get::<i64, _>("block_number")?),
+        block_hash: parse_hex::<H256>(&row.try_get::<String, _>("block_hash")?, "block_hash")?,
+        parent_hash: parse_hex(&row.try_get::<String, _>("parent_hash")?, "parent_hash")?,
+        timestamp: U256::from(row.try_get::<i64, _>("timestamp")?),
+        gas_used: parse_dec(&row.try_get::<String, _>("gas_used")?, "gas_used")?,
+        gas_limit: parse_dec(&row.try_get::<String, _>("gas_limit")?, "gas_limit")?,
+        base_fee_per_gas: row
+            .try_get::<Option<String>, _>("base_fee_per_gas")?
+            .and_then(|s| U256::from_dec_str(&s).ok()),
+    })
+}
+
+/// Appends `AND column IN (...)`; the caller skips empty lists, which match anything.
+fn push_in_list(query_builder: &mut QueryBuilder<'_, Sqlite>, column: &str, values: Vec<String>) {
+    query_builder.push(format!(" AND {} IN (", column));
+    let mut separated = query_builder.separated(", ");
+    for value in values {
+        separated.push_bind(value);
+    }
+    separated.push_unseparated(")");
+}
+
+/// Appends a numeric comparison on a decimal TEXT column. Canonical decimal strings of
+/// different lengths order by length; equal lengths order lexically.
+fn push_decimal_bound(
+    query_builder: &mut QueryBuilder<'_, Sqlite>,
+    column: &str,
+    bound: &str,
+    op: &str,
+    field: &str,
+) -> Result<(), ApiError> {
+    let bound = U256::from_dec_str(bound)
+        .map_err(|_| ApiError::BadRequest(format!("Invalid {} format", field)))?
+        .to_string();
+    query_builder.push(format!(" AND (LENGTH({}) {} LENGTH(", column, &op[..1]));
+    query_builder.push_bind(bound.clone());
+    query_builder.push(format!(") OR (LENGTH({}) = LENGTH(", column));
+    query_builder.push_bind(bound.clone());
+    query_builder.push(format!(") AND {} {} ", column, op));
+    query_builder.push_bind(bound);
+    query_builder.push("))");
+    Ok(())
+}
+
+fn push_page(query_builder: &mut QueryBuilder<'_, Sqlite>, columns: &str, page: Page) {
+    if let Some((block, position)) = page.cursor {
+        query_builder.push(format!(" AND ({}) > (", columns));
+        query_builder.push_bind(block);
+        query_builder.push(", ");
+        query_builder.push_bind(position);
+        query_builder.push(")");
+    }
+    query_builder.push(format!(" ORDER BY {} LIMIT ", columns));
+    query_builder.push_bind(page.limit);
+    if page.cursor.is_none() {
+        query_builder.push(" OFFSET ");
+        query_builder.push_bind(page.offset);
+    }
+}
+
+struct SqliteTransaction(Transaction<'static, Sqlite>);
+
+#[async_trait]
+impl StorageTransaction for SqliteTransaction {
+    async fn insert_block_data(&mut self, block: &MyBlock) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO blocks (
+                block_hash, block_number, parent_hash, timestamp,
+                gas_used, gas_limit, base_fee_per_gas
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (block_hash) DO NOTHING",
+        )
+        .bind(format!("{:#x}", block.block_hash))
+        .bind(block.block_number.as_u64() as i64)
+        .bind(format!("{:#x}", block.parent_hash))
+        .bind(block.timestamp.as_u64() as i64)
+        .bind(block.gas_used.to_string())
+        .bind(block.gas_limit.to_string())
+        .bind(block.base_fee_per_gas.map(|v| v.to_string()))
+        .execute(&mut *self.0)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_transaction_data(&mut self, tx: &MyTransaction) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO transactions (
+                tx_hash, block_number, block_hash, transaction_index,
+                from_address, to_address, value, gas_price, max_fee_per_gas,
+                max_priority_fee_per_gas, gas_provided, input_data, status, gas_used,
+                effective_gas_price, nonce, transaction_type, contract_address
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (tx_hash) DO NOTHING",
+        )
+        .bind(format!("{:#x}", tx.tx_hash))
+        .bind(tx.block_number.as_u64() as i64)
+        .bind(format!("{:#x}", tx.block_hash))
+        .bind(tx.transaction_index.map(|i| i.as_u64() as i64))
+        .bind(format!("{:#x}", tx.from_address))
+        .bind(tx.to_address.map(|a| format!("{:#x}", a)))
+        .bind(tx.value.to_string())
+        .bind(tx.gas_price.map(|v| v.to_string()))
+        .bind(tx.max_fee_per_gas.map(|v| v.to_string()))
+        .bind(tx.max_priority_fee_per_gas.map(|v| v.to_string()))
+        .bind(tx.gas.to_string())
+        .bind(&tx.input_data)
+        .bind(tx.status.map(|s| s as i64))
+        .bind(tx.gas_used.map(|v| v.to_string()))
+        .bind(tx.effective_gas_price.map(|v| v.to_string()))
+        .bind(tx.nonce.map(|v| v.to_string()))
+        .bind(tx.transaction_type.map(|t| t as i64))
+        .bind(tx.contract_address.map(|a| format!("{:#x}", a)))
+        .execute(&mut *self.0)
+        .await?;
+        Ok(())
+    }
+
+    async fn insert_log_data(&mut self, log: &MyLog) -> Result<i64, sqlx::Error> {
+        let topics = serde_json::to_string(&log.topics).expect("topics serialize");
+        let (id,): (i64,) = sqlx::query_as(
+            "INSERT INTO logs (
+                log_index_in_tx, transaction_hash, transaction_index_in_block,
+                block_number, block_hash, contract_address, data,
+                topic0, topic1, topic2, topic3, all_topics
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id",
+        )
+        .bind(log.log_index.map(|li| li.as_u64() as i64))
+        .bind(format!("{:#x}", log.transaction_hash))
+        .bind(log.transaction_index.map(|ti| ti as i64))
+        .bind(log.block_number as i64)
+        .bind(format!("{:#x}", log.block_hash))
+        .bind(format!("{:#x}", log.address))
+        .bind(&log.data)
+        .bind(log.topics.first())
+        .bind(log.topics.get(1))
+        .bind(log.topics.get(2))
+        .bind(log.topics.get(3))
+        .bind(topics)
+        .fetch_one(&mut *self.0)
+        .await?;
+        Ok(id)
+    }
+
+    async fn set_last_synced_block(
+        &mut self,
+        block_number: u64,
+        chain_head: u64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO indexer_status (indexer_name, last_processed_block, chain_head_at_last_poll)
+            VALUES (?, ?, ?)
+            ON CONFLICT (indexer_name) DO UPDATE SET
+                last_processed_block = MAX(last_processed_block, excluded.last_processed_block),
+                chain_head_at_last_poll = excluded.chain_head_at_last_poll",
+        )
+        .bind(INDEXER_NAME)
+        .bind(block_number as i64)
+        .bind(chain_head as i64)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(())
+    }
+
+    /// SQLite keeps the totals only; the activity rollups are Postgres-only.
+    async fn insert_block_stats(&mut self, stats: &BlockStats) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE stats_counters SET blocks = blocks + 1, transactions = transactions + ?,
+            logs = logs + ?",
+        )
+        .bind(stats.tx_count as i64)
+        .bind(stats.log_count as i64)
+        .execute(&mut *self.0)
+        .await?;
+        Ok(())
+    }
+
+    async fn commit(self: Box<Self>) -> Result<(), sqlx::Error> {
+        self.0.commit().await
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn get_last_synced_block(&self) -> Result<Option<u64>, sqlx::Error> {
+        let row: Option<(i64,)> = sqlx::query_as(
+            "SELECT last_processed_block FROM indexer_status WHERE indexer_name = ?",
+        )
+        .bind(INDEXER_NAME)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(|r| r.0 as u64))
+    }
+
+    async fn get_canonical_block_hash_at_height(
+        &self,
+        block_number: u64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT block_hash FROM blocks WHERE block_number = ? LIMIT 1")
+                .bind(block_number as i64)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(row.map(|r| r.0))
+    }
+
+    async fn rollback_from_height(&self, fork_height: u64) -> Result<(), sqlx::Error> {
+        self.delete_blocks(fork_height, None).await
+    }
+
+    async fn delete_block_range(&self, from: u64, to: u64) -> Result<(), sqlx::Error> {
+        self.delete_blocks(from, Some(to)).await
+    }
+
+    async fn begin(&self) -> Result<Box<dyn StorageTransaction>, sqlx::Error> {
+        Ok(Box::new(SqliteTransaction(self.pool.begin().await?)))
+    }
+
+    async fn get_indexer_status(&self) -> Result<Option<(i64, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT last_processed_block, chain_head_at_last_poll \
+             FROM indexer_status WHERE indexer_name = ?",
+        )
+        .bind(INDEXER_NAME)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_stats(&self) -> Result<IndexerStats, ApiError> {
+        let (total_blocks, total_transactions, total_logs): (i64, i64, i64) =
+            sqlx::query_as("SELECT blocks, transactions, logs FROM stats_counters")
+                .fetch_optional(&self.pool)
+                .await?
+                .unwrap_or_default();
+        let status = self.get_indexer_status().await?;
+        Ok(IndexerStats {
+            total_blocks,
+            total_transactions,
+            total_logs,
+            last_synced_block: status.map(|(last, _)| last),
+            ingestion_lag: status.map(|(last, head)| head - last),
+        })
+    }
+
+    async fn get_block(&self, identifier: &str) -> Result<MyBlock, ApiError> {
+        let row = if identifier.starts_with("0x") {
+            sqlx::query(&format!(
+                "SELECT {} FROM blocks WHERE block_hash = ?",
+                BLOCK_COLUMNS
+            ))
+            .bind(identifier.to_lowercase())
+            .fetch_one(&self.pool)
+            .await?
+        } else {
+            let block_number = identifier
+                .parse::<i64>()
+                .map_err(|_| ApiError::BadRequest("Invalid block number format".to_string()))?;
+            sqlx::query(&format!(
+                "SELECT {} FROM blocks WHERE block_number = ?",
+                BLOCK_COLUMNS
+            ))
+            .bind(block_number)
+            .fetch_one(&self.pool)
+            .await?
+        };
+        row_to_block(&row)
+    }
+
+    async fn get_latest_block(&self) -> Result<MyBlock, ApiError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM blocks ORDER BY block_number DESC LIMIT 1",
+            BLOCK_COLUMNS
+        ))
+        .fetch_one(&self.pool)
+        .await?;
+        row_to_block(&row)
+    }
+
+    async fn get_block_number_range(&self) -> Result<Option<(i64, i64)>, ApiError> {
+        let (first, last): (Option<i64>, Option<i64>) =
+            sqlx::query_as("SELECT MIN(block_number), MAX(block_number) FROM blocks")
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(first.zip(last))
+    }
+
+    async fn get_block_at_or_above(
+        &self,
+        block_number: i64,
+    ) -> Result<Option<(i64, i64)>, ApiError> {
+        Ok(sqlx::query_as(
+            "SELECT block_number, timestamp FROM blocks WHERE block_number >= ? \
+             ORDER BY block_number LIMIT 1",
+        )
+        .bind(block_number)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    async fn get_blocks(
+        &self,
+        query: &GetBlocksQuery,
+        limit: u64,
+    ) -> Result<Vec<MyBlock>, ApiError> {
+        let mut query_builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new(format!("SELECT {} FROM blocks WHERE 1=1", BLOCK_COLUMNS));
+        if let Some(fb) = query.from_block {
+            query_builder.push(" AND block_number >= ");
+            query_builder.push_bind(fb as i64);
+        }
+        if let Some(tb) = query.to_block {
+            query_builder.push(" AND block_number <= ");
+            query_builder.push_bind(tb as i64);
+        }
+        if let Some(from_ts) = query.from_timestamp {
+            query_builder.push(" AND timestamp >= ");
+            query_builder.push_bind(from_ts);
+        }
+        if let Some(to_ts) = query.to_timestamp {
+            query_builder.push(" AND timestamp <= ");
+            query_builder.push_bind(to_ts);
+        }
+        if let Some(cb) = query.cursor_block {
+            query_builder.push(" AND block_number < ");
+            query_builder.push_bind(cb);
+        }
+        query_builder.push(" ORDER BY block_number DESC LIMIT ");
+        query_builder.push_bind(limit as i64);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_block).collect()
+    }
+
+    async fn get_block_transactions(
+        &self,
+        block: &MyBlock,
+    ) -> Result<Vec<MyTransaction>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM transactions WHERE block_hash = ? ORDER BY transaction_index ASC",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(format!("{:#x}", block.block_hash))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_transaction).collect()
+    }
+
+    async fn get_block_logs(&self, block: &MyBlock) -> Result<Vec<MyLog>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM logs WHERE block_hash = ? \
+             ORDER BY transaction_index_in_block ASC, log_index_in_tx ASC, id ASC",
+            LOG_COLUMNS
+        ))
+        .bind(format!("{:#x}", block.block_hash))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_log).collect()
+    }
+
+    async fn get_transaction(&self, tx_hash: &str) -> Result<MyTransaction, ApiError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM transactions WHERE tx_hash = ?",
+            TRANSACTION_COLUMNS
+        ))
+        .bind(tx_hash)
+        .fetch_one(&self.pool)
+        .await?;
+        row_to_transaction(&row)
+    }
+
+    async fn get_transaction_logs(&self, tx_hash: &str) -> Result<Vec<MyLog>, ApiError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM logs WHERE transaction_hash = ? ORDER BY log_index_in_tx ASC, id ASC",
+            LOG_COLUMNS
+        ))
+        .bind(tx_hash)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter().map(row_to_log).collect()
+    }
+
+    async fn get_logs(
+        &self,
+        filters: &GetLogsFilter,
+        page: Page,
+    ) -> Result<Vec<(i64, MyLog)>, ApiError> {
+        validate_log_filter(filters)?;
+        let mut query_builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new(format!("SELECT {} FROM logs WHERE 1=1", LOG_COLUMNS));
+
+        if let Some(block_hash) = &filters.block_hash {
+            query_builder.push(" AND block_hash = LOWER(");
+            query_builder.push_bind(block_hash.clone());
+            query_builder.push(")");
+        } else if filters.from_block.is_some() || filters.to_block.is_some() {
+            let status = self.get_indexer_status().await?;
+            if let Some(fb) = filters.from_block {
+                query_builder.push(" AND block_number >= ");
+                query_builder.push_bind(block_tag_height(fb, status)?);
+            }
+            if let Some(tb) = filters.to_block {
+                query_builder.push(" AND block_number <= ");
+                query_builder.push_bind(block_tag_height(tb, status)?);
+            }
+        }
+
+        if let Some(addresses) = &filters.address {
+            let addresses = normalize_hex_values(addresses.as_slice(), 20, "address")?;
+            if !addresses.is_empty() {
+                push_in_list(&mut query_builder, "contract_address", addresses);
+            }
+        }
+        for (position, column) in ["topic0", "topic1", "topic2", "topic3"].iter().enumerate() {
+            if let Some(values) = filters.topic_filter(position) {
+                let values = normalize_hex_values(values.as_slice(), 32, column)?;
+                if !values.is_empty() {
+                    push_in_list(&mut query_builder, column, values);
+                }
+            }
+        }
+        push_page(&mut query_builder, "block_number, id", page);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| Ok((row.try_get("id")?, row_to_log(row)?)))
+            .collect()
+    }
+
+    async fn get_transactions(
+        &self,
+        filters: &GetTransactionsFilter,
+        page: Page,
+    ) -> Result<Vec<MyTransaction>, ApiError> {
+        let mut query_builder: QueryBuilder<Sqlite> = QueryBuilder::new(format!(
+            "SELECT {} FROM transactions WHERE 1=1",
+            TRANSACTION_COLUMNS
+        ));
+
+        if let Some(fb) = filters.from_block {
+            query_builder.push(" AND block_number >= ");
+            query_builder.push_bind(fb as i64);
+        }
+        if let Some(tb) = filters.to_block {
+            query_builder.push(" AND block_number <= ");
+            query_builder.push_bind(tb as i64);
+        }
+        if let Some(from) = &filters.from {
+            query_builder.push(" AND from_address = ");
+            query_builder.push_bind(normalize_address(from, "from")?);
+        }
+        if let Some(to) = &filters.to {
+            query_builder.push(" AND to_address = ");
+            query_builder.push_bind(normalize_address(to, "to")?);
+        }
+        if let Some(status) = filters.status {
+            query_builder.push(" AND status = ");
+            query_builder.push_bind(transaction_status(status)? as i64);
+        }
+        if let Some(min) = &filters.min_value {
+            push_decimal_bound(&mut query_builder, "value", min, ">=", "minValue")?;
+        }
+        if let Some(max) = &filters.max_value {
+            push_decimal_bound(&mut query_builder, "value", max, "<=", "maxValue")?;
+        }
+        if let Some(method_id) = &filters.method_id {
+            let method_id = normalize_method_id(method_id)?;
+            query_builder.push(" AND SUBSTR(input_data, 1, 10) = ");
+            query_builder.push_bind(method_id);
+        }
+        match filters.contract_creation {
+            Some(true) => {
+                query_builder.push(" AND to_address IS NULL");
+            }
+            Some(false) => {
+                query_builder.push(" AND to_address IS NOT NULL");
+            }
+            None => {}
+        }
+        push_page(&mut query_builder, "block_number, transaction_index", page);
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_transaction).collect()
+    }
+}
@@ -0,0 +1,77 @@
+-- Schema for the embedded SQLite backend (DATABASE_URL=sqlite:...)
+-- Applied automatically on startup. Mirrors the core tables in init.sql; hex values are
+-- stored as TEXT and all_topics as a JSON array.
+
+CREATE TABLE IF NOT EXISTS indexer_status (
+  indexer_name TEXT PRIMARY KEY,
+  last_processed_block INTEGER,
+  chain_head_at_last_poll INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS blocks (
+  block_hash TEXT PRIMARY KEY,
+  block_number INTEGER NOT NULL,
+  parent_hash TEXT NOT NULL,
+  timestamp INTEGER NOT NULL,
+  gas_used TEXT NOT NULL,
+  gas_limit TEXT NOT NULL,
+  base_fee_per_gas TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_blocks_number ON blocks(block_number);
+
+CREATE TABLE IF NOT EXISTS transactions (
+  tx_hash TEXT PRIMARY KEY,
+  block_number INTEGER NOT NULL,
+  block_hash TEXT NOT NULL,
+  transaction_index INTEGER,
+  from_address TEXT NOT NULL,
+  to_address TEXT,
+  value TEXT NOT NULL,
+  gas_price TEXT,
+  max_fee_per_gas TEXT,
+  max_priority_fee_per_gas TEXT,
+  gas_provided TEXT NOT NULL,
+  input_data TEXT,
+  status INTEGER,
+  gas_used TEXT,
+  effective_gas_price TEXT,
+  nonce TEXT,
+  transaction_type INTEGER,
+  contract_address TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_block_hash ON transactions(block_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_from_address ON transactions(from_address);
+CREATE INDEX IF NOT EXISTS idx_transactions_to_address ON transactions(to_address);
+CREATE INDEX IF NOT EXISTS idx_transactions_block_tx_index ON transactions(block_number, transaction_index);
+
+CREATE TABLE IF NOT EXISTS logs (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  log_index_in_tx INTEGER,
+  transaction_hash TEXT NOT NULL,
+  transaction_index_in_block INTEGER,
+  block_number INTEGER NOT NULL,
+  block_hash TEXT NOT NULL,
+  contract_address TEXT NOT NULL,
+  data TEXT,
+  topic0 TEXT,
+  topic1 TEXT,
+  topic2 TEXT,
+  topic3 TEXT,
+  all_topics TEXT NOT NULL DEFAULT '[]'
+);
+
+CREATE INDEX IF NOT EXISTS idx_logs_block_id ON logs(block_number, id);
+CREATE INDEX IF NOT EXISTS idx_logs_block_hash ON logs(block_hash);
+CREATE INDEX IF NOT EXISTS idx_logs_transaction_hash ON logs(transaction_hash);
+CREATE INDEX IF NOT EXISTS idx_logs_contract_address ON logs(contract_address, block_number);
+CREATE INDEX IF NOT EXISTS idx_logs_topic0_block ON logs(topic0, block_number);
+
+-- Running totals behind GET /stats, kept by the ingester and rollbacks.
+CREATE TABLE IF NOT EXISTS stats_counters (
+  id INTEGER PRIMARY KEY CHECK (id = 1),
+  blocks INTEGER NOT NULL,
+  transactions INTEGER NOT NULL,
+  logs INTEGER NOT NULL
+);
@@ -0,0 +1,125 @@
+// src/storage/test_support.rs
+//
+// Fixtures shared by the tests that need a real database: a throwaway SQLite file, a scratch
+// Postgres schema, and a chain of headers, numbered from 100 on, that forks can branch off.
+
+use super::{SqliteStorage, Storage};
+use crate::models::MyBlock;
+use ethers::types::{H256, U256, U64};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Hash of block `number` on `fork`. Forks share heights but never hashes.
+pub(crate) fn hash(number: u64, fork: u64) -> H256 {
+    H256::from_low_u64_be(number * 10 + fork)
+}
+
+/// Header of block `number` on `fork`, extending block `number - 1` of `parent_fork`. Blocks
+/// are 12 seconds apart, block 100 at timestamp 1,000.
+pub(crate) fn block(number: u64, fork: u64, parent_fork: u64) -> MyBlock {
+    MyBlock {
+        block_number: U64::from(number),
+        block_hash: hash(number, fork),
+        parent_hash: hash(number - 1, parent_fork),
+        timestamp: U256::from(1_000 + (number - 100) * 12),
+        gas_used: U256::zero(),
+        gas_limit: U256::zero(),
+        base_fee_per_gas: None,
+    }
+}
+
+/// Commits `blocks` one at a time, moving the sync point along like the writer does.
+pub(crate) async fn write_blocks(storage: &dyn Storage, blocks: impl IntoIterator<Item = MyBlock>) {
+    for block in blocks {
+        let number = block.block_number.as_u64();
+        let mut tx = storage.begin().await.unwrap();
+        tx.insert_block_data(&block).await.unwrap();
+        tx.set_last_synced_block(number, 200).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+}
+
+/// A path for a test database in the temp dir, cleared of files left by an earlier run.
+pub(crate) fn temp_db_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("evm_indexer_{}_{}.db", name, std::process::id()));
+    remove_db(&path);
+    path
+}
+
+pub(crate) fn remove_db(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+/// A SQLite database file, removed again on drop. A file rather than `sqlite::memory:`,
+/// which would give every pooled connection a database of its own.
+pub(crate) struct TestDb {
+    pub storage: Arc<dyn Storage>,
+    pub url: String,
+    path: PathBuf,
+}
+
+impl TestDb {
+    pub async fn new(name: &str) -> Self {
+        let path = temp_db_path(name);
+        let url = format!("sqlite:{}", path.display());
+        let storage = Arc::new(SqliteStorage::connect(&url).await.unwrap());
+        TestDb { storage, url, path }
+    }
+
+    pub async fn hash_at(&self, number: u64) -> Option<String> {
+        self.storage
+            .get_canonical_block_hash_at_height(number)
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        remove_db(&self.path);
+    }
+}
+
+/// A schema of its own in the database `TEST_DATABASE_URL` points at, loaded with
+/// `init.sql`. Postgres-only tests skip when the variable is unset.
+pub(crate) struct ScratchSchema {
+    pub pool: PgPool,
+    admin: PgPool,
+    name: String,
+}
+
+impl ScratchSchema {
+    pub async fn new(name: &str) -> Option<Self> {
+        let url = std::env::var("TEST_DATABASE_URL").ok()?;
+        let name = format!("evm_indexer_{}_{}", name, std::process::id());
+        let admin = PgPool::connect(&url).await.unwrap();
+        let reset = format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", name);
+        sqlx::raw_sql(&reset).execute(&admin).await.unwrap();
+        let options = PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", name.as_str())]);
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::raw_sql(include_str!("../../init.sql"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        Some(ScratchSchema { pool, admin, name })
+    }
+
+    /// Drops the schema. Not done on drop, which cannot wait for the database.
+    pub async fn remove(self) {
+        self.pool.close().await;
+        sqlx::raw_sql(&format!("DROP SCHEMA {} CASCADE", self.name))
+            .execute(&self.admin)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,550 @@
+// src/stream.rs
+//
+// Real-time log streaming over Server-Sent Events and WebSocket.
+// Each subscriber gets a task that first replays matching logs from Postgres (when a start
+// block or resume cursor is given), then forwards matching logs from the ingester's event
+// bus as blocks commit. Lagging subscribers catch up from the database instead of dropping
+// data, and reorg rollbacks are pushed as retraction messages.
+
+use crate::api::{
+    normalize_hex_values, push_log_filters, resolve_block_tag, row_to_log, validate_log_filter,
+    ApiError, LOG_COLUMNS,
+};
+use crate::api_models::{GetLogsFilter, StreamLogsQuery, StreamMessage};
+use crate::events::{ChainEvent, EventSender};
+use crate::models::MyLog;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::get,
+    Router,
+};
+use futures::stream::{self, Stream};
+use sqlx::{PgPool, QueryBuilder, Row as SqlxRow};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tracing::warn;
+
+const REPLAY_PAGE_SIZE: i64 = 1000;
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+type Cursor = (i64, i64);
+
+#[derive(Clone)]
+pub struct StreamState {
+    pub pool: PgPool,
+    pub events: EventSender,
+}
+
+/// In-memory equivalent of `push_log_filters`, applied to logs from the event bus.
+struct LiveFilter {
+    addresses: Option<HashSet<String>>,
+    topics: [Option<HashSet<String>>; 4],
+    from_block: Option<u64>,
+}
+
+impl LiveFilter {
+    async fn new(pool: &PgPool, filters: &GetLogsFilter) -> Result<Self, ApiError> {
+        validate_log_filter(filters)?;
+        if filters.block_hash.is_some() || filters.to_block.is_some() {
+            return Err(ApiError::BadRequest(
+                "blockHash and toBlock are not supported for live streams.".to_string(),
+            ));
+        }
+
+        // Empty OR-lists match anything, as in POST /logs.
+        let set = |values: Vec<String>| (!values.is_empty()).then(|| values.into_iter().collect());
+        let addresses = match &filters.address {
+            Some(a) => set(normalize_hex_values(a.as_slice(), 20, "address")?),
+            None => None,
+        };
+        let mut topics: [Option<HashSet<String>>; 4] = Default::default();
+        for (position, slot) in topics.iter_mut().enumerate() {
+            if let Some(values) = filters.topic_filter(position) {
+                *slot = set(normalize_hex_values(values.as_slice(), 32, "topic")?);
+            }
+        }
+        let from_block = match filters.from_block {
+            Some(tag) => Some(resolve_block_tag(pool, tag).await? as u64),
+            None => None,
+        };
+
+        Ok(LiveFilter {
+            addresses,
+            topics,
+            from_block,
+        })
+    }
+
+    fn matches(&self, log: &MyLog) -> bool {
+        if self.from_block.is_some_and(|fb| log.block_number < fb) {
+            return false;
+        }
+        if let Some(addresses) = &self.addresses {
+            if !addresses.contains(&format!("{:#x}", log.address)) {
+                return false;
+            }
+        }
+        self.topics
+            .iter()
+            .enumerate()
+            .all(|(i, wanted)| match wanted {
+                Some(wanted) => log.topics.get(i).is_some_and(|t| wanted.contains(t)),
+                None => true,
+            })
+    }
+}
+
+/// Starts a subscription task and returns the channel its messages arrive on.
+/// The task ends when the receiver is dropped (client disconnected).
+async fn subscribe(
+    state: StreamState,
+    filters: GetLogsFilter,
+    resume: Option<Cursor>,
+) -> Result<mpsc::Receiver<StreamMessage>, ApiError> {
+    let live = LiveFilter::new(&state.pool, &filters).await?;
+    // Subscribe before replaying so nothing committed during the replay is missed.
+    let mut events = state.events.subscribe();
+    let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+    // Position in the stream: everything at or before this cursor has been handled.
+    // Live-only subscriptions start after the block that is currently indexed.
+    let replay_from = resume.or_else(|| live.from_block.map(|fb| (fb as i64 - 1, i64::MAX)));
+    let mut position: Cursor = match replay_from {
+        Some(cursor) => cursor,
+        None => {
+            let head = crate::db::get_last_synced_block(&state.pool).await?;
+            (head.map_or(-1, |h| h as i64), i64::MAX)
+        }
+    };
+
+    tokio::spawn(async move {
+        // Range of the last re-index: its logs are sent again although they are behind
+        // `position`, which stays where it was.
+        let mut reindexing = None;
+        if replay_from.is_some() {
+            match replay(&state.pool, &filters, position, &tx).await {
+                Ok(Some(end)) => position = end,
+                Ok(None) => return,
+                Err(e) => {
+                    let _ = tx.send(error_message(e)).await;
+                    return;
+                }
+            }
+        }
+
+        loop {
+            match events.recv().await {
+                Ok(event) => match &*event {
+                    ChainEvent::BlockCommitted {
+                        block_number, logs, ..
+                    } => {
+                        let reindexed = event.is_reindexed(reindexing);
+                        for indexed in logs {
+                            let cursor = (indexed.log.block_number as i64, indexed.id);
+                            if (cursor <= position && !reindexed) || !live.matches(&indexed.log) {
+                                continue;
+                            }
+                            let msg = StreamMessage::Log {
+                                cursor_block: cursor.0,
+                                cursor_log_id: cursor.1,
+                                log: Box::new(indexed.log.clone()),
+                            };
+                            if tx.send(msg).await.is_err() {
+                                return;
+                            }
+                        }
+                        let end_of_block = (*block_number as i64, i64::MAX);
+                        position = position.max(end_of_block);
+                    }
+                    ChainEvent::Retract {
+                        from_block,
+                        to_block,
+                    } => {
+                        if (*from_block as i64) > position.0 {
+                            continue;
+                        }
+                        reindexing = Some((*from_block, *to_block));
+                        let msg = StreamMessage::Retract {
+                            removed_from_block: *from_block,
+                            removed_to_block: *to_block,
+                        };
+                        if tx.send(msg).await.is_err() {
+                            return;
+                        }
+                    }
+                    ChainEvent::Reorg { fork_height } => {
+                        reindexing = None;
+                        // Re-ingested blocks get new ids, so rewind to just before the fork.
+                        let rewind = (*fork_height as i64 - 1, i64::MAX);
+                        position = position.min(rewind);
+                        let msg = StreamMessage::Reorg {
+                            removed_from_block: *fork_height,
+                        };
+                        if tx.send(msg).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "STREAM: subscriber lagged by {} events; catching up from the database.",
+                        skipped
+                    );
+                    match replay(&state.pool, &filters, position, &tx).await {
+                        Ok(Some(end)) => position = end,
+                        Ok(None) => return,
+                        Err(e) => {
+                            let _ = tx.send(error_message(e)).await;
+                            return;
+                        }
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Sends every stored log matching `filters` after `start`, page by page.
+/// Returns the last cursor sent, or `None` if the subscriber went away.
+async fn replay(
+    pool: &PgPool,
+    filters: &GetLogsFilter,
+    start: Cursor,
+    tx: &mpsc::Sender<StreamMessage>,
+) -> Result<Option<Cursor>, ApiError> {
+    let mut cursor = start;
+    loop {
+        let mut query_builder: QueryBuilder<sqlx::Postgres> =
+            QueryBuilder::new(format!("SELECT {} FROM logs WHERE 1=1", LOG_COLUMNS));
+        push_log_filters(pool, &mut query_builder, filters).await?;
+        query_builder.push(" AND (block_number, id) > (");
+        query_builder.push_bind(cursor.0);
+        query_builder.push(", ");
+        query_builder.push_bind(cursor.1);
+        query_builder.push(") ORDER BY block_number ASC, id ASC LIMIT ");
+        query_builder.push_bind(REPLAY_PAGE_SIZE);
+
+        let rows = query_builder.build().fetch_all(pool).await?;
+        for row in &rows {
+            let log = row_to_log(row)?;
+            cursor = (log.block_number as i64, SqlxRow::try_get(row, "id")?);
+            let msg = StreamMessage::Log {
+                cursor_block: cursor.0,
+                cursor_log_id: cursor.1,
+                log: Box::new(log),
+            };
+            if tx.send(msg).await.is_err() {
+                return Ok(None);
+            }
+        }
+        if (rows.len() as i64) < REPLAY_PAGE_SIZE {
+            return Ok(Some(cursor));
+        }
+    }
+}
+
+fn error_message(err: ApiError) -> StreamMessage {
+    let message = match err {
+        ApiError::DatabaseError(db_err) => {
+            tracing::error!("Stream database error: {:?}", db_err);
+            "A database error occurred".to_string()
+        }
+        ApiError::NotFound(msg)
+        | ApiError::BadRequest(msg)
+        | ApiError::InternalServerError(msg) => msg,
+    };
+    StreamMessage::Error { message }
+}
+
+/// Cursor from a previous response, as used by POST /logs (missing halves default to 0).
+fn filter_cursor(filters: &GetLogsFilter) -> Option<Cursor> {
+    (filters.cursor_block.is_some() || filters.cursor_log_id.is_some()).then(|| {
+        (
+            filters.cursor_block.unwrap_or(0),
+            filters.cursor_log_id.unwrap_or(0),
+        )
+    })
+}
+
+/// Parses the SSE `Last-Event-ID` header, which carries the `block:id` cursor of the last event.
+fn last_event_id(headers: &HeaderMap) -> Option<Cursor> {
+    let value = headers.get("last-event-id")?.to_str().ok()?;
+    let (block, id) = value.split_once(':')?;
+    Some((block.parse().ok()?, id.parse().ok()?))
+}
+
+/// Where a reconnecting SSE client resumes: the `Last-Event-ID` header the browser sends, or
+/// else a cursor given in the query.
+fn resume_cursor(headers: &HeaderMap, filters: &GetLogsFilter) -> Option<Cursor> {
+    last_event_id(headers).or_else(|| filter_cursor(filters))
+}
+
+fn to_sse_event(msg: &StreamMessage) -> Event {
+    let event = match msg {
+        StreamMessage::Log {
+            cursor_block,
+            cursor_log_id,
+            ..
+        } => Event::default()
+            .event("log")
+            .id(format!("{}:{}", cursor_block, cursor_log_id)),
+        StreamMessage::Reorg { .. } => Event::default().event("reorg"),
+        StreamMessage::Retract { .. } => Event::default().event("retract"),
+        StreamMessage::Error { .. } => Event::default().event("error"),
+    };
+    event
+        .json_data(msg)
+        .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+}
+
+/// Stream Logs (Server-Sent Events)
+///
+/// Streams newly committed logs matching the filter as `log` events, and `reorg` events when
+/// a chain reorganization removes data. With `fromBlock` or a cursor, stored logs are replayed
+/// first. Reconnecting clients resume automatically via the `Last-Event-ID` header.
+#[utoipa::path(
+    get,
+    path = "/stream/logs",
+    params(StreamLogsQuery),
+    responses(
+        (status = 200, description = "Event stream of StreamMessage payloads", body = StreamMessage, content_type = "text/event-stream"),
+        (status = 400, description = "Invalid filter", body = crate::api_models::GenericErrorResponse)
+    )
+)]
+pub async fn stream_logs_sse_handler(
+    State(state): State<StreamState>,
+    headers: HeaderMap,
+    Query(query): Query<StreamLogsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let filters = query.into_filter().map_err(ApiError::BadRequest)?;
+    let resume = resume_cursor(&headers, &filters);
+    let rx = subscribe(state, filters, resume).await?;
+
+    let events = stream::unfold(rx, |mut rx| async move {
+        let msg = rx.recv().await?;
+        Some((Ok(to_sse_event(&msg)), rx))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Stream Logs (WebSocket)
+///
+/// After connecting, send one text frame containing a `GetLogsFilter` JSON object
+/// (`cursorBlock`/`cursorLogId` resume after a disconnect). The server then pushes
+/// `StreamMessage` JSON frames.
+#[utoipa::path(
+    get,
+    path = "/stream/logs/ws",
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol")
+    )
+)]
+pub async fn stream_logs_ws_handler(
+    State(state): State<StreamState>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: StreamState) {
+    let filters = loop {
+        match socket.recv().await {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str::<GetLogsFilter>(&text) {
+                Ok(filters) => break filters,
+                Err(e) => {
+                    let msg = StreamMessage::Error {
+                        message: format!("Invalid subscription filter: {}", e),
+                    };
+                    let _ = send_json(&mut socket, &msg).await;
+                    return;
+                }
+            },
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+            Some(Ok(_)) => continue,
+        }
+    };
+
+    let resume = filter_cursor(&filters);
+    let mut rx = match subscribe(state, filters, resume).await {
+        Ok(rx) => rx,
+        Err(e) => {
+            let _ = send_json(&mut socket, &error_message(e)).await;
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(msg) => {
+                    if send_json(&mut socket, &msg).await.is_err() {
+                        return;
+                    }
+                }
+                None => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send_json(socket: &mut WebSocket, msg: &StreamMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(msg).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+pub fn router(pool: PgPool, events: EventSender) -> Router {
+    Router::new()
+        .route("/stream/logs", get(stream_logs_sse_handler))
+        .route("/stream/logs/ws", get(stream_logs_ws_handler))
+        .with_state(StreamState { pool, events })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::IndexedLog;
+    use crate::storage::test_support::{block, write_blocks, ScratchSchema};
+    use crate::storage::{PostgresStorage, Storage};
+    use ethers::types::{Address, H256, U256};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONTRACT: &str = "0x00000000000000000000000000000000000000c0";
+
+    fn filter(value: serde_json::Value) -> GetLogsFilter {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn headers(last_event_id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("last-event-id", last_event_id.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn last_event_id_carries_the_resume_cursor() {
+        assert_eq!(last_event_id(&headers("120:7")), Some((120, 7)));
+        assert_eq!(last_event_id(&HeaderMap::new()), None);
+        for malformed in ["", "120", "120:", ":7", "0x78:7", "120:7:1", "latest"] {
+            assert_eq!(last_event_id(&headers(malformed)), None, "{:?}", malformed);
+        }
+
+        // The header wins over a cursor in the query, which is the fallback.
+        let query = filter(serde_json::json!({ "cursorBlock": 100, "cursorLogId": 3 }));
+        assert_eq!(resume_cursor(&headers("120:7"), &query), Some((120, 7)));
+        assert_eq!(resume_cursor(&HeaderMap::new(), &query), Some((100, 3)));
+        assert_eq!(resume_cursor(&headers("bad"), &query), Some((100, 3)));
+        let only_block = filter(serde_json::json!({ "cursorBlock": 100 }));
+        assert_eq!(
+            resume_cursor(&HeaderMap::new(), &only_block),
+            Some((100, 0))
+        );
+        assert_eq!(
+            resume_cursor(&HeaderMap::new(), &filter(serde_json::json!({}))),
+            None
+        );
+    }
+
+    /// Two logs from `CONTRACT` in block `number`.
+    fn logs(number: u64) -> Vec<MyLog> {
+        (0..2)
+            .map(|i| MyLog {
+                log_index: Some(U256::from(i)),
+                transaction_hash: H256::from_low_u64_be(number * 10 + i),
+                transaction_index: Some(i),
+                block_number: number,
+                block_hash: crate::storage::test_support::hash(number, 0),
+                block_timestamp: None,
+                address: CONTRACT.parse::<Address>().unwrap(),
+                data: "0x".to_string(),
+                topics: vec![],
+                address_ens_name: None,
+            })
+            .collect()
+    }
+
+    async fn next_log(rx: &mut mpsc::Receiver<StreamMessage>) -> Option<Cursor> {
+        let msg = tokio::time::timeout(Duration::from_millis(200), rx.recv()).await;
+        match msg.ok()?? {
+            StreamMessage::Log {
+                cursor_block,
+                cursor_log_id,
+                ..
+            } => Some((cursor_block, cursor_log_id)),
+            other => panic!(
+                "expected a log, got {}",
+                serde_json::to_string(&other).unwrap()
+            ),
+        }
+    }
+
+    /// A client resuming from a cursor gets the stored logs after it, then only live logs
+    /// it has not seen.
+    #[tokio::test]
+    async fn resumes_after_the_cursor() {
+        let Some(schema) = ScratchSchema::new("stream").await else {
+            return;
+        };
+        let storage = PostgresStorage::new(schema.pool.clone());
+        write_blocks(&storage, (100..=102).map(|n| block(n, 0, 0))).await;
+        let mut ids = Vec::new();
+        for number in 100..=102 {
+            let mut tx = storage.begin().await.unwrap();
+            let block_ids = tx.insert_logs_batch(&logs(number)).await.unwrap();
+            tx.commit().await.unwrap();
+            ids.extend(block_ids.into_iter().map(|id| (number as i64, id)));
+        }
+
+        let state = StreamState {
+            pool: schema.pool.clone(),
+            events: crate::events::channel(),
+        };
+        let events = state.events.clone();
+        let filters = filter(serde_json::json!({ "address": CONTRACT }));
+        // The client saw up to the first log of block 101.
+        let mut rx = subscribe(state, filters, Some(ids[2])).await.unwrap();
+        for expected in &ids[3..] {
+            assert_eq!(next_log(&mut rx).await, Some(*expected));
+        }
+        assert_eq!(next_log(&mut rx).await, None);
+
+        // Block 102 arriving from the bus was covered by the replay and is skipped.
+        let committed = |number: u64, first_id: i64| {
+            Arc::new(ChainEvent::BlockCommitted {
+                block_number: number,
+                block_hash: crate::storage::test_support::hash(number, 0),
+                transactions: vec![],
+                logs: logs(number)
+                    .into_iter()
+                    .zip(first_id..)
+                    .map(|(log, id)| IndexedLog { id, log })
+                    .collect(),
+            })
+        };
+        let next_id = ids.last().unwrap().1 + 1;
+        events.send(committed(102, ids[4].1)).unwrap();
+        events.send(committed(103, next_id)).unwrap();
+        assert_eq!(next_log(&mut rx).await, Some((103, next_id)));
+        assert_eq!(next_log(&mut rx).await, Some((103, next_id + 1)));
+        assert_eq!(next_log(&mut rx).await, None);
+
+        drop(rx);
+        schema.remove().await;
+    }
+}
@@ -0,0 +1,209 @@
+// src/supervisor.rs
+//
+// Long-running tasks and process shutdown. `supervise` runs a task in its own tokio task and
+// starts it again, with exponential backoff, whenever it returns or panics, until shutdown is
+// requested. SIGINT or SIGTERM requests shutdown: the API stops accepting connections, and the
+// ingester commits the block it is writing and returns instead of starting the next one.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+const BASE_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+/// A run lasting this long counts as healthy, so the next restart starts from the base backoff.
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+/// Cheap to clone; every clone sees the same request.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+pub struct ShutdownTrigger(watch::Sender<bool>);
+
+pub fn shutdown_channel() -> (ShutdownTrigger, Shutdown) {
+    let (tx, rx) = watch::channel(false);
+    (ShutdownTrigger(tx), Shutdown(rx))
+}
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once shutdown is requested, or at once if it already was.
+    pub async fn requested(&self) {
+        let mut rx = self.0.clone();
+        // The trigger lives as long as `main`; if it is gone, so is everything else.
+        let _ = rx.wait_for(|requested| *requested).await;
+    }
+}
+
+impl ShutdownTrigger {
+    pub fn trigger(&self) {
+        self.0.send_replace(true);
+    }
+
+    /// Requests shutdown on the first SIGINT (Ctrl-C) or SIGTERM, and exits at once on the
+    /// second.
+    pub async fn on_signal(&self) {
+        #[cfg(unix)]
+        let mut terminate =
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(signal) => Some(signal),
+                Err(e) => {
+                    error!("SHUTDOWN: cannot listen for SIGTERM: {}", e);
+                    None
+                }
+            };
+        for first in [true, false] {
+            #[cfg(unix)]
+            let sigterm = async {
+                match terminate.as_mut() {
+                    Some(signal) => {
+                        signal.recv().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let sigterm = std::future::pending::<()>();
+
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => match result {
+                    Ok(()) => info!("SHUTDOWN: SIGINT received."),
+                    Err(e) => {
+                        error!("SHUTDOWN: cannot listen for Ctrl-C: {}", e);
+                        std::future::pending::<()>().await;
+                    }
+                },
+                _ = sigterm => info!("SHUTDOWN: SIGTERM received."),
+            }
+            if !first {
+                warn!("SHUTDOWN: second signal; exiting without waiting.");
+                std::process::exit(1);
+            }
+            info!("SHUTDOWN: finishing in-flight work. Signal again to exit at once.");
+            self.trigger();
+        }
+    }
+}
+
+/// Runs `make()` and restarts it whenever it returns or panics, until `shutdown` is requested.
+/// The returned handle completes once the last run has returned after a shutdown request.
+pub fn supervise<F, Fut>(name: &'static str, shutdown: Shutdown, make: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = eyre::Result<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut backoff = BASE_RESTART_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let outcome = tokio::spawn(make()).await;
+            if shutdown.is_requested() {
+                info!("SUPERVISOR: {} stopped.", name);
+                return;
+            }
+            match outcome {
+                Ok(Ok(())) => error!("SUPERVISOR: {} returned unexpectedly.", name),
+                Ok(Err(e)) => error!("SUPERVISOR: {} failed: {}", name, e),
+                Err(e) if e.is_panic() => error!("SUPERVISOR: {} panicked.", name),
+                Err(e) => error!("SUPERVISOR: {} was cancelled: {}", name, e),
+            }
+
+            if started.elapsed() >= HEALTHY_RUN {
+                backoff = BASE_RESTART_BACKOFF;
+            }
+            warn!("SUPERVISOR: restarting {} in {:?}.", name, backoff);
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = shutdown.requested() => {
+                    info!("SUPERVISOR: {} stopped.", name);
+                    return;
+                }
+            }
+            backoff = (backoff * 2).min(MAX_RESTART_BACKOFF);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    /// Supervises a task that reports when each run starts and fails after `run_for(run)`.
+    /// Returns the start times of the first `runs` runs, then shuts the supervisor down.
+    async fn run_starts(runs: usize, run_for: fn(u32) -> Duration) -> Vec<Duration> {
+        let (trigger, shutdown) = shutdown_channel();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let count = Arc::new(AtomicU32::new(0));
+        let origin = Instant::now();
+        let handle = supervise("test task", shutdown, move || {
+            let tx = tx.clone();
+            let run = count.fetch_add(1, Ordering::SeqCst);
+            async move {
+                tx.send(origin.elapsed()).unwrap();
+                tokio::time::sleep(run_for(run)).await;
+                if run == 0 {
+                    panic!("first run panics");
+                }
+                eyre::bail!("run {} failed", run)
+            }
+        });
+        let mut starts = Vec::new();
+        while starts.len() < runs {
+            starts.push(rx.recv().await.unwrap());
+        }
+        trigger.trigger();
+        handle.await.unwrap();
+        starts
+    }
+
+    fn gaps(starts: &[Duration]) -> Vec<u64> {
+        starts.windows(2).map(|w| (w[1] - w[0]).as_secs()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_back_off_exponentially_up_to_the_cap() {
+        let starts = run_starts(9, |_| Duration::ZERO).await;
+        assert_eq!(gaps(&starts), [1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_healthy_run_resets_the_backoff() {
+        // The fourth run lasts HEALTHY_RUN before it fails.
+        let starts = run_starts(6, |run| {
+            if run == 3 {
+                HEALTHY_RUN
+            } else {
+                Duration::ZERO
+            }
+        })
+        .await;
+        assert_eq!(gaps(&starts), [1, 2, 4, HEALTHY_RUN.as_secs() + 1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_ends_a_pending_restart() {
+        let (trigger, shutdown) = shutdown_channel();
+        let runs = Arc::new(AtomicU32::new(0));
+        let counted = runs.clone();
+        let handle = supervise("test task", shutdown, move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            async { eyre::bail!("failed") }
+        });
+        // Past the first restart and into the 2 s backoff before the second.
+        tokio::time::sleep(Duration::from_millis(1_500)).await;
+        trigger.trigger();
+        let stopped = Instant::now();
+        handle.await.unwrap();
+        assert_eq!(stopped.elapsed(), Duration::ZERO);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,980 @@
+// src/webhooks.rs
+//
+// Webhook notification rules. A rule pairs an address / topic0 / method selector filter with
+// a target URL. The dispatcher checks every committed block from the event bus against the
+// enabled rules and POSTs one signed payload per matching rule and block. Deliveries are
+// recorded in `webhook_deliveries`, retried with exponential backoff, and moved to
+// `webhook_dead_letters` once retries are exhausted. When a reorg rolls back a block that
+// produced a match, a retraction is sent for it.
+//
+// Target hosts must resolve to public addresses, both when a rule is saved and before every
+// delivery, which then connects to exactly the addresses that were checked; redirects are not
+// followed. Otherwise a rule could make the indexer POST to itself, its database or a cloud
+// metadata endpoint. WEBHOOK_ALLOW_PRIVATE_TARGETS=true lifts this for receivers on the same
+// host or private network.
+
+use crate::api::{normalize_hex_values, ApiError, MAX_PAGE_SIZE};
+use crate::api_models::{
+    GenericErrorResponse, GetDeadLettersQuery, WebhookDeadLetter, WebhookPayload, WebhookRule,
+    WebhookRuleRequest,
+};
+use crate::events::{ChainEvent, EventSender, IndexedLog};
+use crate::models::{MyLog, MyTransaction};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{postgres::PgRow, PgPool, QueryBuilder, Row as SqlxRow};
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast::error::RecvError, Semaphore};
+use tracing::{error, info, warn};
+
+const MAX_DELIVERY_ATTEMPTS: i32 = 6;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delivery attempts in flight at once. A burst of matches, or a restart with a long backlog
+/// of pending deliveries, queues here instead of opening a connection per delivery.
+const MAX_CONCURRENT_DELIVERIES: usize = 16;
+const MIN_SECRET_LEN: usize = 16;
+
+const RULE_COLUMNS: &str = "id, name, address, topic0, method_id, target_url, enabled, \
+     EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at";
+
+type HmacSha256 = Hmac<Sha256>;
+
+// ---------------------------------------------------------------------------
+// Rule matching
+// ---------------------------------------------------------------------------
+
+/// An enabled rule as loaded by the dispatcher, including its signing secret.
+struct ActiveRule {
+    id: i64,
+    name: String,
+    address: Option<String>,
+    topic0: Option<String>,
+    method_id: Option<String>,
+    target_url: String,
+    secret: String,
+}
+
+impl ActiveRule {
+    /// Transactions match on sender/recipient and selector; rules with a topic0 never match them.
+    fn matches_transaction(&self, tx: &MyTransaction) -> bool {
+        if self.topic0.is_some() {
+            return false;
+        }
+        if let Some(address) = &self.address {
+            let from = format!("{:#x}", tx.from_address);
+            let to = tx.to_address.map(|a| format!("{:#x}", a));
+            if &from != address && to.as_ref() != Some(address) {
+                return false;
+            }
+        }
+        match &self.method_id {
+            Some(selector) => tx.input_data.to_lowercase().starts_with(selector.as_str()),
+            None => true,
+        }
+    }
+
+    /// Logs match on emitting contract and topic0; rules with a selector never match them.
+    fn matches_log(&self, log: &MyLog) -> bool {
+        if self.method_id.is_some() {
+            return false;
+        }
+        if let Some(address) = &self.address {
+            if &format!("{:#x}", log.address) != address {
+                return false;
+            }
+        }
+        match &self.topic0 {
+            Some(topic0) => log.topics.first() == Some(topic0),
+            None => true,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Dispatcher
+// ---------------------------------------------------------------------------
+
+/// A delivery row ready to be sent (or re-sent).
+struct PendingDelivery {
+    id: i64,
+    kind: String,
+    target_url: String,
+    secret: String,
+    body: String,
+    attempts: i32,
+}
+
+#[derive(Clone)]
+struct Dispatcher {
+    pool: PgPool,
+    /// Used as is only when private targets are allowed; otherwise each delivery builds a
+    /// client pinned to the addresses it checked.
+    client: reqwest::Client,
+    allow_private: bool,
+    /// Bounds the attempts in flight across all deliveries (`MAX_CONCURRENT_DELIVERIES`).
+    permits: Arc<Semaphore>,
+}
+
+fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+}
+
+/// Consumes the event bus for the lifetime of the process and dispatches webhooks.
+pub async fn run_dispatcher(pool: PgPool, events: EventSender) -> eyre::Result<()> {
+    let dispatcher = Dispatcher {
+        pool,
+        client: client_builder().build()?,
+        allow_private: allow_private_targets(),
+        permits: Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES)),
+    };
+
+    // Subscribe before reading the head so no block committed in between is missed.
+    let mut rx = events.subscribe();
+    let mut last_block: i64 = crate::db::get_last_synced_block(&dispatcher.pool)
+        .await?
+        .map_or(-1, |b| b as i64);
+
+    // Deliveries interrupted by a restart pick up where they left off.
+    let resumed = dispatcher.resume_pending().await?;
+    if resumed > 0 {
+        info!("WEBHOOKS: resumed {} pending deliveries.", resumed);
+    }
+
+    // Range of the last re-index: its blocks are matched again although they are not new.
+    let mut reindexing = None;
+    loop {
+        let result = match rx.recv().await {
+            Ok(event) if event.is_reindexed(reindexing) => dispatcher.handle_block(&event).await,
+            Ok(event) => match &*event {
+                ChainEvent::BlockCommitted { block_number, .. } => {
+                    if (*block_number as i64) <= last_block {
+                        continue;
+                    }
+                    last_block = *block_number as i64;
+                    dispatcher.handle_block(&event).await
+                }
+                ChainEvent::Reorg { fork_height } => {
+                    reindexing = None;
+                    last_block = last_block.min(*fork_height as i64 - 1);
+                    dispatcher
+                        .retract(Some(*fork_height..=i64::MAX as u64))
+                        .await
+                }
+                ChainEvent::Retract {
+                    from_block,
+                    to_block,
+                } => {
+                    reindexing = Some((*from_block, *to_block));
+                    dispatcher.retract(Some(*from_block..=*to_block)).await
+                }
+            },
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "WEBHOOKS: dispatcher lagged by {} events; re-checking from the database.",
+                    skipped
+                );
+                dispatcher.catch_up(&mut last_block).await
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if let Err(e) = result {
+            error!("WEBHOOKS: dispatch failed: {}", e);
+        }
+    }
+}
+
+impl Dispatcher {
+    async fn load_rules(&self) -> Result<Vec<ActiveRule>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, name, address, topic0, method_id, target_url, secret \
+             FROM webhook_rules WHERE enabled ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(ActiveRule {
+                    id: row.try_get("id")?,
+                    name: row.try_get("name")?,
+                    address: row.try_get("address")?,
+                    topic0: row.try_get("topic0")?,
+                    method_id: row.try_get("method_id")?,
+                    target_url: row.try_get("target_url")?,
+                    secret: row.try_get("secret")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn handle_block(&self, event: &ChainEvent) -> eyre::Result<()> {
+        let ChainEvent::BlockCommitted {
+            block_number,
+            block_hash,
+            transactions,
+            logs,
+        } = event
+        else {
+            return Ok(());
+        };
+        let block_hash = format!("{:#x}", block_hash);
+        let rules = self.load_rules().await?;
+        for rule in rules {
+            let matched_txs: Vec<MyTransaction> = transactions
+                .iter()
+                .filter(|tx| rule.matches_transaction(tx))
+                .cloned()
+                .collect();
+            let matched_logs: Vec<MyLog> = logs
+                .iter()
+                .map(|indexed: &IndexedLog| &indexed.log)
+                .filter(|log| rule.matches_log(log))
+                .cloned()
+                .collect();
+            if matched_txs.is_empty() && matched_logs.is_empty() {
+                continue;
+            }
+            let payload = WebhookPayload::Match {
+                rule_id: rule.id,
+                rule_name: rule.name.clone(),
+                block_number: *block_number,
+                block_hash: block_hash.clone(),
+                transactions: matched_txs,
+                logs: matched_logs,
+            };
+            self.enqueue(&rule, "match", *block_number, &block_hash, &payload)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Sends retractions for delivered matches that are no longer canonical: those in `blocks`,
+    /// or (when `None`) those whose block hash is no longer in `blocks`.
+    async fn retract(&self, blocks: Option<RangeInclusive<u64>>) -> eyre::Result<()> {
+        let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new(
+            "UPDATE webhook_deliveries d SET retracted = TRUE FROM webhook_rules r \
+             WHERE d.rule_id = r.id AND d.kind = 'match' AND NOT d.retracted",
+        );
+        match blocks {
+            Some(blocks) => {
+                query_builder.push(" AND d.block_number BETWEEN ");
+                query_builder.push_bind(*blocks.start() as i64);
+                query_builder.push(" AND ");
+                query_builder.push_bind((*blocks.end()).min(i64::MAX as u64) as i64);
+            }
+            None => {
+                query_builder.push(
+                    " AND NOT EXISTS (SELECT 1 FROM blocks b WHERE b.block_hash = d.block_hash)",
+                );
+            }
+        }
+        query_builder.push(
+            " RETURNING d.id, d.block_number, d.block_hash, \
+             r.id AS rule_id, r.name, r.address, r.topic0, r.method_id, r.target_url, r.secret",
+        );
+
+        let rows = query_builder.build().fetch_all(&self.pool).await?;
+        for row in &rows {
+            let rule = ActiveRule {
+                id: row.try_get("rule_id")?,
+                name: row.try_get("name")?,
+                address: row.try_get("address")?,
+                topic0: row.try_get("topic0")?,
+                method_id: row.try_get("method_id")?,
+                target_url: row.try_get("target_url")?,
+                secret: row.try_get("secret")?,
+            };
+            let block_number = row.try_get::<i64, _>("block_number")? as u64;
+            let block_hash: String = row.try_get("block_hash")?;
+            let payload = WebhookPayload::Retraction {
+                rule_id: rule.id,
+                rule_name: rule.name.clone(),
+                block_number,
+                block_hash: block_hash.clone(),
+                retracted_delivery_id: row.try_get("id")?,
+            };
+            self.enqueue(&rule, "retraction", block_number, &block_hash, &payload)
+                .await?;
+        }
+        if !rows.is_empty() {
+            info!(
+                "WEBHOOKS: queued {} retractions after a reorg or re-index.",
+                rows.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Re-checks blocks the dispatcher missed while lagging, straight from Postgres.
+    async fn catch_up(&self, last_block: &mut i64) -> eyre::Result<()> {
+        // A missed Reorg event leaves matches on blocks that are no longer stored.
+        self.retract(None).await?;
+
+        let head = crate::db::get_last_synced_block(&self.pool)
+            .await?
+            .map_or(-1, |b| b as i64);
+        while *last_block < head {
+            let block_number = *last_block + 1;
+            if let Some(event) =
+                crate::events::load_block_committed(&self.pool, block_number as u64).await?
+            {
+                self.handle_block(&event).await?;
+            }
+            *last_block = block_number;
+        }
+        Ok(())
+    }
+
+    /// Records a delivery and starts sending it in the background.
+    async fn enqueue(
+        &self,
+        rule: &ActiveRule,
+        kind: &str,
+        block_number: u64,
+        block_hash: &str,
+        payload: &WebhookPayload,
+    ) -> eyre::Result<()> {
+        let body = serde_json::to_string(payload)?;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO webhook_deliveries (rule_id, kind, block_number, block_hash, payload) \
+             VALUES ($1, $2, $3, $4, $5::jsonb) RETURNING id",
+        )
+        .bind(rule.id)
+        .bind(kind)
+        .bind(block_number as i64)
+        .bind(block_hash)
+        .bind(&body)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let delivery = PendingDelivery {
+            id,
+            kind: kind.to_string(),
+            target_url: rule.target_url.clone(),
+            secret: rule.secret.clone(),
+            body,
+            attempts: 0,
+        };
+        tokio::spawn(self.clone().deliver(delivery));
+        Ok(())
+    }
+
+    async fn resume_pending(&self) -> Result<usize, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT d.id, d.kind, d.payload::text AS body, d.attempts, r.target_url, r.secret \
+             FROM webhook_deliveries d JOIN webhook_rules r ON r.id = d.rule_id \
+             WHERE d.status = 'pending' ORDER BY d.id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for row in &rows {
+            let delivery = PendingDelivery {
+                id: row.try_get("id")?,
+                kind: row.try_get("kind")?,
+                target_url: row.try_get("target_url")?,
+                secret: row.try_get("secret")?,
+                body: row.try_get("body")?,
+                attempts: row.try_get("attempts")?,
+            };
+            tokio::spawn(self.clone().deliver(delivery));
+        }
+        Ok(rows.len())
+    }
+
+    /// Sends a delivery until it succeeds or runs out of attempts, then dead-letters it. Each
+    /// attempt waits for a permit; the backoff between attempts does not hold one, so a dead
+    /// target cannot hold up deliveries to the others.
+    async fn deliver(self, mut delivery: PendingDelivery) {
+        loop {
+            delivery.attempts += 1;
+            let result = {
+                let _permit = self.permits.acquire().await.expect("never closed");
+                self.send(&delivery).await
+            };
+            match result {
+                Ok(()) => {
+                    let result = sqlx::query(
+                        "UPDATE webhook_deliveries SET status = 'delivered', attempts = $2, \
+                         last_error = NULL, delivered_at = NOW() WHERE id = $1",
+                    )
+                    .bind(delivery.id)
+                    .bind(delivery.attempts)
+                    .execute(&self.pool)
+                    .await;
+                    if let Err(e) = result {
+                        error!("WEBHOOKS: failed to record delivery {}: {}", delivery.id, e);
+                    }
+                    return;
+                }
+                Err(reason) => {
+                    let exhausted = delivery.attempts >= MAX_DELIVERY_ATTEMPTS;
+                    if let Err(e) = self.record_failure(&delivery, &reason, exhausted).await {
+                        error!("WEBHOOKS: failed to record delivery {}: {}", delivery.id, e);
+                    }
+                    if exhausted {
+                        warn!(
+                            "WEBHOOKS: delivery {} dead-lettered after {} attempts: {}",
+                            delivery.id, delivery.attempts, reason
+                        );
+                        return;
+                    }
+                    tokio::time::sleep(backoff(delivery.attempts)).await;
+                }
+            }
+        }
+    }
+
+    async fn send(&self, delivery: &PendingDelivery) -> Result<(), String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let signature = sign(&delivery.secret, timestamp, &delivery.body);
+
+        // Checked again on every attempt: the host may resolve elsewhere than when the rule
+        // was saved.
+        let client = match self.allow_private {
+            true => self.client.clone(),
+            false => {
+                let url = reqwest::Url::parse(&delivery.target_url).map_err(|e| e.to_string())?;
+                let addrs = resolve_target(&url, false).await?;
+                match url.domain() {
+                    Some(domain) => client_builder()
+                        .resolve_to_addrs(domain, &addrs)
+                        .build()
+                        .map_err(|e| e.to_string())?,
+                    None => self.client.clone(),
+                }
+            }
+        };
+        let response = client
+            .post(&delivery.target_url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", &delivery.kind)
+            .header("X-Webhook-Delivery", delivery.id.to_string())
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header("X-Webhook-Signature", format!("sha256={}", signature))
+            .body(delivery.body.clone())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+
+    async fn record_failure(
+        &self,
+        delivery: &PendingDelivery,
+        reason: &str,
+        exhausted: bool,
+    ) -> Result<(), sqlx::Error> {
+        let mut db_tx = self.pool.begin().await?;
+        sqlx::query(
+            "UPDATE webhook_deliveries SET attempts = $2, last_error = $3, \
+             status = CASE WHEN $4 THEN 'dead' ELSE status END WHERE id = $1",
+        )
+        .bind(delivery.id)
+        .bind(delivery.attempts)
+        .bind(reason)
+        .bind(exhausted)
+        .execute(&mut *db_tx)
+        .await?;
+        if exhausted {
+            sqlx::query(
+                "INSERT INTO webhook_dead_letters \
+                 (delivery_id, rule_id, target_url, payload, attempts, last_error) \
+                 SELECT id, rule_id, $2, payload, attempts, last_error \
+                 FROM webhook_deliveries WHERE id = $1",
+            )
+            .bind(delivery.id)
+            .bind(&delivery.target_url)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+        db_tx.commit().await
+    }
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`. Receivers recompute it with the rule's secret
+/// and should reject stale timestamps to prevent replays.
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn allow_private_targets() -> bool {
+    env::var("WEBHOOK_ALLOW_PRIVATE_TARGETS").is_ok_and(|v| v == "true" || v == "1")
+}
+
+/// Whether `ip` is a public unicast address: not loopback, private, link-local, unique-local,
+/// site-local, shared (carrier-grade NAT), benchmarking, multicast, reserved, unspecified or
+/// broadcast. IPv4-mapped IPv6 addresses are judged as the IPv4 address, and NAT64 ones
+/// (64:ff9b::/96) are refused outright, since the gateway may translate them to anything.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (b & 0xfe) == 18))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => {
+                let segments = ip.segments();
+                let first = segments[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+                    || (first & 0xffc0) == 0xfec0
+                    || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0])
+            }
+        },
+    }
+}
+
+/// The addresses the target's host resolves to. Fails when it doesn't resolve, or when any
+/// address is not public and `allow_private` is false.
+async fn resolve_target(
+    url: &reqwest::Url,
+    allow_private: bool,
+) -> Result<Vec<SocketAddr>, String> {
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| "targetUrl has no port.".to_string())?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| "targetUrl has no host.".to_string())?;
+    // IPv6 literals come bracketed.
+    let addrs: Vec<SocketAddr> = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(format!("{} has no addresses.", host));
+    }
+    if let Some(addr) = addrs
+        .iter()
+        .find(|addr| !allow_private && !is_public_ip(addr.ip()))
+    {
+        return Err(format!(
+            "{} resolves to the non-public address {}; set WEBHOOK_ALLOW_PRIVATE_TARGETS=true \
+             to allow it.",
+            host,
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Delay before the next attempt: 2s, 4s, 8s, ... capped at `MAX_BACKOFF`.
+fn backoff(attempt: i32) -> Duration {
+    let exponent = (attempt - 1).clamp(0, 16) as u32;
+    (INITIAL_BACKOFF * 2u32.pow(exponent)).min(MAX_BACKOFF)
+}
+
+// ---------------------------------------------------------------------------
+// Rule CRUD
+// ---------------------------------------------------------------------------
+
+/// Validated, normalized rule fields ready to be written.
+struct RuleFields {
+    name: String,
+    address: Option<String>,
+    topic0: Option<String>,
+    method_id: Option<String>,
+    target_url: String,
+    secret: Option<String>,
+    enabled: bool,
+}
+
+async fn validate_rule(
+    req: WebhookRuleRequest,
+    require_secret: bool,
+) -> Result<RuleFields, ApiError> {
+    let normalize = |value: Option<String>, byte_len: usize, field: &str| match value {
+        Some(v) => Ok(normalize_hex_values(&[v], byte_len, field)?.pop()),
+        None => Ok::<_, ApiError>(None),
+    };
+    let address = normalize(req.address, 20, "address")?;
+    let topic0 = normalize(req.topic0, 32, "topic0")?;
+    let method_id = normalize(req.method_id, 4, "methodId")?;
+    if address.is_none() && topic0.is_none() && method_id.is_none() {
+        return Err(ApiError::BadRequest(
+            "A rule needs at least one of address, topic0 or methodId.".to_string(),
+        ));
+    }
+    if topic0.is_some() && method_id.is_some() {
+        return Err(ApiError::BadRequest(
+            "topic0 matches logs and methodId matches transactions; a rule cannot use both."
+                .to_string(),
+        ));
+    }
+
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty.".to_string()));
+    }
+    let url = reqwest::Url::parse(&req.target_url)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid targetUrl: {}", e)))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(ApiError::BadRequest(
+            "targetUrl must be an http or https URL.".to_string(),
+        ));
+    }
+    resolve_target(&url, allow_private_targets())
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Invalid targetUrl: {}", e)))?;
+
+    match &req.secret {
+        Some(secret) if secret.len() < MIN_SECRET_LEN => {
+            return Err(ApiError::BadRequest(format!(
+                "secret must be at least {} characters.",
+                MIN_SECRET_LEN
+            )));
+        }
+        None if require_secret => {
+            return Err(ApiError::BadRequest("secret is required.".to_string()));
+        }
+        _ => {}
+    }
+
+    Ok(RuleFields {
+        name,
+        address,
+        topic0,
+        method_id,
+        target_url: url.to_string(),
+        secret: req.secret,
+        enabled: req.enabled,
+    })
+}
+
+fn row_to_rule(row: &PgRow) -> Result<WebhookRule, ApiError> {
+    Ok(WebhookRule {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        address: row.try_get("address")?,
+        topic0: row.try_get("topic0")?,
+        method_id: row.try_get("method_id")?,
+        target_url: row.try_get("target_url")?,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+/// List Webhook Rules
+#[utoipa::path(
+    get,
+    path = "/webhooks/rules",
+    responses(
+        (status = 200, description = "All webhook rules", body = Vec<WebhookRule>)
+    )
+)]
+pub async fn list_rules_handler(
+    State(pool): State<PgPool>,
+) -> Result<Json<Vec<WebhookRule>>, ApiError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM webhook_rules ORDER BY id",
+        RULE_COLUMNS
+    ))
+    .fetch_all(&pool)
+    .await?;
+    let rules = rows.iter().map(row_to_rule).collect::<Result<_, _>>()?;
+    Ok(Json(rules))
+}
+
+/// Create Webhook Rule
+///
+/// The rule is checked against every block committed from now on.
+#[utoipa::path(
+    post,
+    path = "/webhooks/rules",
+    request_body = WebhookRuleRequest,
+    responses(
+        (status = 201, description = "Rule created", body = WebhookRule),
+        (status = 400, description = "Invalid rule", body = GenericErrorResponse)
+    )
+)]
+pub async fn create_rule_handler(
+    State(pool): State<PgPool>,
+    Json(req): Json<WebhookRuleRequest>,
+) -> Result<(StatusCode, Json<WebhookRule>), ApiError> {
+    let fields = validate_rule(req, true).await?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO webhook_rules (name, address, topic0, method_id, target_url, secret, enabled) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        RULE_COLUMNS
+    ))
+    .bind(fields.name)
+    .bind(fields.address)
+    .bind(fields.topic0)
+    .bind(fields.method_id)
+    .bind(fields.target_url)
+    .bind(fields.secret)
+    .bind(fields.enabled)
+    .fetch_one(&pool)
+    .await?;
+    Ok((StatusCode::CREATED, Json(row_to_rule(&row)?)))
+}
+
+/// Get Webhook Rule
+#[utoipa::path(
+    get,
+    path = "/webhooks/rules/{id}",
+    params(("id" = i64, Path, description = "Rule id")),
+    responses(
+        (status = 200, description = "Rule found", body = WebhookRule),
+        (status = 404, description = "Rule not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn get_rule_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+) -> Result<Json<WebhookRule>, ApiError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM webhook_rules WHERE id = $1",
+        RULE_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("Webhook rule {} not found.", id)))?;
+    Ok(Json(row_to_rule(&row)?))
+}
+
+/// Update Webhook Rule
+///
+/// Replaces the rule. Omitting `secret` keeps the current one.
+#[utoipa::path(
+    put,
+    path = "/webhooks/rules/{id}",
+    params(("id" = i64, Path, description = "Rule id")),
+    request_body = WebhookRuleRequest,
+    responses(
+        (status = 200, description = "Rule updated", body = WebhookRule),
+        (status = 400, description = "Invalid rule", body = GenericErrorResponse),
+        (status = 404, description = "Rule not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn update_rule_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+    Json(req): Json<WebhookRuleRequest>,
+) -> Result<Json<WebhookRule>, ApiError> {
+    let fields = validate_rule(req, false).await?;
+    let row = sqlx::query(&format!(
+        "UPDATE webhook_rules SET name = $2, address = $3, topic0 = $4, method_id = $5, \
+         target_url = $6, secret = COALESCE($7, secret), enabled = $8 \
+         WHERE id = $1 RETURNING {}",
+        RULE_COLUMNS
+    ))
+    .bind(id)
+    .bind(fields.name)
+    .bind(fields.address)
+    .bind(fields.topic0)
+    .bind(fields.method_id)
+    .bind(fields.target_url)
+    .bind(fields.secret)
+    .bind(fields.enabled)
+    .fetch_optional(&pool)
+    .await?
+    .ok_or_else(|| ApiError::NotFound(format!("Webhook rule {} not found.", id)))?;
+    Ok(Json(row_to_rule(&row)?))
+}
+
+/// Delete Webhook Rule
+///
+/// Also removes the rule's delivery history and dead letters.
+#[utoipa::path(
+    delete,
+    path = "/webhooks/rules/{id}",
+    params(("id" = i64, Path, description = "Rule id")),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Rule not found", body = GenericErrorResponse)
+    )
+)]
+pub async fn delete_rule_handler(
+    State(pool): State<PgPool>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query("DELETE FROM webhook_rules WHERE id = $1")
+        .bind(id)
+        .execute(&pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!(
+            "Webhook rule {} not found.",
+            id
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List Dead Letters
+///
+/// Deliveries that failed every retry, newest first.
+#[utoipa::path(
+    get,
+    path = "/webhooks/dead-letters",
+    params(GetDeadLettersQuery),
+    responses(
+        (status = 200, description = "Dead-lettered deliveries", body = Vec<WebhookDeadLetter>)
+    )
+)]
+pub async fn list_dead_letters_handler(
+    State(pool): State<PgPool>,
+    Query(params): Query<GetDeadLettersQuery>,
+) -> Result<Json<Vec<WebhookDeadLetter>>, ApiError> {
+    let mut query_builder: QueryBuilder<sqlx::Postgres> = QueryBuilder::new(
+        "SELECT id, delivery_id, rule_id, target_url, payload::text AS payload, attempts, \
+         last_error, EXTRACT(EPOCH FROM created_at)::BIGINT AS created_at \
+         FROM webhook_dead_letters WHERE 1=1",
+    );
+    if let Some(rule_id) = params.rule_id {
+        query_builder.push(" AND rule_id = ");
+        query_builder.push_bind(rule_id);
+    }
+    query_builder.push(" ORDER BY id DESC LIMIT ");
+    query_builder.push_bind(params.limit.clamp(1, MAX_PAGE_SIZE) as i64);
+
+    let rows = query_builder.build().fetch_all(&pool).await?;
+    let dead_letters = rows
+        .iter()
+        .map(|row| {
+            let payload: String = row.try_get("payload")?;
+            Ok(WebhookDeadLetter {
+                id: row.try_get("id")?,
+                delivery_id: row.try_get("delivery_id")?,
+                rule_id: row.try_get("rule_id")?,
+                target_url: row.try_get("target_url")?,
+                payload: serde_json::from_str(&payload).map_err(|e| {
+                    ApiError::InternalServerError(format!("Invalid stored payload: {}", e))
+                })?,
+                attempts: row.try_get("attempts")?,
+                last_error: row.try_get("last_error")?,
+                created_at: row.try_get("created_at")?,
+            })
+        })
+        .collect::<Result<_, ApiError>>()?;
+    Ok(Json(dead_letters))
+}
+
+pub fn router(pool: PgPool) -> Router {
+    Router::new()
+        .route(
+            "/webhooks/rules",
+            get(list_rules_handler).post(create_rule_handler),
+        )
+        .route(
+            "/webhooks/rules/{id}",
+            get(get_rule_handler)
+                .put(update_rule_handler)
+                .delete(delete_rule_handler),
+        )
+        .route("/webhooks/dead-letters", get(list_dead_letters_handler))
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "224.0.0.1",
+            "239.255.255.250",
+            "240.0.0.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "ff02::1",
+            "ff0e::1",
+            "fec0::1",
+            "64:ff9b::1.1.1.1",
+            "::ffff:224.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} is not public", ip);
+        }
+        for ip in [
+            "1.1.1.1",
+            "8.8.8.8",
+            "100.128.0.1",
+            "198.17.255.255",
+            "198.20.0.1",
+            "223.255.255.255",
+            "2606:4700::1111",
+            "2001:4860:4860::8888",
+            "::ffff:1.1.1.1",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn literal_private_targets_are_rejected_unless_allowed() {
+        for target in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/",
+            "http://localhost:3000/hook",
+        ] {
+            let url = reqwest::Url::parse(target).unwrap();
+            assert!(resolve_target(&url, false).await.is_err(), "{}", target);
+            assert!(resolve_target(&url, true).await.is_ok(), "{}", target);
+        }
+        let url = reqwest::Url::parse("https://1.1.1.1/hook").unwrap();
+        assert_eq!(
+            resolve_target(&url, false).await.unwrap(),
+            vec!["1.1.1.1:443".parse::<SocketAddr>().unwrap()]
+        );
+    }
+}